@@ -0,0 +1 @@
+alter table webhook.subscription drop column retry_policy;
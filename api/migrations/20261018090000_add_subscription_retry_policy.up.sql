@@ -0,0 +1 @@
+alter table webhook.subscription add column retry_policy jsonb default null;
@@ -0,0 +1 @@
+alter table webhook.request_attempt drop column is_permanent_failure;
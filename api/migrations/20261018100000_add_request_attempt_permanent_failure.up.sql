@@ -0,0 +1,2 @@
+alter table webhook.request_attempt add column is_permanent_failure boolean not null default false;
+alter table webhook.request_attempt add constraint request_attempt_permanent_failure_is_failed check (not is_permanent_failure or failed_at is not null);
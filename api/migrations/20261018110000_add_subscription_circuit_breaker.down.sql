@@ -0,0 +1,7 @@
+drop index if exists webhook.subscription_disabled_not_notified_idx;
+
+alter table webhook.subscription drop column disabled_notified_at;
+alter table webhook.subscription drop column disabled_reason;
+alter table webhook.subscription drop column disabled_at;
+alter table webhook.subscription drop column failing_since;
+alter table webhook.subscription drop column consecutive_failures;
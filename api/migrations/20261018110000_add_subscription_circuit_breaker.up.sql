@@ -0,0 +1,7 @@
+alter table webhook.subscription add column consecutive_failures integer not null default 0;
+alter table webhook.subscription add column failing_since timestamptz default null;
+alter table webhook.subscription add column disabled_at timestamptz default null;
+alter table webhook.subscription add column disabled_reason text default null;
+alter table webhook.subscription add column disabled_notified_at timestamptz default null;
+
+create index subscription_disabled_not_notified_idx on webhook.subscription (disabled_at) where disabled_at is not null and disabled_notified_at is null;
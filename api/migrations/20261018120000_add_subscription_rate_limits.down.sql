@@ -0,0 +1,5 @@
+alter table webhook.subscription drop constraint subscription_max_in_flight_chk;
+alter table webhook.subscription drop constraint subscription_max_requests_per_second_chk;
+
+alter table webhook.subscription drop column max_in_flight;
+alter table webhook.subscription drop column max_requests_per_second;
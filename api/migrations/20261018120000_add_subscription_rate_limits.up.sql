@@ -0,0 +1,5 @@
+alter table webhook.subscription add column max_requests_per_second integer default null;
+alter table webhook.subscription add column max_in_flight integer default null;
+
+alter table webhook.subscription add constraint subscription_max_requests_per_second_chk check (max_requests_per_second is null or max_requests_per_second > 0);
+alter table webhook.subscription add constraint subscription_max_in_flight_chk check (max_in_flight is null or max_in_flight > 0);
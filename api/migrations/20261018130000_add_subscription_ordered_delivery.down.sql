@@ -0,0 +1,6 @@
+drop index webhook.request_attempt_subscription_waiting_idx;
+
+alter table webhook.subscription drop constraint subscription_ordering_key_requires_ordered_delivery;
+
+alter table webhook.subscription drop column ordering_key;
+alter table webhook.subscription drop column ordered_delivery;
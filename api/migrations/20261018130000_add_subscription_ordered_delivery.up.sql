@@ -0,0 +1,6 @@
+alter table webhook.subscription add column ordered_delivery boolean not null default false;
+alter table webhook.subscription add column ordering_key text default null;
+
+alter table webhook.subscription add constraint subscription_ordering_key_requires_ordered_delivery check (ordering_key is null or ordered_delivery);
+
+create index request_attempt_subscription_waiting_idx on webhook.request_attempt (subscription__id) where succeeded_at is null and failed_at is null;
@@ -0,0 +1,6 @@
+alter table webhook.subscription drop constraint subscription_batching_excludes_ordered_delivery;
+alter table webhook.subscription drop constraint subscription_batch_max_wait_ms_chk;
+alter table webhook.subscription drop constraint subscription_batch_max_size_chk;
+
+alter table webhook.subscription drop column batch_max_wait_ms;
+alter table webhook.subscription drop column batch_max_size;
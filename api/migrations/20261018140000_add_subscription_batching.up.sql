@@ -0,0 +1,6 @@
+alter table webhook.subscription add column batch_max_size integer default null;
+alter table webhook.subscription add column batch_max_wait_ms integer default null;
+
+alter table webhook.subscription add constraint subscription_batch_max_size_chk check (batch_max_size is null or batch_max_size > 0);
+alter table webhook.subscription add constraint subscription_batch_max_wait_ms_chk check (batch_max_wait_ms is null or (batch_max_size is not null and batch_max_wait_ms >= 0));
+alter table webhook.subscription add constraint subscription_batching_excludes_ordered_delivery check (batch_max_size is null or not ordered_delivery);
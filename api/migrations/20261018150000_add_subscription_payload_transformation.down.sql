@@ -0,0 +1,4 @@
+alter table webhook.subscription drop constraint subscription_payload_transformation_excludes_batching;
+alter table webhook.subscription drop constraint subscription_payload_transformation_is_object;
+
+alter table webhook.subscription drop column payload_transformation;
@@ -0,0 +1,4 @@
+alter table webhook.subscription add column payload_transformation jsonb default null;
+
+alter table webhook.subscription add constraint subscription_payload_transformation_is_object check (payload_transformation is null or jsonb_typeof(payload_transformation) = 'object');
+alter table webhook.subscription add constraint subscription_payload_transformation_excludes_batching check (payload_transformation is null or batch_max_size is null);
@@ -0,0 +1,85 @@
+alter table webhook.subscription add column filters jsonb not null default '[]'::jsonb;
+
+alter table webhook.subscription add constraint subscription_filters_is_array check (jsonb_typeof(filters) = 'array');
+
+-- Evaluates the filters of a subscription (see the API for their format); all of them must match
+-- payload is null if the event's payload is not JSON, in which case payload conditions never match
+create function webhook.subscription_filters_match(filters jsonb, labels jsonb, payload jsonb)
+    returns boolean
+    language sql
+    immutable
+as
+$$
+select coalesce(bool_and(
+    case f ->> 'operator'
+        when 'exists' then labels ? (f ->> 'label')
+        when 'not_exists' then not labels ? (f ->> 'label')
+        when 'in' then coalesce((f -> 'values') ? (labels ->> (f ->> 'label')), false)
+        when 'not_in' then not coalesce((f -> 'values') ? (labels ->> (f ->> 'label')), false)
+        else coalesce(jsonb_path_match(
+            payload,
+            (
+                (f ->> 'path')
+                || case f ->> 'operator'
+                    when 'eq' then ' == '
+                    when 'ne' then ' != '
+                    when 'gt' then ' > '
+                    when 'gte' then ' >= '
+                    when 'lt' then ' < '
+                    when 'lte' then ' <= '
+                end
+                || '$value'
+            )::jsonpath,
+            jsonb_build_object('value', f -> 'value'),
+            silent => true
+        ), false)
+    end
+), true)
+from jsonb_array_elements(filters) as f;
+$$;
+
+create or replace function event.dispatch()
+    returns trigger
+    language plpgsql
+as
+$$
+declare
+    payload jsonb;
+begin
+    if new.dispatched_at is not null then
+        return new;
+    end if;
+
+    -- The payload is only parsed if a subscription filters on it
+    -- When it is not stored in the database (object storage), the API provides it through the hook0.event_payload setting
+    if new.payload_content_type = 'application/json' and exists (
+        select 1
+        from webhook.subscription as s
+        where s.is_enabled
+          and s.application__id = new.application__id
+          and s.deleted_at is null
+          and s.filters @? '$[*].path'
+    ) then
+        begin
+            payload := coalesce(convert_from(new.payload, 'UTF8'), nullif(current_setting('hook0.event_payload', true), ''))::jsonb;
+        exception when invalid_text_representation or untranslatable_character or character_not_in_repertoire then
+            payload := null;
+        end;
+    end if;
+
+    insert into webhook.request_attempt (event__id, subscription__id, application__id)
+    select new.event__id, s.subscription__id, s.application__id
+    from webhook.subscription as s
+    inner join webhook.subscription__event_type as set on set.subscription__id = s.subscription__id
+    where s.is_enabled
+      and s.application__id = new.application__id
+      and s.deleted_at is null
+      and set.event_type__name = new.event_type__name
+      and new.labels @> s.labels
+      and webhook.subscription_filters_match(s.filters, new.labels, payload)
+    for share of s;
+
+    update event.event set dispatched_at = statement_timestamp() where event__id = new.event__id;
+    return new;
+end;
+$$;
@@ -0,0 +1,49 @@
+create or replace function event.dispatch()
+    returns trigger
+    language plpgsql
+as
+$$
+declare
+    payload jsonb;
+begin
+    if new.dispatched_at is not null then
+        return new;
+    end if;
+
+    -- The payload is only parsed if a subscription filters on it
+    -- When it is not stored in the database (object storage), the API provides it through the hook0.event_payload setting
+    if new.payload_content_type = 'application/json' and exists (
+        select 1
+        from webhook.subscription as s
+        where s.is_enabled
+          and s.application__id = new.application__id
+          and s.deleted_at is null
+          and s.filters @? '$[*].path'
+    ) then
+        begin
+            payload := coalesce(convert_from(new.payload, 'UTF8'), nullif(current_setting('hook0.event_payload', true), ''))::jsonb;
+        exception when invalid_text_representation or untranslatable_character or character_not_in_repertoire then
+            payload := null;
+        end;
+    end if;
+
+    insert into webhook.request_attempt (event__id, subscription__id, application__id)
+    select new.event__id, s.subscription__id, s.application__id
+    from webhook.subscription as s
+    inner join webhook.subscription__event_type as set on set.subscription__id = s.subscription__id
+    where s.is_enabled
+      and s.application__id = new.application__id
+      and s.deleted_at is null
+      and set.event_type__name = new.event_type__name
+      and new.labels @> s.labels
+      and webhook.subscription_filters_match(s.filters, new.labels, payload)
+    for share of s;
+
+    update event.event set dispatched_at = statement_timestamp() where event__id = new.event__id;
+    return new;
+end;
+$$;
+
+drop function webhook.event_type_matches_pattern(text, text, text, text);
+
+alter table webhook.subscription drop column event_type_patterns;
@@ -0,0 +1 @@
+drop table event.event_type_schema;
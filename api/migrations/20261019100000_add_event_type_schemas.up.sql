@@ -0,0 +1,13 @@
+create table event.event_type_schema (
+    application__id uuid not null,
+    event_type__name text not null,
+    version integer not null,
+    schema jsonb not null,
+    validation_mode text not null,
+    created_at timestamptz not null default statement_timestamp(),
+    primary key (application__id, event_type__name, version),
+    constraint event_type_schema_event_type_fk foreign key (application__id, event_type__name) references event.event_type (application__id, event_type__name) on delete cascade on update cascade,
+    constraint event_type_schema_version_chk check (version > 0),
+    constraint event_type_schema_schema_chk check (jsonb_typeof(schema) in ('object', 'boolean')),
+    constraint event_type_schema_validation_mode_chk check (validation_mode in ('strict', 'warn'))
+);
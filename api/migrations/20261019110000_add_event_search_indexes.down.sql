@@ -0,0 +1,3 @@
+drop index if exists event.event_labels_idx;
+drop index if exists event.event_application__id_occurred_at_idx;
+drop index if exists event.event_application__id_event_type__name_received_at_idx;
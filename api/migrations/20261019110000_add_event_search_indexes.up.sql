@@ -0,0 +1,3 @@
+create index if not exists event_application__id_event_type__name_received_at_idx on event.event (application__id, event_type__name, received_at);
+create index if not exists event_application__id_occurred_at_idx on event.event (application__id, occurred_at);
+create index if not exists event_labels_idx on event.event using gin (labels jsonb_path_ops);
@@ -0,0 +1,29 @@
+create table event.replay_job (
+    replay_job__id uuid not null default public.gen_random_uuid(),
+    application__id uuid not null,
+    min_received_at timestamptz not null,
+    max_received_at timestamptz not null,
+    event_type_names text[] not null default '{}',
+    subscription__id uuid,
+    only_failed boolean not null default false,
+    status text not null default 'pending',
+    total_events bigint,
+    replayed_events bigint not null default 0,
+    failed_events bigint not null default 0,
+    last_error text,
+    cursor_received_at timestamptz,
+    cursor_event__id uuid,
+    created_at timestamptz not null default statement_timestamp(),
+    started_at timestamptz,
+    picked_at timestamptz,
+    finished_at timestamptz,
+    constraint replay_job_pkey primary key (replay_job__id),
+    constraint replay_job_application__id_fkey foreign key (application__id) references event.application (application__id) on delete cascade on update cascade,
+    constraint replay_job_subscription__id_fkey foreign key (subscription__id) references webhook.subscription (subscription__id) on delete cascade on update cascade,
+    constraint replay_job_received_at_chk check (min_received_at <= max_received_at),
+    constraint replay_job_status_chk check (status in ('pending', 'running', 'completed', 'cancelled'))
+);
+
+create index replay_job_application__id_created_at_idx on event.replay_job (application__id, created_at);
+create index replay_job_pending_idx on event.replay_job (picked_at, created_at) where status in ('pending', 'running');
+
@@ -0,0 +1,11 @@
+create table event.idempotency_key (
+    application__id uuid not null,
+    idempotency_key text not null,
+    event__id uuid not null,
+    received_at timestamptz not null,
+    created_at timestamptz not null default statement_timestamp(),
+    constraint idempotency_key_pkey primary key (application__id, idempotency_key),
+    constraint idempotency_key_application__id_fkey foreign key (application__id) references event.application (application__id) on delete cascade on update cascade
+);
+
+create index idempotency_key_created_at_idx on event.idempotency_key (created_at);
@@ -0,0 +1,5 @@
+alter table webhook.target_http
+    drop column ca_certificates,
+    drop column client_certificate__id;
+
+drop table if exists webhook.client_certificate;
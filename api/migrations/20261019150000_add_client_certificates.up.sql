@@ -0,0 +1,19 @@
+create table webhook.client_certificate (
+    client_certificate__id uuid not null default public.gen_random_uuid(),
+    application__id uuid not null,
+    name text not null,
+    certificate text not null,
+    encrypted_private_key bytea not null,
+    created_at timestamptz not null default statement_timestamp(),
+    constraint client_certificate_pkey primary key (client_certificate__id),
+    constraint client_certificate_application__id_fkey foreign key (application__id) references event.application (application__id) on delete cascade on update cascade
+);
+
+create index client_certificate_application__id_created_at_idx on webhook.client_certificate (application__id, created_at);
+
+alter table webhook.target_http
+    add column client_certificate__id uuid,
+    add column ca_certificates text,
+    add constraint target_http_client_certificate__id_fkey foreign key (client_certificate__id) references webhook.client_certificate (client_certificate__id) on delete set null on update cascade;
+
+create index target_http_client_certificate__id_idx on webhook.target_http (client_certificate__id) where client_certificate__id is not null;
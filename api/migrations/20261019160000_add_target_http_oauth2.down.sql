@@ -0,0 +1,6 @@
+alter table webhook.target_http
+    drop constraint target_http_oauth2_check,
+    drop column oauth2_scopes,
+    drop column oauth2_encrypted_client_secret,
+    drop column oauth2_client_id,
+    drop column oauth2_token_url;
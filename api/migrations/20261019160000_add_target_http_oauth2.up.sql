@@ -0,0 +1,10 @@
+alter table webhook.target_http
+    add column oauth2_token_url text,
+    add column oauth2_client_id text,
+    add column oauth2_encrypted_client_secret bytea,
+    add column oauth2_scopes text[],
+    add constraint target_http_oauth2_check check (
+        (oauth2_token_url is null) = (oauth2_client_id is null)
+        and (oauth2_token_url is null) = (oauth2_encrypted_client_secret is null)
+        and (oauth2_token_url is null) = (oauth2_scopes is null)
+    );
@@ -0,0 +1,4 @@
+alter table webhook.subscription
+    drop constraint subscription_previous_secret_check,
+    drop column previous_secret_expires_at,
+    drop column previous_secret;
@@ -0,0 +1,4 @@
+alter table webhook.subscription
+    add column previous_secret uuid,
+    add column previous_secret_expires_at timestamptz,
+    add constraint subscription_previous_secret_check check ((previous_secret is null) = (previous_secret_expires_at is null));
@@ -0,0 +1 @@
+drop table webhook.signing_key;
@@ -0,0 +1,14 @@
+create table webhook.signing_key (
+    signing_key__id uuid not null default public.gen_random_uuid(),
+    application__id uuid not null,
+    public_key bytea not null,
+    encrypted_private_key bytea not null,
+    created_at timestamptz not null default statement_timestamp(),
+    expires_at timestamptz,
+    constraint signing_key_pkey primary key (signing_key__id),
+    constraint signing_key_application__id_fkey foreign key (application__id) references event.application (application__id) on delete cascade on update cascade
+);
+
+-- The key of an application that has no expiration date is the one webhooks are signed with
+create unique index signing_key_application__id_active_idx on webhook.signing_key (application__id) where expires_at is null;
+create index signing_key_application__id_created_at_idx on webhook.signing_key (application__id, created_at);
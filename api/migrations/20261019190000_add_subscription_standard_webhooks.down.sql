@@ -0,0 +1,3 @@
+alter table webhook.subscription
+    drop constraint subscription_standard_webhooks_check,
+    drop column standard_webhooks;
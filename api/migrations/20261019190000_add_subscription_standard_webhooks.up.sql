@@ -0,0 +1,3 @@
+alter table webhook.subscription
+    add column standard_webhooks text,
+    add constraint subscription_standard_webhooks_check check (standard_webhooks in ('in_addition', 'instead'));
@@ -0,0 +1,14 @@
+alter table webhook.target_http
+    drop column encrypted_headers;
+
+alter table webhook.subscription
+    drop constraint subscription_previous_secret_check,
+    drop constraint subscription_encrypted_secret_check,
+    drop column encrypted_previous_secret,
+    drop column encrypted_secret,
+    alter column secret set not null,
+    add constraint subscription_previous_secret_check check ((previous_secret is null) = (previous_secret_expires_at is null));
+
+drop function webhook.application_data_key(uuid, text);
+
+drop table webhook.application_data_key;
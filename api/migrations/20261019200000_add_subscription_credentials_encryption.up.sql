@@ -0,0 +1,56 @@
+-- Data keys encrypt the secrets of subscriptions and the sensitive headers of their targets; they are themselves encrypted with the key of the instance (key encryption key)
+create table webhook.application_data_key (
+    application__id uuid not null,
+    encrypted_data_key bytea not null,
+    created_at timestamptz not null default statement_timestamp(),
+    constraint application_data_key_pkey primary key (application__id),
+    constraint application_data_key_application__id_fkey foreign key (application__id) references event.application (application__id) on delete cascade on update cascade
+);
+
+-- Returns the data key of an application, generating it if the application does not have one yet
+create function webhook.application_data_key(application_id uuid, key_encryption_key text)
+    returns text
+    language plpgsql
+    strict
+as
+$$
+declare
+    data_key text;
+begin
+    select public.pgp_sym_decrypt(dk.encrypted_data_key, key_encryption_key) into data_key
+    from webhook.application_data_key as dk
+    where dk.application__id = application_id;
+
+    if data_key is null then
+        data_key := encode(public.gen_random_bytes(32), 'base64');
+        insert into webhook.application_data_key (application__id, encrypted_data_key)
+        values (application_id, public.pgp_sym_encrypt(data_key, key_encryption_key))
+        on conflict (application__id) do nothing;
+
+        -- Another transaction generated the data key in the meantime
+        if not found then
+            select public.pgp_sym_decrypt(dk.encrypted_data_key, key_encryption_key) into data_key
+            from webhook.application_data_key as dk
+            where dk.application__id = application_id;
+        end if;
+    end if;
+
+    return data_key;
+end;
+$$;
+
+-- Secrets are stored either in plaintext (if the instance has no key encryption key) or encrypted with the data key of the application
+alter table webhook.subscription
+    alter column secret drop not null,
+    add column encrypted_secret bytea,
+    add column encrypted_previous_secret bytea,
+    add constraint subscription_encrypted_secret_check check ((secret is null) <> (encrypted_secret is null)),
+    drop constraint subscription_previous_secret_check,
+    add constraint subscription_previous_secret_check check (
+        (previous_secret is null or encrypted_previous_secret is null)
+        and ((previous_secret is null and encrypted_previous_secret is null) = (previous_secret_expires_at is null))
+    );
+
+-- Values of sensitive headers, encrypted with the data key of the application and encoded in base64; `headers` holds a placeholder for each of them
+alter table webhook.target_http
+    add column encrypted_headers jsonb not null default '{}'::jsonb;
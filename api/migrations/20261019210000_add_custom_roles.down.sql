@@ -0,0 +1,10 @@
+delete from iam.user__organization where role = 'custom';
+
+alter table iam.user__organization
+    drop constraint user__organization_custom_role__id_fkey,
+    drop constraint user__organization_custom_role__id_chk,
+    drop constraint user__organization_role_chk,
+    add constraint user__organization_role_chk check (role in ('editor', 'viewer')),
+    drop column custom_role__id;
+
+drop table iam.custom_role;
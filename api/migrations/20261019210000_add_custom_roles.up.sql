@@ -0,0 +1,21 @@
+create table iam.custom_role (
+    custom_role__id uuid not null default public.gen_random_uuid(),
+    organization__id uuid not null,
+    name text not null,
+    actions text[] not null,
+    -- NULL means that the actions are granted on all the applications of the organization
+    application__ids uuid[],
+    created_at timestamptz not null default statement_timestamp(),
+    constraint custom_role_pkey primary key (custom_role__id),
+    constraint custom_role_organization__id_fkey foreign key (organization__id) references iam.organization (organization__id) on delete cascade on update cascade,
+    constraint custom_role_organization__id_name_key unique (organization__id, name),
+    constraint custom_role_organization__id_custom_role__id_key unique (organization__id, custom_role__id),
+    constraint custom_role_name_chk check (length(name) > 1)
+);
+
+alter table iam.user__organization
+    add column custom_role__id uuid,
+    drop constraint user__organization_role_chk,
+    add constraint user__organization_role_chk check (role in ('editor', 'viewer', 'custom')),
+    add constraint user__organization_custom_role__id_chk check ((role = 'custom') = (custom_role__id is not null)),
+    add constraint user__organization_custom_role__id_fkey foreign key (organization__id, custom_role__id) references iam.custom_role (organization__id, custom_role__id) on delete restrict on update cascade;
@@ -0,0 +1 @@
+alter table webhook.request_attempt drop column cancelled_at;
@@ -0,0 +1,11 @@
+-- Request attempts of cancelled events also get a failed_at date, so that everything that only looks for request attempts that are not done yet keeps ignoring them
+alter table webhook.request_attempt add column cancelled_at timestamptz;
+
+update webhook.request_attempt as ra
+set cancelled_at = ra.failed_at
+from event.event as e
+where e.event__id = ra.event__id
+  and e.delivery_cancelled_at is not null
+  and ra.succeeded_at is null
+  and ra.response__id is null
+  and ra.failed_at >= e.delivery_cancelled_at;
@@ -0,0 +1 @@
+drop function webhook.pending_ordered_predecessors(webhook.subscription, event.event);
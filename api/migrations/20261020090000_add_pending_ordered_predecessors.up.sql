@@ -0,0 +1,19 @@
+-- Request attempts of a subscription with ordered delivery that must be done before the given event is delivered (previous events in the same ordering sequence)
+-- Events held until a later delivery date do not block the events received after them until that date
+create function webhook.pending_ordered_predecessors(s webhook.subscription, e event.event)
+    returns setof webhook.request_attempt
+    language sql
+    stable
+as
+$$
+select ra_prev.*
+from webhook.request_attempt as ra_prev
+inner join event.event as e_prev on e_prev.event__id = ra_prev.event__id
+where s.ordered_delivery
+  and ra_prev.subscription__id = s.subscription__id
+  and ra_prev.succeeded_at is null
+  and ra_prev.failed_at is null
+  and (e_prev.received_at, e_prev.event__id) < (e.received_at, e.event__id)
+  and (e_prev.deliver_at is null or e_prev.deliver_at <= statement_timestamp())
+  and (s.ordering_key is null or e_prev.labels ->> s.ordering_key is not distinct from e.labels ->> s.ordering_key);
+$$;
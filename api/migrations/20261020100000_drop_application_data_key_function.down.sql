@@ -0,0 +1,31 @@
+-- Returns the data key of an application, generating it if the application does not have one yet
+create function webhook.application_data_key(application_id uuid, key_encryption_key text)
+    returns text
+    language plpgsql
+    strict
+as
+$$
+declare
+    data_key text;
+begin
+    select public.pgp_sym_decrypt(dk.encrypted_data_key, key_encryption_key) into data_key
+    from webhook.application_data_key as dk
+    where dk.application__id = application_id;
+
+    if data_key is null then
+        data_key := encode(public.gen_random_bytes(32), 'base64');
+        insert into webhook.application_data_key (application__id, encrypted_data_key)
+        values (application_id, public.pgp_sym_encrypt(data_key, key_encryption_key))
+        on conflict (application__id) do nothing;
+
+        -- Another transaction generated the data key in the meantime
+        if not found then
+            select public.pgp_sym_decrypt(dk.encrypted_data_key, key_encryption_key) into data_key
+            from webhook.application_data_key as dk
+            where dk.application__id = application_id;
+        end if;
+    end if;
+
+    return data_key;
+end;
+$$;
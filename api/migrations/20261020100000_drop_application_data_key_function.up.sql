@@ -0,0 +1,3 @@
+-- Target credentials are now encrypted and decrypted with AES-256-GCM by the API and output workers, so that the key of the instance is never sent to the database
+-- Credentials that were encrypted with pgcrypto before this migration cannot be decrypted anymore and must be set again
+drop function webhook.application_data_key(uuid, text);
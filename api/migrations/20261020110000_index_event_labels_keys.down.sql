@@ -0,0 +1,2 @@
+drop index if exists event.event_labels_idx;
+create index event_labels_idx on event.event using gin (labels jsonb_path_ops);
@@ -0,0 +1,3 @@
+-- The default operator class also supports the `?` operator, which is used to filter events by label key
+drop index if exists event.event_labels_idx;
+create index event_labels_idx on event.event using gin (labels);
@@ -0,0 +1,558 @@
+use actix_web::rt::time::sleep;
+use aes_gcm::aead::{Aead, Generate};
+use aes_gcm::{Aes256Gcm, KeyInit, Nonce};
+use base64::Engine;
+use base64::engine::general_purpose::STANDARD as Base64;
+use serde_json::Value;
+use sqlx::{Acquire, PgPool, Postgres, query, query_as, query_scalar};
+use std::collections::HashMap;
+use std::collections::hash_map::Entry;
+use std::str::FromStr;
+use std::time::{Duration, Instant};
+use thiserror::Error;
+use thousands::Separable;
+use tokio::sync::Semaphore;
+use tracing::{error, info, trace};
+use uuid::Uuid;
+
+use crate::handlers::subscriptions::{REDACTED_HEADER_VALUE, StoredTargetHeaders};
+use crate::humanize::humanize_duration;
+
+const STARTUP_GRACE_PERIOD: Duration = Duration::from_secs(20);
+
+/// Size of the nonce that is stored before each ciphertext
+const NONCE_SIZE: usize = 12;
+
+/// AES-256-GCM key that encrypts target credentials (key encryption key of the instance or data key of an application)
+///
+/// Encryption happens in the API and decryption in output workers, so that keys are never sent to the database, which only stores ciphertexts.
+#[derive(Clone)]
+pub struct EncryptionKey([u8; 32]);
+
+impl EncryptionKey {
+    /// Generate a random key
+    pub fn generate() -> Self {
+        Self(<[u8; 32]>::generate())
+    }
+
+    /// Encrypt data with a random nonce, which is stored before the ciphertext
+    pub fn encrypt(&self, plaintext: &[u8]) -> Vec<u8> {
+        let nonce = Nonce::generate();
+        let ciphertext = Aes256Gcm::new(&self.0.into())
+            .encrypt(&nonce, plaintext)
+            .expect("AES-256-GCM encryption failed");
+
+        let mut data = nonce.to_vec();
+        data.extend(ciphertext);
+        data
+    }
+
+    /// Decrypt data that was encrypted with [`EncryptionKey::encrypt`]
+    ///
+    /// Returns `None` if the data was not encrypted with this key or was tampered with.
+    pub fn decrypt(&self, data: &[u8]) -> Option<Vec<u8>> {
+        let (nonce, ciphertext) = data.split_at_checked(NONCE_SIZE)?;
+        let nonce: [u8; NONCE_SIZE] = nonce.try_into().ok()?;
+        Aes256Gcm::new(&self.0.into())
+            .decrypt(&nonce.into(), ciphertext)
+            .ok()
+    }
+
+    fn decrypt_key(&self, encrypted_key: &[u8]) -> Option<Self> {
+        self.decrypt(encrypted_key)
+            .and_then(|key| <[u8; 32]>::try_from(key.as_slice()).ok())
+            .map(Self)
+    }
+}
+
+impl FromStr for EncryptionKey {
+    type Err = String;
+
+    /// Parse a key encoded in base64 (for example generated with `openssl rand -base64 32`)
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let key = Base64
+            .decode(s.trim())
+            .map_err(|e| format!("key is not valid base64: {e}"))?;
+        <[u8; 32]>::try_from(key.as_slice())
+            .map(Self)
+            .map_err(|_| format!("key must be 32 bytes long, not {}", key.len()))
+    }
+}
+
+impl std::fmt::Debug for EncryptionKey {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_tuple("EncryptionKey").field(&"[REDACTED]").finish()
+    }
+}
+
+#[derive(Debug, Error)]
+pub enum CredentialsEncryptionError {
+    #[error(transparent)]
+    Database(#[from] sqlx::Error),
+    #[error("Could not decrypt {0}; the key used to encrypt target credentials may be wrong")]
+    Decryption(String),
+}
+
+/// Get the data key of an application, generating it if the application does not have one yet
+///
+/// Data keys encrypt the secrets of subscriptions and the sensitive headers of their targets; they are stored encrypted with the key of the instance.
+pub async fn application_data_key<'a, A: Acquire<'a, Database = Postgres>>(
+    db: A,
+    key_encryption_key: &EncryptionKey,
+    application_id: &Uuid,
+) -> Result<EncryptionKey, CredentialsEncryptionError> {
+    let mut conn = db.acquire().await?;
+
+    let encrypted_data_key = query_scalar!(
+        "
+            SELECT encrypted_data_key
+            FROM webhook.application_data_key
+            WHERE application__id = $1
+        ",
+        application_id,
+    )
+    .fetch_optional(&mut *conn)
+    .await?;
+    let encrypted_data_key = match encrypted_data_key {
+        Some(encrypted_data_key) => encrypted_data_key,
+        None => {
+            let data_key = EncryptionKey::generate();
+            let inserted = query!(
+                "
+                    INSERT INTO webhook.application_data_key (application__id, encrypted_data_key)
+                    VALUES ($1, $2)
+                    ON CONFLICT (application__id) DO NOTHING
+                ",
+                application_id,
+                key_encryption_key.encrypt(&data_key.0),
+            )
+            .execute(&mut *conn)
+            .await?;
+            if inserted.rows_affected() > 0 {
+                return Ok(data_key);
+            }
+
+            // Another transaction generated the data key in the meantime
+            query_scalar!(
+                "
+                    SELECT encrypted_data_key
+                    FROM webhook.application_data_key
+                    WHERE application__id = $1
+                ",
+                application_id,
+            )
+            .fetch_one(&mut *conn)
+            .await?
+        }
+    };
+
+    key_encryption_key
+        .decrypt_key(&encrypted_data_key)
+        .ok_or_else(|| {
+            CredentialsEncryptionError::Decryption(format!(
+                "the data key of application {application_id}"
+            ))
+        })
+}
+
+/// Encrypt the subscription secrets and sensitive target headers that were stored in plaintext, once, after the API started
+///
+/// They can be left from before the instance was configured with a key to encrypt credentials.
+pub async fn encrypt_plaintext_credentials(
+    housekeeping_semaphore: &Semaphore,
+    db: &PgPool,
+    encryption_key: &EncryptionKey,
+) {
+    sleep(STARTUP_GRACE_PERIOD).await;
+
+    if let Ok(permit) = housekeeping_semaphore.acquire().await {
+        let mut data_keys = DataKeys::new(encryption_key);
+        if let Err(e) = encrypt_subscription_secrets(db, &mut data_keys).await {
+            error!("Could not encrypt plaintext subscription secrets: {e}");
+        }
+        if let Err(e) = encrypt_target_headers(db, &mut data_keys).await {
+            error!("Could not encrypt plaintext target headers: {e}");
+        }
+        drop(permit);
+    }
+}
+
+/// Data keys of applications, so that they are only decrypted once
+struct DataKeys<'a> {
+    key_encryption_key: &'a EncryptionKey,
+    applications: HashMap<Uuid, EncryptionKey>,
+}
+
+impl<'a> DataKeys<'a> {
+    fn new(key_encryption_key: &'a EncryptionKey) -> Self {
+        Self {
+            key_encryption_key,
+            applications: HashMap::new(),
+        }
+    }
+
+    async fn get(
+        &mut self,
+        db: &PgPool,
+        application_id: Uuid,
+    ) -> Result<&EncryptionKey, CredentialsEncryptionError> {
+        match self.applications.entry(application_id) {
+            Entry::Occupied(e) => Ok(e.into_mut()),
+            Entry::Vacant(e) => Ok(
+                e.insert(application_data_key(db, self.key_encryption_key, &application_id).await?)
+            ),
+        }
+    }
+}
+
+async fn encrypt_subscription_secrets(
+    db: &PgPool,
+    data_keys: &mut DataKeys<'_>,
+) -> Result<(), CredentialsEncryptionError> {
+    trace!("Encrypting plaintext subscription secrets...");
+    let start = Instant::now();
+
+    #[allow(non_snake_case)]
+    struct Subscription {
+        subscription__id: Uuid,
+        application__id: Uuid,
+        secret: Option<Uuid>,
+        previous_secret: Option<Uuid>,
+    }
+
+    let subscriptions = query_as!(
+        Subscription,
+        "
+            SELECT subscription__id, application__id, secret, previous_secret
+            FROM webhook.subscription
+            WHERE secret IS NOT NULL OR previous_secret IS NOT NULL
+        ",
+    )
+    .fetch_all(db)
+    .await?;
+
+    let mut encrypted_subscriptions = 0u64;
+    for subscription in subscriptions {
+        let data_key = data_keys.get(db, subscription.application__id).await?;
+
+        // Secrets are only replaced if they were not changed in the meantime
+        let res = query!(
+            "
+                UPDATE webhook.subscription
+                SET secret = NULL,
+                    encrypted_secret = COALESCE($4, encrypted_secret),
+                    previous_secret = NULL,
+                    encrypted_previous_secret = COALESCE($5, encrypted_previous_secret)
+                WHERE subscription__id = $1
+                    AND secret IS NOT DISTINCT FROM $2
+                    AND previous_secret IS NOT DISTINCT FROM $3
+            ",
+            &subscription.subscription__id,
+            subscription.secret,
+            subscription.previous_secret,
+            subscription
+                .secret
+                .map(|secret| data_key.encrypt(secret.as_bytes())),
+            subscription
+                .previous_secret
+                .map(|previous_secret| data_key.encrypt(previous_secret.as_bytes())),
+        )
+        .execute(db)
+        .await?;
+        encrypted_subscriptions += res.rows_affected();
+    }
+
+    if encrypted_subscriptions > 0 {
+        info!(
+            "Encrypted the secrets of {} subscriptions in {}",
+            encrypted_subscriptions.separate_with_commas(),
+            humanize_duration(start.elapsed()),
+        );
+    }
+    Ok(())
+}
+
+async fn encrypt_target_headers(
+    db: &PgPool,
+    data_keys: &mut DataKeys<'_>,
+) -> Result<(), CredentialsEncryptionError> {
+    trace!("Encrypting plaintext target headers...");
+    let start = Instant::now();
+
+    #[allow(non_snake_case)]
+    struct Target {
+        target__id: Uuid,
+        application__id: Uuid,
+        headers: Value,
+        encrypted_headers: Value,
+    }
+
+    // Only targets with at least one header that is not a redaction placeholder can have sensitive headers to encrypt
+    let targets = query_as!(
+        Target,
+        "
+            SELECT t_http.target__id, s.application__id, t_http.headers, t_http.encrypted_headers
+            FROM webhook.target_http AS t_http
+            INNER JOIN webhook.subscription AS s ON s.target__id = t_http.target__id
+            WHERE EXISTS (
+                SELECT 1
+                FROM jsonb_each(t_http.headers) AS h
+                WHERE h.value <> to_jsonb($1::text)
+            )
+        ",
+        REDACTED_HEADER_VALUE,
+    )
+    .fetch_all(db)
+    .await?;
+
+    let mut encrypted_targets = 0u64;
+    for target in targets {
+        let mut headers = StoredTargetHeaders::new(
+            &target.headers,
+            true,
+            Some((&target.headers, &target.encrypted_headers)),
+        );
+        if headers.headers_to_encrypt.is_empty() {
+            continue;
+        }
+        headers.encrypt(data_keys.get(db, target.application__id).await?);
+
+        // Headers are only replaced if they were not changed in the meantime
+        let res = query!(
+            "
+                UPDATE webhook.target_http
+                SET headers = $2, encrypted_headers = $3
+                WHERE target__id = $1 AND headers = $4
+            ",
+            &target.target__id,
+            Value::Object(headers.headers),
+            Value::Object(headers.encrypted_headers),
+            &target.headers,
+        )
+        .execute(db)
+        .await?;
+        encrypted_targets += res.rows_affected();
+    }
+
+    if encrypted_targets > 0 {
+        info!(
+            "Encrypted the sensitive headers of {} targets in {}",
+            encrypted_targets.separate_with_commas(),
+            humanize_duration(start.elapsed()),
+        );
+    }
+    Ok(())
+}
+
+/// Decrypt data with the current key of the instance and encrypt it with the new one
+fn reencrypt(
+    current_key: &EncryptionKey,
+    new_key: &EncryptionKey,
+    encrypted: &[u8],
+    what: impl FnOnce() -> String,
+) -> Result<Vec<u8>, CredentialsEncryptionError> {
+    current_key
+        .decrypt(encrypted)
+        .map(|plaintext| new_key.encrypt(&plaintext))
+        .ok_or_else(|| CredentialsEncryptionError::Decryption(what()))
+}
+
+/// Re-encrypt everything that is encrypted with the instance key with a new key, in a single transaction
+///
+/// Subscription secrets and target headers are encrypted with the data keys of their applications, so only these data keys need to be re-encrypted.
+pub async fn rotate_encryption_key(
+    db: &PgPool,
+    current_key: &EncryptionKey,
+    new_key: &EncryptionKey,
+) -> Result<(), CredentialsEncryptionError> {
+    info!("Re-encrypting target credentials with the new key...");
+    let start = Instant::now();
+    let mut tx = db.begin().await?;
+
+    let data_keys = query!(
+        "
+            SELECT application__id, encrypted_data_key
+            FROM webhook.application_data_key
+            FOR UPDATE
+        ",
+    )
+    .fetch_all(&mut *tx)
+    .await?;
+    for data_key in &data_keys {
+        let reencrypted = reencrypt(current_key, new_key, &data_key.encrypted_data_key, || {
+            format!("the data key of application {}", data_key.application__id)
+        })?;
+        query!(
+            "
+                UPDATE webhook.application_data_key
+                SET encrypted_data_key = $2
+                WHERE application__id = $1
+            ",
+            &data_key.application__id,
+            reencrypted,
+        )
+        .execute(&mut *tx)
+        .await?;
+    }
+
+    let client_certificates = query!(
+        "
+            SELECT client_certificate__id, encrypted_private_key
+            FROM webhook.client_certificate
+            FOR UPDATE
+        ",
+    )
+    .fetch_all(&mut *tx)
+    .await?;
+    for client_certificate in &client_certificates {
+        let reencrypted = reencrypt(
+            current_key,
+            new_key,
+            &client_certificate.encrypted_private_key,
+            || {
+                format!(
+                    "the private key of client certificate {}",
+                    client_certificate.client_certificate__id
+                )
+            },
+        )?;
+        query!(
+            "
+                UPDATE webhook.client_certificate
+                SET encrypted_private_key = $2
+                WHERE client_certificate__id = $1
+            ",
+            &client_certificate.client_certificate__id,
+            reencrypted,
+        )
+        .execute(&mut *tx)
+        .await?;
+    }
+
+    let oauth2_client_secrets = query!(
+        r#"
+            SELECT target__id, oauth2_encrypted_client_secret AS "oauth2_encrypted_client_secret!"
+            FROM webhook.target_http
+            WHERE oauth2_encrypted_client_secret IS NOT NULL
+            FOR UPDATE
+        "#,
+    )
+    .fetch_all(&mut *tx)
+    .await?;
+    for oauth2_client_secret in &oauth2_client_secrets {
+        let reencrypted = reencrypt(
+            current_key,
+            new_key,
+            &oauth2_client_secret.oauth2_encrypted_client_secret,
+            || {
+                format!(
+                    "the OAuth2 client secret of target {}",
+                    oauth2_client_secret.target__id
+                )
+            },
+        )?;
+        query!(
+            "
+                UPDATE webhook.target_http
+                SET oauth2_encrypted_client_secret = $2
+                WHERE target__id = $1
+            ",
+            &oauth2_client_secret.target__id,
+            reencrypted,
+        )
+        .execute(&mut *tx)
+        .await?;
+    }
+
+    let signing_keys = query!(
+        "
+            SELECT signing_key__id, encrypted_private_key
+            FROM webhook.signing_key
+            FOR UPDATE
+        ",
+    )
+    .fetch_all(&mut *tx)
+    .await?;
+    for signing_key in &signing_keys {
+        let reencrypted = reencrypt(
+            current_key,
+            new_key,
+            &signing_key.encrypted_private_key,
+            || format!("signing key {}", signing_key.signing_key__id),
+        )?;
+        query!(
+            "
+                UPDATE webhook.signing_key
+                SET encrypted_private_key = $2
+                WHERE signing_key__id = $1
+            ",
+            &signing_key.signing_key__id,
+            reencrypted,
+        )
+        .execute(&mut *tx)
+        .await?;
+    }
+
+    tx.commit().await?;
+    info!(
+        "Re-encrypted {} application data keys, {} client certificates, {} OAuth2 client secrets and {} signing keys in {}; the API and output workers must now use the new key",
+        data_keys.len().separate_with_commas(),
+        client_certificates.len().separate_with_commas(),
+        oauth2_client_secrets.len().separate_with_commas(),
+        signing_keys.len().separate_with_commas(),
+        humanize_duration(start.elapsed()),
+    );
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn encrypt_and_decrypt() {
+        let key = EncryptionKey::generate();
+        let data = key.encrypt(b"secret");
+
+        assert_eq!(key.decrypt(&data).as_deref(), Some(b"secret".as_slice()));
+        assert_ne!(key.encrypt(b"secret"), data, "nonces must be random");
+        assert_eq!(EncryptionKey::generate().decrypt(&data), None);
+        assert_eq!(key.decrypt(&data[..NONCE_SIZE]), None);
+
+        let mut tampered = data.clone();
+        *tampered.last_mut().unwrap() ^= 1;
+        assert_eq!(key.decrypt(&tampered), None);
+
+        let data_key = EncryptionKey::generate();
+        let encrypted_data_key = key.encrypt(&data_key.0);
+        assert_eq!(key.decrypt_key(&encrypted_data_key).unwrap().0, data_key.0);
+        assert!(key.decrypt_key(&data).is_none());
+    }
+
+    #[test]
+    fn reencrypt_with_new_key() {
+        let current_key = EncryptionKey::generate();
+        let new_key = EncryptionKey::generate();
+        let data = current_key.encrypt(b"private key");
+
+        let reencrypted = reencrypt(&current_key, &new_key, &data, String::new).unwrap();
+        assert_eq!(
+            new_key.decrypt(&reencrypted).as_deref(),
+            Some(b"private key".as_slice())
+        );
+        assert!(matches!(
+            reencrypt(&new_key, &current_key, &data, || "data".to_owned()),
+            Err(CredentialsEncryptionError::Decryption(what)) if what == "data"
+        ));
+    }
+
+    #[test]
+    fn parse_key() {
+        let key = EncryptionKey::from_str(&Base64.encode([4; 32])).unwrap();
+        assert_eq!(key.0, [4; 32]);
+        assert_eq!(format!("{key:?}"), r#"EncryptionKey("[REDACTED]")"#);
+
+        assert!(EncryptionKey::from_str(&Base64.encode([4; 16])).is_err());
+        assert!(EncryptionKey::from_str("not base64!").is_err());
+    }
+}
@@ -0,0 +1,157 @@
+use actix_web::rt::time::sleep;
+use lettre::Address;
+use lettre::message::Mailbox;
+use sqlx::{PgPool, query_as};
+use std::str::FromStr;
+use std::time::Duration;
+use tracing::{error, info, trace};
+use url::Url;
+use uuid::Uuid;
+
+use crate::mailer::{Mail, Mailer};
+
+const STARTUP_GRACE_PERIOD: Duration = Duration::from_secs(50);
+
+pub async fn periodically_notify_disabled_subscriptions(
+    db: &PgPool,
+    mailer: &Mailer,
+    app_url: &Url,
+    period: Duration,
+) {
+    sleep(STARTUP_GRACE_PERIOD).await;
+
+    loop {
+        if let Err(e) = notify_disabled_subscriptions(db, mailer, app_url).await {
+            error!("Could not notify about disabled subscriptions: {e}");
+        }
+
+        sleep(period).await;
+    }
+}
+
+async fn notify_disabled_subscriptions(
+    db: &PgPool,
+    mailer: &Mailer,
+    app_url: &Url,
+) -> Result<(), sqlx::Error> {
+    trace!("Looking for disabled subscriptions to notify about...");
+
+    #[allow(non_snake_case)]
+    struct DisabledSubscription {
+        subscription__id: Uuid,
+        description: Option<String>,
+        disabled_reason: Option<String>,
+        application__id: Uuid,
+        application_name: String,
+        organization__id: Uuid,
+        target_url: String,
+    }
+    // Subscriptions are claimed before sending emails so that several API instances do not notify about the same subscription
+    // They stay marked as notified even if some emails could not be sent, so that users are not spammed in case of a persistent SMTP error
+    let subscriptions = query_as!(
+        DisabledSubscription,
+        "
+            UPDATE webhook.subscription AS s
+            SET disabled_notified_at = statement_timestamp()
+            FROM event.application AS a, webhook.target_http AS t
+            WHERE a.application__id = s.application__id
+                AND t.target__id = s.target__id
+                AND s.disabled_at IS NOT NULL
+                AND s.disabled_notified_at IS NULL
+                AND s.deleted_at IS NULL
+                AND a.deleted_at IS NULL
+            RETURNING s.subscription__id, s.description, s.disabled_reason, a.application__id, a.name AS application_name, a.organization__id, t.url AS target_url
+        ",
+    )
+    .fetch_all(db)
+    .await?;
+
+    for subscription in subscriptions {
+        struct User {
+            first_name: String,
+            last_name: String,
+            email: String,
+        }
+        // Only members who can enable the subscription again are notified
+        let users = match query_as!(
+            User,
+            "
+                SELECT u.first_name, u.last_name, u.email
+                FROM iam.user AS u
+                INNER JOIN iam.user__organization AS ou ON ou.user__id = u.user__id
+                LEFT JOIN iam.custom_role AS cr ON cr.organization__id = ou.organization__id AND cr.custom_role__id = ou.custom_role__id
+                WHERE ou.organization__id = $1
+                    AND (
+                        ou.role = 'editor'
+                        OR (
+                            ou.role = 'custom'
+                            AND 'subscription:edit' = ANY(cr.actions)
+                            AND (cr.application__ids IS NULL OR $2 = ANY(cr.application__ids))
+                        )
+                    )
+            ",
+            subscription.organization__id,
+            subscription.application__id,
+        )
+        .fetch_all(db)
+        .await
+        {
+            Ok(users) => users,
+            Err(e) => {
+                // The subscription is already claimed, so the other ones must still be notified
+                error!(
+                    subscription_id = %subscription.subscription__id,
+                    "Could not fetch users to notify about disabled subscription: {e}"
+                );
+                continue;
+            }
+        };
+
+        let mut url = app_url.clone();
+        url.set_path(&format!(
+            "/organizations/{}/applications/{}/subscriptions/{}",
+            subscription.organization__id,
+            subscription.application__id,
+            subscription.subscription__id
+        ));
+
+        for user in users {
+            let recipient_address = match Address::from_str(&user.email) {
+                Ok(address) => address,
+                Err(e) => {
+                    error!("Error trying to parse email address: {e}");
+                    continue;
+                }
+            };
+            let recipient = Mailbox::new(
+                Some(format!("{} {}", user.first_name, user.last_name)),
+                recipient_address,
+            );
+
+            let mail = Mail::SubscriptionDisabled {
+                recipient_first_name: Some(user.first_name),
+                application_name: subscription.application_name.to_owned(),
+                subscription_description: subscription
+                    .description
+                    .to_owned()
+                    .unwrap_or_else(|| subscription.subscription__id.to_string()),
+                target_url: subscription.target_url.to_owned(),
+                disabled_reason: subscription
+                    .disabled_reason
+                    .to_owned()
+                    .unwrap_or_else(|| "deliveries kept failing".to_owned()),
+                url: url.clone(),
+            };
+            if let Err(e) = mailer.send_mail(mail, recipient).await {
+                error!("Error trying to send email: {e}");
+            }
+        }
+
+        info!(
+            subscription_id = %subscription.subscription__id,
+            "Notified organization editors that subscription was disabled"
+        );
+    }
+
+    Ok(())
+}
@@ -0,0 +1,40 @@
+use actix_web::FromRequest;
+use futures_util::future::{Ready, ready};
+use paperclip::actix::OperationModifier;
+use paperclip::v2::schema::Apiv2Schema;
+
+use crate::problems::Hook0Problem;
+
+pub const IDEMPOTENCY_KEY_HEADER: &str = "Idempotency-Key";
+
+#[derive(Debug, Clone, Default)]
+/// Extractor for the optional `Idempotency-Key` header
+pub struct IdempotencyKey(pub Option<String>);
+
+impl IdempotencyKey {
+    pub fn into_inner(self) -> Option<String> {
+        self.0
+    }
+}
+
+impl FromRequest for IdempotencyKey {
+    type Error = Hook0Problem;
+
+    type Future = Ready<Result<Self, Self::Error>>;
+
+    fn from_request(
+        req: &actix_web::HttpRequest,
+        _payload: &mut actix_web::dev::Payload,
+    ) -> Self::Future {
+        ready(match req.headers().get(IDEMPOTENCY_KEY_HEADER) {
+            None => Ok(Self(None)),
+            Some(value) => value
+                .to_str()
+                .map(|key| Self(Some(key.to_owned())))
+                .map_err(|_| Hook0Problem::InvalidIdempotencyKey),
+        })
+    }
+}
+
+impl Apiv2Schema for IdempotencyKey {}
+impl OperationModifier for IdempotencyKey {}
@@ -785,6 +785,7 @@ pub(crate) mod test_support {
             "E2E",
             "User",
             vec![(org, role.to_string())],
+            vec![],
         )
         .expect("mint user access token");
 
@@ -875,6 +876,9 @@ pub(crate) mod test_support {
             health_check_timeout: Duration::from_secs(5),
             max_authorization_time: Duration::from_secs(10),
             debug_authorizer: false,
+            idempotency_window: Duration::from_secs(24 * 60 * 60),
+            max_events_per_batch: 1000,
+            target_credentials_encryption_key: None,
             enable_quota_enforcement: false,
             matomo_url: None,
             matomo_site_id: None,
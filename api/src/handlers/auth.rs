@@ -16,9 +16,9 @@ use uuid::Uuid;
 use validator::Validate;
 
 use crate::iam::{
-    Action, authorize_email_verification, authorize_only_user, authorize_refresh_token,
-    authorize_reset_password, create_refresh_token, create_reset_password_token,
-    create_user_access_token,
+    Action, CustomRoleGrant, authorize_email_verification, authorize_only_user,
+    authorize_refresh_token, authorize_reset_password, create_refresh_token,
+    create_reset_password_token, create_user_access_token,
 };
 use crate::mailer::Mail;
 use crate::openapi::{OaBiscuitRefresh, OaBiscuitUserAccess};
@@ -320,6 +320,20 @@ async fn do_login<'a, A: Acquire<'a, Database = Postgres>>(
     .map(|or| (or.organization_id, or.role))
     .collect::<Vec<_>>();
 
+    let custom_roles = query_as!(
+        CustomRoleGrant,
+        "
+            SELECT cr.organization__id AS organization_id, cr.custom_role__id AS custom_role_id, cr.actions, cr.application__ids AS application_ids
+            FROM iam.user__organization AS uo
+            INNER JOIN iam.custom_role AS cr ON cr.custom_role__id = uo.custom_role__id
+            WHERE uo.user__id = $1
+        ",
+        &user.user_id,
+    )
+    .fetch_all(&mut *db)
+    .await
+    .map_err(Hook0Problem::from)?;
+
     let session_id = session_id.unwrap_or_else(Uuid::new_v4);
     let access_token_id = Uuid::new_v4();
     let (access_token, access_token_expiration) = create_user_access_token(
@@ -331,6 +345,7 @@ async fn do_login<'a, A: Acquire<'a, Database = Postgres>>(
         &user.first_name,
         &user.last_name,
         roles,
+        custom_roles,
     )
     .and_then(|rt| {
         if let Some(expired_at) = rt.expired_at {
@@ -0,0 +1,223 @@
+use actix_web::web::ReqData;
+use biscuit_auth::Biscuit;
+use chrono::{DateTime, Utc};
+use paperclip::actix::web::{Data, Json, Path, Query};
+use paperclip::actix::{Apiv2Schema, CreatedJson, NoContent, api_v2_operation};
+use reqwest::{Certificate, Identity};
+use serde::{Deserialize, Serialize};
+use sqlx::{query, query_as};
+use uuid::Uuid;
+use validator::Validate;
+
+use crate::iam::{Action, authorize_for_application};
+use crate::openapi::OaBiscuit;
+use crate::problems::Hook0Problem;
+
+/// Certificate that subscriptions can present to their targets to authenticate with mutual TLS
+///
+/// The private key is never returned; it is stored encrypted and only decrypted by output workers.
+#[derive(Debug, Serialize, Apiv2Schema)]
+pub struct ClientCertificate {
+    client_certificate_id: Uuid,
+    application_id: Uuid,
+    name: String,
+    /// PEM-encoded certificate chain (leaf certificate first)
+    certificate: String,
+    created_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Apiv2Schema)]
+pub struct Qs {
+    application_id: Uuid,
+}
+
+#[derive(Debug, Deserialize, Apiv2Schema, Validate)]
+pub struct ClientCertificatePost {
+    application_id: Uuid,
+    #[validate(non_control_character, length(min = 1, max = 50))]
+    name: String,
+    /// PEM-encoded certificate chain (leaf certificate first)
+    #[validate(length(min = 1, max = 65536))]
+    certificate: String,
+    /// PEM-encoded private key of the certificate (PKCS#8, PKCS#1 or SEC1)
+    #[validate(length(min = 1, max = 65536))]
+    private_key: String,
+}
+
+#[api_v2_operation(
+    summary = "List client certificates",
+    description = "Lists the client certificates of an application. Private keys are never returned.",
+    operation_id = "clientCertificates.list",
+    consumes = "application/json",
+    produces = "application/json",
+    tags("Subscriptions Management")
+)]
+pub async fn list(
+    state: Data<crate::State>,
+    _: OaBiscuit,
+    biscuit: ReqData<Biscuit>,
+    qs: Query<Qs>,
+) -> Result<Json<Vec<ClientCertificate>>, Hook0Problem> {
+    authorize_for_application(
+        &state.db,
+        &biscuit,
+        Action::ClientCertificateList {
+            application_id: &qs.application_id,
+        },
+        state.max_authorization_time,
+        state.debug_authorizer,
+    )
+    .await?;
+
+    let client_certificates = query_as!(
+        ClientCertificate,
+        "
+            SELECT client_certificate__id AS client_certificate_id, application__id AS application_id, name, certificate, created_at
+            FROM webhook.client_certificate
+            WHERE application__id = $1
+            ORDER BY created_at ASC
+        ",
+        &qs.application_id,
+    )
+    .fetch_all(&state.db)
+    .await
+    .map_err(Hook0Problem::from)?;
+
+    Ok(Json(client_certificates))
+}
+
+#[api_v2_operation(
+    summary = "Upload a client certificate",
+    description = "Stores a client certificate and its private key so that subscriptions of the application can use them to authenticate to their targets with mutual TLS. The private key is encrypted before being stored.",
+    operation_id = "clientCertificates.create",
+    consumes = "application/json",
+    produces = "application/json",
+    tags("Subscriptions Management")
+)]
+pub async fn create(
+    state: Data<crate::State>,
+    _: OaBiscuit,
+    biscuit: ReqData<Biscuit>,
+    body: Json<ClientCertificatePost>,
+) -> Result<CreatedJson<ClientCertificate>, Hook0Problem> {
+    authorize_for_application(
+        &state.db,
+        &biscuit,
+        Action::ClientCertificateCreate {
+            application_id: &body.application_id,
+        },
+        state.max_authorization_time,
+        state.debug_authorizer,
+    )
+    .await?;
+
+    let encryption_key = state
+        .target_credentials_encryption_key
+        .as_ref()
+        .ok_or(Hook0Problem::ClientCertificatesDisabled)?;
+
+    if let Err(e) = body.validate() {
+        return Err(Hook0Problem::Validation(e));
+    }
+
+    // Make sure output workers will be able to use the certificate
+    match Certificate::from_pem_bundle(body.certificate.as_bytes()) {
+        Ok(certificates) if !certificates.is_empty() => Ok(()),
+        Ok(_) => Err(Hook0Problem::ClientCertificateInvalid(
+            "no certificate was found".to_owned(),
+        )),
+        Err(e) => Err(Hook0Problem::ClientCertificateInvalid(e.to_string())),
+    }?;
+    Identity::from_pem(format!("{}\n{}", body.certificate, body.private_key).as_bytes())
+        .map_err(|e| Hook0Problem::ClientCertificateInvalid(e.to_string()))?;
+
+    let client_certificate = query_as!(
+        ClientCertificate,
+        "
+            INSERT INTO webhook.client_certificate (application__id, name, certificate, encrypted_private_key)
+            VALUES ($1, $2, $3, $4)
+            RETURNING client_certificate__id AS client_certificate_id, application__id AS application_id, name, certificate, created_at
+        ",
+        &body.application_id,
+        body.name,
+        body.certificate,
+        encryption_key.encrypt(body.private_key.as_bytes()),
+    )
+    .fetch_one(&state.db)
+    .await
+    .map_err(Hook0Problem::from)?;
+
+    Ok(CreatedJson(client_certificate))
+}
+
+#[api_v2_operation(
+    summary = "Delete a client certificate",
+    description = "Deletes a client certificate and its private key. Client certificates that are used by subscriptions cannot be deleted.",
+    operation_id = "clientCertificates.delete",
+    consumes = "application/json",
+    produces = "application/json",
+    tags("Subscriptions Management")
+)]
+pub async fn delete(
+    state: Data<crate::State>,
+    _: OaBiscuit,
+    biscuit: ReqData<Biscuit>,
+    client_certificate_id: Path<Uuid>,
+    qs: Query<Qs>,
+) -> Result<NoContent, Hook0Problem> {
+    authorize_for_application(
+        &state.db,
+        &biscuit,
+        Action::ClientCertificateDelete {
+            application_id: &qs.application_id,
+        },
+        state.max_authorization_time,
+        state.debug_authorizer,
+    )
+    .await?;
+
+    let client_certificate_id = client_certificate_id.into_inner();
+    let mut tx = state.db.begin().await.map_err(Hook0Problem::from)?;
+
+    let in_use = query!(
+        r#"
+            SELECT EXISTS (
+                SELECT 1
+                FROM webhook.target_http AS t_http
+                INNER JOIN webhook.subscription AS s ON s.target__id = t_http.target__id
+                WHERE t_http.client_certificate__id = cc.client_certificate__id
+                    AND s.deleted_at IS NULL
+            ) AS "in_use!"
+            FROM webhook.client_certificate AS cc
+            WHERE cc.application__id = $1 AND cc.client_certificate__id = $2
+            FOR UPDATE OF cc
+        "#,
+        &qs.application_id,
+        &client_certificate_id,
+    )
+    .fetch_optional(&mut *tx)
+    .await
+    .map_err(Hook0Problem::from)?
+    .map(|row| row.in_use);
+
+    match in_use {
+        Some(false) => {
+            query!(
+                "
+                    DELETE FROM webhook.client_certificate
+                    WHERE application__id = $1 AND client_certificate__id = $2
+                ",
+                &qs.application_id,
+                &client_certificate_id,
+            )
+            .execute(&mut *tx)
+            .await
+            .map_err(Hook0Problem::from)?;
+            tx.commit().await.map_err(Hook0Problem::from)?;
+
+            Ok(NoContent)
+        }
+        Some(true) => Err(Hook0Problem::ClientCertificateInUse),
+        None => Err(Hook0Problem::NotFound),
+    }
+}
@@ -0,0 +1,420 @@
+use actix_web::web::ReqData;
+use biscuit_auth::Biscuit;
+use chrono::{DateTime, Utc};
+use paperclip::actix::web::{Data, Json, Path, Query};
+use paperclip::actix::{Apiv2Schema, CreatedJson, NoContent, api_v2_operation};
+use serde::{Deserialize, Serialize};
+use sqlx::{PgPool, query, query_as, query_scalar};
+use uuid::Uuid;
+use validator::Validate;
+
+use crate::iam::{
+    Action, CUSTOM_ROLE_ACTIONS, CUSTOM_ROLE_ORGANIZATION_ACTIONS, authorize_for_organization,
+};
+use crate::openapi::OaBiscuit;
+use crate::problems::Hook0Problem;
+
+/// Named set of actions that can be assigned to members of an organization instead of the built-in roles
+#[derive(Debug, Serialize, Apiv2Schema)]
+pub struct CustomRole {
+    custom_role_id: Uuid,
+    organization_id: Uuid,
+    name: String,
+    /// Names of the granted actions, such as `event:replay` or `request_attempt:list`
+    actions: Vec<String>,
+    /// Applications on which actions are granted; if null, actions are granted on all the applications of the organization
+    application_ids: Option<Vec<Uuid>>,
+    created_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Apiv2Schema)]
+pub struct Qs {
+    organization_id: Uuid,
+}
+
+#[derive(Debug, Deserialize, Apiv2Schema, Validate)]
+pub struct CustomRolePost {
+    organization_id: Uuid,
+    #[validate(non_control_character, length(min = 2, max = 50))]
+    name: String,
+    /// Names of the granted actions, such as `event:replay` or `request_attempt:list`
+    #[validate(length(min = 1, max = 100))]
+    actions: Vec<String>,
+    /// Applications on which actions are granted; if null or missing, actions are granted on all the applications of the organization
+    ///
+    /// Custom roles restricted to some applications cannot grant actions that do not target a specific application, such as `application:create`.
+    #[validate(length(min = 1, max = 100))]
+    application_ids: Option<Vec<Uuid>>,
+}
+
+impl CustomRolePost {
+    /// Make sure the custom role only grants known actions, and only actions that target an application if it is restricted to some applications
+    fn validate_actions(&self) -> Result<(), Hook0Problem> {
+        let unknown_actions = self
+            .actions
+            .iter()
+            .filter(|action| !CUSTOM_ROLE_ACTIONS.contains(&action.as_str()))
+            .map(|action| action.as_str())
+            .collect::<Vec<_>>();
+        if !unknown_actions.is_empty() {
+            return Err(Hook0Problem::CustomRoleInvalid(format!(
+                "the following actions cannot be granted by custom roles: {}. Valid actions are: {}.",
+                unknown_actions.join(", "),
+                CUSTOM_ROLE_ACTIONS.join(", "),
+            )));
+        }
+
+        if self.application_ids.is_some() {
+            let organization_actions = self
+                .actions
+                .iter()
+                .filter(|action| CUSTOM_ROLE_ORGANIZATION_ACTIONS.contains(&action.as_str()))
+                .map(|action| action.as_str())
+                .collect::<Vec<_>>();
+            if !organization_actions.is_empty() {
+                return Err(Hook0Problem::CustomRoleInvalid(format!(
+                    "the following actions do not target a specific application and cannot be granted by custom roles restricted to some applications: {}",
+                    organization_actions.join(", "),
+                )));
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Make sure the custom role only grants known actions on applications of its organization, and return its sorted and deduplicated actions
+    async fn validate_grants(&self, db: &PgPool) -> Result<Vec<String>, Hook0Problem> {
+        self.validate_actions()?;
+
+        if let Some(application_ids) = &self.application_ids {
+            let unknown_applications = query_scalar!(
+                r#"
+                    SELECT a_id AS "application_id!"
+                    FROM unnest($2::uuid[]) AS a_id
+                    WHERE NOT EXISTS (
+                        SELECT 1
+                        FROM event.application
+                        WHERE application__id = a_id
+                            AND organization__id = $1
+                            AND deleted_at IS NULL
+                    )
+                "#,
+                &self.organization_id,
+                application_ids,
+            )
+            .fetch_all(db)
+            .await
+            .map_err(Hook0Problem::from)?;
+            if !unknown_applications.is_empty() {
+                return Err(Hook0Problem::CustomRoleInvalid(format!(
+                    "the following applications do not exist or belong to another organization: {}",
+                    unknown_applications
+                        .iter()
+                        .map(|id| id.to_string())
+                        .collect::<Vec<_>>()
+                        .join(", "),
+                )));
+            }
+        }
+
+        let mut actions = self.actions.to_owned();
+        actions.sort();
+        actions.dedup();
+        Ok(actions)
+    }
+}
+
+#[api_v2_operation(
+    summary = "List custom roles",
+    description = "Lists the custom roles of an organization. Custom roles are named sets of actions, optionally restricted to some applications, that can be assigned to members instead of the built-in roles.",
+    operation_id = "customRoles.list",
+    consumes = "application/json",
+    produces = "application/json",
+    tags("Organizations Management")
+)]
+pub async fn list(
+    state: Data<crate::State>,
+    _: OaBiscuit,
+    biscuit: ReqData<Biscuit>,
+    qs: Query<Qs>,
+) -> Result<Json<Vec<CustomRole>>, Hook0Problem> {
+    authorize_for_organization(
+        &biscuit,
+        Some(qs.organization_id),
+        Action::CustomRoleList,
+        state.max_authorization_time,
+        state.debug_authorizer,
+    )?;
+
+    let custom_roles = query_as!(
+        CustomRole,
+        "
+            SELECT custom_role__id AS custom_role_id, organization__id AS organization_id, name, actions, application__ids AS application_ids, created_at
+            FROM iam.custom_role
+            WHERE organization__id = $1
+            ORDER BY created_at ASC
+        ",
+        &qs.organization_id,
+    )
+    .fetch_all(&state.db)
+    .await
+    .map_err(Hook0Problem::from)?;
+
+    Ok(Json(custom_roles))
+}
+
+#[api_v2_operation(
+    summary = "Create a custom role",
+    description = "Creates a custom role in an organization. Once created, it can be assigned to members of the organization by inviting them or editing their role with the 'custom' role and the ID of the custom role.",
+    operation_id = "customRoles.create",
+    consumes = "application/json",
+    produces = "application/json",
+    tags("Organizations Management")
+)]
+pub async fn create(
+    state: Data<crate::State>,
+    _: OaBiscuit,
+    biscuit: ReqData<Biscuit>,
+    body: Json<CustomRolePost>,
+) -> Result<CreatedJson<CustomRole>, Hook0Problem> {
+    authorize_for_organization(
+        &biscuit,
+        Some(body.organization_id),
+        Action::CustomRoleCreate,
+        state.max_authorization_time,
+        state.debug_authorizer,
+    )?;
+
+    if let Err(e) = body.validate() {
+        return Err(Hook0Problem::Validation(e));
+    }
+    let actions = body.validate_grants(&state.db).await?;
+
+    let custom_role = query_as!(
+        CustomRole,
+        "
+            INSERT INTO iam.custom_role (organization__id, name, actions, application__ids)
+            VALUES ($1, $2, $3, $4)
+            RETURNING custom_role__id AS custom_role_id, organization__id AS organization_id, name, actions, application__ids AS application_ids, created_at
+        ",
+        &body.organization_id,
+        body.name,
+        &actions,
+        body.application_ids.as_deref(),
+    )
+    .fetch_one(&state.db)
+    .await
+    .map_err(Hook0Problem::from)?;
+
+    Ok(CreatedJson(custom_role))
+}
+
+#[api_v2_operation(
+    summary = "Get a custom role",
+    description = "Retrieves a custom role of an organization.",
+    operation_id = "customRoles.get",
+    consumes = "application/json",
+    produces = "application/json",
+    tags("Organizations Management")
+)]
+pub async fn get(
+    state: Data<crate::State>,
+    _: OaBiscuit,
+    biscuit: ReqData<Biscuit>,
+    custom_role_id: Path<Uuid>,
+    qs: Query<Qs>,
+) -> Result<Json<CustomRole>, Hook0Problem> {
+    authorize_for_organization(
+        &biscuit,
+        Some(qs.organization_id),
+        Action::CustomRoleGet,
+        state.max_authorization_time,
+        state.debug_authorizer,
+    )?;
+
+    let custom_role = query_as!(
+        CustomRole,
+        "
+            SELECT custom_role__id AS custom_role_id, organization__id AS organization_id, name, actions, application__ids AS application_ids, created_at
+            FROM iam.custom_role
+            WHERE custom_role__id = $1
+                AND organization__id = $2
+        ",
+        custom_role_id.as_ref(),
+        &qs.organization_id,
+    )
+    .fetch_optional(&state.db)
+    .await
+    .map_err(Hook0Problem::from)?;
+
+    match custom_role {
+        Some(cr) => Ok(Json(cr)),
+        None => Err(Hook0Problem::NotFound),
+    }
+}
+
+#[api_v2_operation(
+    summary = "Edit a custom role",
+    description = "Updates the name, actions and applications of a custom role. Members that have this role get the new permissions the next time their access token is refreshed.",
+    operation_id = "customRoles.edit",
+    consumes = "application/json",
+    produces = "application/json",
+    tags("Organizations Management")
+)]
+pub async fn edit(
+    state: Data<crate::State>,
+    _: OaBiscuit,
+    biscuit: ReqData<Biscuit>,
+    custom_role_id: Path<Uuid>,
+    body: Json<CustomRolePost>,
+) -> Result<Json<CustomRole>, Hook0Problem> {
+    let custom_role_id = custom_role_id.into_inner();
+
+    authorize_for_organization(
+        &biscuit,
+        Some(body.organization_id),
+        Action::CustomRoleEdit {
+            custom_role_id: &custom_role_id,
+        },
+        state.max_authorization_time,
+        state.debug_authorizer,
+    )?;
+
+    if let Err(e) = body.validate() {
+        return Err(Hook0Problem::Validation(e));
+    }
+    let actions = body.validate_grants(&state.db).await?;
+
+    let custom_role = query_as!(
+        CustomRole,
+        "
+            UPDATE iam.custom_role
+            SET name = $3, actions = $4, application__ids = $5
+            WHERE custom_role__id = $1
+                AND organization__id = $2
+            RETURNING custom_role__id AS custom_role_id, organization__id AS organization_id, name, actions, application__ids AS application_ids, created_at
+        ",
+        &custom_role_id,
+        &body.organization_id,
+        body.name,
+        &actions,
+        body.application_ids.as_deref(),
+    )
+    .fetch_optional(&state.db)
+    .await
+    .map_err(Hook0Problem::from)?;
+
+    match custom_role {
+        Some(cr) => Ok(Json(cr)),
+        None => Err(Hook0Problem::NotFound),
+    }
+}
+
+#[api_v2_operation(
+    summary = "Delete a custom role",
+    description = "Deletes a custom role. Custom roles that are assigned to members of the organization cannot be deleted.",
+    operation_id = "customRoles.delete",
+    consumes = "application/json",
+    produces = "application/json",
+    tags("Organizations Management")
+)]
+pub async fn delete(
+    state: Data<crate::State>,
+    _: OaBiscuit,
+    biscuit: ReqData<Biscuit>,
+    custom_role_id: Path<Uuid>,
+    qs: Query<Qs>,
+) -> Result<NoContent, Hook0Problem> {
+    let custom_role_id = custom_role_id.into_inner();
+
+    authorize_for_organization(
+        &biscuit,
+        Some(qs.organization_id),
+        Action::CustomRoleDelete {
+            custom_role_id: &custom_role_id,
+        },
+        state.max_authorization_time,
+        state.debug_authorizer,
+    )?;
+
+    let mut tx = state.db.begin().await.map_err(Hook0Problem::from)?;
+
+    let in_use = query!(
+        r#"
+            SELECT EXISTS (
+                SELECT 1
+                FROM iam.user__organization AS uo
+                WHERE uo.organization__id = cr.organization__id
+                    AND uo.custom_role__id = cr.custom_role__id
+            ) AS "in_use!"
+            FROM iam.custom_role AS cr
+            WHERE cr.organization__id = $1 AND cr.custom_role__id = $2
+            FOR UPDATE OF cr
+        "#,
+        &qs.organization_id,
+        &custom_role_id,
+    )
+    .fetch_optional(&mut *tx)
+    .await
+    .map_err(Hook0Problem::from)?
+    .map(|row| row.in_use);
+
+    match in_use {
+        Some(false) => {
+            query!(
+                "
+                    DELETE FROM iam.custom_role
+                    WHERE organization__id = $1 AND custom_role__id = $2
+                ",
+                &qs.organization_id,
+                &custom_role_id,
+            )
+            .execute(&mut *tx)
+            .await
+            .map_err(Hook0Problem::from)?;
+            tx.commit().await.map_err(Hook0Problem::from)?;
+
+            Ok(NoContent)
+        }
+        Some(true) => Err(Hook0Problem::CustomRoleInUse),
+        None => Err(Hook0Problem::NotFound),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn custom_role(actions: &[&str], application_ids: Option<Vec<Uuid>>) -> CustomRolePost {
+        CustomRolePost {
+            organization_id: Uuid::nil(),
+            name: "Support".to_owned(),
+            actions: actions.iter().map(|action| (*action).to_owned()).collect(),
+            application_ids,
+        }
+    }
+
+    #[test]
+    fn validate_actions() {
+        assert!(
+            custom_role(&["event:replay", "organization:get"], None)
+                .validate_actions()
+                .is_ok()
+        );
+        assert!(
+            custom_role(&["event:replay"], Some(vec![Uuid::nil()]))
+                .validate_actions()
+                .is_ok()
+        );
+        assert!(matches!(
+            custom_role(&["custom_role:create"], None).validate_actions(),
+            Err(Hook0Problem::CustomRoleInvalid(_))
+        ));
+        for action in CUSTOM_ROLE_ORGANIZATION_ACTIONS {
+            assert!(matches!(
+                custom_role(&["event:replay", action], Some(vec![Uuid::nil()])).validate_actions(),
+                Err(Hook0Problem::CustomRoleInvalid(_))
+            ));
+        }
+    }
+}
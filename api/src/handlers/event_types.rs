@@ -1,11 +1,14 @@
 use actix_web::web::ReqData;
 use biscuit_auth::Biscuit;
-use chrono::Utc;
+use chrono::{DateTime, Utc};
 use paperclip::actix::web::{Data, Json, Path, Query};
 use paperclip::actix::{Apiv2Schema, CreatedJson, NoContent, api_v2_operation};
 use serde::{Deserialize, Serialize};
-use sqlx::{query, query_as, query_scalar};
-use tracing::error;
+use serde_json::Value;
+use sqlx::{PgConnection, PgPool, query, query_as, query_scalar};
+use std::str::FromStr;
+use strum::{EnumString, IntoStaticStr};
+use tracing::{error, warn};
 use uuid::Uuid;
 use validator::Validate;
 
@@ -38,6 +41,203 @@ pub struct EventTypePost {
     resource_type: String,
     #[validate(non_control_character, length(min = 1, max = 50))]
     verb: String,
+    /// JSON Schema that payloads of events of this type must match (it becomes the first version of the schema of this event type)
+    schema: Option<Value>,
+    /// How events that do not match the schema are handled (`strict` by default)
+    #[serde(default)]
+    validation_mode: SchemaValidationMode,
+}
+
+#[derive(
+    Debug,
+    Default,
+    Clone,
+    Copy,
+    PartialEq,
+    Eq,
+    Serialize,
+    Deserialize,
+    Apiv2Schema,
+    EnumString,
+    IntoStaticStr,
+)]
+#[serde(rename_all = "snake_case")]
+#[strum(serialize_all = "snake_case")]
+pub enum SchemaValidationMode {
+    /// Events whose payload does not match the schema are rejected
+    #[default]
+    Strict,
+    /// Events whose payload does not match the schema are ingested, but a warning is logged
+    Warn,
+}
+
+#[derive(Debug, Serialize, Apiv2Schema)]
+pub struct EventTypeSchema {
+    event_type_name: String,
+    version: i32,
+    /// JSON Schema that payloads of events of this type must match
+    schema: Value,
+    validation_mode: SchemaValidationMode,
+    created_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Apiv2Schema)]
+pub struct EventTypeSchemaPost {
+    application_id: Uuid,
+    /// JSON Schema that payloads of events of this type must match (external references are not supported)
+    schema: Value,
+    /// How events that do not match the schema are handled (`strict` by default)
+    #[serde(default)]
+    validation_mode: SchemaValidationMode,
+}
+
+#[allow(non_snake_case)]
+struct RawEventTypeSchema {
+    event_type__name: String,
+    version: i32,
+    schema: Value,
+    validation_mode: String,
+    created_at: DateTime<Utc>,
+}
+
+impl From<RawEventTypeSchema> for EventTypeSchema {
+    fn from(raw: RawEventTypeSchema) -> Self {
+        Self {
+            event_type_name: raw.event_type__name,
+            version: raw.version,
+            schema: raw.schema,
+            validation_mode: SchemaValidationMode::from_str(&raw.validation_mode)
+                .unwrap_or_default(),
+            created_at: raw.created_at,
+        }
+    }
+}
+
+fn compile_schema(schema: &Value) -> Result<jsonschema::Validator, Hook0Problem> {
+    jsonschema::validator_for(schema)
+        .map_err(|e| Hook0Problem::EventTypeInvalidSchema(e.to_string()))
+}
+
+/// Store a new version of the schema of an event type
+async fn insert_schema(
+    conn: &mut PgConnection,
+    application_id: &Uuid,
+    event_type_name: &str,
+    schema: &Value,
+    validation_mode: SchemaValidationMode,
+) -> Result<EventTypeSchema, Hook0Problem> {
+    compile_schema(schema)?;
+
+    let validation_mode: &str = validation_mode.into();
+    let schema = query_as!(
+        RawEventTypeSchema,
+        "
+            INSERT INTO event.event_type_schema (application__id, event_type__name, version, schema, validation_mode)
+            SELECT $1, $2, COALESCE(MAX(version), 0) + 1, $3, $4
+            FROM event.event_type_schema
+            WHERE application__id = $1 AND event_type__name = $2
+            RETURNING event_type__name, version, schema, validation_mode, created_at
+        ",
+        application_id,
+        event_type_name,
+        schema,
+        validation_mode,
+    )
+    .fetch_one(conn)
+    .await
+    .map_err(Hook0Problem::from)?;
+
+    Ok(schema.into())
+}
+
+/// Check a JSON payload against the latest version of the schema of its event type (if it has one)
+pub async fn validate_payload(
+    db: &PgPool,
+    application_id: &Uuid,
+    event_type_name: &str,
+    payload: &[u8],
+) -> Result<(), Hook0Problem> {
+    match get_payload_validator(db, application_id, event_type_name).await? {
+        Some(validator) => validator.validate(application_id, payload),
+        None => Ok(()),
+    }
+}
+
+/// Compiled latest version of the schema of an event type, which can be used to check many payloads
+pub struct PayloadValidator {
+    event_type_name: String,
+    version: i32,
+    validation_mode: SchemaValidationMode,
+    validator: jsonschema::Validator,
+}
+
+impl PayloadValidator {
+    pub fn validate(&self, application_id: &Uuid, payload: &[u8]) -> Result<(), Hook0Problem> {
+        let payload = serde_json::from_slice::<Value>(payload)
+            .map_err(|e| Hook0Problem::EventInvalidJsonPayload(e.to_string()))?;
+        let errors = self
+            .validator
+            .iter_errors(&payload)
+            .take(10)
+            .map(|e| format!("#{}: {e}", e.instance_path()))
+            .collect::<Vec<_>>();
+
+        if !errors.is_empty() {
+            let errors = format!(
+                "version {} of the schema of event type '{}' is not matched ({})",
+                self.version,
+                self.event_type_name,
+                errors.join("; ")
+            );
+            match self.validation_mode {
+                SchemaValidationMode::Strict => {
+                    return Err(Hook0Problem::EventPayloadSchemaMismatch(errors));
+                }
+                SchemaValidationMode::Warn => {
+                    warn!(
+                        "Event payload of application {application_id} does not match its schema: {errors}"
+                    );
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Get a validator for the latest version of the schema of an event type (if it has one)
+pub async fn get_payload_validator(
+    db: &PgPool,
+    application_id: &Uuid,
+    event_type_name: &str,
+) -> Result<Option<PayloadValidator>, Hook0Problem> {
+    let schema = query_as!(
+        RawEventTypeSchema,
+        "
+            SELECT event_type__name, version, schema, validation_mode, created_at
+            FROM event.event_type_schema
+            WHERE application__id = $1 AND event_type__name = $2
+            ORDER BY version DESC
+            LIMIT 1
+        ",
+        application_id,
+        event_type_name,
+    )
+    .fetch_optional(db)
+    .await
+    .map_err(Hook0Problem::from)?
+    .map(EventTypeSchema::from);
+
+    schema
+        .map(|schema| {
+            Ok(PayloadValidator {
+                validator: compile_schema(&schema.schema)?,
+                event_type_name: event_type_name.to_owned(),
+                version: schema.version,
+                validation_mode: schema.validation_mode,
+            })
+        })
+        .transpose()
 }
 
 #[api_v2_operation(
@@ -153,6 +353,17 @@ pub async fn create(
         .await
         .map_err(Hook0Problem::from)?;
 
+    if let Some(schema) = &body.schema {
+        insert_schema(
+            &mut *tx,
+            &body.application_id,
+            &event_type.event_type_name,
+            schema,
+            body.validation_mode,
+        )
+        .await?;
+    }
+
     tx.commit().await.map_err(Hook0Problem::from)?;
 
     if let Some(hook0_client) = state.hook0_client.as_ref() {
@@ -345,3 +556,108 @@ pub async fn delete(
         None => Err(Hook0Problem::NotFound),
     }
 }
+
+#[api_v2_operation(
+    summary = "List the schema versions of an event type",
+    description = "Retrieves all versions of the JSON Schema of an event type, the latest being last. Payloads of new events of this type are checked against the latest version. Subscribers can use it as the contract of the event type.",
+    operation_id = "eventTypes.listSchemas",
+    consumes = "application/json",
+    produces = "application/json",
+    tags("Events Management", "mcp")
+)]
+pub async fn list_schemas(
+    state: Data<crate::State>,
+    _: OaBiscuit,
+    biscuit: ReqData<Biscuit>,
+    event_type_name: Path<String>,
+    qs: Query<Qs>,
+) -> Result<Json<Vec<EventTypeSchema>>, Hook0Problem> {
+    authorize_for_application(
+        &state.db,
+        &biscuit,
+        Action::EventTypeGet {
+            application_id: &qs.application_id,
+        },
+        state.max_authorization_time,
+        state.debug_authorizer,
+    )
+    .await?;
+
+    let schemas = query_as!(
+        RawEventTypeSchema,
+        "
+            SELECT event_type__name, version, schema, validation_mode, created_at
+            FROM event.event_type_schema
+            WHERE application__id = $1 AND event_type__name = $2
+            ORDER BY version ASC
+        ",
+        &qs.application_id,
+        &event_type_name.into_inner(),
+    )
+    .fetch_all(&state.db)
+    .await
+    .map_err(Hook0Problem::from)?;
+
+    Ok(Json(
+        schemas.into_iter().map(EventTypeSchema::from).collect(),
+    ))
+}
+
+#[api_v2_operation(
+    summary = "Add a schema version to an event type",
+    description = "Attaches a new version of the JSON Schema of an event type. Payloads of new events of this type are checked against it: in strict mode, events that do not match are rejected; in warn mode, they are ingested and a warning is logged.",
+    operation_id = "eventTypes.addSchema",
+    consumes = "application/json",
+    produces = "application/json",
+    tags("Events Management", "mcp")
+)]
+pub async fn add_schema(
+    state: Data<crate::State>,
+    _: OaBiscuit,
+    biscuit: ReqData<Biscuit>,
+    event_type_name: Path<String>,
+    body: Json<EventTypeSchemaPost>,
+) -> Result<CreatedJson<EventTypeSchema>, Hook0Problem> {
+    authorize_for_application(
+        &state.db,
+        &biscuit,
+        Action::EventTypeCreate {
+            application_id: &body.application_id,
+        },
+        state.max_authorization_time,
+        state.debug_authorizer,
+    )
+    .await?;
+
+    let event_type_name = event_type_name.into_inner();
+    let exists = query_scalar!(
+        r#"
+            SELECT EXISTS (
+                SELECT 1
+                FROM event.event_type
+                WHERE application__id = $1 AND event_type__name = $2 AND deactivated_at IS NULL
+            ) AS "exists!"
+        "#,
+        &body.application_id,
+        &event_type_name,
+    )
+    .fetch_one(&state.db)
+    .await
+    .map_err(Hook0Problem::from)?;
+    if !exists {
+        return Err(Hook0Problem::NotFound);
+    }
+
+    let mut tx = state.db.begin().await.map_err(Hook0Problem::from)?;
+    let schema = insert_schema(
+        &mut *tx,
+        &body.application_id,
+        &event_type_name,
+        &body.schema,
+        body.validation_mode,
+    )
+    .await?;
+    tx.commit().await.map_err(Hook0Problem::from)?;
+
+    Ok(CreatedJson(schema))
+}
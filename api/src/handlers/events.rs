@@ -1,19 +1,25 @@
+use actix_web::body::BoxBody;
+use actix_web::http::StatusCode;
 use actix_web::rt::time::timeout;
 use actix_web::web::ReqData;
+use actix_web::{HttpResponse, Responder};
 use aws_sdk_s3::error::DisplayErrorContext;
 use aws_sdk_s3::primitives::ByteStream;
 use base64::Engine;
 use base64::engine::general_purpose::STANDARD as Base64;
 use biscuit_auth::Biscuit;
-use chrono::{DateTime, Utc};
+use chrono::{DateTime, TimeDelta, Utc};
 use futures_util::future::try_join_all;
+use futures_util::stream::{self, StreamExt as _};
 use paperclip::actix::web::{Data, Json, Path, Query};
-use paperclip::actix::{Apiv2Schema, CreatedJson, NoContent, api_v2_operation};
+use paperclip::actix::{Apiv2Schema, NoContent, OperationModifier, api_v2_operation};
 use serde::{Deserialize, Serialize};
 use serde_json::{Value, json};
 use sqlx::types::ipnetwork::IpNetwork;
-use sqlx::{query, query_as, query_scalar};
-use std::collections::HashMap;
+use sqlx::{Acquire, PgConnection, PgPool, Postgres, query, query_as, query_scalar};
+use std::borrow::Borrow;
+use std::collections::hash_map::Entry;
+use std::collections::{BTreeMap, HashMap, HashSet};
 use std::str::FromStr;
 use std::sync::Arc;
 use std::time::{Duration, Instant};
@@ -22,11 +28,17 @@ use tracing::{error, trace};
 use uuid::Uuid;
 use validator::Validate;
 
-use paperclip::v2::models::{DataType, DefaultSchemaRaw};
-use paperclip::v2::schema::Apiv2Schema;
+use paperclip::v2::models::{
+    DataType, DataTypeFormat, DefaultSchemaRaw, Either, Reference, Response,
+};
+use paperclip::v2::schema::{Apiv2Schema, TypedData};
+use url::Url;
+use validator::ValidationError;
 
 use crate::PulsarConfig;
+use crate::extractor_idempotency_key::IdempotencyKey;
 use crate::extractor_user_ip::UserIp;
+use crate::handlers::event_types::{PayloadValidator, get_payload_validator};
 use crate::iam::{Action, authorize_for_application};
 use crate::mailer::Mail;
 use crate::openapi::OaBiscuit;
@@ -35,10 +47,12 @@ use crate::opentelemetry::{
     report_ingested_events, report_ingestion_duration, report_ingestion_phase_durations,
     report_replayed_events, report_request_attempts_sent_to_pulsar,
 };
-use crate::problems::Hook0Problem;
-use crate::quotas::{Quota, QuotaNotificationType};
+use crate::pagination::{Cursor, EncodedDescCursor, NextPageParts, Paginated};
+use crate::problems::{Hook0Problem, Problem};
+use crate::quotas::{Quota, QuotaNotificationType, QuotaValue};
 use hook0_protobuf::RequestAttempt;
 use hook0_sentry_integration::log_object_storage_error_with_context;
+use pulsar::SerializeMessage;
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, IntoStaticStr, VariantNames)]
 pub enum PayloadContentType {
@@ -130,6 +144,8 @@ struct EventRaw {
     occurred_at: DateTime<Utc>,
     received_at: DateTime<Utc>,
     labels: Value,
+    deliver_at: Option<DateTime<Utc>>,
+    delivery_cancelled_at: Option<DateTime<Utc>>,
 }
 
 impl EventRaw {
@@ -143,6 +159,8 @@ impl EventRaw {
             occurred_at: self.occurred_at,
             received_at: self.received_at,
             labels: self.labels.clone(),
+            deliver_at: self.deliver_at,
+            delivery_cancelled_at: self.delivery_cancelled_at,
         }
     }
 }
@@ -157,11 +175,71 @@ pub struct Event {
     occurred_at: DateTime<Utc>,
     received_at: DateTime<Utc>,
     labels: Value,
+    /// If set, the event is not delivered before this date
+    deliver_at: Option<DateTime<Utc>>,
+    /// Date at which the delivery of the event was cancelled
+    delivery_cancelled_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Deserialize, Apiv2Schema, Validate)]
+#[validate(schema(function = "validate_list_qs"))]
+pub struct ListQs {
+    application_id: Uuid,
+    pagination_cursor: Option<EncodedDescCursor>,
+    /// Comma-separated event types
+    event_type_names: Option<String>,
+    /// Only return events whose type starts with this value (for example `billing.` or `billing.invoice.`)
+    event_type_prefix: Option<String>,
+    /// Only return events that have this label
+    label_key: Option<String>,
+    /// Only return events whose label `label_key` has this value (requires `label_key`)
+    label_value: Option<String>,
+    min_received_at: Option<DateTime<Utc>>,
+    max_received_at: Option<DateTime<Utc>>,
+    min_occurred_at: Option<DateTime<Utc>>,
+    max_occurred_at: Option<DateTime<Utc>>,
+    /// Only return events that have this metadata property
+    metadata_key: Option<String>,
+    /// Only return events whose delivery has this status (based on the latest request attempt of each subscription)
+    delivery_status: Option<EventDeliveryStatus>,
+}
+
+fn validate_list_qs(qs: &ListQs) -> Result<(), ValidationError> {
+    if qs.label_value.is_some() && qs.label_key.is_none() {
+        Err(ValidationError::new("label-value-requires-label-key")
+            .with_message("'label_value' can only be set if 'label_key' is set".into()))
+    } else {
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, strum::Display)]
+#[serde(rename_all = "snake_case")]
+#[strum(serialize_all = "snake_case")]
+pub enum EventDeliveryStatus {
+    /// The latest request attempt of at least one subscription failed (and was not retried)
+    Failed,
+    /// The latest request attempt of every subscription succeeded
+    Successful,
+    /// The latest request attempt of at least one subscription is neither successful nor failed yet
+    Pending,
+    /// The delivery of the event is held until its `deliver_at` date
+    Held,
+}
+
+impl TypedData for EventDeliveryStatus {
+    fn data_type() -> DataType {
+        DataType::String
+    }
+
+    fn format() -> Option<DataTypeFormat> {
+        None
+    }
 }
 
 #[api_v2_operation(
     summary = "List latest events",
-    description = "Retrieves the 100 most recently ingested events for an application. Each event includes its type, payload content type, metadata, labels, and timestamps. Use application_id query parameter to filter by application.",
+    description = "Retrieves the most recently ingested events for an application, 100 at a time. Each event includes its type, payload content type, metadata, labels, and timestamps. Filter by event types (or prefix), label, reception or occurrence date range, metadata property, or delivery status. Paginated via Link header.",
     operation_id = "events.list",
     consumes = "application/json",
     produces = "application/json",
@@ -171,8 +249,8 @@ pub async fn list(
     state: Data<crate::State>,
     _: OaBiscuit,
     biscuit: ReqData<Biscuit>,
-    qs: Query<Qs>,
-) -> Result<Json<Vec<Event>>, Hook0Problem> {
+    qs: Query<ListQs>,
+) -> Result<Paginated<Json<Vec<Event>>>, Hook0Problem> {
     authorize_for_application(
         &state.db,
         &biscuit,
@@ -184,23 +262,145 @@ pub async fn list(
     )
     .await?;
 
+    if let Err(e) = qs.validate() {
+        return Err(Hook0Problem::Validation(e));
+    }
+
+    let min_received_at = qs.min_received_at.unwrap_or(DateTime::<Utc>::UNIX_EPOCH);
+    let max_received_at = qs.max_received_at.unwrap_or_else(Utc::now);
+    let event_type_names = qs
+        .event_type_names
+        .as_ref()
+        .map(|s| {
+            s.split(",")
+                .map(|p| p.trim().to_owned())
+                .filter(|p| !p.is_empty())
+                .collect::<Vec<_>>()
+        })
+        .unwrap_or_default();
+    let label = qs
+        .label_value
+        .as_ref()
+        .zip(qs.label_key.as_ref())
+        .map(|(value, key)| json!({ key: value }));
+    let pagination = qs.pagination_cursor.unwrap_or_default().0;
+
     let raw_events = query_as!(
-            EventRaw,
-            "
-                SELECT event__id, event_type__name, payload_content_type, ip, metadata, occurred_at, received_at, labels
-                FROM event.event
-                WHERE application__id = $1
-                ORDER BY received_at DESC
-                LIMIT 100
-            ",
-            &qs.application_id,
-        )
-        .fetch_all(&state.db)
-        .await
-        .map_err(Hook0Problem::from)?;
+        EventRaw,
+        "
+            SELECT e.event__id, e.event_type__name, e.payload_content_type, e.ip, e.metadata, e.occurred_at, e.received_at, e.labels, e.deliver_at, e.delivery_cancelled_at
+            FROM event.event AS e
+            WHERE e.application__id = $1
+                AND e.received_at BETWEEN $2 AND $3
+                AND (e.received_at, e.event__id) < ($4, $5)
+                AND (e.event_type__name = any($6) OR $6 = '{}')
+                AND ($7::text IS NULL OR starts_with(e.event_type__name, $7))
+                AND ($8::text IS NULL OR e.labels ? $8)
+                AND ($9::jsonb IS NULL OR e.labels @> $9)
+                AND ($10::timestamptz IS NULL OR e.occurred_at >= $10)
+                AND ($11::timestamptz IS NULL OR e.occurred_at <= $11)
+                AND ($12::text IS NULL OR e.metadata ? $12)
+                AND ($13::text IS NULL OR CASE $13
+                    WHEN 'held' THEN e.deliver_at > statement_timestamp() AND e.delivery_cancelled_at IS NULL
+                    -- Only the latest request attempt of each subscription tells how the delivery to this subscription went (earlier attempts were retried)
+                    ELSE (
+                        SELECT CASE $13
+                            WHEN 'failed' THEN bool_or(latest.failed_at IS NOT NULL)
+                            WHEN 'successful' THEN bool_and(latest.succeeded_at IS NOT NULL)
+                            WHEN 'pending' THEN bool_or(latest.succeeded_at IS NULL AND latest.failed_at IS NULL)
+                        END
+                        FROM (
+                            SELECT DISTINCT ON (ra.subscription__id) ra.succeeded_at, ra.failed_at
+                            FROM webhook.request_attempt AS ra
+                            WHERE ra.event__id = e.event__id
+                            ORDER BY ra.subscription__id, ra.created_at DESC
+                        ) AS latest
+                    )
+                END)
+            ORDER BY e.received_at DESC, e.event__id DESC
+            LIMIT 100
+        ",
+        &qs.application_id,
+        min_received_at,
+        max_received_at,
+        pagination.date,
+        pagination.id,
+        &event_type_names,
+        qs.event_type_prefix.as_deref(),
+        qs.label_key.as_deref(),
+        label,
+        qs.min_occurred_at,
+        qs.max_occurred_at,
+        qs.metadata_key.as_deref(),
+        qs.delivery_status.map(|status| status.to_string()),
+    )
+    .fetch_all(&state.db)
+    .await
+    .map_err(Hook0Problem::from)?;
 
-    let events = raw_events.iter().map(|re| re.to_event()).collect();
-    Ok(Json(events))
+    let events = raw_events
+        .iter()
+        .map(|re| re.to_event())
+        .collect::<Vec<_>>();
+
+    let next_page_parts = events.last().and_then(|e| {
+        if state.app_url.as_str().ends_with('/') {
+            Ok(state.app_url.clone())
+        } else {
+            Url::parse(&format!("{}/", state.app_url))
+        }
+        .inspect_err(|e| {
+            error!("Error that should never happen while building app URL for pagination: {e}");
+        })
+        .ok()
+        .and_then(|app_url| {
+            app_url
+                .join("/api/v1/events")
+                .inspect_err(|e| {
+                    error!(
+                        "Error that should never happen while building app URL for pagination: {e}"
+                    );
+                })
+                .ok()
+        })
+        .map(|endpoint_url| NextPageParts {
+            endpoint_url,
+            qs: vec![
+                ("application_id", Some(qs.application_id.to_string())),
+                ("event_type_names", qs.event_type_names.to_owned()),
+                ("event_type_prefix", qs.event_type_prefix.to_owned()),
+                ("label_key", qs.label_key.to_owned()),
+                ("label_value", qs.label_value.to_owned()),
+                (
+                    "min_received_at",
+                    qs.min_received_at.map(|v| v.to_rfc3339()),
+                ),
+                (
+                    "max_received_at",
+                    qs.max_received_at.map(|v| v.to_rfc3339()),
+                ),
+                (
+                    "min_occurred_at",
+                    qs.min_occurred_at.map(|v| v.to_rfc3339()),
+                ),
+                (
+                    "max_occurred_at",
+                    qs.max_occurred_at.map(|v| v.to_rfc3339()),
+                ),
+                ("metadata_key", qs.metadata_key.to_owned()),
+                ("delivery_status", qs.delivery_status.map(|v| v.to_string())),
+            ],
+            cursor: Cursor {
+                date: e.received_at,
+                id: e.event_id,
+            },
+        })
+    });
+
+    Ok(Paginated {
+        data: Json(events),
+        next_page_parts,
+    })
 }
 
 #[derive(Debug)]
@@ -215,6 +415,8 @@ struct EventWithPayloadRaw {
     occurred_at: DateTime<Utc>,
     received_at: DateTime<Utc>,
     labels: Value,
+    deliver_at: Option<DateTime<Utc>>,
+    delivery_cancelled_at: Option<DateTime<Utc>>,
 }
 
 impl EventWithPayloadRaw {
@@ -229,6 +431,8 @@ impl EventWithPayloadRaw {
             occurred_at: self.occurred_at,
             received_at: self.received_at,
             labels: self.labels.clone(),
+            deliver_at: self.deliver_at,
+            delivery_cancelled_at: self.delivery_cancelled_at,
         }
     }
 }
@@ -244,6 +448,10 @@ pub struct EventWithPayload {
     occurred_at: DateTime<Utc>,
     received_at: DateTime<Utc>,
     labels: Value,
+    /// If set, the event is not delivered before this date
+    deliver_at: Option<DateTime<Utc>>,
+    /// Date at which the delivery of the event was cancelled
+    delivery_cancelled_at: Option<DateTime<Utc>>,
 }
 
 #[api_v2_operation(
@@ -277,7 +485,7 @@ pub async fn get(
     let raw_event = query_as!(
             EventWithPayloadRaw,
             "
-                SELECT event__id, event_type__name, payload, payload_content_type, ip, metadata, occurred_at, received_at, labels
+                SELECT event__id, event_type__name, payload, payload_content_type, ip, metadata, occurred_at, received_at, labels, deliver_at, delivery_cancelled_at
                 FROM event.event
                 WHERE application__id = $1 AND event__id = $2
             ",
@@ -289,55 +497,71 @@ pub async fn get(
         .map_err(Hook0Problem::from)?;
 
     match raw_event {
-        Some(re) => {
-            if let Some(p) = &re.payload {
-                Ok(Json(re.to_event(p)))
-            } else if let Some(object_storage) = &state.object_storage {
-                let key = format!(
-                    "{}/event/{}/{event_id}",
-                    qs.application_id,
-                    re.received_at.naive_utc().date()
-                );
-                let payload_object = object_storage
-                    .client
-                    .get_object()
-                    .bucket(&object_storage.bucket)
-                    .key(&key)
-                    .send()
-                    .await
-                    .map_err(|e| {
-                        log_object_storage_error_with_context!(
-                            "S3 GET OBJECT failed",
-                            error_chain = DisplayErrorContext(&e).to_string(),
-                            object_key = &key,
-                        );
-                        Hook0Problem::InternalServerError
-                    })?;
-                let payload = payload_object
-                    .body
-                    .collect()
-                    .await
-                    .map_err(|e| {
-                        log_object_storage_error_with_context!(
-                            "S3 GET OBJECT body collect failed",
-                            error_chain = format!("{e}"),
-                            object_key = &key,
-                        );
-                        Hook0Problem::InternalServerError
-                    })?
-                    .to_vec();
-                Ok(Json(re.to_event(&payload)))
-            } else {
-                error!(
-                    "Payload of event {event_id} is not in database but object storage is disabled"
-                );
-                Ok(Json(re.to_event(&[])))
-            }
+        Some(mut re) => {
+            let payload = load_payload(
+                &state,
+                &qs.application_id,
+                &event_id,
+                re.received_at,
+                re.payload.take(),
+            )
+            .await?;
+            Ok(Json(re.to_event(&payload)))
         }
         None => Err(Hook0Problem::NotFound),
     }
 }
 
+/// Retrieve the payload of an event, which is stored either in database (`payload`) or in object storage
+pub async fn load_payload(
+    state: &crate::State,
+    application_id: &Uuid,
+    event_id: &Uuid,
+    received_at: DateTime<Utc>,
+    payload: Option<Vec<u8>>,
+) -> Result<Vec<u8>, Hook0Problem> {
+    if let Some(p) = payload {
+        Ok(p)
+    } else if let Some(object_storage) = &state.object_storage {
+        let key = format!(
+            "{application_id}/event/{}/{event_id}",
+            received_at.naive_utc().date()
+        );
+        let payload_object = object_storage
+            .client
+            .get_object()
+            .bucket(&object_storage.bucket)
+            .key(&key)
+            .send()
+            .await
+            .map_err(|e| {
+                log_object_storage_error_with_context!(
+                    "S3 GET OBJECT failed",
+                    error_chain = DisplayErrorContext(&e).to_string(),
+                    object_key = &key,
+                );
+                Hook0Problem::InternalServerError
+            })?;
+        let payload = payload_object
+            .body
+            .collect()
+            .await
+            .map_err(|e| {
+                log_object_storage_error_with_context!(
+                    "S3 GET OBJECT body collect failed",
+                    error_chain = format!("{e}"),
+                    object_key = &key,
+                );
+                Hook0Problem::InternalServerError
+            })?
+            .to_vec();
+        Ok(payload)
+    } else {
+        error!("Payload of event {event_id} is not in database but object storage is disabled");
+        Ok(Vec::new())
+    }
+}
+
 /// Event to be ingested into Hook0.
 #[derive(Debug, Deserialize, Apiv2Schema, Validate)]
 pub struct EventPost {
@@ -359,18 +583,145 @@ pub struct EventPost {
     metadata: Option<HashMap<String, String>>,
     /// Timestamp when the event occurred.
     occurred_at: DateTime<Utc>,
+    /// Optional timestamp before which the event must not be delivered. Until then, the event is held and its delivery can be cancelled. It cannot be later than the end of the event's retention period.
+    deliver_at: Option<DateTime<Utc>>,
     /// Labels for event filtering and routing to subscriptions.
     #[validate(custom(function = "crate::validators::labels"))]
     labels: HashMap<String, String>,
+    /// Optional idempotency key, which can also be sent in the `Idempotency-Key` header. If an event was already ingested with the same key during the idempotency window (24 hours by default), this event is not ingested and the original one is returned instead. Length: 1-255 visible ASCII characters.
+    idempotency_key: Option<String>,
 }
 
-#[derive(Debug, Serialize, Apiv2Schema)]
+#[derive(Debug, Clone, Serialize, Apiv2Schema)]
 pub struct IngestedEvent {
     application_id: Uuid,
     event_id: Uuid,
     received_at: DateTime<Utc>,
 }
 
+/// Response of the ingestion endpoint: `201 Created` for a new event, `200 OK` when an event was already ingested with the same idempotency key
+pub enum IngestResponse {
+    Created(IngestedEvent),
+    AlreadyIngested(IngestedEvent),
+}
+
+impl Responder for IngestResponse {
+    type Body = BoxBody;
+
+    fn respond_to(self, _: &actix_web::HttpRequest) -> HttpResponse<Self::Body> {
+        match self {
+            Self::Created(event) => HttpResponse::Created().json(event),
+            Self::AlreadyIngested(event) => HttpResponse::Ok().json(event),
+        }
+    }
+}
+
+impl Apiv2Schema for IngestResponse {
+    fn name() -> Option<String> {
+        IngestedEvent::name()
+    }
+
+    fn raw_schema() -> DefaultSchemaRaw {
+        IngestedEvent::raw_schema()
+    }
+}
+
+impl OperationModifier for IngestResponse {
+    fn update_parameter(op: &mut paperclip::v2::models::DefaultOperationRaw) {
+        IngestedEvent::update_parameter(op);
+    }
+
+    fn update_response(op: &mut paperclip::v2::models::DefaultOperationRaw) {
+        IngestedEvent::update_response(op);
+        let schema_with_ref = IngestedEvent::schema_with_ref();
+        let response = match schema_with_ref.reference {
+            Some(reference) => Either::Left(Reference { reference }),
+            None => Either::Right(Response {
+                description: schema_with_ref.description.to_owned(),
+                schema: Some(schema_with_ref),
+                headers: BTreeMap::new(),
+            }),
+        };
+        op.responses.insert("201".to_owned(), response.clone());
+        op.responses.insert("200".to_owned(), response);
+    }
+
+    fn update_definitions(map: &mut BTreeMap<String, DefaultSchemaRaw>) {
+        IngestedEvent::update_definitions(map);
+    }
+
+    fn update_security(op: &mut paperclip::v2::models::DefaultOperationRaw) {
+        IngestedEvent::update_security(op);
+    }
+
+    fn update_security_definitions(
+        map: &mut BTreeMap<String, paperclip::v2::models::SecurityScheme>,
+    ) {
+        IngestedEvent::update_security_definitions(map);
+    }
+}
+
+/// Pick the idempotency key from the `Idempotency-Key` header or the `idempotency_key` property, and check it is valid
+fn resolve_idempotency_key(
+    header: Option<String>,
+    property: Option<&String>,
+) -> Result<Option<String>, Hook0Problem> {
+    let key = match (header, property) {
+        (Some(header), Some(property)) if &header != property => {
+            return Err(Hook0Problem::InvalidIdempotencyKey);
+        }
+        (Some(header), _) => Some(header),
+        (None, property) => property.cloned(),
+    };
+    if let Some(key) = &key
+        && (key.is_empty() || key.len() > 255 || !key.bytes().all(|b| b.is_ascii_graphic()))
+    {
+        Err(Hook0Problem::InvalidIdempotencyKey)
+    } else {
+        Ok(key)
+    }
+}
+
+/// Find the event that was ingested with an idempotency key, if the key is still within the idempotency window
+async fn find_idempotent_event<'a, A: Acquire<'a, Database = Postgres>>(
+    db: A,
+    application_id: Uuid,
+    idempotency_key: &str,
+    idempotency_window: Duration,
+) -> Result<Option<IngestedEvent>, Hook0Problem> {
+    let mut db = db.acquire().await?;
+
+    query_as!(
+        IngestedEvent,
+        "
+            SELECT application__id AS application_id, event__id AS event_id, received_at
+            FROM event.idempotency_key
+            WHERE application__id = $1
+                AND idempotency_key = $2
+                AND created_at > statement_timestamp() - make_interval(secs => $3)
+        ",
+        application_id,
+        idempotency_key,
+        idempotency_window.as_secs_f64(),
+    )
+    .fetch_optional(&mut *db)
+    .await
+    .map_err(Hook0Problem::from)
+}
+
+/// Check that an event held until `deliver_at` will not be deleted by the retention policy before being delivered
+fn check_deliver_at(
+    deliver_at: DateTime<Utc>,
+    days_of_events_retention: QuotaValue,
+) -> Result<(), Hook0Problem> {
+    match Utc::now().checked_add_signed(TimeDelta::days(i64::from(days_of_events_retention))) {
+        Some(max_deliver_at) if deliver_at > max_deliver_at => Err(
+            Hook0Problem::EventDeliverAtBeyondRetention(days_of_events_retention),
+        ),
+        _ => Ok(()),
+    }
+}
+
 #[api_v2_operation(
     summary = "Ingest an event",
     description = "Sends an event to Hook0 for processing. The event will be matched against active subscriptions based on event type and labels, triggering webhook deliveries to matching endpoints. Requires event_type, payload, payload_content_type, labels, and occurred_at.",
@@ -384,8 +735,9 @@ pub async fn ingest(
     _: OaBiscuit,
     biscuit: ReqData<Biscuit>,
     ip: UserIp,
+    idempotency_key: IdempotencyKey,
     body: Json<EventPost>,
-) -> Result<CreatedJson<IngestedEvent>, Hook0Problem> {
+) -> Result<IngestResponse, Hook0Problem> {
     let started_at = Instant::now();
     // Phase durations are accumulated here and only reported once the event has actually been ingested, so a request that fails halfway reports nothing.
     let mut phases: Vec<(&'static str, Duration)> = Vec::with_capacity(10);
@@ -416,89 +768,41 @@ pub async fn ingest(
     };
     let labels = serde_json::to_value(body.labels.clone())
         .expect("could not serialize event labels into JSON");
+    let idempotency_key =
+        resolve_idempotency_key(idempotency_key.into_inner(), body.idempotency_key.as_ref())?;
     phases.push(("validation", phase_started_at.elapsed()));
 
-    let phase_started_at = Instant::now();
-
-    let can_exceed_events_per_day_quota = query_scalar!(
-        "
-            SELECT o.price__id
-            FROM event.application AS a
-            INNER JOIN iam.organization AS o ON o.organization__id = a.organization__id
-            WHERE a.application__id = $1
-        ",
-        &application_id
-    )
-    .fetch_one(&state.db)
-    .await
-    .map_err(Hook0Problem::from)?
-    .is_some();
-
-    let events_per_days_limit = state
-        .quotas
-        .get_limit_for_application(&state.db, Quota::EventsPerDay, &application_id)
-        .await?;
-
-    let current_events_per_day = if can_exceed_events_per_day_quota {
-        0
-    } else {
-        query_scalar!(
-            r#"
-                SELECT COALESCE(amount, 0) AS "amount!"
-                FROM event.events_per_day
-                WHERE application__id = $1 AND date = current_date
-            "#,
-            application_id
-        )
-        .fetch_optional(&state.db)
-        .await
-        .map_err(Hook0Problem::from)?
-        .unwrap_or(0)
-    };
+    // Duplicates are answered before quota checks so that retrying producers get the original event even if the quota was reached in the meantime
+    if let Some(key) = &idempotency_key {
+        let phase_started_at = Instant::now();
+        let ingested_event =
+            find_idempotent_event(&state.db, application_id, key, state.idempotency_window).await?;
+        phases.push(("idempotency_check", phase_started_at.elapsed()));
+
+        if let Some(ingested_event) = ingested_event {
+            trace!(
+                "Event {} was already ingested with the same idempotency key",
+                ingested_event.event_id
+            );
+            return Ok(IngestResponse::AlreadyIngested(ingested_event));
+        }
+    }
 
+    let phase_started_at = Instant::now();
+    if let Some(deliver_at) = body.deliver_at {
+        let days_of_events_retention = state
+            .quotas
+            .get_limit_for_application(&state.db, Quota::DaysOfEventsRetention, &application_id)
+            .await?;
+        check_deliver_at(deliver_at, days_of_events_retention)?;
+    }
+    let quota = get_events_per_day_quota(&state, application_id).await?;
     phases.push(("quota_checks", phase_started_at.elapsed()));
 
-    let can_ingest = if can_exceed_events_per_day_quota {
-        true
-    } else {
-        current_events_per_day < events_per_days_limit
-    };
-
-    if can_ingest {
-        if state.enable_quota_based_email_notifications {
-            let actual_consumption_percent = 100 * current_events_per_day / events_per_days_limit;
-
-            if actual_consumption_percent
-                > i32::from(state.quota_notification_events_per_day_threshold)
-            {
-                let phase_started_at = Instant::now();
-
-                // Template Mail — `recipient_first_name` is intentionally None
-                // here and hydrated per-admin inside the send loop
-                // (`quotas.rs::send_organization_email_notification`). A render
-                // attempt before hydration returns Err (see
-                // `mailer.rs::Mail::render` fail-fast check).
-                let mail = Mail::QuotaEventsPerDayWarning {
-                    recipient_first_name: None,
-                    pricing_url_hash: "#pricing".to_owned(),
-                    actual_consumption_percent,
-                    current_events_per_day,
-                    events_per_days_limit,
-                    extra_variables: Vec::new(),
-                };
-                state
-                    .quotas
-                    .send_application_email_notification(
-                        &state,
-                        Quota::EventsPerDay,
-                        QuotaNotificationType::Warning,
-                        application_id,
-                        mail,
-                    )
-                    .await?;
-
-                phases.push(("quota_notification", phase_started_at.elapsed()));
-            }
+    if quota.remaining() > 0 {
+        let phase_started_at = Instant::now();
+        if notify_events_per_day_quota_warning(&state, application_id, &quota).await? {
+            phases.push(("quota_notification", phase_started_at.elapsed()));
         }
 
         let phase_started_at = Instant::now();
@@ -506,10 +810,68 @@ pub async fn ingest(
         let payload = content_type.validate_and_decode(&body.payload)?;
         phases.push(("payload_decode", phase_started_at.elapsed()));
 
+        if content_type == PayloadContentType::Json {
+            let phase_started_at = Instant::now();
+            crate::handlers::event_types::validate_payload(
+                &state.db,
+                &application_id,
+                &body.event_type,
+                &payload,
+            )
+            .await?;
+            phases.push(("schema_validation", phase_started_at.elapsed()));
+        }
+
         let phase_started_at = Instant::now();
         let mut tx = state.db.begin().await?;
         phases.push(("db_begin", phase_started_at.elapsed()));
 
+        // The key is claimed before the event is inserted so that concurrent duplicates wait for the first request to be committed (or rolled back)
+        let mut event_id = body.event_id;
+        let mut received_at = None;
+        if let Some(key) = &idempotency_key {
+            let phase_started_at = Instant::now();
+            let claimed = query!(
+                "
+                    INSERT INTO event.idempotency_key AS ik (application__id, idempotency_key, event__id, received_at)
+                    VALUES ($1, $2, COALESCE($3, uuidv7()), statement_timestamp())
+                    ON CONFLICT (application__id, idempotency_key) DO UPDATE
+                    SET event__id = EXCLUDED.event__id, received_at = EXCLUDED.received_at, created_at = EXCLUDED.created_at
+                    WHERE ik.created_at <= statement_timestamp() - make_interval(secs => $4)
+                    RETURNING event__id, received_at
+                ",
+                application_id,
+                key,
+                body.event_id,
+                state.idempotency_window.as_secs_f64(),
+            )
+            .fetch_optional(&mut *tx)
+            .await
+            .map_err(Hook0Problem::from)?;
+            phases.push(("idempotency_claim", phase_started_at.elapsed()));
+
+            match claimed {
+                Some(claimed) => {
+                    event_id = Some(claimed.event__id);
+                    received_at = Some(claimed.received_at);
+                }
+                None => {
+                    tx.rollback().await?;
+                    return match find_idempotent_event(
+                        &state.db,
+                        application_id,
+                        key,
+                        state.idempotency_window,
+                    )
+                    .await?
+                    {
+                        Some(ingested_event) => Ok(IngestResponse::AlreadyIngested(ingested_event)),
+                        None => Err(Hook0Problem::EventAlreadyIngested),
+                    };
+                }
+            }
+        }
+
         let phase_started_at = Instant::now();
         let payload_to_insert = if let Some(true) =
             state.object_storage.as_ref().map(|object_storage| {
@@ -523,15 +885,21 @@ pub async fn ingest(
         } else {
             Some(&payload)
         };
+        if payload_to_insert.is_none()
+            && content_type == PayloadContentType::Json
+            && let Ok(json_payload) = std::str::from_utf8(&payload)
+        {
+            set_dispatch_payload(&mut *tx, json_payload).await?;
+        }
         let event = query_as!(
                 IngestedEvent,
                 "
-                    INSERT INTO event.event (application__id, event__id, event_type__name, payload, payload_content_type, ip, metadata, occurred_at, received_at, labels)
-                    VALUES ($1, COALESCE($2, uuidv7()), $3, $4, $5, $6, $7, $8, statement_timestamp(), $9)
+                    INSERT INTO event.event (application__id, event__id, event_type__name, payload, payload_content_type, ip, metadata, occurred_at, received_at, labels, deliver_at)
+                    VALUES ($1, COALESCE($2, uuidv7()), $3, $4, $5, $6, $7, $8, COALESCE($10, statement_timestamp()), $9, $11)
                     RETURNING application__id AS application_id, event__id AS event_id, received_at
                 ",
                 application_id,
-                body.event_id,
+                event_id,
                 &body.event_type,
                 payload_to_insert,
                 &body.payload_content_type,
@@ -539,6 +907,8 @@ pub async fn ingest(
                 metadata,
                 &body.occurred_at,
                 labels,
+                received_at,
+                body.deliver_at,
             )
             .fetch_one(&mut *tx)
             .await
@@ -657,90 +1027,1102 @@ pub async fn ingest(
         report_ingestion_duration(started_at.elapsed());
         report_ingestion_phase_durations(&phases);
 
-        Ok(CreatedJson(event))
+        Ok(IngestResponse::Created(event))
     } else {
-        if state.enable_quota_based_email_notifications {
-            // Template Mail — same hydration pattern as
-            // `QuotaEventsPerDayWarning` above.
-            let mail = Mail::QuotaEventsPerDayReached {
-                recipient_first_name: None,
-                pricing_url_hash: "#pricing".to_owned(),
-                current_events_per_day,
-                events_per_days_limit,
-                extra_variables: Vec::new(),
-            };
-            state
-                .quotas
-                .send_application_email_notification(
-                    &state,
-                    Quota::EventsPerDay,
-                    QuotaNotificationType::Reached,
-                    application_id,
-                    mail,
-                )
-                .await?;
-        }
-        Err(Hook0Problem::TooManyEventsToday(events_per_days_limit))
+        notify_events_per_day_quota_reached(&state, application_id, &quota).await?;
+        Err(Hook0Problem::TooManyEventsToday(quota.limit))
     }
 }
 
-#[derive(Debug, Deserialize, Apiv2Schema)]
-pub struct ReplayEvent {
-    application_id: Uuid,
+/// Events per day quota of an application, as it stands before ingesting new events
+struct EventsPerDayQuota {
+    /// Organizations that have a plan can exceed their quota
+    can_exceed: bool,
+    limit: QuotaValue,
+    current: QuotaValue,
 }
 
-#[api_v2_operation(
-    summary = "Replay an event",
-    description = "Re-triggers webhook deliveries for an existing event. All active subscriptions matching the event type and labels will receive the event again. Useful for retrying failed deliveries or testing webhooks.",
-    operation_id = "events.replay",
-    consumes = "application/json",
-    tags("Events Management", "mcp")
-)]
-pub async fn replay(
-    state: Data<crate::State>,
-    _: OaBiscuit,
-    biscuit: ReqData<Biscuit>,
-    event_id: Path<Uuid>,
-    body: Json<ReplayEvent>,
-) -> Result<NoContent, Hook0Problem> {
-    let event_id = event_id.into_inner();
-
-    authorize_for_application(
-        &state.db,
-        &biscuit,
-        Action::EventReplay {
-            application_id: &body.application_id,
-        },
-        state.max_authorization_time,
-        state.debug_authorizer,
-    )
-    .await?;
-
-    let mut tx = state.db.begin().await?;
-
-    struct ReplayedEvent {
-        received_at: DateTime<Utc>,
-        event_type: String,
-        payload: Option<Vec<u8>>,
-        payload_content_type: String,
+impl EventsPerDayQuota {
+    /// Number of events that can still be ingested today
+    fn remaining(&self) -> usize {
+        if self.can_exceed {
+            usize::MAX
+        } else {
+            usize::try_from(self.limit - self.current).unwrap_or(0)
+        }
     }
-    let replayed = query_as!(
-        ReplayedEvent,
+}
+
+async fn get_events_per_day_quota(
+    state: &crate::State,
+    application_id: Uuid,
+) -> Result<EventsPerDayQuota, Hook0Problem> {
+    let can_exceed = query_scalar!(
         "
-            UPDATE event.event
-            SET dispatched_at = NULL
-            WHERE event__id = $1
-                AND application__id = $2
-            RETURNING received_at, event_type__name AS event_type, payload, payload_content_type
+            SELECT o.price__id
+            FROM event.application AS a
+            INNER JOIN iam.organization AS o ON o.organization__id = a.organization__id
+            WHERE a.application__id = $1
         ",
-        event_id,
-        body.application_id,
+        &application_id
     )
-    .fetch_optional(&mut *tx)
+    .fetch_one(&state.db)
     .await
-    .map_err(Hook0Problem::from)?;
+    .map_err(Hook0Problem::from)?
+    .is_some();
 
-    match replayed {
+    let limit = state
+        .quotas
+        .get_limit_for_application(&state.db, Quota::EventsPerDay, &application_id)
+        .await?;
+
+    let current = if can_exceed {
+        0
+    } else {
+        query_scalar!(
+            r#"
+                SELECT COALESCE(amount, 0) AS "amount!"
+                FROM event.events_per_day
+                WHERE application__id = $1 AND date = current_date
+            "#,
+            application_id
+        )
+        .fetch_optional(&state.db)
+        .await
+        .map_err(Hook0Problem::from)?
+        .unwrap_or(0)
+    };
+
+    Ok(EventsPerDayQuota {
+        can_exceed,
+        limit,
+        current,
+    })
+}
+
+/// Send a warning email if the consumption of the events per day quota is above the notification threshold
+///
+/// Returns `true` if a notification was sent.
+async fn notify_events_per_day_quota_warning(
+    state: &Data<crate::State>,
+    application_id: Uuid,
+    quota: &EventsPerDayQuota,
+) -> Result<bool, Hook0Problem> {
+    if !state.enable_quota_based_email_notifications {
+        return Ok(false);
+    }
+
+    let actual_consumption_percent = 100 * quota.current / quota.limit;
+    if actual_consumption_percent <= i32::from(state.quota_notification_events_per_day_threshold) {
+        return Ok(false);
+    }
+
+    // Template Mail — `recipient_first_name` is intentionally None
+    // here and hydrated per-admin inside the send loop
+    // (`quotas.rs::send_organization_email_notification`). A render
+    // attempt before hydration returns Err (see
+    // `mailer.rs::Mail::render` fail-fast check).
+    let mail = Mail::QuotaEventsPerDayWarning {
+        recipient_first_name: None,
+        pricing_url_hash: "#pricing".to_owned(),
+        actual_consumption_percent,
+        current_events_per_day: quota.current,
+        events_per_days_limit: quota.limit,
+        extra_variables: Vec::new(),
+    };
+    state
+        .quotas
+        .send_application_email_notification(
+            state,
+            Quota::EventsPerDay,
+            QuotaNotificationType::Warning,
+            application_id,
+            mail,
+        )
+        .await?;
+    Ok(true)
+}
+
+/// Send an email telling that the events per day quota was reached
+async fn notify_events_per_day_quota_reached(
+    state: &Data<crate::State>,
+    application_id: Uuid,
+    quota: &EventsPerDayQuota,
+) -> Result<(), Hook0Problem> {
+    if state.enable_quota_based_email_notifications {
+        // Template Mail — same hydration pattern as
+        // `QuotaEventsPerDayWarning` above.
+        let mail = Mail::QuotaEventsPerDayReached {
+            recipient_first_name: None,
+            pricing_url_hash: "#pricing".to_owned(),
+            current_events_per_day: quota.current,
+            events_per_days_limit: quota.limit,
+            extra_variables: Vec::new(),
+        };
+        state
+            .quotas
+            .send_application_email_notification(
+                state,
+                Quota::EventsPerDay,
+                QuotaNotificationType::Reached,
+                application_id,
+                mail,
+            )
+            .await?;
+    }
+    Ok(())
+}
+
+#[derive(Debug, Deserialize, Apiv2Schema)]
+pub struct EventBatchPost {
+    /// Events to ingest, which can belong to different applications (1000 events at most with the default configuration)
+    events: Vec<EventPost>,
+}
+
+#[derive(Debug, Clone, Serialize, Apiv2Schema)]
+pub struct EventBatchItemResult {
+    /// HTTP status code that ingesting this event alone would have returned: 201 if it was ingested, 200 if it was already ingested with the same idempotency key, or an error status code
+    status: u16,
+    /// The ingested event (if it was ingested)
+    event: Option<IngestedEvent>,
+    /// Why the event was not ingested
+    problem: Option<EventBatchItemProblem>,
+}
+
+#[derive(Debug, Clone, Serialize, Apiv2Schema)]
+pub struct EventBatchItemProblem {
+    id: String,
+    title: String,
+    detail: String,
+    validation: Option<Value>,
+}
+
+impl From<IngestResponse> for EventBatchItemResult {
+    fn from(response: IngestResponse) -> Self {
+        let (status, event) = match response {
+            IngestResponse::Created(event) => (StatusCode::CREATED, event),
+            IngestResponse::AlreadyIngested(event) => (StatusCode::OK, event),
+        };
+        Self {
+            status: status.as_u16(),
+            event: Some(event),
+            problem: None,
+        }
+    }
+}
+
+impl From<Hook0Problem> for EventBatchItemResult {
+    fn from(hook0_problem: Hook0Problem) -> Self {
+        let problem = Problem::from(hook0_problem);
+        Self {
+            status: problem.status.as_u16(),
+            event: None,
+            problem: Some(EventBatchItemProblem {
+                id: problem.id.to_string(),
+                title: problem.title.to_owned(),
+                detail: problem.detail.into_owned(),
+                validation: problem.validation,
+            }),
+        }
+    }
+}
+
+/// An event of a batch that passed the checks that can be done on its own
+struct BatchEvent<'a> {
+    index: usize,
+    post: &'a EventPost,
+    event_id: Uuid,
+    content_type: PayloadContentType,
+    payload: Vec<u8>,
+    metadata: Value,
+    labels: Value,
+    idempotency_key: Option<String>,
+    received_at: Option<DateTime<Utc>>,
+    in_object_storage: bool,
+}
+
+/// Maximum number of payloads of a batch that are written to object storage concurrently
+const BATCH_OBJECT_STORAGE_CONCURRENCY: usize = 16;
+
+#[api_v2_operation(
+    summary = "Ingest a batch of events",
+    description = "Sends several events to Hook0 in a single request. Each event is processed like with the event ingestion endpoint, but authorization and quotas are checked once per application and events are inserted together. The response contains one result per event, in the same order as the request: either the ingested event or the problem that prevented its ingestion. Events that share an idempotency key with a previous event of the batch get the result of this event.",
+    operation_id = "events.ingest_batch",
+    consumes = "application/json",
+    produces = "application/json",
+    tags("Events Management")
+)]
+pub async fn ingest_batch(
+    state: Data<crate::State>,
+    _: OaBiscuit,
+    biscuit: ReqData<Biscuit>,
+    ip: UserIp,
+    body: Json<EventBatchPost>,
+) -> Result<Json<Vec<EventBatchItemResult>>, Hook0Problem> {
+    let posts = &body.events;
+    if posts.is_empty() || posts.len() > usize::from(state.max_events_per_batch) {
+        return Err(Hook0Problem::EventBatchInvalidSize(
+            state.max_events_per_batch,
+        ));
+    }
+
+    let application_ids = posts
+        .iter()
+        .map(|post| post.application_id)
+        .collect::<HashSet<_>>();
+    let mut quotas = HashMap::with_capacity(application_ids.len());
+    let mut days_of_events_retention = HashMap::new();
+    for application_id in application_ids {
+        authorize_for_application(
+            &state.db,
+            &biscuit,
+            Action::EventIngest {
+                application_id: &application_id,
+            },
+            state.max_authorization_time,
+            state.debug_authorizer,
+        )
+        .await?;
+        quotas.insert(
+            application_id,
+            get_events_per_day_quota(&state, application_id).await?,
+        );
+        if posts
+            .iter()
+            .any(|post| post.application_id == application_id && post.deliver_at.is_some())
+        {
+            days_of_events_retention.insert(
+                application_id,
+                state
+                    .quotas
+                    .get_limit_for_application(
+                        &state.db,
+                        Quota::DaysOfEventsRetention,
+                        &application_id,
+                    )
+                    .await?,
+            );
+        }
+    }
+
+    let in_object_storage = |application_id: &Uuid| {
+        state.object_storage.as_ref().is_some_and(|object_storage| {
+            object_storage.store_event_payloads
+                && (object_storage.store_event_only_for.is_empty()
+                    || object_storage.store_event_only_for.contains(application_id))
+        })
+    };
+
+    let mut results: Vec<Option<EventBatchItemResult>> = vec![None; posts.len()];
+    // Events that share an idempotency key with a previous event of the batch, with the index of this previous event
+    let mut duplicates = Vec::new();
+    let mut first_index_by_idempotency_key = HashMap::new();
+    let mut event_ids = HashSet::new();
+    let mut validators = HashMap::new();
+    let mut events = Vec::with_capacity(posts.len());
+    for (index, post) in posts.iter().enumerate() {
+        match prepare_batch_event(&state.db, &mut validators, index, post).await {
+            Ok(mut event) => {
+                if let Some(key) = &event.idempotency_key {
+                    match first_index_by_idempotency_key
+                        .entry((post.application_id, key.to_owned()))
+                    {
+                        Entry::Occupied(first) => {
+                            duplicates.push((index, *first.get()));
+                            continue;
+                        }
+                        Entry::Vacant(first) => {
+                            first.insert(index);
+                        }
+                    }
+                }
+                if event_ids.insert(event.event_id) {
+                    event.in_object_storage = in_object_storage(&post.application_id);
+                    events.push(event);
+                } else {
+                    results[index] = Some(Hook0Problem::EventAlreadyIngested.into());
+                }
+            }
+            Err(problem) => results[index] = Some(problem.into()),
+        }
+    }
+
+    // Duplicates are answered before other checks so that retrying producers get the original events even if the quota was reached in the meantime
+    let already_ingested_events =
+        find_idempotent_events(&state.db, &events, state.idempotency_window).await?;
+    events.retain(|e| {
+        match e
+            .idempotency_key
+            .as_ref()
+            .and_then(|key| already_ingested_events.get(&(e.post.application_id, key.to_owned())))
+        {
+            Some(ingested_event) => {
+                results[e.index] =
+                    Some(IngestResponse::AlreadyIngested(ingested_event.to_owned()).into());
+                false
+            }
+            None => true,
+        }
+    });
+
+    events.retain(|e| {
+        if let Some(deliver_at) = e.post.deliver_at
+            && let Some(days) = days_of_events_retention.get(&e.post.application_id)
+            && let Err(problem) = check_deliver_at(deliver_at, *days)
+        {
+            results[e.index] = Some(problem.into());
+            false
+        } else {
+            true
+        }
+    });
+
+    // Event types and client-generated event IDs are checked beforehand so that a single invalid event does not make the insertion of the whole batch fail
+    let event_types = query!(
+        "
+            SELECT application__id, event_type__name
+            FROM event.event_type
+            WHERE (application__id, event_type__name) IN (SELECT * FROM UNNEST($1::uuid[], $2::text[]))
+        ",
+        &events.iter().map(|e| e.post.application_id).collect::<Vec<_>>(),
+        &events
+            .iter()
+            .map(|e| e.post.event_type.to_owned())
+            .collect::<Vec<_>>(),
+    )
+    .fetch_all(&state.db)
+    .await
+    .map_err(Hook0Problem::from)?
+    .into_iter()
+    .map(|r| (r.application__id, r.event_type__name))
+    .collect::<HashSet<_>>();
+    let existing_event_ids = query_scalar!(
+        "
+            SELECT event__id
+            FROM event.event
+            WHERE event__id = ANY($1)
+        ",
+        &events
+            .iter()
+            .filter_map(|e| e.post.event_id)
+            .collect::<Vec<_>>(),
+    )
+    .fetch_all(&state.db)
+    .await
+    .map_err(Hook0Problem::from)?
+    .into_iter()
+    .collect::<HashSet<_>>();
+    events.retain(|e| {
+        let problem =
+            if !event_types.contains(&(e.post.application_id, e.post.event_type.to_owned())) {
+                Hook0Problem::EventTypeDoesNotExist
+            } else if existing_event_ids.contains(&e.event_id) {
+                Hook0Problem::EventAlreadyIngested
+            } else {
+                return true;
+            };
+        results[e.index] = Some(problem.into());
+        false
+    });
+
+    for (application_id, quota) in &quotas {
+        if quota.remaining() > 0 {
+            notify_events_per_day_quota_warning(&state, *application_id, quota).await?;
+        }
+    }
+    let mut reached_quotas = HashSet::new();
+    events.retain(|e| match quotas.get_mut(&e.post.application_id) {
+        Some(quota) if quota.remaining() > 0 => {
+            quota.current += 1;
+            true
+        }
+        Some(quota) => {
+            results[e.index] = Some(Hook0Problem::TooManyEventsToday(quota.limit).into());
+            reached_quotas.insert(e.post.application_id);
+            false
+        }
+        None => false,
+    });
+
+    let ingested_events = if events.is_empty() {
+        Vec::new()
+    } else {
+        let mut tx = state.db.begin().await?;
+
+        // Keys are claimed before events are inserted so that concurrent duplicates wait for this batch to be committed (or rolled back)
+        let keyed_events = events
+            .iter()
+            .filter(|e| e.idempotency_key.is_some())
+            .collect::<Vec<_>>();
+        if !keyed_events.is_empty() {
+            let claimed_keys = query!(
+                "
+                    INSERT INTO event.idempotency_key AS ik (application__id, idempotency_key, event__id, received_at)
+                    SELECT k.application__id, k.idempotency_key, k.event__id, statement_timestamp()
+                    FROM UNNEST($1::uuid[], $2::text[], $3::uuid[]) AS k(application__id, idempotency_key, event__id)
+                    ON CONFLICT (application__id, idempotency_key) DO UPDATE
+                    SET event__id = EXCLUDED.event__id, received_at = EXCLUDED.received_at, created_at = EXCLUDED.created_at
+                    WHERE ik.created_at <= statement_timestamp() - make_interval(secs => $4)
+                    RETURNING event__id, received_at
+                ",
+                &keyed_events
+                    .iter()
+                    .map(|e| e.post.application_id)
+                    .collect::<Vec<_>>(),
+                &keyed_events
+                    .iter()
+                    .filter_map(|e| e.idempotency_key.to_owned())
+                    .collect::<Vec<_>>(),
+                &keyed_events.iter().map(|e| e.event_id).collect::<Vec<_>>(),
+                state.idempotency_window.as_secs_f64(),
+            )
+            .fetch_all(&mut *tx)
+            .await
+            .map_err(Hook0Problem::from)?
+            .into_iter()
+            .map(|r| (r.event__id, r.received_at))
+            .collect::<HashMap<_, _>>();
+
+            // Keys that could not be claimed were used by concurrent requests
+            let already_ingested_events = find_idempotent_events(
+                &mut *tx,
+                &events
+                    .iter()
+                    .filter(|e| {
+                        e.idempotency_key.is_some() && !claimed_keys.contains_key(&e.event_id)
+                    })
+                    .collect::<Vec<_>>(),
+                state.idempotency_window,
+            )
+            .await?;
+            events.retain_mut(|e| {
+                let Some(key) = &e.idempotency_key else {
+                    return true;
+                };
+                if let Some(received_at) = claimed_keys.get(&e.event_id) {
+                    e.received_at = Some(*received_at);
+                    true
+                } else {
+                    results[e.index] = Some(
+                        match already_ingested_events.get(&(e.post.application_id, key.to_owned()))
+                        {
+                            Some(ingested_event) => {
+                                IngestResponse::AlreadyIngested(ingested_event.to_owned()).into()
+                            }
+                            None => Hook0Problem::EventAlreadyIngested.into(),
+                        },
+                    );
+                    false
+                }
+            });
+        }
+
+        // The dispatch trigger needs JSON payloads to evaluate subscription filters; payloads that are not stored in the database are given to it one event at a time
+        let ip = IpNetwork::from(ip.into_inner());
+        let (individual_events, bulk_events): (Vec<_>, Vec<_>) = events
+            .iter()
+            .partition(|e| e.in_object_storage && e.content_type == PayloadContentType::Json);
+        let mut ingested_events = insert_batch_events(&mut *tx, &bulk_events, ip).await?;
+        for event in individual_events {
+            if let Ok(json_payload) = std::str::from_utf8(&event.payload) {
+                set_dispatch_payload(&mut *tx, json_payload).await?;
+            }
+            ingested_events.append(&mut insert_batch_events(&mut *tx, &[event], ip).await?);
+        }
+        let received_at_by_event_id = ingested_events
+            .iter()
+            .map(|e| (e.event_id, e.received_at))
+            .collect::<HashMap<_, _>>();
+
+        if let Some(object_storage) = &state.object_storage {
+            let events_to_store = events
+                .iter()
+                .filter(|e| e.in_object_storage)
+                .filter_map(|e| {
+                    received_at_by_event_id
+                        .get(&e.event_id)
+                        .map(|received_at| (e, *received_at))
+                })
+                .collect::<Vec<_>>();
+            let failed_events = stream::iter(events_to_store.iter())
+                .map(|(e, received_at)| {
+                    let key = format!(
+                        "{}/event/{}/{}",
+                        e.post.application_id,
+                        received_at.naive_utc().date(),
+                        e.event_id
+                    );
+                    async move {
+                        let res = object_storage
+                            .client
+                            .put_object()
+                            .bucket(&object_storage.bucket)
+                            .key(&key)
+                            .content_type(&e.post.payload_content_type)
+                            .body(ByteStream::from(e.payload.clone()))
+                            .send()
+                            .await;
+                        match res {
+                            Ok(_) => None,
+                            Err(err) => {
+                                log_object_storage_error_with_context!(
+                                    "S3 PUT OBJECT failed for an event of a batch",
+                                    error_chain = DisplayErrorContext(&err).to_string(),
+                                    object_key = &key,
+                                );
+                                Some(e)
+                            }
+                        }
+                    }
+                })
+                .buffer_unordered(BATCH_OBJECT_STORAGE_CONCURRENCY)
+                .filter_map(|e| async move { e })
+                .collect::<Vec<_>>()
+                .await;
+
+            report_event_payloads_stored_in_object_storage(
+                (events_to_store.len() - failed_events.len()) as u64,
+            );
+
+            if !failed_events.is_empty() {
+                if object_storage.db_fallback_on_write_failure {
+                    // Same as for single events: payloads are backfilled into the database so that events are still deliverable
+                    query!(
+                        "
+                            UPDATE event.event AS e
+                            SET payload = p.payload
+                            FROM UNNEST($1::uuid[], $2::bytea[]) AS p(event__id, payload)
+                            WHERE e.event__id = p.event__id
+                        ",
+                        &failed_events.iter().map(|e| e.event_id).collect::<Vec<_>>(),
+                        &failed_events
+                            .iter()
+                            .map(|e| e.payload.clone())
+                            .collect::<Vec<_>>(),
+                    )
+                    .execute(&mut *tx)
+                    .await
+                    .map_err(Hook0Problem::from)?;
+                    report_event_payloads_stored_in_db_fallback(failed_events.len() as u64);
+                } else {
+                    // DB fallback disabled: fail the request (and roll back the tx) so
+                    // payloads are never stored in the database.
+                    return Err(Hook0Problem::InternalServerError);
+                }
+            }
+        }
+
+        tx.commit().await?;
+
+        if let Some(pulsar) = &state.pulsar {
+            let pulsar_events = events
+                .iter()
+                .filter_map(|e| {
+                    received_at_by_event_id
+                        .get(&e.event_id)
+                        .map(|received_at| PulsarEvent {
+                            application_id: e.post.application_id,
+                            event_id: e.event_id,
+                            received_at: *received_at,
+                            event_type: &e.post.event_type,
+                            payload: &e.payload,
+                            payload_content_type: &e.post.payload_content_type,
+                        })
+                })
+                .collect::<Vec<_>>();
+            if let Err(e) =
+                send_events_request_attempts_to_pulsar(&state.db, pulsar, &pulsar_events, false)
+                    .await
+            {
+                error!(
+                    error = ?e,
+                    "Some/all request attempts of a batch of events may not have been enqueued to Pulsar after commit; output-worker will need to reconcile"
+                );
+            }
+        }
+
+        report_ingested_events(ingested_events.len() as u64);
+        ingested_events
+    };
+
+    let indexes_by_event_id = events
+        .iter()
+        .map(|e| (e.event_id, e.index))
+        .collect::<HashMap<_, _>>();
+    for ingested_event in ingested_events {
+        if let Some(index) = indexes_by_event_id.get(&ingested_event.event_id) {
+            results[*index] = Some(IngestResponse::Created(ingested_event).into());
+        }
+    }
+    for (index, first_index) in duplicates {
+        results[index] = results[first_index].to_owned().map(|mut result| {
+            if result.status == StatusCode::CREATED.as_u16() {
+                result.status = StatusCode::OK.as_u16();
+            }
+            result
+        });
+    }
+
+    for application_id in reached_quotas {
+        if let Some(quota) = quotas.get(&application_id) {
+            notify_events_per_day_quota_reached(&state, application_id, quota).await?;
+        }
+    }
+
+    Ok(Json(
+        results
+            .into_iter()
+            .map(|result| result.unwrap_or_else(|| Hook0Problem::InternalServerError.into()))
+            .collect(),
+    ))
+}
+
+/// Run the checks that do not depend on other events of the batch
+async fn prepare_batch_event<'a>(
+    db: &PgPool,
+    validators: &mut HashMap<(Uuid, String), Option<PayloadValidator>>,
+    index: usize,
+    post: &'a EventPost,
+) -> Result<BatchEvent<'a>, Hook0Problem> {
+    if let Err(e) = post.validate() {
+        return Err(Hook0Problem::Validation(e));
+    }
+    let idempotency_key = resolve_idempotency_key(None, post.idempotency_key.as_ref())?;
+
+    let content_type = PayloadContentType::from_str(&post.payload_content_type)?;
+    let payload = content_type.validate_and_decode(&post.payload)?;
+    if content_type == PayloadContentType::Json {
+        // Schemas are only fetched and compiled once per event type
+        let validator = match validators.entry((post.application_id, post.event_type.to_owned())) {
+            Entry::Occupied(validator) => validator.into_mut(),
+            Entry::Vacant(validator) => validator
+                .insert(get_payload_validator(db, &post.application_id, &post.event_type).await?),
+        };
+        if let Some(validator) = validator {
+            validator.validate(&post.application_id, &payload)?;
+        }
+    }
+
+    let metadata = match post.metadata.as_ref() {
+        Some(m) => serde_json::to_value(m.clone())
+            .expect("could not serialize subscription metadata into JSON"),
+        None => json!({}),
+    };
+    let labels = serde_json::to_value(post.labels.clone())
+        .expect("could not serialize event labels into JSON");
+
+    Ok(BatchEvent {
+        index,
+        post,
+        event_id: post.event_id.unwrap_or_else(Uuid::now_v7),
+        content_type,
+        payload,
+        metadata,
+        labels,
+        idempotency_key,
+        received_at: None,
+        in_object_storage: false,
+    })
+}
+
+/// Find the events that were ingested with the idempotency keys of events of a batch, if the keys are still within the idempotency window
+async fn find_idempotent_events<'a, A: Acquire<'a, Database = Postgres>>(
+    db: A,
+    events: &[impl Borrow<BatchEvent<'_>>],
+    idempotency_window: Duration,
+) -> Result<HashMap<(Uuid, String), IngestedEvent>, Hook0Problem> {
+    let (application_ids, idempotency_keys): (Vec<_>, Vec<_>) = events
+        .iter()
+        .filter_map(|e| {
+            let e = e.borrow();
+            e.idempotency_key
+                .to_owned()
+                .map(|key| (e.post.application_id, key))
+        })
+        .unzip();
+    if idempotency_keys.is_empty() {
+        return Ok(HashMap::new());
+    }
+
+    let mut db = db.acquire().await?;
+    let rows = query!(
+        "
+            SELECT application__id, idempotency_key, event__id, received_at
+            FROM event.idempotency_key
+            WHERE (application__id, idempotency_key) IN (SELECT * FROM UNNEST($1::uuid[], $2::text[]))
+                AND created_at > statement_timestamp() - make_interval(secs => $3)
+        ",
+        &application_ids,
+        &idempotency_keys,
+        idempotency_window.as_secs_f64(),
+    )
+    .fetch_all(&mut *db)
+    .await
+    .map_err(Hook0Problem::from)?;
+
+    Ok(rows
+        .into_iter()
+        .map(|r| {
+            (
+                (r.application__id, r.idempotency_key),
+                IngestedEvent {
+                    application_id: r.application__id,
+                    event_id: r.event__id,
+                    received_at: r.received_at,
+                },
+            )
+        })
+        .collect())
+}
+
+/// Insert events of a batch with a single query
+async fn insert_batch_events(
+    conn: &mut PgConnection,
+    events: &[&BatchEvent<'_>],
+    ip: IpNetwork,
+) -> Result<Vec<IngestedEvent>, Hook0Problem> {
+    if events.is_empty() {
+        return Ok(Vec::new());
+    }
+
+    query_as!(
+        IngestedEvent,
+        "
+            INSERT INTO event.event (application__id, event__id, event_type__name, payload, payload_content_type, ip, metadata, occurred_at, received_at, labels, deliver_at)
+            SELECT e.application__id, e.event__id, e.event_type__name, e.payload, e.payload_content_type, $6, e.metadata, e.occurred_at, COALESCE(e.received_at, statement_timestamp()), e.labels, e.deliver_at
+            FROM UNNEST($1::uuid[], $2::uuid[], $3::text[], $4::bytea[], $5::text[], $7::jsonb[], $8::timestamptz[], $9::timestamptz[], $10::jsonb[], $11::timestamptz[])
+                AS e(application__id, event__id, event_type__name, payload, payload_content_type, metadata, occurred_at, received_at, labels, deliver_at)
+            RETURNING application__id AS application_id, event__id AS event_id, received_at
+        ",
+        &events.iter().map(|e| e.post.application_id).collect::<Vec<_>>(),
+        &events.iter().map(|e| e.event_id).collect::<Vec<_>>(),
+        &events
+            .iter()
+            .map(|e| e.post.event_type.to_owned())
+            .collect::<Vec<_>>(),
+        &events
+            .iter()
+            .map(|e| (!e.in_object_storage).then(|| e.payload.clone()))
+            .collect::<Vec<_>>(),
+        &events
+            .iter()
+            .map(|e| e.post.payload_content_type.to_owned())
+            .collect::<Vec<_>>(),
+        ip,
+        &events.iter().map(|e| e.metadata.clone()).collect::<Vec<_>>(),
+        &events.iter().map(|e| e.post.occurred_at).collect::<Vec<_>>(),
+        &events.iter().map(|e| e.received_at).collect::<Vec<_>>(),
+        &events.iter().map(|e| e.labels.clone()).collect::<Vec<_>>(),
+        &events.iter().map(|e| e.post.deliver_at).collect::<Vec<_>>(),
+    )
+    .fetch_all(conn)
+    .await
+    .map_err(Hook0Problem::from)
+}
+
+#[derive(Debug, Deserialize, Apiv2Schema)]
+pub struct ReplayEvent {
+    application_id: Uuid,
+}
+
+#[api_v2_operation(
+    summary = "Replay an event",
+    description = "Re-triggers webhook deliveries for an existing event. All active subscriptions matching the event type and labels will receive the event again. Useful for retrying failed deliveries or testing webhooks. Events whose delivery was cancelled cannot be replayed.",
+    operation_id = "events.replay",
+    consumes = "application/json",
+    tags("Events Management", "mcp")
+)]
+pub async fn replay(
+    state: Data<crate::State>,
+    _: OaBiscuit,
+    biscuit: ReqData<Biscuit>,
+    event_id: Path<Uuid>,
+    body: Json<ReplayEvent>,
+) -> Result<NoContent, Hook0Problem> {
+    let event_id = event_id.into_inner();
+
+    authorize_for_application(
+        &state.db,
+        &biscuit,
+        Action::EventReplay {
+            application_id: &body.application_id,
+        },
+        state.max_authorization_time,
+        state.debug_authorizer,
+    )
+    .await?;
+
+    replay_event(&state, body.application_id, event_id, None).await?;
+    Ok(NoContent)
+}
+
+#[api_v2_operation(
+    summary = "Cancel the delivery of an event",
+    description = "Stops the delivery of an event to the subscriptions that did not receive it yet, for example because it was sent by mistake or contains sensitive data. Its request attempts that are not done yet get the `cancelled` status and are not retried; requests that are being sent cannot be recalled. This also works for held events (ingested with a `deliver_at` date that is not reached yet). A cancelled event cannot be replayed and its request attempts cannot be retried.",
+    operation_id = "events.cancel",
+    consumes = "application/json",
+    produces = "application/json",
+    tags("Events Management")
+)]
+pub async fn cancel(
+    state: Data<crate::State>,
+    _: OaBiscuit,
+    biscuit: ReqData<Biscuit>,
+    event_id: Path<Uuid>,
+    qs: Query<Qs>,
+) -> Result<Json<Event>, Hook0Problem> {
+    let event_id = event_id.into_inner();
+
+    authorize_for_application(
+        &state.db,
+        &biscuit,
+        Action::EventCancel {
+            application_id: &qs.application_id,
+        },
+        state.max_authorization_time,
+        state.debug_authorizer,
+    )
+    .await?;
+
+    let mut tx = state.db.begin().await?;
+
+    let cancelled_event = query_as!(
+        EventRaw,
+        "
+            UPDATE event.event AS e
+            SET delivery_cancelled_at = statement_timestamp()
+            WHERE e.application__id = $1
+                AND e.event__id = $2
+                AND e.delivery_cancelled_at IS NULL
+                AND (
+                    e.deliver_at > statement_timestamp()
+                    OR EXISTS (
+                        SELECT 1
+                        FROM webhook.request_attempt AS ra
+                        WHERE ra.event__id = e.event__id
+                            AND ra.succeeded_at IS NULL
+                            AND ra.failed_at IS NULL
+                    )
+                )
+            RETURNING event__id, event_type__name, payload_content_type, ip, metadata, occurred_at, received_at, labels, deliver_at, delivery_cancelled_at
+        ",
+        qs.application_id,
+        event_id,
+    )
+    .fetch_optional(&mut *tx)
+    .await
+    .map_err(Hook0Problem::from)?;
+
+    match cancelled_event {
+        Some(event) => {
+            // Pending request attempts are also marked as failed so that everything that waits for them to be done (such as ordered delivery) moves on
+            // Request attempts that are being sent are locked by their output worker and skipped; the worker does not retry them once it sees the event is cancelled
+            query!(
+                "
+                    UPDATE webhook.request_attempt
+                    SET failed_at = statement_timestamp(), cancelled_at = statement_timestamp()
+                    WHERE request_attempt__id IN (
+                        SELECT request_attempt__id
+                        FROM webhook.request_attempt
+                        WHERE event__id = $1
+                            AND application__id = $2
+                            AND succeeded_at IS NULL
+                            AND failed_at IS NULL
+                        FOR UPDATE SKIP LOCKED
+                    )
+                ",
+                event_id,
+                qs.application_id,
+            )
+            .execute(&mut *tx)
+            .await
+            .map_err(Hook0Problem::from)?;
+
+            tx.commit().await?;
+            Ok(Json(event.to_event()))
+        }
+        None => {
+            tx.rollback().await?;
+
+            let event_exists = query_scalar!(
+                r#"
+                    SELECT EXISTS (
+                        SELECT 1
+                        FROM event.event
+                        WHERE application__id = $1
+                            AND event__id = $2
+                    ) AS "exists!"
+                "#,
+                qs.application_id,
+                event_id,
+            )
+            .fetch_one(&state.db)
+            .await
+            .map_err(Hook0Problem::from)?;
+
+            if event_exists {
+                Err(Hook0Problem::EventNotCancellable)
+            } else {
+                Err(Hook0Problem::NotFound)
+            }
+        }
+    }
+}
+
+/// Create new request attempts for an event
+///
+/// They are created for all the subscriptions that currently match the event, or only for the given subscription. Events whose delivery was cancelled cannot be replayed.
+pub async fn replay_event(
+    state: &crate::State,
+    application_id: Uuid,
+    event_id: Uuid,
+    subscription_id: Option<Uuid>,
+) -> Result<(), Hook0Problem> {
+    let mut tx = state.db.begin().await?;
+    replay_event_in_transaction(&mut tx, state, application_id, event_id, subscription_id).await?;
+    tx.commit().await?;
+
+    report_replayed_events(1);
+    Ok(())
+}
+
+/// Create new request attempts for an event, like [`replay_event`], in a transaction that the caller commits
+///
+/// This lets callers record the replay along with other changes.
+pub async fn replay_event_in_transaction(
+    conn: &mut PgConnection,
+    state: &crate::State,
+    application_id: Uuid,
+    event_id: Uuid,
+    subscription_id: Option<Uuid>,
+) -> Result<(), Hook0Problem> {
+    // Events whose delivery was cancelled must not be delivered again; the lock prevents their delivery from being cancelled while they are replayed
+    let delivery_cancelled = query_scalar!(
+        r#"
+            SELECT delivery_cancelled_at IS NOT NULL AS "delivery_cancelled!"
+            FROM event.event
+            WHERE event__id = $1
+                AND application__id = $2
+            FOR SHARE
+        "#,
+        event_id,
+        application_id,
+    )
+    .fetch_optional(&mut *conn)
+    .await
+    .map_err(Hook0Problem::from)?;
+    match delivery_cancelled {
+        None => return Err(Hook0Problem::NotFound),
+        Some(true) => return Err(Hook0Problem::EventDeliveryCancelled),
+        Some(false) => {}
+    }
+
+    // Subscriptions can filter on JSON payloads, which the dispatch trigger cannot read from object storage
+    if subscription_id.is_none() {
+        let stored_event = query!(
+            r#"
+                SELECT
+                    received_at,
+                    payload IS NULL AND payload_content_type = 'application/json' AS "json_payload_in_object_storage!",
+                    EXISTS (
+                        SELECT 1
+                        FROM webhook.subscription AS s
+                        WHERE s.application__id = $2
+                            AND s.is_enabled
+                            AND s.deleted_at IS NULL
+                            AND s.filters @? '$[*].path'
+                    ) AS "has_payload_filters!"
+                FROM event.event
+                WHERE event__id = $1
+                    AND application__id = $2
+            "#,
+            event_id,
+            application_id,
+        )
+        .fetch_optional(&mut *conn)
+        .await
+        .map_err(Hook0Problem::from)?;
+        if let Some(stored_event) = stored_event
+            && stored_event.json_payload_in_object_storage
+            && stored_event.has_payload_filters
+        {
+            let payload = load_payload(
+                state,
+                &application_id,
+                &event_id,
+                stored_event.received_at,
+                None,
+            )
+            .await?;
+            if let Ok(json_payload) = std::str::from_utf8(&payload) {
+                set_dispatch_payload(&mut *conn, json_payload).await?;
+            }
+        }
+    }
+
+    struct ReplayedEvent {
+        received_at: DateTime<Utc>,
+        event_type: String,
+        payload: Option<Vec<u8>>,
+        payload_content_type: String,
+    }
+    let replayed = match subscription_id {
+        None => query_as!(
+            ReplayedEvent,
+            "
+                UPDATE event.event
+                SET dispatched_at = NULL
+                WHERE event__id = $1
+                    AND application__id = $2
+                RETURNING received_at, event_type__name AS event_type, payload, payload_content_type
+            ",
+            event_id,
+            application_id,
+        )
+        .fetch_optional(&mut *conn)
+        .await
+        .map_err(Hook0Problem::from)?,
+        Some(subscription_id) => {
+            let event = query_as!(
+                ReplayedEvent,
+                "
+                    SELECT received_at, event_type__name AS event_type, payload, payload_content_type
+                    FROM event.event
+                    WHERE event__id = $1
+                        AND application__id = $2
+                ",
+                event_id,
+                application_id,
+            )
+            .fetch_optional(&mut *conn)
+            .await
+            .map_err(Hook0Problem::from)?;
+
+            if event.is_some() {
+                query!(
+                    "
+                        INSERT INTO webhook.request_attempt (event__id, subscription__id, application__id)
+                        SELECT $1, s.subscription__id, s.application__id
+                        FROM webhook.subscription AS s
+                        WHERE s.subscription__id = $2
+                            AND s.application__id = $3
+                            AND s.is_enabled
+                            AND s.deleted_at IS NULL
+                    ",
+                    event_id,
+                    subscription_id,
+                    application_id,
+                )
+                .execute(&mut *conn)
+                .await
+                .map_err(Hook0Problem::from)?;
+            }
+
+            event
+        }
+    };
+
+    match replayed {
         Some(event) => {
             if let Some(pulsar) = &state.pulsar {
                 let payload = if let Some(p) = event.payload {
@@ -748,7 +2130,7 @@ pub async fn replay(
                 } else if let Some(os) = &state.object_storage {
                     let key = format!(
                         "{}/event/{}/{event_id}",
-                        body.application_id,
+                        application_id,
                         event.received_at.naive_utc().date(),
                     );
                     match os
@@ -785,9 +2167,9 @@ pub async fn replay(
 
                 if let Some(p) = payload {
                     send_request_attempts_to_pulsar(
-                        &mut *tx,
+                        &mut *conn,
                         pulsar,
-                        body.application_id,
+                        application_id,
                         event_id,
                         event.received_at,
                         &event.event_type,
@@ -797,25 +2179,37 @@ pub async fn replay(
                     )
                     .await?;
 
-                    tx.commit().await?;
-                    report_replayed_events(1);
-                    Ok(NoContent)
+                    Ok(())
                 } else {
-                    tx.rollback().await?;
                     Err(Hook0Problem::InternalServerError)
                 }
             } else {
-                tx.commit().await?;
-                report_replayed_events(1);
-                Ok(NoContent)
+                Ok(())
             }
         }
         None => Err(Hook0Problem::NotFound),
     }
 }
 
+/// Provide the JSON payload of an event that is not stored in the database to the dispatch trigger, which needs it to evaluate subscription filters
+///
+/// The payload is only visible in the current transaction.
+async fn set_dispatch_payload(
+    conn: &mut PgConnection,
+    json_payload: &str,
+) -> Result<(), Hook0Problem> {
+    query!(
+        "SELECT set_config('hook0.event_payload', $1, true)",
+        json_payload
+    )
+    .execute(conn)
+    .await
+    .map_err(Hook0Problem::from)?;
+    Ok(())
+}
+
 #[allow(clippy::too_many_arguments)]
-async fn send_request_attempts_to_pulsar<'e, E>(
+pub async fn send_request_attempts_to_pulsar<'e, E>(
     executor: E,
     pulsar: &Arc<PulsarConfig>,
     application_id: Uuid,
@@ -826,21 +2220,59 @@ async fn send_request_attempts_to_pulsar<'e, E>(
     payload_content_type: &str,
     wait_for_receipts: bool,
 ) -> Result<(), Hook0Problem>
+where
+    E: sqlx::PgExecutor<'e>,
+{
+    send_events_request_attempts_to_pulsar(
+        executor,
+        pulsar,
+        &[PulsarEvent {
+            application_id,
+            event_id,
+            received_at: event_received_at,
+            event_type,
+            payload,
+            payload_content_type,
+        }],
+        wait_for_receipts,
+    )
+    .await
+}
+
+/// An event whose pending request attempts must be sent to Pulsar
+pub struct PulsarEvent<'a> {
+    pub application_id: Uuid,
+    pub event_id: Uuid,
+    pub received_at: DateTime<Utc>,
+    pub event_type: &'a str,
+    pub payload: &'a [u8],
+    pub payload_content_type: &'a str,
+}
+
+/// Send the pending request attempts of several events to Pulsar, fetching them with a single query
+pub async fn send_events_request_attempts_to_pulsar<'e, E>(
+    executor: E,
+    pulsar: &Arc<PulsarConfig>,
+    events: &[PulsarEvent<'_>],
+    wait_for_receipts: bool,
+) -> Result<(), Hook0Problem>
 where
     E: sqlx::PgExecutor<'e>,
 {
     #[derive(Debug, Clone)]
     #[allow(non_snake_case)]
     struct RawRequestAttempt {
+        event__id: Uuid,
         request_attempt__id: Uuid,
         subscription__id: Uuid,
         created_at: DateTime<Utc>,
         http_method: String,
         http_url: String,
         http_headers: serde_json::Value,
-        secret: Uuid,
+        secret: Option<Uuid>,
         worker_id: Option<Uuid>,
         worker_queue_type: Option<String>,
+        delay_until: Option<DateTime<Utc>>,
     }
 
     // Rows are materialized (rather than streamed) so that when `executor` is the pool the connection
@@ -849,6 +2281,7 @@ where
         RawRequestAttempt,
         "
             SELECT
+                ra.event__id,
                 ra.request_attempt__id,
                 ra.subscription__id,
                 ra.created_at,
@@ -857,7 +2290,8 @@ where
                 t_http.headers AS http_headers,
                 s.secret,
                 COALESCE(sw.worker__id, ow.worker__id) AS worker_id,
-                COALESCE(w1.queue_type, w2.queue_type) AS worker_queue_type
+                COALESCE(w1.queue_type, w2.queue_type) AS worker_queue_type,
+                ra.delay_until
             FROM webhook.request_attempt AS ra
             INNER JOIN webhook.subscription AS s ON s.subscription__id = ra.subscription__id
             INNER JOIN webhook.target_http AS t_http ON t_http.target__id = s.target__id
@@ -866,37 +2300,52 @@ where
             INNER JOIN event.application AS a ON a.application__id = s.application__id
             LEFT JOIN iam.organization__worker AS ow ON ow.organization__id = a.organization__id AND ow.default = true
             LEFT JOIN infrastructure.worker AS w2 ON w2.worker__id = ow.worker__id
-            WHERE ra.event__id = $1
+            WHERE ra.event__id = ANY($1)
                 AND ra.succeeded_at IS NULL AND ra.failed_at IS NULL
                 AND a.deleted_at IS NULL
         ",
-        &event_id,
+        &events.iter().map(|e| e.event_id).collect::<Vec<_>>(),
     )
     .fetch_all(executor)
     .await?;
 
+    let events_by_id = events
+        .iter()
+        .map(|e| (e.event_id, e))
+        .collect::<HashMap<_, _>>();
     let mut receipt_futures = Vec::new();
 
     for ra in request_attempts {
         if let Some(worker_id) = ra.worker_id
             && ra.worker_queue_type.as_deref() == Some("pulsar")
+            && let Some(event) = events_by_id.get(&ra.event__id)
         {
             let request_attempt = RequestAttempt {
-                application_id,
+                application_id: event.application_id,
                 request_attempt_id: ra.request_attempt__id,
-                event_id,
-                event_received_at,
+                event_id: event.event_id,
+                event_received_at: event.received_at,
                 subscription_id: ra.subscription__id,
                 created_at: ra.created_at,
                 retry_count: 0,
                 http_method: ra.http_method,
                 http_url: ra.http_url,
                 http_headers: ra.http_headers,
-                event_type_name: event_type.to_owned(),
-                payload: payload.to_owned(),
-                payload_content_type: payload_content_type.to_owned(),
+                event_type_name: event.event_type.to_owned(),
+                payload: event.payload.to_owned(),
+                payload_content_type: event.payload_content_type.to_owned(),
                 secret: ra.secret,
             };
+            let mut message = RequestAttempt::serialize_message(request_attempt).map_err(|e| {
+                error!("Could not serialize request attempt for Pulsar: {e}");
+                Hook0Problem::InternalServerError
+            })?;
+            // Request attempts of held events must not be consumed before the delivery date of their event
+            if let Some(delay_until) = ra.delay_until
+                && delay_until > Utc::now()
+            {
+                message.deliver_at_time = Some(delay_until.timestamp_millis());
+            }
 
             let send_future = timeout(
                 Duration::from_secs(3),
@@ -912,7 +2361,7 @@ where
                     "persistent://{}/{}/{}.request_attempt",
                     pulsar.tenant, pulsar.namespace, worker_id,
                 ),
-                request_attempt,
+                message,
             )
             .await
             .map_err(|e| {
@@ -1054,3 +2503,271 @@ mod tests {
         ));
     }
 }
+
+#[cfg(test)]
+mod ordered_delivery_tests {
+    use crate::google_ads::test_support::{seed_org, seed_user};
+    use sqlx::PgPool;
+    use uuid::Uuid;
+
+    /// Seed an application with one event type and a subscription with ordered
+    /// delivery listening to it; return `(application_id, subscription_id)`.
+    async fn seed_ordered_subscription(pool: &PgPool) -> (Uuid, Uuid) {
+        let user = seed_user(pool).await;
+        let org = seed_org(pool, user).await;
+        let application_id = Uuid::new_v4();
+        let subscription_id = Uuid::new_v4();
+
+        sqlx::query(
+            "INSERT INTO event.application (application__id, organization__id, name) VALUES ($1, $2, 'Ordered')",
+        )
+        .bind(application_id)
+        .bind(org)
+        .execute(pool)
+        .await
+        .expect("seed application");
+        for query in [
+            "INSERT INTO event.service (application__id, service__name) VALUES ($1, 'billing')",
+            "INSERT INTO event.resource_type (application__id, service__name, resource_type__name) VALUES ($1, 'billing', 'invoice')",
+            "INSERT INTO event.verb (application__id, verb__name) VALUES ($1, 'paid')",
+            "INSERT INTO event.event_type (application__id, service__name, resource_type__name, verb__name) VALUES ($1, 'billing', 'invoice', 'paid')",
+        ] {
+            sqlx::query(query)
+                .bind(application_id)
+                .execute(pool)
+                .await
+                .expect("seed event type");
+        }
+        sqlx::query(
+            r#"
+                INSERT INTO webhook.subscription (subscription__id, application__id, target__id, labels, ordered_delivery, event_type_patterns)
+                VALUES ($1, $2, gen_random_uuid(), '{"all": "yes"}', true, '{*}')
+            "#,
+        )
+        .bind(subscription_id)
+        .bind(application_id)
+        .execute(pool)
+        .await
+        .expect("seed subscription");
+
+        (application_id, subscription_id)
+    }
+
+    async fn seed_event(
+        pool: &PgPool,
+        application_id: Uuid,
+        received_ago: &str,
+        deliver_at: Option<&str>,
+    ) -> Uuid {
+        let event_id = Uuid::new_v4();
+        sqlx::query(
+            r#"
+                INSERT INTO event.event (event__id, application__id, event_type__name, payload, payload_content_type, ip, occurred_at, received_at, labels, deliver_at)
+                VALUES ($1, $2, 'billing.invoice.paid', '{}', 'application/json', '127.0.0.1', statement_timestamp(), statement_timestamp() - $3::interval, '{"all": "yes"}', statement_timestamp() + $4::interval)
+            "#,
+        )
+        .bind(event_id)
+        .bind(application_id)
+        .bind(received_ago)
+        .bind(deliver_at)
+        .execute(pool)
+        .await
+        .expect("seed event");
+        event_id
+    }
+
+    async fn pending_predecessors(pool: &PgPool, subscription_id: Uuid, event_id: Uuid) -> i64 {
+        sqlx::query_scalar(
+            r#"
+                SELECT count(*)
+                FROM webhook.subscription AS s, event.event AS e, webhook.pending_ordered_predecessors(s, e)
+                WHERE s.subscription__id = $1 AND e.event__id = $2
+            "#,
+        )
+        .bind(subscription_id)
+        .bind(event_id)
+        .fetch_one(pool)
+        .await
+        .expect("count pending predecessors")
+    }
+
+    /// An event held until a later date must not block the events received
+    /// after it on a subscription with ordered delivery, until that date.
+    #[sqlx::test]
+    async fn held_events_do_not_block_ordered_delivery(pool: PgPool) {
+        let (application_id, subscription_id) = seed_ordered_subscription(&pool).await;
+        let held = seed_event(&pool, application_id, "2 minutes", Some("30 days")).await;
+        let previous = seed_event(&pool, application_id, "1 minute", None).await;
+        let next = seed_event(&pool, application_id, "0 seconds", None).await;
+
+        // Only the previous event that is not held blocks the next one
+        assert_eq!(pending_predecessors(&pool, subscription_id, next).await, 1);
+        assert_eq!(
+            pending_predecessors(&pool, subscription_id, previous).await,
+            0
+        );
+
+        // Once its delivery date has passed, the held event takes its place in the sequence again
+        sqlx::query(
+            "UPDATE event.event SET deliver_at = statement_timestamp() - interval '1 second' WHERE event__id = $1",
+        )
+        .bind(held)
+        .execute(&pool)
+        .await
+        .expect("release held event");
+        assert_eq!(pending_predecessors(&pool, subscription_id, next).await, 2);
+        assert_eq!(
+            pending_predecessors(&pool, subscription_id, previous).await,
+            1
+        );
+    }
+}
+
+#[cfg(test)]
+mod delivery_status_tests {
+    use crate::google_ads::test_support::{
+        issue_user_token, seed_membership, seed_org, seed_user, test_state,
+    };
+    use actix_web::{App, test, web};
+    use sqlx::PgPool;
+    use uuid::Uuid;
+
+    /// The delivery status of an event only depends on the latest request
+    /// attempt of each subscription: a failed attempt that was successfully
+    /// retried does not make the event failed.
+    #[sqlx::test]
+    async fn delivery_status_is_based_on_latest_request_attempts(pool: PgPool) {
+        let keypair = biscuit_auth::KeyPair::new();
+        let private_key = keypair.private();
+        let state = test_state(pool.clone(), private_key.clone(), None).await;
+
+        let user = seed_user(&pool).await;
+        let org = seed_org(&pool, user).await;
+        seed_membership(&pool, user, org, "editor").await;
+        let token = issue_user_token(&pool, &private_key, user, org, "editor").await;
+
+        let application_id = Uuid::new_v4();
+        sqlx::query(
+            "INSERT INTO event.application (application__id, organization__id, name) VALUES ($1, $2, 'Delivery status')",
+        )
+        .bind(application_id)
+        .bind(org)
+        .execute(&pool)
+        .await
+        .expect("seed application");
+        for query in [
+            "INSERT INTO event.service (application__id, service__name) VALUES ($1, 'billing')",
+            "INSERT INTO event.resource_type (application__id, service__name, resource_type__name) VALUES ($1, 'billing', 'invoice')",
+            "INSERT INTO event.verb (application__id, verb__name) VALUES ($1, 'paid')",
+            "INSERT INTO event.event_type (application__id, service__name, resource_type__name, verb__name) VALUES ($1, 'billing', 'invoice', 'paid')",
+        ] {
+            sqlx::query(query)
+                .bind(application_id)
+                .execute(&pool)
+                .await
+                .expect("seed event type");
+        }
+
+        let subscriptions = [Uuid::new_v4(), Uuid::new_v4()];
+        for subscription_id in subscriptions {
+            sqlx::query(
+                r#"
+                    INSERT INTO webhook.subscription (subscription__id, application__id, target__id, labels, event_type_patterns)
+                    VALUES ($1, $2, gen_random_uuid(), '{"all": "yes"}', '{*}')
+                "#,
+            )
+            .bind(subscription_id)
+            .bind(application_id)
+            .execute(&pool)
+            .await
+            .expect("seed subscription");
+        }
+
+        let [retried, failed, pending] = [(); 3].map(|_| Uuid::new_v4());
+        for event_id in [retried, failed, pending] {
+            sqlx::query(
+                r#"
+                    INSERT INTO event.event (event__id, application__id, event_type__name, payload, payload_content_type, ip, occurred_at, received_at, labels)
+                    VALUES ($1, $2, 'billing.invoice.paid', '{}', 'application/json', '127.0.0.1', statement_timestamp(), statement_timestamp() - interval '1 hour', '{"all": "yes"}')
+                "#,
+            )
+            .bind(event_id)
+            .bind(application_id)
+            .execute(&pool)
+            .await
+            .expect("seed event");
+        }
+
+        // Request attempts are failed (`Some(false)`), successful (`Some(true)`) or pending (`None`)
+        for (event_id, subscription_id, created_ago, succeeded) in [
+            // The first attempt failed and its retry succeeded
+            (retried, subscriptions[0], "2 minutes", Some(false)),
+            (retried, subscriptions[0], "1 minute", Some(true)),
+            // Hook0 gave up
+            (failed, subscriptions[0], "2 minutes", Some(false)),
+            // Delivered to one subscription and still being retried for the other one
+            (pending, subscriptions[0], "2 minutes", Some(true)),
+            (pending, subscriptions[1], "2 minutes", Some(false)),
+            (pending, subscriptions[1], "1 minute", None),
+        ] {
+            sqlx::query(
+                r#"
+                    INSERT INTO webhook.request_attempt (application__id, event__id, subscription__id, created_at, succeeded_at, failed_at)
+                    VALUES (
+                        $1, $2, $3, statement_timestamp() - $4::interval,
+                        CASE WHEN $5 THEN statement_timestamp() END,
+                        CASE WHEN NOT $5 THEN statement_timestamp() END
+                    )
+                "#,
+            )
+            .bind(application_id)
+            .bind(event_id)
+            .bind(subscription_id)
+            .bind(created_ago)
+            .bind(succeeded)
+            .execute(&pool)
+            .await
+            .expect("seed request attempt");
+        }
+
+        let biscuit_auth = crate::middleware_biscuit::BiscuitAuth {
+            db: pool.clone(),
+            biscuit_private_key: private_key.clone(),
+            master_api_key: None,
+            enable_application_secret_compatibility: true,
+        };
+        let app = test::init_service(
+            App::new().app_data(web::Data::new(state)).service(
+                web::scope("/api/v1/events")
+                    .wrap(biscuit_auth)
+                    .route("", web::get().to(super::list)),
+            ),
+        )
+        .await;
+
+        for (delivery_status, expected) in [
+            ("successful", retried),
+            ("failed", failed),
+            ("pending", pending),
+        ] {
+            let req = test::TestRequest::get()
+                .uri(&format!(
+                    "/api/v1/events?application_id={application_id}&delivery_status={delivery_status}"
+                ))
+                .insert_header(("Authorization", format!("Bearer {token}")))
+                .to_request();
+            let resp = test::call_service(&app, req).await;
+            assert!(
+                resp.status().is_success(),
+                "listing events failed: {}",
+                resp.status()
+            );
+            let events: Vec<serde_json::Value> = test::read_body_json(resp).await;
+            let event_ids = events
+                .iter()
+                .map(|event| event["event_id"].as_str().expect("event_id").to_owned())
+                .collect::<Vec<_>>();
+            assert_eq!(event_ids, vec![expected.to_string()], "{delivery_status}");
+        }
+    }
+}
@@ -1,5 +1,7 @@
 pub mod applications;
 pub mod auth;
+pub mod client_certificates;
+pub mod custom_roles;
 pub mod environment_variables;
 pub mod errors;
 pub mod event_types;
@@ -8,9 +10,11 @@ pub mod events_per_day;
 pub mod instance;
 pub mod organizations;
 pub mod registrations;
+pub mod replay_jobs;
 pub mod request_attempts;
 pub mod responses;
 pub mod service_token;
+pub mod signing_keys;
 pub mod subscriptions;
 
 #[cfg(feature = "application-secret-compatibility")]
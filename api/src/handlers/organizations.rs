@@ -4,7 +4,7 @@ use chrono::Utc;
 use paperclip::actix::web::{Data, Json, Path};
 use paperclip::actix::{Apiv2Schema, NoContent, api_v2_operation};
 use serde::{Deserialize, Serialize};
-use sqlx::{query, query_as, query_scalar};
+use sqlx::{PgPool, query, query_as, query_scalar};
 use std::str::FromStr;
 use tracing::error;
 use uuid::Uuid;
@@ -72,6 +72,8 @@ pub struct OrganizationUser {
     pub first_name: String,
     pub last_name: String,
     pub role: Role,
+    /// Custom role of the organization that is assigned to the user, if `role` is `custom`
+    pub custom_role_id: Option<Uuid>,
 }
 
 #[api_v2_operation(
@@ -265,6 +267,7 @@ pub async fn create(
             first_name: token.first_name,
             last_name: token.last_name,
             role: Role::Editor,
+            custom_role_id: None,
         }],
         quotas,
         consumption: OrganizationConsumption {
@@ -343,11 +346,12 @@ pub async fn get(
             pub first_name: String,
             pub last_name: String,
             pub role: String,
+            pub custom_role_id: Option<Uuid>,
         }
         let users = query_as!(
             UserWithRole,
             r#"
-                SELECT u.user__id AS user_id, u.email, u.first_name, u.last_name, uo.role
+                SELECT u.user__id AS user_id, u.email, u.first_name, u.last_name, uo.role, uo.custom_role__id AS custom_role_id
                 FROM iam.user AS u
                 INNER JOIN iam.user__organization AS uo ON uo.user__id = u.user__id
                 WHERE uo.organization__id = $1
@@ -368,6 +372,7 @@ pub async fn get(
                         first_name: u.first_name,
                         last_name: u.last_name,
                         role,
+                        custom_role_id: u.custom_role_id,
                     }]
                 } else {
                     vec![]
@@ -517,6 +522,43 @@ pub struct UserInvitation {
     #[validate(non_control_character, email, length(max = 100))]
     email: String,
     role: String,
+    /// Custom role of the organization to assign to the user; required if and only if `role` is `custom`
+    custom_role_id: Option<Uuid>,
+}
+
+/// Make sure that a custom role of the organization is provided if and only if the role given to a member is `custom`
+async fn check_custom_role(
+    db: &PgPool,
+    organization_id: &Uuid,
+    role: Role,
+    custom_role_id: Option<&Uuid>,
+) -> Result<(), Hook0Problem> {
+    match (role, custom_role_id) {
+        (Role::Custom, Some(custom_role_id)) => {
+            let custom_role_exists = query_scalar!(
+                r#"
+                    SELECT EXISTS (
+                        SELECT 1
+                        FROM iam.custom_role
+                        WHERE custom_role__id = $1
+                            AND organization__id = $2
+                    ) AS "exists!"
+                "#,
+                custom_role_id,
+                organization_id,
+            )
+            .fetch_one(db)
+            .await?;
+
+            if custom_role_exists {
+                Ok(())
+            } else {
+                Err(Hook0Problem::CustomRoleDoesNotExist)
+            }
+        }
+        (Role::Custom, None) | (_, Some(_)) => Err(Hook0Problem::CustomRoleDoesNotExist),
+        (_, None) => Ok(()),
+    }
 }
 
 #[api_v2_operation(
@@ -550,6 +592,14 @@ pub async fn invite(
 
     match Role::from_str(&body.role) {
         Ok(role) => {
+            check_custom_role(
+                &state.db,
+                &organization_id,
+                role,
+                body.custom_role_id.as_ref(),
+            )
+            .await?;
+
             let user_id = query_scalar!(
                 "
                     SELECT user__id
@@ -565,12 +615,13 @@ pub async fn invite(
                 Some(uid) => {
                     query!(
                         "
-                            INSERT INTO iam.user__organization (user__id, organization__id, role)
-                            VALUES ($1, $2, $3)
+                            INSERT INTO iam.user__organization (user__id, organization__id, role, custom_role__id)
+                            VALUES ($1, $2, $3, $4)
                         ",
                         &uid,
                         &organization_id,
                         role.as_ref(),
+                        body.custom_role_id,
                     )
                     .execute(&state.db)
                     .await?;
@@ -681,11 +732,13 @@ pub async fn revoke(
 pub struct OrganizationEditRole {
     user_id: Uuid,
     role: String,
+    /// Custom role of the organization to assign to the user; required if and only if `role` is `custom`
+    custom_role_id: Option<Uuid>,
 }
 
 #[api_v2_operation(
     summary = "Edit a user's role in an organization",
-    description = "Change the role of a user that has already access to an organization you have write access to.",
+    description = "Change the role of a user that has already access to an organization you have write access to. To assign a custom role of the organization, use the 'custom' role and provide the ID of the custom role.",
     operation_id = "organizations.edit_role",
     consumes = "application/json",
     produces = "application/json",
@@ -714,14 +767,24 @@ pub async fn edit_role(
         return Err(Hook0Problem::Forbidden);
     }
 
+    let role = Role::from_str(&body.role).map_err(|_| Hook0Problem::InvalidRole)?;
+    check_custom_role(
+        &state.db,
+        &organization_id,
+        role,
+        body.custom_role_id.as_ref(),
+    )
+    .await?;
+
     query!(
         "
             UPDATE iam.user__organization
-            SET role = $1
-            WHERE user__id = $2
-                AND organization__id = $3
+            SET role = $1, custom_role__id = $2
+            WHERE user__id = $3
+                AND organization__id = $4
         ",
-        &body.role,
+        role.as_ref(),
+        body.custom_role_id,
         &body.user_id,
         &organization_id,
     )
@@ -0,0 +1,339 @@
+use actix_web::web::ReqData;
+use biscuit_auth::Biscuit;
+use chrono::{DateTime, Utc};
+use paperclip::actix::web::{Data, Json, Path, Query};
+use paperclip::actix::{Apiv2Schema, CreatedJson, api_v2_operation};
+use serde::{Deserialize, Serialize};
+use sqlx::query_as;
+use std::str::FromStr;
+use strum::{EnumString, IntoStaticStr};
+use uuid::Uuid;
+use validator::{Validate, ValidationError};
+
+use crate::iam::{Action, authorize_for_application};
+use crate::openapi::OaBiscuit;
+use crate::problems::Hook0Problem;
+
+#[derive(Debug, Serialize, Apiv2Schema)]
+pub struct ReplayJob {
+    replay_job_id: Uuid,
+    application_id: Uuid,
+    min_received_at: DateTime<Utc>,
+    max_received_at: DateTime<Utc>,
+    /// Only events of these types are replayed (all types if empty)
+    event_type_names: Vec<String>,
+    /// If set, events are only replayed to this subscription
+    subscription_id: Option<Uuid>,
+    /// If true, only events that have at least one failed request attempt are replayed
+    only_failed: bool,
+    status: ReplayJobStatus,
+    /// Number of events matching the filter (known once the job has started)
+    total_events: Option<i64>,
+    replayed_events: i64,
+    failed_events: i64,
+    /// Last error that occurred while replaying an event
+    last_error: Option<String>,
+    created_at: DateTime<Utc>,
+    started_at: Option<DateTime<Utc>>,
+    finished_at: Option<DateTime<Utc>>,
+}
+
+#[derive(
+    Debug,
+    Default,
+    Clone,
+    Copy,
+    PartialEq,
+    Eq,
+    Serialize,
+    Deserialize,
+    Apiv2Schema,
+    EnumString,
+    IntoStaticStr,
+)]
+#[serde(rename_all = "snake_case")]
+#[strum(serialize_all = "snake_case")]
+pub enum ReplayJobStatus {
+    /// The job is waiting to be picked by the replay worker
+    #[default]
+    Pending,
+    /// Events are being replayed
+    Running,
+    /// All the events matching the filter were replayed
+    Completed,
+    /// The job was cancelled before all events were replayed
+    Cancelled,
+}
+
+#[allow(non_snake_case)]
+struct RawReplayJob {
+    replay_job__id: Uuid,
+    application__id: Uuid,
+    min_received_at: DateTime<Utc>,
+    max_received_at: DateTime<Utc>,
+    event_type_names: Vec<String>,
+    subscription__id: Option<Uuid>,
+    only_failed: bool,
+    status: String,
+    total_events: Option<i64>,
+    replayed_events: i64,
+    failed_events: i64,
+    last_error: Option<String>,
+    created_at: DateTime<Utc>,
+    started_at: Option<DateTime<Utc>>,
+    finished_at: Option<DateTime<Utc>>,
+}
+
+impl From<RawReplayJob> for ReplayJob {
+    fn from(raw: RawReplayJob) -> Self {
+        Self {
+            replay_job_id: raw.replay_job__id,
+            application_id: raw.application__id,
+            min_received_at: raw.min_received_at,
+            max_received_at: raw.max_received_at,
+            event_type_names: raw.event_type_names,
+            subscription_id: raw.subscription__id,
+            only_failed: raw.only_failed,
+            status: ReplayJobStatus::from_str(&raw.status).unwrap_or_default(),
+            total_events: raw.total_events,
+            replayed_events: raw.replayed_events,
+            failed_events: raw.failed_events,
+            last_error: raw.last_error,
+            created_at: raw.created_at,
+            started_at: raw.started_at,
+            finished_at: raw.finished_at,
+        }
+    }
+}
+
+#[derive(Debug, Deserialize, Apiv2Schema, Validate)]
+#[validate(schema(function = "validate_replay_job_post"))]
+pub struct ReplayJobPost {
+    application_id: Uuid,
+    /// Only events received at or after this date are replayed
+    min_received_at: DateTime<Utc>,
+    /// Only events received at or before this date are replayed
+    max_received_at: DateTime<Utc>,
+    /// Only events of these types are replayed (all types if empty)
+    #[serde(default)]
+    #[validate(length(max = 50))]
+    event_type_names: Vec<String>,
+    /// If set, only events matching this subscription are replayed, and only to this subscription
+    subscription_id: Option<Uuid>,
+    /// If true, only events that have at least one failed request attempt (to the subscription, if set) are replayed
+    #[serde(default)]
+    only_failed: bool,
+}
+
+fn validate_replay_job_post(body: &ReplayJobPost) -> Result<(), ValidationError> {
+    if body.min_received_at > body.max_received_at {
+        Err(ValidationError::new("invalid-received-at-range")
+            .with_message("'min_received_at' must be before 'max_received_at'".into()))
+    } else {
+        Ok(())
+    }
+}
+
+#[api_v2_operation(
+    summary = "Replay events matching a filter",
+    description = "Creates a replay job that re-triggers webhook deliveries for all the events of an application received in a time range, optionally restricted to some event types, to a subscription, or to events that have failed request attempts. Events are replayed in the background, in small batches; use the returned replay job to follow its progress or cancel it.",
+    operation_id = "events.replay_bulk",
+    consumes = "application/json",
+    produces = "application/json",
+    tags("Events Management")
+)]
+pub async fn create(
+    state: Data<crate::State>,
+    _: OaBiscuit,
+    biscuit: ReqData<Biscuit>,
+    body: Json<ReplayJobPost>,
+) -> Result<CreatedJson<ReplayJob>, Hook0Problem> {
+    authorize_for_application(
+        &state.db,
+        &biscuit,
+        Action::EventReplay {
+            application_id: &body.application_id,
+        },
+        state.max_authorization_time,
+        state.debug_authorizer,
+    )
+    .await?;
+
+    if let Err(e) = body.validate() {
+        return Err(Hook0Problem::Validation(e));
+    }
+
+    let replay_job = query_as!(
+        RawReplayJob,
+        "
+            INSERT INTO event.replay_job (application__id, min_received_at, max_received_at, event_type_names, subscription__id, only_failed)
+            SELECT $1, $2, $3, $4, $5, $6
+            WHERE $5::uuid IS NULL OR EXISTS (
+                SELECT 1
+                FROM webhook.subscription
+                WHERE subscription__id = $5
+                    AND application__id = $1
+                    AND deleted_at IS NULL
+            )
+            RETURNING replay_job__id, application__id, min_received_at, max_received_at, event_type_names, subscription__id, only_failed, status, total_events, replayed_events, failed_events, last_error, created_at, started_at, finished_at
+        ",
+        body.application_id,
+        body.min_received_at,
+        body.max_received_at,
+        &body.event_type_names,
+        body.subscription_id,
+        body.only_failed,
+    )
+    .fetch_optional(&state.db)
+    .await
+    .map_err(Hook0Problem::from)?;
+
+    match replay_job {
+        Some(replay_job) => Ok(CreatedJson(replay_job.into())),
+        None => Err(Hook0Problem::NotFound),
+    }
+}
+
+#[derive(Debug, Deserialize, Apiv2Schema)]
+pub struct Qs {
+    application_id: Uuid,
+}
+
+#[api_v2_operation(
+    summary = "List replay jobs",
+    description = "Lists the 100 most recent replay jobs of an application, with their filter, status and progress.",
+    operation_id = "replay_jobs.list",
+    consumes = "application/json",
+    produces = "application/json",
+    tags("Events Management")
+)]
+pub async fn list(
+    state: Data<crate::State>,
+    _: OaBiscuit,
+    biscuit: ReqData<Biscuit>,
+    qs: Query<Qs>,
+) -> Result<Json<Vec<ReplayJob>>, Hook0Problem> {
+    authorize_for_application(
+        &state.db,
+        &biscuit,
+        Action::ReplayJobList {
+            application_id: &qs.application_id,
+        },
+        state.max_authorization_time,
+        state.debug_authorizer,
+    )
+    .await?;
+
+    let replay_jobs = query_as!(
+        RawReplayJob,
+        "
+            SELECT replay_job__id, application__id, min_received_at, max_received_at, event_type_names, subscription__id, only_failed, status, total_events, replayed_events, failed_events, last_error, created_at, started_at, finished_at
+            FROM event.replay_job
+            WHERE application__id = $1
+            ORDER BY created_at DESC
+            LIMIT 100
+        ",
+        qs.application_id,
+    )
+    .fetch_all(&state.db)
+    .await
+    .map_err(Hook0Problem::from)?;
+
+    Ok(Json(replay_jobs.into_iter().map(ReplayJob::from).collect()))
+}
+
+#[api_v2_operation(
+    summary = "Get a replay job",
+    description = "Retrieves a replay job with its filter, status and progress. Once the job is completed or cancelled, it also serves as the final report of the replay.",
+    operation_id = "replay_jobs.get",
+    consumes = "application/json",
+    produces = "application/json",
+    tags("Events Management")
+)]
+pub async fn get(
+    state: Data<crate::State>,
+    _: OaBiscuit,
+    biscuit: ReqData<Biscuit>,
+    replay_job_id: Path<Uuid>,
+    qs: Query<Qs>,
+) -> Result<Json<ReplayJob>, Hook0Problem> {
+    authorize_for_application(
+        &state.db,
+        &biscuit,
+        Action::ReplayJobGet {
+            application_id: &qs.application_id,
+        },
+        state.max_authorization_time,
+        state.debug_authorizer,
+    )
+    .await?;
+
+    let replay_job = query_as!(
+        RawReplayJob,
+        "
+            SELECT replay_job__id, application__id, min_received_at, max_received_at, event_type_names, subscription__id, only_failed, status, total_events, replayed_events, failed_events, last_error, created_at, started_at, finished_at
+            FROM event.replay_job
+            WHERE replay_job__id = $1
+                AND application__id = $2
+        ",
+        replay_job_id.into_inner(),
+        qs.application_id,
+    )
+    .fetch_optional(&state.db)
+    .await
+    .map_err(Hook0Problem::from)?;
+
+    match replay_job {
+        Some(replay_job) => Ok(Json(replay_job.into())),
+        None => Err(Hook0Problem::NotFound),
+    }
+}
+
+#[api_v2_operation(
+    summary = "Cancel a replay job",
+    description = "Stops a pending or running replay job. Events that were already replayed are not affected. Cancelling a job that is already completed or cancelled has no effect.",
+    operation_id = "replay_jobs.cancel",
+    consumes = "application/json",
+    produces = "application/json",
+    tags("Events Management")
+)]
+pub async fn cancel(
+    state: Data<crate::State>,
+    _: OaBiscuit,
+    biscuit: ReqData<Biscuit>,
+    replay_job_id: Path<Uuid>,
+    qs: Query<Qs>,
+) -> Result<Json<ReplayJob>, Hook0Problem> {
+    authorize_for_application(
+        &state.db,
+        &biscuit,
+        Action::ReplayJobCancel {
+            application_id: &qs.application_id,
+        },
+        state.max_authorization_time,
+        state.debug_authorizer,
+    )
+    .await?;
+
+    let replay_job = query_as!(
+        RawReplayJob,
+        "
+            UPDATE event.replay_job
+            SET status = CASE WHEN status IN ('pending', 'running') THEN 'cancelled' ELSE status END,
+                finished_at = CASE WHEN status IN ('pending', 'running') THEN statement_timestamp() ELSE finished_at END
+            WHERE replay_job__id = $1
+                AND application__id = $2
+            RETURNING replay_job__id, application__id, min_received_at, max_received_at, event_type_names, subscription__id, only_failed, status, total_events, replayed_events, failed_events, last_error, created_at, started_at, finished_at
+        ",
+        replay_job_id.into_inner(),
+        qs.application_id,
+    )
+    .fetch_optional(&state.db)
+    .await
+    .map_err(Hook0Problem::from)?;
+
+    match replay_job {
+        Some(replay_job) => Ok(Json(replay_job.into())),
+        None => Err(Hook0Problem::NotFound),
+    }
+}
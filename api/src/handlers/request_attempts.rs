@@ -2,18 +2,20 @@ use actix_web::web::ReqData;
 use biscuit_auth::Biscuit;
 use chrono::{DateTime, Utc};
 use paperclip::actix::web::{Data, Json, Path, Query};
-use paperclip::actix::{Apiv2Schema, api_v2_operation};
+use paperclip::actix::{Apiv2Schema, CreatedJson, api_v2_operation};
 use paperclip::v2::models::{DataType, DataTypeFormat, DefaultSchemaRaw};
-use paperclip::v2::schema::Apiv2Schema as Apiv2SchemaTrait;
+use paperclip::v2::schema::{Apiv2Schema as Apiv2SchemaTrait, TypedData};
 use serde::{Deserialize, Serialize};
 use sqlx::query_as;
 use std::cmp::max;
 use std::collections::BTreeMap;
-use tracing::error;
+use tracing::{error, info};
 use url::Url;
 use uuid::Uuid;
 
-use crate::iam::{Action, authorize_for_application};
+use crate::handlers::events::{load_payload, send_request_attempts_to_pulsar};
+use crate::hook0_client::{EventRequestAttemptRetried, Hook0ClientEvent};
+use crate::iam::{Action, authorize_for_application, get_owner_organization};
 use crate::openapi::OaBiscuit;
 use crate::pagination::{Cursor, EncodedDescCursor, NextPageParts, Paginated};
 use crate::problems::Hook0Problem;
@@ -27,6 +29,7 @@ pub struct RequestAttempt {
     pub created_at: DateTime<Utc>,
     pub picked_at: Option<DateTime<Utc>>,
     pub failed_at: Option<DateTime<Utc>>,
+    pub cancelled_at: Option<DateTime<Utc>>,
     pub succeeded_at: Option<DateTime<Utc>>,
     pub delay_until: Option<DateTime<Utc>>,
     pub response_id: Option<Uuid>,
@@ -68,6 +71,15 @@ pub enum RequestAttemptStatus {
         at: DateTime<Utc>,
         full_processing_ms: i64,
     },
+    #[serde(rename = "permanently_failed")]
+    PermanentlyFailed {
+        at: DateTime<Utc>,
+        full_processing_ms: i64,
+    },
+    Cancelled {
+        at: DateTime<Utc>,
+        full_processing_ms: i64,
+    },
 }
 
 impl Apiv2SchemaTrait for RequestAttemptStatus {
@@ -86,6 +98,8 @@ impl Apiv2SchemaTrait for RequestAttemptStatus {
         // - in_progress: {type: "in_progress", since: DateTime}
         // - successful: {type: "successful", at: DateTime, full_processing_ms: i64}
         // - failed: {type: "failed", at: DateTime, full_processing_ms: i64}
+        // - permanently_failed: {type: "permanently_failed", at: DateTime, full_processing_ms: i64}
+        // - cancelled: {type: "cancelled", at: DateTime, full_processing_ms: i64}
 
         let mut properties = BTreeMap::new();
 
@@ -95,7 +109,7 @@ impl Apiv2SchemaTrait for RequestAttemptStatus {
             Box::new(DefaultSchemaRaw {
                 data_type: Some(DataType::String),
                 description: Some(
-                    "Status type discriminator. One of: waiting, pending, in_progress, successful, failed"
+                    "Status type discriminator. One of: waiting, pending, in_progress, successful, failed, permanently_failed, cancelled"
                         .to_owned(),
                 ),
                 enum_: vec![
@@ -104,6 +118,8 @@ impl Apiv2SchemaTrait for RequestAttemptStatus {
                     serde_json::Value::String("in_progress".to_owned()),
                     serde_json::Value::String("successful".to_owned()),
                     serde_json::Value::String("failed".to_owned()),
+                    serde_json::Value::String("permanently_failed".to_owned()),
+                    serde_json::Value::String("cancelled".to_owned()),
                 ],
                 ..Default::default()
             }),
@@ -136,27 +152,28 @@ impl Apiv2SchemaTrait for RequestAttemptStatus {
             }),
         );
 
-        // at field (present in successful, failed)
+        // at field (present in successful, failed, permanently_failed, cancelled)
         properties.insert(
             "at".to_owned(),
             Box::new(DefaultSchemaRaw {
                 data_type: Some(DataType::String),
                 format: Some(DataTypeFormat::DateTime),
                 description: Some(
-                    "Timestamp when completed (present in successful, failed)".to_owned(),
+                    "Timestamp when completed (present in successful, failed, permanently_failed, cancelled)"
+                        .to_owned(),
                 ),
                 ..Default::default()
             }),
         );
 
-        // full_processing_ms field (present in successful, failed)
+        // full_processing_ms field (present in successful, failed, permanently_failed, cancelled)
         properties.insert(
             "full_processing_ms".to_owned(),
             Box::new(DefaultSchemaRaw {
                 data_type: Some(DataType::Integer),
                 format: Some(DataTypeFormat::Int64),
                 description: Some(
-                    "Total processing time in milliseconds (present in successful, failed)"
+                    "Total processing time in milliseconds (present in successful, failed, permanently_failed, cancelled)"
                         .to_owned(),
                 ),
                 ..Default::default()
@@ -175,7 +192,9 @@ impl Apiv2SchemaTrait for RequestAttemptStatus {
                  - pending: {type, since} - Ready to be processed \
                  - in_progress: {type, since} - Currently being delivered \
                  - successful: {type, at, full_processing_ms} - Delivered successfully \
-                 - failed: {type, at, full_processing_ms} - Delivery failed"
+                 - failed: {type, at, full_processing_ms} - Delivery failed \
+                 - permanently_failed: {type, at, full_processing_ms} - Delivery failed and will not be retried (e.g. target answered 410 Gone or is invalid) \
+                 - cancelled: {type, at, full_processing_ms} - Delivery of the event was cancelled before this request attempt was sent"
                     .to_owned(),
             ),
             properties,
@@ -191,15 +210,28 @@ impl RequestAttemptStatus {
         created_at: &DateTime<Utc>,
         picked_at: &Option<DateTime<Utc>>,
         failed_at: &Option<DateTime<Utc>>,
+        cancelled_at: &Option<DateTime<Utc>>,
         succeeded_at: &Option<DateTime<Utc>>,
         delay_until: &Option<DateTime<Utc>>,
+        is_permanent_failure: bool,
     ) -> Self {
         let start = match delay_until {
             Some(d) => max(created_at, d),
             None => created_at,
         };
 
+        if let Some(at) = cancelled_at {
+            return Self::Cancelled {
+                at: *at,
+                full_processing_ms: (*at - *start).num_milliseconds(),
+            };
+        }
+
         match (delay_until, picked_at, succeeded_at, failed_at) {
+            (_, _, _, Some(at)) if is_permanent_failure => Self::PermanentlyFailed {
+                at: *at,
+                full_processing_ms: (*at - *start).num_milliseconds(),
+            },
             (_, _, _, Some(at)) => Self::Failed {
                 at: *at,
                 full_processing_ms: (*at - *start).num_milliseconds(),
@@ -258,12 +290,14 @@ pub async fn get(
         created_at: DateTime<Utc>,
         picked_at: Option<DateTime<Utc>>,
         failed_at: Option<DateTime<Utc>>,
+        cancelled_at: Option<DateTime<Utc>>,
         succeeded_at: Option<DateTime<Utc>>,
         delay_until: Option<DateTime<Utc>>,
         response__id: Option<Uuid>,
         retry_count: i16,
         event_type__name: String,
         http_response_status: Option<i16>,
+        is_permanent_failure: bool,
     }
 
     let raw = query_as!(
@@ -276,10 +310,12 @@ pub async fn get(
                 ra.created_at,
                 ra.picked_at,
                 ra.failed_at,
+                ra.cancelled_at,
                 ra.succeeded_at,
                 ra.delay_until,
                 ra.response__id,
                 ra.retry_count,
+                ra.is_permanent_failure,
                 s.description AS subscription__description,
                 e.event_type__name,
                 r.http_code AS http_response_status
@@ -312,6 +348,7 @@ pub async fn get(
             created_at: ra.created_at,
             picked_at: ra.picked_at,
             failed_at: ra.failed_at,
+            cancelled_at: ra.cancelled_at,
             succeeded_at: ra.succeeded_at,
             delay_until: ra.delay_until,
             response_id: ra.response__id,
@@ -322,8 +359,10 @@ pub async fn get(
                 &ra.created_at,
                 &ra.picked_at,
                 &ra.failed_at,
+                &ra.cancelled_at,
                 &ra.succeeded_at,
                 &ra.delay_until,
+                ra.is_permanent_failure,
             ),
         })),
         None => Err(Hook0Problem::NotFound),
@@ -341,11 +380,40 @@ pub struct Qs {
     /// Comma-separated event types
     #[serde(rename = "event.event_type_names")]
     event_type_names: Option<String>,
+    /// Only return request attempts with this status type
+    status: Option<RequestAttemptStatusType>,
+    /// Only return failed request attempts that will not be retried automatically (dead-letter queue), leaving out the ones of events whose delivery was cancelled
+    #[serde(default)]
+    dead_letter: bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, strum::Display)]
+#[serde(rename_all = "snake_case")]
+#[strum(serialize_all = "snake_case")]
+pub enum RequestAttemptStatusType {
+    Waiting,
+    Pending,
+    #[serde(alias = "inprogress")]
+    InProgress,
+    Successful,
+    Failed,
+    PermanentlyFailed,
+    Cancelled,
+}
+
+impl TypedData for RequestAttemptStatusType {
+    fn data_type() -> DataType {
+        DataType::String
+    }
+
+    fn format() -> Option<DataTypeFormat> {
+        None
+    }
 }
 
 #[api_v2_operation(
     summary = "List request attempts",
-    description = "Retrieves webhook delivery attempts for an application. Each attempt shows the delivery status (pending, in_progress, successful, failed, waiting), retry count, and timestamps. Filter by event_id, subscription_id, date range, or event types. Paginated via Link header.",
+    description = "Retrieves webhook delivery attempts for an application. Each attempt shows the delivery status (pending, in_progress, successful, failed, permanently_failed, cancelled, waiting), retry count, and timestamps. Filter by event_id, subscription_id, date range, event types, or status. Use dead_letter=true to only get failed attempts that will not be retried automatically (request attempts of cancelled events are left out). Paginated via Link header.",
     operation_id = "requestAttempts.read",
     consumes = "application/json",
     produces = "application/json",
@@ -393,12 +461,14 @@ pub async fn list(
         created_at: DateTime<Utc>,
         picked_at: Option<DateTime<Utc>>,
         failed_at: Option<DateTime<Utc>>,
+        cancelled_at: Option<DateTime<Utc>>,
         succeeded_at: Option<DateTime<Utc>>,
         delay_until: Option<DateTime<Utc>>,
         response__id: Option<Uuid>,
         retry_count: i16,
         event_type__name: String,
         http_response_status: Option<i16>,
+        is_permanent_failure: bool,
     }
     let raw_request_attempts = query_as!(
         RawRequestAttempt,
@@ -410,10 +480,12 @@ pub async fn list(
                 ra.created_at,
                 ra.picked_at,
                 ra.failed_at,
+                ra.cancelled_at,
                 ra.succeeded_at,
                 ra.delay_until,
                 ra.response__id,
                 ra.retry_count,
+                ra.is_permanent_failure,
                 s.description AS subscription__description,
                 e.event_type__name,
                 r.http_code AS http_response_status
@@ -427,6 +499,26 @@ pub async fn list(
                 AND ra.created_at BETWEEN $4 AND $5
                 AND (ra.created_at, ra.request_attempt__id) < ($6, $7)
                 AND (e.event_type__name = any($8) OR $8 = '{}')
+                AND ($9::text IS NULL OR $9 = CASE
+                    WHEN ra.cancelled_at IS NOT NULL THEN 'cancelled'
+                    WHEN ra.failed_at IS NOT NULL AND ra.is_permanent_failure THEN 'permanently_failed'
+                    WHEN ra.failed_at IS NOT NULL THEN 'failed'
+                    WHEN ra.succeeded_at IS NOT NULL THEN 'successful'
+                    WHEN ra.picked_at IS NOT NULL THEN 'in_progress'
+                    WHEN ra.delay_until > statement_timestamp() THEN 'waiting'
+                    ELSE 'pending'
+                END)
+                AND (NOT $10 OR (
+                    ra.failed_at IS NOT NULL
+                    AND e.delivery_cancelled_at IS NULL
+                    AND NOT EXISTS (
+                        SELECT 1
+                        FROM webhook.request_attempt AS next_ra
+                        WHERE next_ra.event__id = ra.event__id
+                            AND next_ra.subscription__id = ra.subscription__id
+                            AND next_ra.created_at > ra.created_at
+                    )
+                ))
             ORDER BY
                 ra.created_at DESC,
                 ra.request_attempt__id ASC
@@ -440,6 +532,8 @@ pub async fn list(
         pagination.date,
         pagination.id,
         &event_type_names,
+        qs.status.map(|status| status.to_string()),
+        qs.dead_letter,
     )
     .fetch_all(&state.db)
     .await
@@ -461,6 +555,7 @@ pub async fn list(
             created_at: ra.created_at,
             picked_at: ra.picked_at,
             failed_at: ra.failed_at,
+            cancelled_at: ra.cancelled_at,
             succeeded_at: ra.succeeded_at,
             delay_until: ra.delay_until,
             response_id: ra.response__id,
@@ -471,8 +566,10 @@ pub async fn list(
                 &ra.created_at,
                 &ra.picked_at,
                 &ra.failed_at,
+                &ra.cancelled_at,
                 &ra.succeeded_at,
                 &ra.delay_until,
+                ra.is_permanent_failure,
             ),
         })
         .collect::<Vec<_>>();
@@ -506,6 +603,8 @@ pub async fn list(
                 ("min_created_at", qs.min_created_at.map(|v| v.to_string())),
                 ("max_created_at", qs.max_created_at.map(|v| v.to_string())),
                 ("event.event_type_names", qs.event_type_names.to_owned()),
+                ("status", qs.status.map(|v| v.to_string())),
+                ("dead_letter", qs.dead_letter.then(|| "true".to_owned())),
             ],
             cursor: Cursor {
                 date: ra.created_at,
@@ -520,6 +619,192 @@ pub async fn list(
     })
 }
 
+#[derive(Debug, Deserialize, Apiv2Schema)]
+pub struct RetryRequestAttempt {
+    application_id: Uuid,
+}
+
+#[api_v2_operation(
+    summary = "Retry a failed request attempt",
+    description = "Creates a new request attempt for the event and subscription of a failed request attempt that will not be retried automatically (for example because it exhausted its retries). Unlike replaying the event, only this subscription receives the event again. The subscription must be enabled, and the delivery of the event must not have been cancelled.",
+    operation_id = "requestAttempts.retry",
+    consumes = "application/json",
+    produces = "application/json",
+    tags("Subscriptions Management", "mcp")
+)]
+pub async fn retry(
+    state: Data<crate::State>,
+    _: OaBiscuit,
+    biscuit: ReqData<Biscuit>,
+    request_attempt_id: Path<Uuid>,
+    body: Json<RetryRequestAttempt>,
+) -> Result<CreatedJson<RequestAttempt>, Hook0Problem> {
+    let request_attempt_id = request_attempt_id.into_inner();
+
+    authorize_for_application(
+        &state.db,
+        &biscuit,
+        Action::RequestAttemptRetry {
+            application_id: &body.application_id,
+        },
+        state.max_authorization_time,
+        state.debug_authorizer,
+    )
+    .await?;
+
+    let mut tx = state.db.begin().await?;
+
+    #[allow(non_snake_case)]
+    struct FailedRequestAttempt {
+        event__id: Uuid,
+        subscription__id: Uuid,
+        subscription__description: Option<String>,
+        event_type__name: String,
+        received_at: DateTime<Utc>,
+        payload: Option<Vec<u8>>,
+        payload_content_type: String,
+    }
+    // Locking the request attempt prevents concurrent retries from creating several new request attempts, and locking the event prevents its delivery from being cancelled meanwhile
+    let failed = query_as!(
+        FailedRequestAttempt,
+        "
+            SELECT
+                ra.event__id,
+                ra.subscription__id,
+                s.description AS subscription__description,
+                e.event_type__name,
+                e.received_at,
+                e.payload,
+                e.payload_content_type
+            FROM webhook.request_attempt AS ra
+            INNER JOIN webhook.subscription AS s ON s.subscription__id = ra.subscription__id
+            INNER JOIN event.event AS e ON e.event__id = ra.event__id
+            WHERE ra.application__id = $1
+                AND ra.request_attempt__id = $2
+                AND s.deleted_at IS NULL
+            FOR UPDATE OF ra
+            FOR SHARE OF e
+        ",
+        body.application_id,
+        request_attempt_id,
+    )
+    .fetch_optional(&mut *tx)
+    .await
+    .map_err(Hook0Problem::from)?
+    .ok_or(Hook0Problem::NotFound)?;
+
+    #[allow(non_snake_case)]
+    struct NewRequestAttempt {
+        request_attempt__id: Uuid,
+        created_at: DateTime<Utc>,
+    }
+    let retry = query_as!(
+        NewRequestAttempt,
+        "
+            INSERT INTO webhook.request_attempt (application__id, event__id, subscription__id)
+            SELECT ra.application__id, ra.event__id, ra.subscription__id
+            FROM webhook.request_attempt AS ra
+            INNER JOIN webhook.subscription AS s ON s.subscription__id = ra.subscription__id
+            INNER JOIN event.event AS e ON e.event__id = ra.event__id
+            WHERE ra.request_attempt__id = $1
+                AND ra.failed_at IS NOT NULL
+                AND s.is_enabled
+                AND e.delivery_cancelled_at IS NULL
+                AND NOT EXISTS (
+                    SELECT 1
+                    FROM webhook.request_attempt AS next_ra
+                    WHERE next_ra.event__id = ra.event__id
+                        AND next_ra.subscription__id = ra.subscription__id
+                        AND next_ra.created_at > ra.created_at
+                )
+            RETURNING request_attempt__id, created_at
+        ",
+        request_attempt_id,
+    )
+    .fetch_optional(&mut *tx)
+    .await
+    .map_err(Hook0Problem::from)?
+    .ok_or(Hook0Problem::RequestAttemptNotRetryable)?;
+
+    if let Some(pulsar) = &state.pulsar {
+        let payload = load_payload(
+            &state,
+            &body.application_id,
+            &failed.event__id,
+            failed.received_at,
+            failed.payload,
+        )
+        .await?;
+        send_request_attempts_to_pulsar(
+            &mut *tx,
+            pulsar,
+            body.application_id,
+            failed.event__id,
+            failed.received_at,
+            &failed.event_type__name,
+            &payload,
+            &failed.payload_content_type,
+            true,
+        )
+        .await?;
+    }
+
+    tx.commit().await?;
+
+    info!(
+        application_id = %body.application_id,
+        %request_attempt_id,
+        retry_request_attempt_id = %retry.request_attempt__id,
+        "Failed request attempt was manually retried"
+    );
+
+    if let Some(hook0_client) = state.hook0_client.as_ref() {
+        let hook0_client_event: Hook0ClientEvent = EventRequestAttemptRetried {
+            organization_id: get_owner_organization(&state.db, &body.application_id)
+                .await
+                .unwrap_or(Uuid::nil()),
+            application_id: body.application_id,
+            event_id: failed.event__id,
+            subscription_id: failed.subscription__id,
+            request_attempt_id,
+            retry_request_attempt_id: retry.request_attempt__id,
+            created_at: retry.created_at,
+        }
+        .into();
+        if let Err(e) = hook0_client
+            .send_event(&hook0_client_event.mk_hook0_event())
+            .await
+        {
+            error!("Hook0ClientError: {e}");
+        };
+    }
+
+    Ok(CreatedJson(RequestAttempt {
+        request_attempt_id: retry.request_attempt__id,
+        event_id: failed.event__id,
+        event: EventSummary {
+            event_id: failed.event__id,
+            event_type_name: failed.event_type__name,
+        },
+        subscription: SubscriptionSummary {
+            subscription_id: failed.subscription__id,
+            description: failed.subscription__description,
+        },
+        created_at: retry.created_at,
+        picked_at: None,
+        failed_at: None,
+        cancelled_at: None,
+        succeeded_at: None,
+        delay_until: None,
+        response_id: None,
+        retry_count: 0,
+        http_response_status: None,
+        status: RequestAttemptStatus::Pending {
+            since: retry.created_at,
+        },
+    }))
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -548,8 +833,8 @@ mod tests {
         );
         assert_eq!(
             type_field.enum_.len(),
-            5,
-            "Should have 5 status type values"
+            7,
+            "Should have 7 status type values"
         );
 
         let type_values: Vec<&str> = type_field.enum_.iter().filter_map(|v| v.as_str()).collect();
@@ -565,6 +850,14 @@ mod tests {
             "Missing 'successful' type"
         );
         assert!(type_values.contains(&"failed"), "Missing 'failed' type");
+        assert!(
+            type_values.contains(&"permanently_failed"),
+            "Missing 'permanently_failed' type"
+        );
+        assert!(
+            type_values.contains(&"cancelled"),
+            "Missing 'cancelled' type"
+        );
     }
 
     #[test]
@@ -616,6 +909,52 @@ mod tests {
         assert_eq!(full_processing_ms.format, Some(DataTypeFormat::Int64));
     }
 
+    #[test]
+    fn request_attempt_status_compute_failures() {
+        let created_at = Utc::now();
+        let failed_at = created_at + chrono::Duration::seconds(1);
+
+        assert!(matches!(
+            RequestAttemptStatus::compute(
+                &failed_at,
+                &created_at,
+                &Some(created_at),
+                &Some(failed_at),
+                &None,
+                &None,
+                &None,
+                false,
+            ),
+            RequestAttemptStatus::Failed { .. }
+        ));
+        assert!(matches!(
+            RequestAttemptStatus::compute(
+                &failed_at,
+                &created_at,
+                &Some(created_at),
+                &Some(failed_at),
+                &None,
+                &None,
+                &None,
+                true,
+            ),
+            RequestAttemptStatus::PermanentlyFailed { .. }
+        ));
+        assert!(matches!(
+            RequestAttemptStatus::compute(
+                &failed_at,
+                &created_at,
+                &None,
+                &Some(failed_at),
+                &Some(failed_at),
+                &None,
+                &None,
+                false,
+            ),
+            RequestAttemptStatus::Cancelled { .. }
+        ));
+    }
+
     #[test]
     fn request_attempt_status_type_is_required() {
         let schema = RequestAttemptStatus::raw_schema();
@@ -0,0 +1,197 @@
+use actix_web::web::ReqData;
+use argon2::password_hash::rand_core::OsRng;
+use base64::Engine;
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use biscuit_auth::Biscuit;
+use chrono::{DateTime, Utc};
+use ed25519_dalek::SigningKey;
+use paperclip::actix::web::{Data, Json, Path};
+use paperclip::actix::{Apiv2Schema, CreatedJson, api_v2_operation};
+use serde::{Deserialize, Serialize};
+use sqlx::query;
+use uuid::Uuid;
+use validator::Validate;
+
+use crate::iam::{Action, authorize_for_application};
+use crate::openapi::OaBiscuit;
+use crate::problems::Hook0Problem;
+
+/// Default duration during which the previous signing key of an application stays published after a rotation
+const DEFAULT_SIGNING_KEY_ROTATION_GRACE_PERIOD_IN_S: i32 = 604800;
+
+/// Public key used to verify `v2` (Ed25519) webhook signatures, as a JSON Web Key (RFC 8037)
+#[derive(Debug, Serialize, Apiv2Schema)]
+pub struct Jwk {
+    /// Always `OKP`
+    kty: String,
+    /// Always `Ed25519`
+    crv: String,
+    /// Always `EdDSA`
+    alg: String,
+    /// Always `sig`
+    #[serde(rename = "use")]
+    key_use: String,
+    /// ID of the key, as found in the `k` field of the signature header
+    kid: Uuid,
+    /// Public key (base64url-encoded)
+    x: String,
+    created_at: DateTime<Utc>,
+    /// Date after which the key is no longer published (null for the key that currently signs webhooks)
+    expires_at: Option<DateTime<Utc>>,
+}
+
+impl Jwk {
+    fn ed25519(
+        signing_key_id: Uuid,
+        public_key: &[u8],
+        created_at: DateTime<Utc>,
+        expires_at: Option<DateTime<Utc>>,
+    ) -> Self {
+        Self {
+            kty: "OKP".to_owned(),
+            crv: "Ed25519".to_owned(),
+            alg: "EdDSA".to_owned(),
+            key_use: "sig".to_owned(),
+            kid: signing_key_id,
+            x: URL_SAFE_NO_PAD.encode(public_key),
+            created_at,
+            expires_at,
+        }
+    }
+}
+
+/// JSON Web Key Set (RFC 7517)
+#[derive(Debug, Serialize, Apiv2Schema)]
+pub struct Jwks {
+    keys: Vec<Jwk>,
+}
+
+#[api_v2_operation(
+    summary = "Get the public keys of an application",
+    description = "Returns the public keys that verify `v2` (Ed25519) signatures of the webhooks of an application, as a JSON Web Key Set. This endpoint does not require authentication so that webhook consumers can fetch it. Keys stay published for a while after they are rotated; consumers should fetch the key set again when a signature refers to a key they do not know.",
+    operation_id = "signingKeys.jwks",
+    consumes = "application/json",
+    produces = "application/json",
+    tags("Subscriptions Management")
+)]
+pub async fn jwks(
+    state: Data<crate::State>,
+    application_id: Path<Uuid>,
+) -> Result<Json<Jwks>, Hook0Problem> {
+    let keys = query!(
+        "
+            SELECT sk.signing_key__id AS signing_key_id, sk.public_key, sk.created_at, sk.expires_at
+            FROM webhook.signing_key AS sk
+            INNER JOIN event.application AS a ON a.application__id = sk.application__id
+            WHERE sk.application__id = $1
+                AND a.deleted_at IS NULL
+                AND (sk.expires_at IS NULL OR sk.expires_at > statement_timestamp())
+            ORDER BY sk.created_at DESC
+        ",
+        &application_id.into_inner(),
+    )
+    .fetch_all(&state.db)
+    .await
+    .map_err(Hook0Problem::from)?;
+
+    Ok(Json(Jwks {
+        keys: keys
+            .into_iter()
+            .map(|key| {
+                Jwk::ed25519(
+                    key.signing_key_id,
+                    &key.public_key,
+                    key.created_at,
+                    key.expires_at,
+                )
+            })
+            .collect(),
+    }))
+}
+
+#[derive(Debug, Deserialize, Apiv2Schema, Validate)]
+pub struct SigningKeyRotationPost {
+    application_id: Uuid,
+    /// How long the current key stays published after the rotation, in seconds (defaults to 7 days)
+    #[validate(range(min = 0, max = 31536000))]
+    grace_period_in_s: Option<i32>,
+}
+
+#[api_v2_operation(
+    summary = "Rotate the signing key of an application",
+    description = "Generates a new Ed25519 key that output workers use to sign webhooks of the application (`v2` signatures) from now on. The previous key stays published in the key set of the application during the grace period. The first call creates the first key of the application. The private key is encrypted before being stored and is never returned.",
+    operation_id = "signingKeys.rotate",
+    consumes = "application/json",
+    produces = "application/json",
+    tags("Subscriptions Management")
+)]
+pub async fn rotate(
+    state: Data<crate::State>,
+    _: OaBiscuit,
+    biscuit: ReqData<Biscuit>,
+    body: Json<SigningKeyRotationPost>,
+) -> Result<CreatedJson<Jwk>, Hook0Problem> {
+    authorize_for_application(
+        &state.db,
+        &biscuit,
+        Action::SigningKeyRotate {
+            application_id: &body.application_id,
+        },
+        state.max_authorization_time,
+        state.debug_authorizer,
+    )
+    .await?;
+
+    let encryption_key = state
+        .target_credentials_encryption_key
+        .as_ref()
+        .ok_or(Hook0Problem::SigningKeysDisabled)?;
+
+    if let Err(e) = body.validate() {
+        return Err(Hook0Problem::Validation(e));
+    }
+
+    let grace_period_in_s = body
+        .grace_period_in_s
+        .unwrap_or(DEFAULT_SIGNING_KEY_ROTATION_GRACE_PERIOD_IN_S);
+    let signing_key = SigningKey::generate(&mut OsRng);
+    let public_key = signing_key.verifying_key().to_bytes();
+
+    let mut tx = state.db.begin().await.map_err(Hook0Problem::from)?;
+
+    query!(
+        "
+            UPDATE webhook.signing_key
+            SET expires_at = statement_timestamp() + make_interval(secs => $2)
+            WHERE application__id = $1 AND expires_at IS NULL
+        ",
+        &body.application_id,
+        grace_period_in_s,
+    )
+    .execute(&mut *tx)
+    .await
+    .map_err(Hook0Problem::from)?;
+
+    let new_key = query!(
+        "
+            INSERT INTO webhook.signing_key (application__id, public_key, encrypted_private_key)
+            VALUES ($1, $2, $3)
+            RETURNING signing_key__id AS signing_key_id, created_at
+        ",
+        &body.application_id,
+        public_key.as_slice(),
+        encryption_key.encrypt(signing_key.to_bytes().as_slice()),
+    )
+    .fetch_one(&mut *tx)
+    .await
+    .map_err(Hook0Problem::from)?;
+
+    tx.commit().await.map_err(Hook0Problem::from)?;
+
+    Ok(CreatedJson(Jwk::ed25519(
+        new_key.signing_key_id,
+        &public_key,
+        new_key.created_at,
+        None,
+    )))
+}
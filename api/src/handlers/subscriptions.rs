@@ -1,20 +1,27 @@
 use actix_web::web::ReqData;
+use base64::Engine;
+use base64::engine::general_purpose::STANDARD as Base64;
 use biscuit_auth::Biscuit;
 use chrono::{DateTime, Utc};
+use hook0_payload_transformation::TransformationContext;
 use paperclip::actix::web::{Data, Json, Path, Query};
 use paperclip::actix::{Apiv2Schema, CreatedJson, NoContent, api_v2_operation};
 use paperclip::v2::models::{DataType, DataTypeFormat, DefaultSchemaRaw};
 use paperclip::v2::schema::Apiv2Schema;
 use reqwest::Url;
+use reqwest::header::HeaderValue;
 use serde::{Deserialize, Deserializer, Serialize};
 use serde_json::{Map, Value, json};
-use sqlx::{query, query_as, query_scalar};
+use sqlx::{PgPool, query, query_as, query_scalar};
 use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
 use std::ops::Deref;
+use std::str::FromStr;
+use strum::{EnumString, IntoStaticStr};
 use tracing::error;
 use uuid::Uuid;
-use validator::{Validate, ValidationErrors};
+use validator::{Validate, ValidationError, ValidationErrors};
 
+use crate::credentials_encryption::{EncryptionKey, application_data_key};
 use crate::hook0_client::{
     EventSubscriptionCreated, EventSubscriptionRemoved, EventSubscriptionUpdated, Hook0ClientEvent,
 };
@@ -24,8 +31,8 @@ use crate::opentelemetry::report_cancelled_request_attempts;
 use crate::problems::Hook0Problem;
 use crate::quotas::Quota;
 use crate::validators::{
-    subscription_target_http_method, subscription_target_http_method_headers,
-    subscription_target_http_url,
+    subscription_target_http_ca_certificates, subscription_target_http_method,
+    subscription_target_http_method_headers, subscription_target_http_url,
 };
 
 #[derive(Debug, Serialize, Apiv2Schema)]
@@ -34,8 +41,11 @@ pub struct Subscription {
     pub subscription_id: Uuid,
     pub is_enabled: bool,
     pub event_types: Vec<String>,
+    /// Patterns such as `billing.*.*` or `*`; events whose type matches one of them are delivered, including event types created after the subscription
+    pub event_type_patterns: Vec<String>,
     pub description: Option<String>,
-    pub secret: Uuid,
+    /// Secret used to sign webhooks; if the instance encrypts secrets at rest, it is only returned when the subscription is created (see the `rotateSecret` operation to get a new one)
+    pub secret: Option<Uuid>,
     pub metadata: HashMap<String, String>,
     /// _Kept for backward compatibility, you should use `labels`_
     pub label_key: String,
@@ -46,6 +56,29 @@ pub struct Subscription {
     pub created_at: DateTime<Utc>,
     pub updated_at: DateTime<Utc>,
     pub dedicated_workers: Vec<String>,
+    pub retry_policy: Option<RetryPolicy>,
+    /// Maximum number of requests per second sent to the target by each output worker (no limit if null)
+    pub max_requests_per_second: Option<i32>,
+    /// Maximum number of concurrent requests sent to the target (no limit if null; enforced by each output worker when request attempts go through Pulsar)
+    pub max_in_flight: Option<i32>,
+    /// Whether events are delivered one at a time, in the order they were received
+    pub ordered_delivery: bool,
+    /// Label whose value splits ordered deliveries into independent sequences (a single sequence is used if null)
+    pub ordering_key: Option<String>,
+    /// Maximum number of events sent in a single request (events are sent one by one if null)
+    pub batch_max_size: Option<i32>,
+    /// Maximum duration (in milliseconds) to wait for a batch to fill up before sending it
+    pub batch_max_wait_ms: Option<i32>,
+    /// Template used to build the body of webhooks from events (events are sent as is if null)
+    pub payload_transformation: Option<PayloadTransformation>,
+    /// Whether webhooks carry Standard Webhooks headers, in addition to or instead of `X-Hook0-Signature` (the configuration of the instance applies if null)
+    pub standard_webhooks: Option<StandardWebhooksMode>,
+    /// Conditions that events must all meet to be delivered, on top of event types and labels
+    pub filters: Vec<SubscriptionFilter>,
+    /// Date at which the subscription was automatically disabled because deliveries kept failing (reset when the subscription is enabled again)
+    pub disabled_at: Option<DateTime<Utc>>,
+    /// Why the subscription was automatically disabled
+    pub disabled_reason: Option<String>,
 }
 
 #[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
@@ -56,6 +89,15 @@ pub enum Target {
         #[serde(deserialize_with = "deserialize_http_url")]
         url: HttpUrl,
         headers: HeaderMap,
+        /// Client certificate presented to the target (mutual TLS)
+        #[serde(default)]
+        client_certificate_id: Option<Uuid>,
+        /// PEM-encoded certificate authorities used to verify the certificate of the target instead of the public ones
+        #[serde(default)]
+        ca_certificates: Option<String>,
+        /// OAuth2 client credentials used to obtain access tokens that are sent to the target
+        #[serde(default)]
+        authentication: Option<TargetAuthentication>,
     },
 }
 
@@ -63,6 +105,114 @@ pub enum Target {
 #[into(owned, ref)]
 pub struct HeaderMap(#[serde(with = "http_serde::header_map")] pub reqwest::header::HeaderMap);
 
+impl Target {
+    /// Replace the values of sensitive headers so that the target can be returned
+    pub fn with_redacted_headers(mut self) -> Self {
+        match &mut self {
+            Target::Http { headers, .. } => {
+                for (name, value) in headers.0.iter_mut() {
+                    if is_sensitive_header(name.as_str()) {
+                        *value = HeaderValue::from_static(REDACTED_HEADER_VALUE);
+                    }
+                }
+            }
+        }
+        self
+    }
+}
+
+/// Value returned instead of the values of sensitive headers of targets
+pub const REDACTED_HEADER_VALUE: &str = "[REDACTED]";
+
+/// Whether a header of a target is likely to hold a credential
+///
+/// Values of such headers are redacted in responses and encrypted at rest if the instance has a key to do so.
+pub fn is_sensitive_header(name: &str) -> bool {
+    const SENSITIVE_WORDS: &[&str] = &[
+        "auth",
+        "cookie",
+        "credential",
+        "key",
+        "password",
+        "secret",
+        "session",
+        "signature",
+        "token",
+    ];
+
+    let name = name.to_lowercase();
+    SENSITIVE_WORDS.iter().any(|word| name.contains(word))
+}
+
+/// Headers of an HTTP target, split the way they are stored in `webhook.target_http`
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct StoredTargetHeaders {
+    /// Headers that are not sensitive, placeholders for sensitive headers that are encrypted and sensitive headers that cannot be encrypted
+    pub headers: Map<String, Value>,
+    /// Encrypted values of sensitive headers that did not change (`encrypted_headers` column)
+    pub encrypted_headers: Map<String, Value>,
+    /// Values of sensitive headers that must be encrypted before being added to `encrypted_headers`
+    pub headers_to_encrypt: Map<String, Value>,
+}
+
+impl StoredTargetHeaders {
+    /// Split the headers of a target, given the `headers` and `encrypted_headers` columns of the current target if it is being updated
+    ///
+    /// A sensitive header whose value is the redaction placeholder keeps its current value, so that a target can be updated with the headers that the API returned.
+    pub fn new(headers: &Value, encrypt: bool, current: Option<(&Value, &Value)>) -> Self {
+        let mut stored = Self::default();
+
+        for (name, value) in headers.as_object().into_iter().flatten() {
+            if !is_sensitive_header(name) {
+                stored.headers.insert(name.to_owned(), value.to_owned());
+                continue;
+            }
+
+            let value = match current {
+                Some((current_headers, current_encrypted_headers))
+                    if value.as_str() == Some(REDACTED_HEADER_VALUE) =>
+                {
+                    if let Some(encrypted_value) = current_encrypted_headers.get(name) {
+                        stored
+                            .headers
+                            .insert(name.to_owned(), Value::from(REDACTED_HEADER_VALUE));
+                        stored
+                            .encrypted_headers
+                            .insert(name.to_owned(), encrypted_value.to_owned());
+                        continue;
+                    }
+                    current_headers.get(name).unwrap_or(value)
+                }
+                _ => value,
+            };
+
+            if encrypt && value.is_string() {
+                stored
+                    .headers
+                    .insert(name.to_owned(), Value::from(REDACTED_HEADER_VALUE));
+                stored
+                    .headers_to_encrypt
+                    .insert(name.to_owned(), value.to_owned());
+            } else {
+                stored.headers.insert(name.to_owned(), value.to_owned());
+            }
+        }
+
+        stored
+    }
+
+    /// Encrypt the values of sensitive headers with the data key of the application and add them to `encrypted_headers`, encoded in base64
+    pub fn encrypt(&mut self, data_key: &EncryptionKey) {
+        for (name, value) in std::mem::take(&mut self.headers_to_encrypt) {
+            if let Some(value) = value.as_str() {
+                let encrypted_value = Base64.encode(data_key.encrypt(value.as_bytes()));
+                self.encrypted_headers
+                    .insert(name, Value::String(encrypted_value));
+            }
+        }
+    }
+}
+
 // This implementation is manual because paperclip could not handle the Target enum automatically and exposed it as a string in the generated OpenAPI document
 impl Apiv2Schema for Target {
     fn raw_schema() -> DefaultSchemaRaw {
@@ -84,6 +234,16 @@ impl Apiv2Schema for Target {
             data_type: Some(DataType::Object),
             ..Default::default()
         };
+        let client_certificate_id = DefaultSchemaRaw {
+            data_type: Some(DataType::String),
+            format: Some(DataTypeFormat::Uuid),
+            ..Default::default()
+        };
+        let ca_certificates = DefaultSchemaRaw {
+            data_type: Some(DataType::String),
+            ..Default::default()
+        };
+        let authentication = TargetAuthentication::raw_schema();
 
         DefaultSchemaRaw {
             data_type: Some(DataType::Object),
@@ -92,6 +252,12 @@ impl Apiv2Schema for Target {
                 ("method".to_owned(), Box::new(method)),
                 ("url".to_owned(), Box::new(url)),
                 ("headers".to_owned(), Box::new(headers)),
+                (
+                    "client_certificate_id".to_owned(),
+                    Box::new(client_certificate_id),
+                ),
+                ("ca_certificates".to_owned(), Box::new(ca_certificates)),
+                ("authentication".to_owned(), Box::new(authentication)),
             ]),
             required: BTreeSet::from_iter([
                 "type".to_owned(),
@@ -111,6 +277,9 @@ impl Validate for Target {
                 method,
                 url,
                 headers,
+                ca_certificates,
+                authentication,
+                ..
             } => {
                 let mut errors = ValidationErrors::new();
 
@@ -129,6 +298,18 @@ impl Validate for Target {
                     errors.add("headers", e)
                 }
 
+                if let Some(ca_certificates) = ca_certificates {
+                    let ca_certificates_validation =
+                        subscription_target_http_ca_certificates(ca_certificates);
+                    if let Err(e) = ca_certificates_validation {
+                        errors.add("ca_certificates", e)
+                    }
+                }
+
+                if let Some(authentication) = authentication {
+                    errors.merge_self("authentication", authentication.validate());
+                }
+
                 if errors.is_empty() {
                     Ok(())
                 } else {
@@ -139,9 +320,44 @@ impl Validate for Target {
     }
 }
 
+/// OAuth2 client credentials grant used to authenticate deliveries to an HTTP target
+///
+/// Output workers request access tokens from the token URL, cache them until they expire and send them to the target in the `Authorization` header. The client secret is never returned; it is stored encrypted.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize, Apiv2Schema, Validate)]
+pub struct TargetAuthentication {
+    /// Token endpoint of the authorization server
+    #[serde(deserialize_with = "deserialize_http_url")]
+    #[validate(custom(function = "validate_token_url"))]
+    pub token_url: HttpUrl,
+    #[validate(non_control_character, length(min = 1, max = 255))]
+    pub client_id: String,
+    /// Required when authentication is added to a target; can be omitted when updating a target to keep its current client secret
+    #[serde(default, skip_serializing)]
+    #[validate(length(min = 1, max = 1000))]
+    pub client_secret: Option<String>,
+    /// Scopes requested for access tokens (no `scope` parameter is sent if empty)
+    #[serde(default)]
+    #[validate(custom(function = "crate::validators::subscription_target_authentication_scopes"))]
+    pub scopes: Vec<String>,
+}
+
+fn validate_token_url(token_url: &HttpUrl) -> Result<(), ValidationError> {
+    subscription_target_http_url(token_url.as_str())
+}
+
 #[derive(Debug, Clone, PartialEq, Eq, Serialize)]
 pub struct HttpUrl(Url);
 
+impl Apiv2Schema for HttpUrl {
+    fn raw_schema() -> DefaultSchemaRaw {
+        DefaultSchemaRaw {
+            data_type: Some(DataType::String),
+            format: Some(DataTypeFormat::Url),
+            ..Default::default()
+        }
+    }
+}
+
 impl Deref for HttpUrl {
     type Target = Url;
 
@@ -178,6 +394,183 @@ where
     }
 }
 
+/// Retry schedule applied when deliveries to a subscription fail (if not set, the worker's default schedule is used)
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize, Apiv2Schema, Validate)]
+#[validate(schema(function = "validate_retry_policy_parameters"))]
+pub struct RetryPolicy {
+    /// How delays between retries are computed
+    pub strategy: RetryStrategy,
+    /// Delay before the first retry, in seconds (required by `exponential` and `linear` strategies)
+    #[validate(range(min = 1, max = 604800))]
+    pub initial_delay_in_s: Option<u32>,
+    /// Factor applied to the delay after each retry (`exponential` strategy only; defaults to 2)
+    #[validate(range(min = 1, max = 10))]
+    pub multiplier: Option<u32>,
+    /// Duration added to the delay after each retry, in seconds (`linear` strategy only; defaults to `initial_delay_in_s`)
+    #[validate(range(min = 1, max = 604800))]
+    pub increment_in_s: Option<u32>,
+    /// Upper bound of a single delay, in seconds (`exponential` and `linear` strategies only; defaults to 604800, one week)
+    #[validate(range(min = 1, max = 604800))]
+    pub max_delay_in_s: Option<u32>,
+    /// Delays before each successive retry, in seconds; no more retries are scheduled once the list is exhausted (required by `custom` strategy)
+    #[validate(custom(function = "crate::validators::subscription_retry_policy_delays"))]
+    pub delays_in_s: Option<Vec<u32>>,
+    /// Maximum number of delivery attempts, including the first one
+    #[validate(range(min = 1, max = 100))]
+    pub max_attempts: u16,
+    /// Maximum time span during which retries can be scheduled, in seconds
+    #[validate(range(min = 1, max = 2592000))]
+    pub max_age_in_s: Option<u32>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize, Apiv2Schema)]
+#[serde(rename_all = "lowercase")]
+pub enum RetryStrategy {
+    Exponential,
+    Linear,
+    Custom,
+}
+
+fn validate_retry_policy_parameters(policy: &RetryPolicy) -> Result<(), ValidationError> {
+    let missing_parameter = match policy.strategy {
+        RetryStrategy::Exponential | RetryStrategy::Linear
+            if policy.initial_delay_in_s.is_none() =>
+        {
+            Some("initial_delay_in_s")
+        }
+        RetryStrategy::Custom if policy.delays_in_s.is_none() => Some("delays_in_s"),
+        _ => None,
+    };
+
+    match missing_parameter {
+        Some(parameter) => Err(ValidationError::new("retry-policy-missing-parameter")
+            .with_message(format!("'{parameter}' is required by this retry strategy").into())),
+        None => Ok(()),
+    }
+}
+
+/// How webhooks are signed according to the [Standard Webhooks](https://www.standardwebhooks.com/) specification
+#[derive(
+    Debug,
+    Clone,
+    Copy,
+    PartialEq,
+    Eq,
+    Serialize,
+    Deserialize,
+    Apiv2Schema,
+    EnumString,
+    IntoStaticStr,
+)]
+#[serde(rename_all = "snake_case")]
+#[strum(serialize_all = "snake_case")]
+pub enum StandardWebhooksMode {
+    /// Standard Webhooks headers are sent along with `X-Hook0-Signature`
+    InAddition,
+    /// Standard Webhooks headers are sent instead of `X-Hook0-Signature`
+    Instead,
+}
+
+/// Template used to build the body of webhooks from events, instead of sending event payloads as is
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize, Apiv2Schema, Validate)]
+#[validate(schema(function = "validate_payload_transformation"))]
+pub struct PayloadTransformation {
+    /// [MiniJinja](https://docs.rs/minijinja) template; `event_id`, `event_type`, `received_at`, `payload`, `payload_content_type`, `labels` and `metadata` are available (when the content type is JSON, printed values are escaped as JSON)
+    pub template: String,
+    /// Value of the `Content-Type` header of webhooks (defaults to `application/json`)
+    #[serde(default = "default_payload_transformation_content_type")]
+    #[validate(non_control_character, length(min = 1, max = 100))]
+    pub content_type: String,
+}
+
+fn default_payload_transformation_content_type() -> String {
+    "application/json".to_owned()
+}
+
+impl From<&PayloadTransformation> for hook0_payload_transformation::PayloadTransformation {
+    fn from(transformation: &PayloadTransformation) -> Self {
+        Self {
+            template: transformation.template.to_owned(),
+            content_type: transformation.content_type.to_owned(),
+        }
+    }
+}
+
+fn validate_payload_transformation(
+    transformation: &PayloadTransformation,
+) -> Result<(), ValidationError> {
+    hook0_payload_transformation::PayloadTransformation::from(transformation)
+        .check()
+        .map_err(|e| {
+            ValidationError::new("invalid-payload-transformation")
+                .with_message(e.to_string().into())
+        })
+}
+
+/// Condition that events must meet to be delivered to a subscription, on top of event types and labels
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize, Apiv2Schema, Validate)]
+#[validate(schema(function = "validate_filter_parameters"))]
+pub struct SubscriptionFilter {
+    /// Event label the condition applies to (`in`, `not_in`, `exists` and `not_exists` operators)
+    #[validate(non_control_character, length(min = 1, max = 50))]
+    pub label: Option<String>,
+    /// JSONPath of the payload value the condition applies to, such as `$.customer.country` (`eq`, `ne`, `gt`, `gte`, `lt` and `lte` operators; only JSON payloads can match)
+    #[validate(custom(function = "crate::validators::subscription_filter_path"))]
+    pub path: Option<String>,
+    pub operator: FilterOperator,
+    /// Accepted or rejected label values (`in` and `not_in` operators)
+    #[validate(length(min = 1, max = 50))]
+    pub values: Option<Vec<String>>,
+    /// String, number, boolean or null the payload value is compared to (`eq`, `ne`, `gt`, `gte`, `lt` and `lte` operators)
+    pub value: Option<Value>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize, Apiv2Schema)]
+#[serde(rename_all = "snake_case")]
+pub enum FilterOperator {
+    In,
+    NotIn,
+    Exists,
+    NotExists,
+    Eq,
+    Ne,
+    Gt,
+    Gte,
+    Lt,
+    Lte,
+}
+
+fn validate_filter_parameters(filter: &SubscriptionFilter) -> Result<(), ValidationError> {
+    let (needs_label, needs_values, needs_value) = match filter.operator {
+        FilterOperator::In | FilterOperator::NotIn => (true, true, false),
+        FilterOperator::Exists | FilterOperator::NotExists => (true, false, false),
+        FilterOperator::Eq
+        | FilterOperator::Ne
+        | FilterOperator::Gt
+        | FilterOperator::Gte
+        | FilterOperator::Lt
+        | FilterOperator::Lte => (false, false, true),
+    };
+
+    if filter.label.is_some() != needs_label || filter.path.is_some() == needs_label {
+        Err(ValidationError::new("filter-invalid-subject").with_message(
+            "'label' must be set for label operators and 'path' must be set for payload operators (but not both)".into(),
+        ))
+    } else if filter.values.is_some() != needs_values {
+        Err(ValidationError::new("filter-invalid-values")
+            .with_message("'values' must be set for 'in' and 'not_in' operators only".into()))
+    } else if filter.value.is_some() != needs_value
+        || matches!(filter.value, Some(Value::Array(_) | Value::Object(_)))
+    {
+        Err(ValidationError::new("filter-invalid-value").with_message(
+            "'value' must be a string, number or boolean and be set for payload operators only"
+                .into(),
+        ))
+    } else {
+        Ok(())
+    }
+}
+
 #[derive(Debug, Deserialize, Serialize, Apiv2Schema)]
 pub struct Qs {
     application_id: Uuid,
@@ -213,14 +606,27 @@ pub async fn list(
         subscription__id: Uuid,
         is_enabled: bool,
         event_types: Option<Vec<String>>,
+        event_type_patterns: Vec<String>,
         description: Option<String>,
-        secret: Uuid,
+        secret: Option<Uuid>,
         metadata: Value,
         labels: Value,
         target_json: Option<Value>,
         created_at: DateTime<Utc>,
         updated_at: DateTime<Utc>,
         dedicated_workers: Option<Vec<String>>,
+        retry_policy: Option<Value>,
+        max_requests_per_second: Option<i32>,
+        max_in_flight: Option<i32>,
+        ordered_delivery: bool,
+        ordering_key: Option<String>,
+        batch_max_size: Option<i32>,
+        batch_max_wait_ms: Option<i32>,
+        payload_transformation: Option<Value>,
+        standard_webhooks: Option<String>,
+        filters: Value,
+        disabled_at: Option<DateTime<Utc>>,
+        disabled_reason: Option<String>,
     }
 
     let raw_subscriptions = query_as!(
@@ -228,7 +634,7 @@ pub async fn list(
         r#"
             WITH subs AS (
                 SELECT
-                    s.subscription__id, s.is_enabled, s.description, s.secret, s.metadata, s.labels, s.target__id, s.created_at, s.updated_at,
+                    s.subscription__id, s.is_enabled, s.description, s.secret, s.metadata, s.labels, s.target__id, s.created_at, s.updated_at, s.retry_policy, s.max_requests_per_second, s.max_in_flight, s.ordered_delivery, s.ordering_key, s.batch_max_size, s.batch_max_wait_ms, s.payload_transformation, s.standard_webhooks, s.filters, s.event_type_patterns, s.disabled_at, s.disabled_reason,
                     CASE WHEN length((array_agg(set.event_type__name))[1]) > 0
                         THEN array_agg(set.event_type__name)
                         ELSE ARRAY[]::text[] END AS event_types,
@@ -247,11 +653,18 @@ pub async fn list(
                     'type', replace(tableoid::regclass::text, 'webhook.target_', ''),
                     'method', method,
                     'url', url,
-                    'headers', headers
+                    'headers', headers,
+                    'client_certificate_id', client_certificate__id,
+                    'ca_certificates', ca_certificates,
+                    'authentication', CASE WHEN oauth2_token_url IS NOT NULL THEN jsonb_build_object(
+                        'token_url', oauth2_token_url,
+                        'client_id', oauth2_client_id,
+                        'scopes', oauth2_scopes
+                    ) END
                 ) AS target_json FROM webhook.target_http
                 WHERE target__id IN (SELECT target__id FROM subs)
             )
-            SELECT subs.subscription__id AS "subscription__id!", subs.is_enabled AS "is_enabled!", subs.description, subs.secret AS "secret!", subs.metadata AS "metadata!", subs.labels AS "labels!", subs.created_at AS "created_at!", subs.updated_at AS "updated_at!", subs.event_types, targets.target_json, subs.dedicated_workers
+            SELECT subs.subscription__id AS "subscription__id!", subs.is_enabled AS "is_enabled!", subs.description, subs.secret, subs.metadata AS "metadata!", subs.labels AS "labels!", subs.created_at AS "created_at!", subs.updated_at AS "updated_at!", subs.event_types, targets.target_json, subs.dedicated_workers, subs.retry_policy, subs.max_requests_per_second, subs.max_in_flight, subs.ordered_delivery AS "ordered_delivery!", subs.ordering_key, subs.batch_max_size, subs.batch_max_wait_ms, subs.payload_transformation, subs.standard_webhooks, subs.filters AS "filters!", subs.event_type_patterns AS "event_type_patterns!", subs.disabled_at, subs.disabled_reason
             FROM subs
             INNER JOIN targets ON subs.target__id = targets.target__id
         "#, // Column aliases ending with "!" are there because sqlx does not seem to infer correctly that these columns' types are not options
@@ -280,17 +693,38 @@ pub async fn list(
                 subscription_id: s.subscription__id,
                 is_enabled: s.is_enabled,
                 event_types: s.event_types.unwrap_or_default(),
+                event_type_patterns: s.event_type_patterns,
                 description: s.description,
                 secret: s.secret,
                 metadata: serde_json::from_value(s.metadata).unwrap_or_else(|_| HashMap::new()),
                 label_key: first_label.0,
                 label_value: first_label.1,
                 labels,
-                target: serde_json::from_value(s.target_json.unwrap())
-                    .expect("Could not parse subscription target"),
+                target: serde_json::from_value::<Target>(s.target_json.unwrap())
+                    .expect("Could not parse subscription target")
+                    .with_redacted_headers(),
                 created_at: s.created_at,
                 updated_at: s.updated_at,
                 dedicated_workers: s.dedicated_workers.unwrap_or_default(),
+                retry_policy: s
+                    .retry_policy
+                    .and_then(|rp| serde_json::from_value(rp).ok()),
+                max_requests_per_second: s.max_requests_per_second,
+                max_in_flight: s.max_in_flight,
+                ordered_delivery: s.ordered_delivery,
+                ordering_key: s.ordering_key,
+                batch_max_size: s.batch_max_size,
+                batch_max_wait_ms: s.batch_max_wait_ms,
+                payload_transformation: s
+                    .payload_transformation
+                    .and_then(|pt| serde_json::from_value(pt).ok()),
+                standard_webhooks: s
+                    .standard_webhooks
+                    .as_deref()
+                    .and_then(|sw| StandardWebhooksMode::from_str(sw).ok()),
+                filters: serde_json::from_value(s.filters).unwrap_or_default(),
+                disabled_at: s.disabled_at,
+                disabled_reason: s.disabled_reason,
             }
         })
         .collect::<Vec<_>>();
@@ -349,14 +783,27 @@ pub async fn get(
         subscription__id: Uuid,
         is_enabled: bool,
         event_types: Option<Vec<String>>,
+        event_type_patterns: Vec<String>,
         description: Option<String>,
-        secret: Uuid,
+        secret: Option<Uuid>,
         metadata: Value,
         labels: Value,
         target_json: Option<Value>,
         created_at: DateTime<Utc>,
         updated_at: DateTime<Utc>,
         dedicated_workers: Option<Vec<String>>,
+        retry_policy: Option<Value>,
+        max_requests_per_second: Option<i32>,
+        max_in_flight: Option<i32>,
+        ordered_delivery: bool,
+        ordering_key: Option<String>,
+        batch_max_size: Option<i32>,
+        batch_max_wait_ms: Option<i32>,
+        payload_transformation: Option<Value>,
+        standard_webhooks: Option<String>,
+        filters: Value,
+        disabled_at: Option<DateTime<Utc>>,
+        disabled_reason: Option<String>,
     }
 
     let raw_subscription = query_as!(
@@ -364,7 +811,7 @@ pub async fn get(
         r#"
             WITH subs AS (
                 SELECT
-                    s.application__id, s.subscription__id, s.is_enabled, s.description, s.secret, s.metadata, s.labels, s.target__id, s.created_at, s.updated_at,
+                    s.application__id, s.subscription__id, s.is_enabled, s.description, s.secret, s.metadata, s.labels, s.target__id, s.created_at, s.updated_at, s.retry_policy, s.max_requests_per_second, s.max_in_flight, s.ordered_delivery, s.ordering_key, s.batch_max_size, s.batch_max_wait_ms, s.payload_transformation, s.standard_webhooks, s.filters, s.event_type_patterns, s.disabled_at, s.disabled_reason,
                     CASE WHEN length((array_agg(set.event_type__name))[1]) > 0
                         THEN array_agg(set.event_type__name)
                         ELSE ARRAY[]::text[] END AS event_types,
@@ -383,11 +830,18 @@ pub async fn get(
                     'type', replace(tableoid::regclass::text, 'webhook.target_', ''),
                     'method', method,
                     'url', url,
-                    'headers', headers
+                    'headers', headers,
+                    'client_certificate_id', client_certificate__id,
+                    'ca_certificates', ca_certificates,
+                    'authentication', CASE WHEN oauth2_token_url IS NOT NULL THEN jsonb_build_object(
+                        'token_url', oauth2_token_url,
+                        'client_id', oauth2_client_id,
+                        'scopes', oauth2_scopes
+                    ) END
                 ) AS target_json FROM webhook.target_http
                 WHERE target__id IN (SELECT target__id FROM subs)
             )
-            SELECT subs.application__id AS "application__id!", subs.subscription__id AS "subscription__id!", subs.is_enabled AS "is_enabled!", subs.description, subs.secret AS "secret!", subs.metadata AS "metadata!", subs.labels AS "labels!", subs.created_at AS "created_at!", subs.updated_at AS "updated_at!", subs.event_types, targets.target_json, subs.dedicated_workers
+            SELECT subs.application__id AS "application__id!", subs.subscription__id AS "subscription__id!", subs.is_enabled AS "is_enabled!", subs.description, subs.secret, subs.metadata AS "metadata!", subs.labels AS "labels!", subs.created_at AS "created_at!", subs.updated_at AS "updated_at!", subs.event_types, targets.target_json, subs.dedicated_workers, subs.retry_policy, subs.max_requests_per_second, subs.max_in_flight, subs.ordered_delivery AS "ordered_delivery!", subs.ordering_key, subs.batch_max_size, subs.batch_max_wait_ms, subs.payload_transformation, subs.standard_webhooks, subs.filters AS "filters!", subs.event_type_patterns AS "event_type_patterns!", subs.disabled_at, subs.disabled_reason
             FROM subs
             INNER JOIN targets ON subs.target__id = targets.target__id
             LIMIT 1
@@ -417,17 +871,38 @@ pub async fn get(
                 subscription_id: s.subscription__id,
                 is_enabled: s.is_enabled,
                 event_types: s.event_types.unwrap_or_default(),
+                event_type_patterns: s.event_type_patterns,
                 description: s.description,
                 secret: s.secret,
                 metadata: serde_json::from_value(s.metadata).unwrap_or_else(|_| HashMap::new()),
                 label_key: first_label.0,
                 label_value: first_label.1,
                 labels,
-                target: serde_json::from_value(s.target_json.unwrap())
-                    .expect("Could not parse subscription target"),
+                target: serde_json::from_value::<Target>(s.target_json.unwrap())
+                    .expect("Could not parse subscription target")
+                    .with_redacted_headers(),
                 created_at: s.created_at,
                 updated_at: s.updated_at,
                 dedicated_workers: s.dedicated_workers.unwrap_or_default(),
+                retry_policy: s
+                    .retry_policy
+                    .and_then(|rp| serde_json::from_value(rp).ok()),
+                max_requests_per_second: s.max_requests_per_second,
+                max_in_flight: s.max_in_flight,
+                ordered_delivery: s.ordered_delivery,
+                ordering_key: s.ordering_key,
+                batch_max_size: s.batch_max_size,
+                batch_max_wait_ms: s.batch_max_wait_ms,
+                payload_transformation: s
+                    .payload_transformation
+                    .and_then(|pt| serde_json::from_value(pt).ok()),
+                standard_webhooks: s
+                    .standard_webhooks
+                    .as_deref()
+                    .and_then(|sw| StandardWebhooksMode::from_str(sw).ok()),
+                filters: serde_json::from_value(s.filters).unwrap_or_default(),
+                disabled_at: s.disabled_at,
+                disabled_reason: s.disabled_reason,
             }))
         }
         None => Err(Hook0Problem::NotFound),
@@ -435,11 +910,17 @@ pub async fn get(
 }
 
 #[derive(Debug, Serialize, Deserialize, Apiv2Schema, Validate)]
+#[validate(schema(function = "validate_delivery_parameters"))]
 pub struct SubscriptionPost {
     application_id: Uuid,
     is_enabled: bool,
-    #[validate(custom(function = "crate::validators::event_types"))]
+    /// Names of the event types to deliver (can be empty if `event_type_patterns` is not)
+    #[serde(default)]
     event_types: Vec<String>,
+    /// Deliver events whose type matches one of these patterns: `*` or `service.resource_type.verb` where any part can be `*` (for example `billing.*.*`)
+    #[serde(default)]
+    #[validate(custom(function = "crate::validators::event_type_patterns"))]
+    event_type_patterns: Vec<String>,
     #[validate(length(min = 1, max = 100))]
     description: Option<String>,
     #[validate(custom(function = "crate::validators::metadata"))]
@@ -456,6 +937,144 @@ pub struct SubscriptionPost {
     target: Target,
     #[validate(length(min = 1, max = 20))]
     dedicated_workers: Option<Vec<String>>,
+    #[validate(nested)]
+    retry_policy: Option<RetryPolicy>,
+    /// Maximum number of requests per second sent to the target; deliveries over the limit are postponed
+    ///
+    /// Each output worker enforces this limit on its own, so the target can receive up to this value multiplied by the number of output workers delivering to the subscription (use `dedicated_workers` with a worker that runs as a single process to bound it).
+    #[validate(range(min = 1, max = 10000))]
+    max_requests_per_second: Option<i32>,
+    /// Maximum number of concurrent requests sent to the target; deliveries over the limit are postponed
+    ///
+    /// This limit is shared by output workers that pick request attempts from the database, but each output worker enforces it on its own when request attempts go through Pulsar.
+    #[validate(range(min = 1, max = 1000))]
+    max_in_flight: Option<i32>,
+    /// Deliver events one at a time, in the order they were received; the next event waits until the previous one succeeded or exhausted its retries
+    #[serde(default)]
+    ordered_delivery: bool,
+    /// Label whose value splits ordered deliveries into independent sequences (requires `ordered_delivery`)
+    #[validate(non_control_character, length(min = 1, max = 50))]
+    ordering_key: Option<String>,
+    /// Send up to this number of events in a single request, as a JSON array (events are sent one by one if null); not available on instances that use Pulsar
+    #[validate(range(min = 1, max = 1000))]
+    batch_max_size: Option<i32>,
+    /// Maximum duration (in milliseconds) to wait for a batch to fill up before sending it (requires `batch_max_size`)
+    #[validate(range(min = 0, max = 60000))]
+    batch_max_wait_ms: Option<i32>,
+    /// Template used to build the body of webhooks from events (events are sent as is if null)
+    #[validate(nested)]
+    payload_transformation: Option<PayloadTransformation>,
+    /// Send Standard Webhooks headers (`webhook-id`, `webhook-timestamp` and `webhook-signature`), in addition to or instead of `X-Hook0-Signature` (the configuration of the instance applies if null)
+    standard_webhooks: Option<StandardWebhooksMode>,
+    /// Conditions that events must all meet to be delivered, on top of event types and labels
+    #[serde(default)]
+    #[validate(length(max = 20), nested)]
+    filters: Vec<SubscriptionFilter>,
+}
+
+fn validate_delivery_parameters(body: &SubscriptionPost) -> Result<(), ValidationError> {
+    // Event types are optional when patterns are given
+    if !body.event_types.is_empty() || body.event_type_patterns.is_empty() {
+        crate::validators::event_types(&body.event_types)?;
+    }
+
+    if body.ordering_key.is_some() && !body.ordered_delivery {
+        Err(
+            ValidationError::new("ordering-key-requires-ordered-delivery").with_message(
+                "'ordering_key' can only be set if 'ordered_delivery' is enabled".into(),
+            ),
+        )
+    } else if body.batch_max_wait_ms.is_some() && body.batch_max_size.is_none() {
+        Err(
+            ValidationError::new("batch-max-wait-requires-batch-max-size").with_message(
+                "'batch_max_wait_ms' can only be set if 'batch_max_size' is set".into(),
+            ),
+        )
+    } else if body.batch_max_size.is_some() && body.ordered_delivery {
+        Err(ValidationError::new("batching-excludes-ordered-delivery")
+            .with_message("'batch_max_size' cannot be set if 'ordered_delivery' is enabled".into()))
+    } else if body.payload_transformation.is_some() && body.batch_max_size.is_some() {
+        Err(
+            ValidationError::new("payload-transformation-excludes-batching").with_message(
+                "'payload_transformation' cannot be set if 'batch_max_size' is set".into(),
+            ),
+        )
+    } else {
+        Ok(())
+    }
+}
+
+/// Client certificates can only be used by subscriptions of the application they belong to
+async fn check_client_certificate(
+    db: &PgPool,
+    application_id: &Uuid,
+    target: &Target,
+) -> Result<(), Hook0Problem> {
+    match target {
+        Target::Http {
+            client_certificate_id: Some(client_certificate_id),
+            ..
+        } => {
+            let exists = query_scalar!(
+                r#"
+                    SELECT EXISTS (
+                        SELECT 1
+                        FROM webhook.client_certificate
+                        WHERE client_certificate__id = $1
+                            AND application__id = $2
+                    ) AS "exists!"
+                "#,
+                client_certificate_id,
+                application_id,
+            )
+            .fetch_one(db)
+            .await?;
+
+            if exists {
+                Ok(())
+            } else {
+                Err(Hook0Problem::ClientCertificateDoesNotExist)
+            }
+        }
+        Target::Http { .. } => Ok(()),
+    }
+}
+
+/// Client secrets of OAuth2 authentication are encrypted with a key that must be configured
+fn check_target_authentication(
+    encryption_key: Option<&EncryptionKey>,
+    target: &Target,
+) -> Result<(), Hook0Problem> {
+    match target {
+        Target::Http {
+            authentication:
+                Some(TargetAuthentication {
+                    client_secret: Some(_),
+                    ..
+                }),
+            ..
+        } if encryption_key.is_none() => Err(Hook0Problem::TargetAuthenticationDisabled),
+        Target::Http { .. } => Ok(()),
+    }
+}
+
+/// Encrypt the client secret of the OAuth2 authentication of a target with the key of the instance, if a new one was given
+fn encrypt_client_secret(
+    encryption_key: Option<&EncryptionKey>,
+    authentication: Option<&TargetAuthentication>,
+) -> Option<Vec<u8>> {
+    encryption_key
+        .zip(authentication.and_then(|a| a.client_secret.as_deref()))
+        .map(|(encryption_key, client_secret)| encryption_key.encrypt(client_secret.as_bytes()))
+}
+
+/// Request attempts consumed from Pulsar are delivered one by one, so batches could never be filled
+fn check_batching(pulsar_enabled: bool, body: &SubscriptionPost) -> Result<(), Hook0Problem> {
+    if pulsar_enabled && (body.batch_max_size.is_some() || body.batch_max_wait_ms.is_some()) {
+        Err(Hook0Problem::SubscriptionBatchingDisabled)
+    } else {
+        Ok(())
+    }
 }
 
 #[api_v2_operation(
@@ -493,6 +1112,12 @@ pub async fn create(
     if let Err(e) = body.validate() {
         return Err(Hook0Problem::Validation(e));
     }
+    check_client_certificate(&state.db, &body.application_id, &body.target).await?;
+    check_target_authentication(
+        state.target_credentials_encryption_key.as_ref(),
+        &body.target,
+    )?;
+    check_batching(state.pulsar.is_some(), &body)?;
 
     let organization_id = get_owner_organization(&state.db, &body.application_id)
         .await
@@ -533,32 +1158,82 @@ pub async fn create(
         None => json!({}),
     };
 
+    let retry_policy = body.retry_policy.as_ref().map(|rp| {
+        serde_json::to_value(rp).expect("could not serialize subscription retry policy into JSON")
+    });
+
+    let payload_transformation = body.payload_transformation.as_ref().map(|pt| {
+        serde_json::to_value(pt)
+            .expect("could not serialize subscription payload transformation into JSON")
+    });
+
+    let filters = serde_json::to_value(&body.filters)
+        .expect("could not serialize subscription filters into JSON");
+
+    // The secret is generated here because it cannot be read back once encrypted
+    let secret = Uuid::new_v4();
+    let encryption_key = state.target_credentials_encryption_key.as_ref();
+
     let mut tx = state.db.begin().await.map_err(Hook0Problem::from)?;
 
+    let data_key = match encryption_key {
+        Some(encryption_key) => {
+            Some(application_data_key(&mut *tx, encryption_key, &body.application_id).await?)
+        }
+        None => None,
+    };
+
     #[allow(non_snake_case)]
     struct RawSubscription {
         subscription__id: Uuid,
         is_enabled: bool,
         description: Option<String>,
-        secret: Uuid,
         metadata: Value,
         labels: Value,
         target__id: Uuid,
         created_at: DateTime<Utc>,
         updated_at: DateTime<Utc>,
+        retry_policy: Option<Value>,
+        max_requests_per_second: Option<i32>,
+        max_in_flight: Option<i32>,
+        ordered_delivery: bool,
+        ordering_key: Option<String>,
+        batch_max_size: Option<i32>,
+        batch_max_wait_ms: Option<i32>,
+        payload_transformation: Option<Value>,
+        standard_webhooks: Option<String>,
+        filters: Value,
+        event_type_patterns: Vec<String>,
+        disabled_at: Option<DateTime<Utc>>,
+        disabled_reason: Option<String>,
     }
     let subscription = query_as!(
             RawSubscription,
             "
-                INSERT INTO webhook.subscription (subscription__id, application__id, is_enabled, description, secret, metadata, labels, target__id, created_at, updated_at)
-                VALUES (public.gen_random_uuid(), $1, $2, $3, public.gen_random_uuid(), $4, $5, public.gen_random_uuid(), statement_timestamp(), statement_timestamp())
-                RETURNING subscription__id, is_enabled, description, secret, metadata, labels, target__id, created_at, updated_at
+                INSERT INTO webhook.subscription (subscription__id, application__id, is_enabled, description, secret, encrypted_secret, metadata, labels, target__id, created_at, updated_at, retry_policy, max_requests_per_second, max_in_flight, ordered_delivery, ordering_key, batch_max_size, batch_max_wait_ms, payload_transformation, filters, event_type_patterns, standard_webhooks)
+                VALUES (public.gen_random_uuid(), $1, $2, $3, $17, $18, $4, $5, public.gen_random_uuid(), statement_timestamp(), statement_timestamp(), $6, $7, $8, $9, $10, $11, $12, $13, $14, $15, $16)
+                RETURNING subscription__id, is_enabled, description, metadata, labels, target__id, created_at, updated_at, retry_policy, max_requests_per_second, max_in_flight, ordered_delivery, ordering_key, batch_max_size, batch_max_wait_ms, payload_transformation, standard_webhooks, filters, event_type_patterns, disabled_at, disabled_reason
             ",
             &body.application_id,
             &body.is_enabled,
             body.description,
             metadata,
             labels,
+            retry_policy,
+            body.max_requests_per_second,
+            body.max_in_flight,
+            body.ordered_delivery,
+            body.ordering_key,
+            body.batch_max_size,
+            body.batch_max_wait_ms,
+            payload_transformation,
+            filters,
+            &body.event_type_patterns,
+            body.standard_webhooks.map(<&'static str>::from),
+            data_key.is_none().then_some(secret),
+            data_key
+                .as_ref()
+                .map(|data_key| data_key.encrypt(secret.as_bytes())),
         )
             .fetch_one(&mut *tx)
             .await
@@ -569,19 +1244,40 @@ pub async fn create(
             method,
             url,
             headers,
-        } => query!(
-            "
-                INSERT INTO webhook.target_http (target__id, method, url, headers)
-                VALUES ($1, $2, $3, $4)
-            ",
-            &subscription.target__id,
-            method.to_uppercase(),
-            url.as_str(),
-            serde_json::to_value(headers).expect("could not serialize target headers into JSON"),
-        )
-        .execute(&mut *tx)
-        .await
-        .map_err(Hook0Problem::from)?,
+            client_certificate_id,
+            ca_certificates,
+            authentication,
+        } => {
+            let mut headers = StoredTargetHeaders::new(
+                &serde_json::to_value(headers)
+                    .expect("could not serialize target headers into JSON"),
+                data_key.is_some(),
+                None,
+            );
+            if let Some(data_key) = &data_key {
+                headers.encrypt(data_key);
+            }
+            query!(
+                "
+                    INSERT INTO webhook.target_http (target__id, method, url, headers, encrypted_headers, client_certificate__id, ca_certificates, oauth2_token_url, oauth2_client_id, oauth2_encrypted_client_secret, oauth2_scopes)
+                    VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11)
+                ",
+                &subscription.target__id,
+                method.to_uppercase(),
+                url.as_str(),
+                Value::Object(headers.headers),
+                Value::Object(headers.encrypted_headers),
+                client_certificate_id.as_ref(),
+                ca_certificates.as_deref(),
+                authentication.as_ref().map(|a| a.token_url.as_str()),
+                authentication.as_ref().map(|a| a.client_id.as_str()),
+                encrypt_client_secret(encryption_key, authentication.as_ref()),
+                authentication.as_ref().map(|a| a.scopes.as_slice()),
+            )
+            .execute(&mut *tx)
+            .await
+            .map_err(Hook0Problem::from)?
+        }
     };
 
     for event_type in &body.event_types {
@@ -664,17 +1360,37 @@ pub async fn create(
         subscription_id: subscription.subscription__id,
         is_enabled: subscription.is_enabled,
         event_types: body.event_types.clone(),
+        event_type_patterns: subscription.event_type_patterns,
         description: subscription.description,
-        secret: subscription.secret,
+        secret: Some(secret),
         metadata: serde_json::from_value(subscription.metadata.clone())
             .unwrap_or_else(|_| HashMap::new()),
         label_key: first_label.0,
         label_value: first_label.1,
         labels,
-        target: body.target.clone(),
+        target: body.target.clone().with_redacted_headers(),
         created_at: subscription.created_at,
         updated_at: subscription.updated_at,
         dedicated_workers: body.dedicated_workers.clone().unwrap_or_default(),
+        retry_policy: subscription
+            .retry_policy
+            .and_then(|rp| serde_json::from_value(rp).ok()),
+        max_requests_per_second: subscription.max_requests_per_second,
+        max_in_flight: subscription.max_in_flight,
+        ordered_delivery: subscription.ordered_delivery,
+        ordering_key: subscription.ordering_key,
+        batch_max_size: subscription.batch_max_size,
+        batch_max_wait_ms: subscription.batch_max_wait_ms,
+        payload_transformation: subscription
+            .payload_transformation
+            .and_then(|pt| serde_json::from_value(pt).ok()),
+        standard_webhooks: subscription
+            .standard_webhooks
+            .as_deref()
+            .and_then(|sw| StandardWebhooksMode::from_str(sw).ok()),
+        filters: serde_json::from_value(subscription.filters).unwrap_or_default(),
+        disabled_at: subscription.disabled_at,
+        disabled_reason: subscription.disabled_reason,
     };
 
     if let Some(hook0_client) = state.hook0_client.as_ref() {
@@ -739,6 +1455,12 @@ pub async fn edit(
     if let Err(e) = body.validate() {
         return Err(Hook0Problem::Validation(e));
     }
+    check_client_certificate(&state.db, &body.application_id, &body.target).await?;
+    check_target_authentication(
+        state.target_credentials_encryption_key.as_ref(),
+        &body.target,
+    )?;
+    check_batching(state.pulsar.is_some(), &body)?;
 
     let organization_id = get_owner_organization(&state.db, &body.application_id)
         .await
@@ -752,6 +1474,18 @@ pub async fn edit(
         None => json!({}),
     };
 
+    let retry_policy = body.retry_policy.as_ref().map(|rp| {
+        serde_json::to_value(rp).expect("could not serialize subscription retry policy into JSON")
+    });
+
+    let payload_transformation = body.payload_transformation.as_ref().map(|pt| {
+        serde_json::to_value(pt)
+            .expect("could not serialize subscription payload transformation into JSON")
+    });
+
+    let filters = serde_json::to_value(&body.filters)
+        .expect("could not serialize subscription filters into JSON");
+
     let mut tx = state.db.begin().await.map_err(Hook0Problem::from)?;
 
     let subscription_id = subscription_id.into_inner();
@@ -761,12 +1495,25 @@ pub async fn edit(
         subscription__id: Uuid,
         is_enabled: bool,
         description: Option<String>,
-        secret: Uuid,
+        secret: Option<Uuid>,
         metadata: Value,
         labels: Value,
         target__id: Uuid,
         created_at: DateTime<Utc>,
         updated_at: DateTime<Utc>,
+        retry_policy: Option<Value>,
+        max_requests_per_second: Option<i32>,
+        max_in_flight: Option<i32>,
+        ordered_delivery: bool,
+        ordering_key: Option<String>,
+        batch_max_size: Option<i32>,
+        batch_max_wait_ms: Option<i32>,
+        payload_transformation: Option<Value>,
+        standard_webhooks: Option<String>,
+        filters: Value,
+        event_type_patterns: Vec<String>,
+        disabled_at: Option<DateTime<Utc>>,
+        disabled_reason: Option<String>,
     }
 
     // Update all fields including is_enabled, description, metadata, labels
@@ -774,16 +1521,32 @@ pub async fn edit(
         RawSubscription,
         "
             UPDATE webhook.subscription
-            SET is_enabled = $1, description = $2, metadata = $3, labels = $4, updated_at = statement_timestamp()
-            WHERE subscription__id = $5 AND application__id = $6 AND deleted_at IS NULL
-            RETURNING subscription__id, is_enabled, description, secret, metadata, labels, target__id, created_at, updated_at
+            SET is_enabled = $1, description = $2, metadata = $3, labels = $4, retry_policy = $5, max_requests_per_second = $8, max_in_flight = $9, ordered_delivery = $10, ordering_key = $11, batch_max_size = $12, batch_max_wait_ms = $13, payload_transformation = $14, filters = $15, event_type_patterns = $16, standard_webhooks = $17, updated_at = statement_timestamp(),
+                -- Enabling the subscription resets the state of its circuit breaker
+                consecutive_failures = CASE WHEN $1 THEN 0 ELSE consecutive_failures END,
+                failing_since = CASE WHEN $1 THEN NULL ELSE failing_since END,
+                disabled_at = CASE WHEN $1 THEN NULL ELSE disabled_at END,
+                disabled_reason = CASE WHEN $1 THEN NULL ELSE disabled_reason END
+            WHERE subscription__id = $6 AND application__id = $7 AND deleted_at IS NULL
+            RETURNING subscription__id, is_enabled, description, secret, metadata, labels, target__id, created_at, updated_at, retry_policy, max_requests_per_second, max_in_flight, ordered_delivery, ordering_key, batch_max_size, batch_max_wait_ms, payload_transformation, standard_webhooks, filters, event_type_patterns, disabled_at, disabled_reason
         ",
         &body.is_enabled,
         body.description,
         metadata,
         labels,
+        retry_policy,
         &subscription_id,
-        &body.application_id
+        &body.application_id,
+        body.max_requests_per_second,
+        body.max_in_flight,
+        body.ordered_delivery,
+        body.ordering_key,
+        body.batch_max_size,
+        body.batch_max_wait_ms,
+        payload_transformation,
+        filters,
+        &body.event_type_patterns,
+        body.standard_webhooks.map(<&'static str>::from),
     )
     .fetch_optional(&mut *tx)
     .await
@@ -796,21 +1559,66 @@ pub async fn edit(
                     method,
                     url,
                     headers,
-                } => query!(
-                    "
-                        UPDATE webhook.target_http
-                        SET method = $1, url = $2, headers = $3
-                        WHERE target__id = $4
-                    ",
-                    method.to_uppercase(),
-                    url.as_str(),
-                    serde_json::to_value(headers)
-                        .expect("could not serialize target headers into JSON"),
-                    &s.target__id
-                )
-                .execute(&mut *tx)
-                .await
-                .map_err(Hook0Problem::from)?,
+                    client_certificate_id,
+                    ca_certificates,
+                    authentication,
+                } => {
+                    let current_headers = query!(
+                        "
+                            SELECT headers, encrypted_headers
+                            FROM webhook.target_http
+                            WHERE target__id = $1
+                        ",
+                        &s.target__id,
+                    )
+                    .fetch_optional(&mut *tx)
+                    .await
+                    .map_err(Hook0Problem::from)?;
+                    let mut headers = StoredTargetHeaders::new(
+                        &serde_json::to_value(headers)
+                            .expect("could not serialize target headers into JSON"),
+                        state.target_credentials_encryption_key.is_some(),
+                        current_headers
+                            .as_ref()
+                            .map(|current| (&current.headers, &current.encrypted_headers)),
+                    );
+                    if let Some(encryption_key) = &state.target_credentials_encryption_key
+                        && !headers.headers_to_encrypt.is_empty()
+                    {
+                        headers.encrypt(
+                            &application_data_key(&mut *tx, encryption_key, &body.application_id)
+                                .await?,
+                        );
+                    }
+
+                    query!(
+                        "
+                            UPDATE webhook.target_http
+                            SET method = $1, url = $2, headers = $3, encrypted_headers = $11, client_certificate__id = $4, ca_certificates = $5, oauth2_token_url = $6, oauth2_client_id = $7, oauth2_encrypted_client_secret = CASE
+                                WHEN $8::bytea IS NOT NULL THEN $8
+                                WHEN oauth2_token_url = $6 AND oauth2_client_id = $7 THEN oauth2_encrypted_client_secret
+                            END, oauth2_scopes = $9
+                            WHERE target__id = $10
+                        ",
+                        method.to_uppercase(),
+                        url.as_str(),
+                        Value::Object(headers.headers),
+                        client_certificate_id.as_ref(),
+                        ca_certificates.as_deref(),
+                        authentication.as_ref().map(|a| a.token_url.as_str()),
+                        authentication.as_ref().map(|a| a.client_id.as_str()),
+                        encrypt_client_secret(
+                            state.target_credentials_encryption_key.as_ref(),
+                            authentication.as_ref(),
+                        ),
+                        authentication.as_ref().map(|a| a.scopes.as_slice()),
+                        &s.target__id,
+                        Value::Object(headers.encrypted_headers),
+                    )
+                    .execute(&mut *tx)
+                    .await
+                    .map_err(Hook0Problem::from)?
+                }
             };
 
             query!(
@@ -951,6 +1759,7 @@ pub async fn edit(
                 subscription_id: s.subscription__id,
                 is_enabled: s.is_enabled,
                 event_types: body.event_types.clone(),
+                event_type_patterns: s.event_type_patterns,
                 description: s.description,
                 secret: s.secret,
                 metadata: serde_json::from_value(s.metadata.clone())
@@ -958,10 +1767,29 @@ pub async fn edit(
                 label_key: first_label.0,
                 label_value: first_label.1,
                 labels,
-                target: body.target.clone(),
+                target: body.target.clone().with_redacted_headers(),
                 created_at: s.created_at,
                 updated_at: s.updated_at,
                 dedicated_workers: body.dedicated_workers.clone().unwrap_or_default(),
+                retry_policy: s
+                    .retry_policy
+                    .and_then(|rp| serde_json::from_value(rp).ok()),
+                max_requests_per_second: s.max_requests_per_second,
+                max_in_flight: s.max_in_flight,
+                ordered_delivery: s.ordered_delivery,
+                ordering_key: s.ordering_key,
+                batch_max_size: s.batch_max_size,
+                batch_max_wait_ms: s.batch_max_wait_ms,
+                payload_transformation: s
+                    .payload_transformation
+                    .and_then(|pt| serde_json::from_value(pt).ok()),
+                standard_webhooks: s
+                    .standard_webhooks
+                    .as_deref()
+                    .and_then(|sw| StandardWebhooksMode::from_str(sw).ok()),
+                filters: serde_json::from_value(s.filters).unwrap_or_default(),
+                disabled_at: s.disabled_at,
+                disabled_reason: s.disabled_reason,
             };
 
             if let Some(hook0_client) = state.hook0_client.as_ref() {
@@ -1102,6 +1930,205 @@ pub async fn delete(
     }
 }
 
+#[derive(Debug, Deserialize, Apiv2Schema, Validate)]
+pub struct SubscriptionSecretRotationPost {
+    application_id: Uuid,
+    /// How long the current secret keeps being used to sign webhooks alongside the new one, in seconds (defaults to 24 hours; 0 revokes it immediately)
+    #[validate(range(min = 0, max = 2592000))]
+    grace_period_in_s: Option<i32>,
+}
+
+/// Default duration during which the previous secret of a subscription keeps being used after a rotation
+const DEFAULT_SECRET_ROTATION_GRACE_PERIOD_IN_S: i32 = 86400;
+
+#[derive(Debug, Serialize, Apiv2Schema)]
+pub struct SubscriptionSecretRotation {
+    /// New secret of the subscription
+    secret: Uuid,
+    /// Secret that was replaced; webhooks are also signed with it until `previous_secret_expires_at` (not returned if the instance encrypts secrets at rest)
+    previous_secret: Option<Uuid>,
+    previous_secret_expires_at: Option<DateTime<Utc>>,
+}
+
+#[api_v2_operation(
+    summary = "Rotate the secret of a subscription",
+    description = "Generates a new secret for a subscription. During the grace period, webhooks are signed with both the new and the previous secret (the `X-Hook0-Signature` header then holds several signatures), so that the target can switch to the new secret without rejecting any webhook. Rotating again during the grace period revokes the secret that was replaced first.",
+    operation_id = "subscriptions.rotateSecret",
+    consumes = "application/json",
+    produces = "application/json",
+    tags("Subscriptions Management")
+)]
+pub async fn rotate_secret(
+    state: Data<crate::State>,
+    _: OaBiscuit,
+    biscuit: ReqData<Biscuit>,
+    subscription_id: Path<Uuid>,
+    body: Json<SubscriptionSecretRotationPost>,
+) -> Result<Json<SubscriptionSecretRotation>, Hook0Problem> {
+    authorize_for_application(
+        &state.db,
+        &biscuit,
+        Action::SubscriptionRotateSecret {
+            application_id: &body.application_id,
+            subscription_id: &subscription_id,
+        },
+        state.max_authorization_time,
+        state.debug_authorizer,
+    )
+    .await?;
+
+    if let Err(e) = body.validate() {
+        return Err(Hook0Problem::Validation(e));
+    }
+
+    let grace_period_in_s = body
+        .grace_period_in_s
+        .unwrap_or(DEFAULT_SECRET_ROTATION_GRACE_PERIOD_IN_S);
+
+    // The secret is generated here because it cannot be read back once encrypted
+    let secret = Uuid::new_v4();
+    let encrypted_secret = match &state.target_credentials_encryption_key {
+        Some(encryption_key) => Some(
+            application_data_key(&state.db, encryption_key, &body.application_id)
+                .await?
+                .encrypt(secret.as_bytes()),
+        ),
+        None => None,
+    };
+
+    let rotation = query!(
+        "
+            UPDATE webhook.subscription
+            SET secret = $4,
+                encrypted_secret = $5,
+                previous_secret = CASE WHEN $3 > 0 THEN secret END,
+                encrypted_previous_secret = CASE WHEN $3 > 0 THEN encrypted_secret END,
+                previous_secret_expires_at = CASE WHEN $3 > 0 THEN statement_timestamp() + make_interval(secs => $3) END,
+                updated_at = statement_timestamp()
+            WHERE application__id = $1 AND subscription__id = $2 AND deleted_at IS NULL
+            RETURNING previous_secret, previous_secret_expires_at
+        ",
+        &body.application_id,
+        &subscription_id.into_inner(),
+        grace_period_in_s,
+        encrypted_secret.is_none().then_some(secret),
+        encrypted_secret,
+    )
+    .fetch_optional(&state.db)
+    .await
+    .map_err(Hook0Problem::from)?;
+
+    rotation
+        .map(|r| {
+            Json(SubscriptionSecretRotation {
+                secret,
+                previous_secret: r.previous_secret,
+                previous_secret_expires_at: r.previous_secret_expires_at,
+            })
+        })
+        .ok_or(Hook0Problem::NotFound)
+}
+
+#[derive(Debug, Deserialize, Apiv2Schema, Validate)]
+pub struct PayloadTransformationPreviewPost {
+    application_id: Uuid,
+    /// Event the payload transformation is applied to
+    event_id: Uuid,
+    #[validate(nested)]
+    payload_transformation: PayloadTransformation,
+}
+
+#[derive(Debug, Serialize, Apiv2Schema)]
+pub struct PayloadTransformationPreview {
+    /// Value of the `Content-Type` header that would be sent
+    content_type: String,
+    /// Body that would be sent
+    payload: String,
+}
+
+#[api_v2_operation(
+    summary = "Preview a payload transformation",
+    description = "Applies a payload transformation to an existing event and returns the body that would be sent to the subscription's target, without sending anything. Use it to check a template before setting it on a subscription.",
+    operation_id = "subscriptions.preview_payload_transformation",
+    consumes = "application/json",
+    produces = "application/json",
+    tags("Subscriptions Management")
+)]
+pub async fn preview_payload_transformation(
+    state: Data<crate::State>,
+    _: OaBiscuit,
+    biscuit: ReqData<Biscuit>,
+    body: Json<PayloadTransformationPreviewPost>,
+) -> Result<Json<PayloadTransformationPreview>, Hook0Problem> {
+    authorize_for_application(
+        &state.db,
+        &biscuit,
+        Action::EventGet {
+            application_id: &body.application_id,
+        },
+        state.max_authorization_time,
+        state.debug_authorizer,
+    )
+    .await?;
+
+    if let Err(e) = body.validate() {
+        return Err(Hook0Problem::Validation(e));
+    }
+
+    #[allow(non_snake_case)]
+    struct RawEvent {
+        event_type__name: String,
+        payload: Option<Vec<u8>>,
+        payload_content_type: String,
+        metadata: Option<Value>,
+        received_at: DateTime<Utc>,
+        labels: Value,
+    }
+    let event = query_as!(
+        RawEvent,
+        "
+            SELECT event_type__name, payload, payload_content_type, metadata, received_at, labels
+            FROM event.event
+            WHERE application__id = $1 AND event__id = $2
+        ",
+        &body.application_id,
+        &body.event_id,
+    )
+    .fetch_optional(&state.db)
+    .await
+    .map_err(Hook0Problem::from)?
+    .ok_or(Hook0Problem::NotFound)?;
+
+    let payload = crate::handlers::events::load_payload(
+        &state,
+        &body.application_id,
+        &body.event_id,
+        event.received_at,
+        event.payload,
+    )
+    .await?;
+
+    let transformation =
+        hook0_payload_transformation::PayloadTransformation::from(&body.payload_transformation);
+    let context = TransformationContext::new(
+        body.event_id,
+        &event.event_type__name,
+        event.received_at,
+        &event.payload_content_type,
+        &payload,
+        event.labels,
+        event.metadata,
+    );
+    let rendered = transformation
+        .render(&context)
+        .map_err(|e| Hook0Problem::PayloadTransformationFailed(e.to_string()))?;
+
+    Ok(Json(PayloadTransformationPreview {
+        content_type: transformation.content_type,
+        payload: String::from_utf8_lossy(&rendered).into_owned(),
+    }))
+}
+
 #[cfg(test)]
 mod tests {
     use serde_json::from_value;
@@ -1121,6 +2148,9 @@ mod tests {
             method: "GET".to_owned(),
             url: HttpUrl(Url::parse(url).unwrap()),
             headers: HeaderMap(reqwest::header::HeaderMap::new()),
+            client_certificate_id: None,
+            ca_certificates: None,
+            authentication: None,
         };
         assert_eq!(from_value::<Target>(input).unwrap(), expected);
     }
@@ -1158,4 +2188,211 @@ mod tests {
                 .contains("host")
         );
     }
+
+    #[test]
+    fn test_validate_delivery_parameters() {
+        let body = |extra: Value| {
+            let mut input = json!({
+                "application_id": Uuid::nil(),
+                "is_enabled": true,
+                "event_types": ["service.resource.verb"],
+                "labels": { "env": "test" },
+                "target": {
+                    "type": "http",
+                    "method": "POST",
+                    "headers": {},
+                    "url": "https://www.hook0.com",
+                },
+            });
+            input
+                .as_object_mut()
+                .unwrap()
+                .extend(extra.as_object().unwrap().clone());
+            from_value::<SubscriptionPost>(input).unwrap()
+        };
+
+        assert!(validate_delivery_parameters(&body(json!({}))).is_ok());
+        assert!(
+            validate_delivery_parameters(&body(
+                json!({ "batch_max_size": 100, "batch_max_wait_ms": 500 })
+            ))
+            .is_ok()
+        );
+        assert!(validate_delivery_parameters(&body(json!({ "batch_max_wait_ms": 500 }))).is_err());
+        assert!(
+            validate_delivery_parameters(&body(
+                json!({ "batch_max_size": 100, "ordered_delivery": true })
+            ))
+            .is_err()
+        );
+        assert!(
+            validate_delivery_parameters(&body(json!({ "ordering_key": "customer" }))).is_err()
+        );
+        assert!(
+            validate_delivery_parameters(&body(json!({
+                "batch_max_size": 100,
+                "payload_transformation": { "template": "{{ payload }}" },
+            })))
+            .is_err()
+        );
+        assert!(
+            validate_delivery_parameters(&body(json!({
+                "event_types": [],
+                "event_type_patterns": ["billing.*.*"],
+            })))
+            .is_ok()
+        );
+        assert!(validate_delivery_parameters(&body(json!({ "event_types": [] }))).is_err());
+
+        let batched = body(json!({ "batch_max_size": 100 }));
+        assert!(check_batching(false, &batched).is_ok());
+        assert!(matches!(
+            check_batching(true, &batched),
+            Err(Hook0Problem::SubscriptionBatchingDisabled)
+        ));
+        assert!(check_batching(true, &body(json!({}))).is_ok());
+    }
+
+    #[test]
+    fn test_validate_filter_parameters() {
+        let filter = |input: Value| from_value::<SubscriptionFilter>(input).unwrap();
+
+        for valid in [
+            json!({ "label": "country", "operator": "in", "values": ["FR", "BE"] }),
+            json!({ "label": "env", "operator": "not_in", "values": ["test"] }),
+            json!({ "label": "tenant", "operator": "exists" }),
+            json!({ "path": "$.amount", "operator": "gt", "value": 1000 }),
+            json!({ "path": "$.country", "operator": "eq", "value": "FR" }),
+        ] {
+            assert!(filter(valid.clone()).validate().is_ok(), "{valid}");
+        }
+
+        for invalid in [
+            json!({ "label": "country", "operator": "in" }),
+            json!({ "path": "$.country", "operator": "in", "values": ["FR"] }),
+            json!({ "label": "tenant", "path": "$.tenant", "operator": "exists" }),
+            json!({ "label": "tenant", "operator": "exists", "value": "acme" }),
+            json!({ "path": "$.amount", "operator": "gt" }),
+            json!({ "path": "$.tags", "operator": "eq", "value": ["a"] }),
+            json!({ "path": "$.amount > 1000", "operator": "eq", "value": true }),
+        ] {
+            assert!(filter(invalid.clone()).validate().is_err(), "{invalid}");
+        }
+    }
+
+    #[test]
+    fn test_validate_payload_transformation() {
+        let transformation = from_value::<PayloadTransformation>(json!({
+            "template": "{\"text\": {{ event_type }}}",
+        }))
+        .unwrap();
+        assert_eq!(transformation.content_type, "application/json");
+        assert!(transformation.validate().is_ok());
+
+        let transformation = from_value::<PayloadTransformation>(json!({
+            "template": "{{ event_type",
+            "content_type": "text/plain",
+        }))
+        .unwrap();
+        assert!(transformation.validate().is_err());
+    }
+
+    #[test]
+    fn test_redact_target_headers() {
+        let target = from_value::<Target>(json!({
+            "type": "http",
+            "method": "POST",
+            "url": "https://www.hook0.com",
+            "headers": {
+                "authorization": "Bearer token",
+                "x-api-key": "key",
+                "x-tenant": "acme",
+            },
+        }))
+        .unwrap()
+        .with_redacted_headers();
+        assert_eq!(
+            serde_json::to_value(target).unwrap()["headers"],
+            json!({
+                "authorization": REDACTED_HEADER_VALUE,
+                "x-api-key": REDACTED_HEADER_VALUE,
+                "x-tenant": "acme",
+            })
+        );
+    }
+
+    #[test]
+    fn test_stored_target_headers() {
+        let headers = json!({
+            "authorization": "Bearer token",
+            "x-api-key": "key",
+            "x-tenant": "acme",
+        });
+
+        let plaintext = StoredTargetHeaders::new(&headers, false, None);
+        assert_eq!(Value::Object(plaintext.headers), headers);
+        assert!(plaintext.encrypted_headers.is_empty());
+        assert!(plaintext.headers_to_encrypt.is_empty());
+
+        let encrypted = StoredTargetHeaders::new(&headers, true, None);
+        assert_eq!(
+            Value::Object(encrypted.headers),
+            json!({
+                "authorization": REDACTED_HEADER_VALUE,
+                "x-api-key": REDACTED_HEADER_VALUE,
+                "x-tenant": "acme",
+            })
+        );
+        assert!(encrypted.encrypted_headers.is_empty());
+        assert_eq!(
+            Value::Object(encrypted.headers_to_encrypt),
+            json!({
+                "authorization": "Bearer token",
+                "x-api-key": "key",
+            })
+        );
+    }
+
+    #[test]
+    fn test_stored_target_headers_keep_redacted_values() {
+        let headers = json!({
+            "authorization": REDACTED_HEADER_VALUE,
+            "x-api-key": REDACTED_HEADER_VALUE,
+            "x-auth-token": "new token",
+        });
+        let current_headers = json!({
+            "authorization": REDACTED_HEADER_VALUE,
+            "x-api-key": "key",
+            "x-auth-token": REDACTED_HEADER_VALUE,
+        });
+        let current_encrypted_headers = json!({
+            "authorization": "ZW5jcnlwdGVkIHRva2Vu",
+            "x-auth-token": "ZW5jcnlwdGVkIHRva2Vu",
+        });
+
+        let stored = StoredTargetHeaders::new(
+            &headers,
+            true,
+            Some((&current_headers, &current_encrypted_headers)),
+        );
+        assert_eq!(
+            Value::Object(stored.headers),
+            json!({
+                "authorization": REDACTED_HEADER_VALUE,
+                "x-api-key": REDACTED_HEADER_VALUE,
+                "x-auth-token": REDACTED_HEADER_VALUE,
+            })
+        );
+        assert_eq!(
+            Value::Object(stored.encrypted_headers),
+            json!({ "authorization": "ZW5jcnlwdGVkIHRva2Vu" })
+        );
+        assert_eq!(
+            Value::Object(stored.headers_to_encrypt),
+            json!({
+                "x-api-key": "key",
+                "x-auth-token": "new token",
+            })
+        );
+    }
 }
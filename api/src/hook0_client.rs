@@ -37,6 +37,7 @@ pub const EVENT_TYPES: &[&str] = &[
     "api.subscription.created",
     "api.subscription.updated",
     "api.subscription.removed",
+    "api.request_attempt.retried",
 ];
 
 pub fn initialize(
@@ -131,6 +132,7 @@ pub enum Hook0ClientEvent {
     SubscriptionCreated(EventSubscriptionCreated),
     SubscriptionUpdated(EventSubscriptionUpdated),
     SubscriptionRemoved(EventSubscriptionRemoved),
+    RequestAttemptRetried(EventRequestAttemptRetried),
 }
 
 impl Hook0ClientEvent {
@@ -181,6 +183,9 @@ impl Hook0ClientEvent {
             }
             Self::SubscriptionUpdated(e) => to_event(e, None),
             Self::SubscriptionRemoved(e) => to_event(e, None),
+            Self::RequestAttemptRetried(e @ EventRequestAttemptRetried { created_at, .. }) => {
+                to_event(e, Some(created_at))
+            }
         }
     }
 }
@@ -804,3 +809,42 @@ impl From<EventSubscriptionRemoved> for Hook0ClientEvent {
         Self::SubscriptionRemoved(e)
     }
 }
+
+#[derive(Debug, Clone, Serialize)]
+pub struct EventRequestAttemptRetried {
+    pub organization_id: Uuid,
+    pub application_id: Uuid,
+    pub event_id: Uuid,
+    pub subscription_id: Uuid,
+    /// Failed request attempt that was retried
+    pub request_attempt_id: Uuid,
+    /// Request attempt created by the retry
+    pub retry_request_attempt_id: Uuid,
+    pub created_at: DateTime<Utc>,
+}
+
+impl Event for EventRequestAttemptRetried {
+    fn event_type(&self) -> &'static str {
+        "api.request_attempt.retried"
+    }
+
+    fn labels(&self) -> Vec<(String, String)> {
+        vec![
+            (INSTANCE_LABEL.to_owned(), INSTANCE_VALUE.to_owned()),
+            (
+                ORGANIZATION_LABEL.to_owned(),
+                self.organization_id.to_string(),
+            ),
+            (
+                APPLICATION_LABEL.to_owned(),
+                self.application_id.to_string(),
+            ),
+        ]
+    }
+}
+
+impl From<EventRequestAttemptRetried> for Hook0ClientEvent {
+    fn from(e: EventRequestAttemptRetried) -> Self {
+        Self::RequestAttemptRetried(e)
+    }
+}
@@ -81,6 +81,8 @@ pub enum Role {
     #[default]
     Viewer,
     Editor,
+    /// Actions are granted by the custom role of the organization that is assigned to the member
+    Custom,
 }
 
 impl TypedData for Role {
@@ -105,8 +107,10 @@ impl Serialize for Role {
 impl Role {
     #[cfg(feature = "migrate-users-from-keycloak")]
     pub fn from_string_with_prefix(str: &str) -> Option<Self> {
+        // Custom roles did not exist in Keycloak
         str.strip_prefix(ROLE_GROUP_PREFIX)
             .and_then(|s| Self::from_str(s).ok())
+            .filter(|role| *role != Self::Custom)
     }
 }
 
@@ -144,6 +148,17 @@ pub struct RootToken {
 const USER_ACCESS_TOKEN_VERSION: i64 = 1;
 const USER_ACCESS_TOKEN_EXPIRATION: Duration = Duration::from_secs(60 * 5);
 
+/// Custom role assigned to a user in an organization, as it is embedded in user access tokens
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CustomRoleGrant {
+    pub organization_id: Uuid,
+    pub custom_role_id: Uuid,
+    /// Names of the granted actions (see [`CUSTOM_ROLE_ACTIONS`])
+    pub actions: Vec<String>,
+    /// Applications on which actions are granted; `None` means all the applications of the organization
+    pub application_ids: Option<Vec<Uuid>>,
+}
+
 #[allow(clippy::too_many_arguments)]
 pub fn create_user_access_token(
     private_key: &PrivateKey,
@@ -154,6 +169,7 @@ pub fn create_user_access_token(
     first_name: &str,
     last_name: &str,
     roles: Vec<(Uuid, String)>,
+    custom_roles: Vec<CustomRoleGrant>,
 ) -> Result<RootToken, biscuit_auth::error::Token> {
     let keypair = KeyPair::from(private_key);
     let created_at = SystemTime::now();
@@ -178,6 +194,34 @@ pub fn create_user_access_token(
         for (organization_id, role) in roles {
             biscuit = biscuit.fact(fact!("organization_role({organization_id}, {role})"))?;
         }
+        for custom_role in custom_roles {
+            let organization_id = custom_role.organization_id;
+            let custom_role_id = custom_role.custom_role_id;
+            biscuit = biscuit.fact(Fact::new(
+                "organization_custom_role".to_owned(),
+                vec![
+                    Term::Bytes(organization_id.as_bytes().to_vec()),
+                    Term::Bytes(custom_role_id.as_bytes().to_vec()),
+                    Term::Set(BTreeSet::from_iter(
+                        custom_role.actions.into_iter().map(Term::Str),
+                    )),
+                ],
+            ))?;
+            match custom_role.application_ids {
+                None => {
+                    biscuit = biscuit.fact(fact!(
+                        "organization_custom_role_all_applications({organization_id}, {custom_role_id})"
+                    ))?;
+                }
+                Some(application_ids) => {
+                    for application_id in application_ids {
+                        biscuit = biscuit.fact(fact!(
+                            "organization_custom_role_application({organization_id}, {custom_role_id}, {application_id})"
+                        ))?;
+                    }
+                }
+            }
+        }
         biscuit.build(&keypair)?
     };
     let serialized_biscuit = biscuit.to_base64()?;
@@ -361,6 +405,75 @@ pub fn create_master_access_token(
     Ok(biscuit)
 }
 
+/// Names of the actions that custom roles can grant
+///
+/// Actions that manage the organization, its members, its service tokens and its custom roles are left to editors so that custom roles cannot be used to escalate privileges.
+pub const CUSTOM_ROLE_ACTIONS: &[&str] = &[
+    "organization:get",
+    //
+    "application:list",
+    "application:create",
+    "application:get",
+    "application:edit",
+    "application:delete",
+    //
+    #[cfg(feature = "application-secret-compatibility")]
+    "application_secret:list",
+    #[cfg(feature = "application-secret-compatibility")]
+    "application_secret:create",
+    #[cfg(feature = "application-secret-compatibility")]
+    "application_secret:edit",
+    #[cfg(feature = "application-secret-compatibility")]
+    "application_secret:delete",
+    //
+    "event_type:list",
+    "event_type:create",
+    "event_type:get",
+    "event_type:delete",
+    //
+    "subscription:list",
+    "subscription:create",
+    "subscription:get",
+    "subscription:edit",
+    "subscription:delete",
+    "subscription:rotate_secret",
+    //
+    "client_certificate:list",
+    "client_certificate:create",
+    "client_certificate:delete",
+    //
+    "signing_key:rotate",
+    //
+    "event:list",
+    "event:get",
+    "event:ingest",
+    "event:replay",
+    "event:cancel",
+    //
+    "replay_job:list",
+    "replay_job:get",
+    "replay_job:cancel",
+    //
+    "request_attempt:list",
+    "request_attempt:get",
+    "request_attempt:retry",
+    //
+    "response:get",
+    //
+    "events_per_day:application",
+    "events_per_day:organization",
+];
+
+/// Names of the actions that custom roles can grant but that do not target a specific application
+///
+/// They cannot be granted by custom roles that are restricted to some applications.
+pub const CUSTOM_ROLE_ORGANIZATION_ACTIONS: &[&str] = &[
+    "organization:get",
+    "application:list",
+    "application:create",
+    "events_per_day:organization",
+];
+
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum Action<'a> {
     #[cfg(test)]
@@ -384,6 +497,16 @@ pub enum Action<'a> {
     OrganizationEditRole,
     OrganizationDelete,
     //
+    CustomRoleList,
+    CustomRoleCreate,
+    CustomRoleGet,
+    CustomRoleEdit {
+        custom_role_id: &'a Uuid,
+    },
+    CustomRoleDelete {
+        custom_role_id: &'a Uuid,
+    },
+    //
     ServiceTokenList,
     ServiceTokenCreate,
     ServiceTokenGet,
@@ -455,6 +578,24 @@ pub enum Action<'a> {
         application_id: &'a Uuid,
         subscription_id: &'a Uuid,
     },
+    SubscriptionRotateSecret {
+        application_id: &'a Uuid,
+        subscription_id: &'a Uuid,
+    },
+    //
+    ClientCertificateList {
+        application_id: &'a Uuid,
+    },
+    ClientCertificateCreate {
+        application_id: &'a Uuid,
+    },
+    ClientCertificateDelete {
+        application_id: &'a Uuid,
+    },
+    //
+    SigningKeyRotate {
+        application_id: &'a Uuid,
+    },
     //
     EventList {
         application_id: &'a Uuid,
@@ -468,6 +609,19 @@ pub enum Action<'a> {
     EventReplay {
         application_id: &'a Uuid,
     },
+    EventCancel {
+        application_id: &'a Uuid,
+    },
+    //
+    ReplayJobList {
+        application_id: &'a Uuid,
+    },
+    ReplayJobGet {
+        application_id: &'a Uuid,
+    },
+    ReplayJobCancel {
+        application_id: &'a Uuid,
+    },
     //
     RequestAttemptList {
         application_id: &'a Uuid,
@@ -476,6 +630,9 @@ pub enum Action<'a> {
     RequestAttemptGet {
         application_id: &'a Uuid,
     },
+    RequestAttemptRetry {
+        application_id: &'a Uuid,
+    },
     //
     ResponseGet {
         application_id: &'a Uuid,
@@ -509,6 +666,12 @@ impl Action<'_> {
             Self::OrganizationEditRole => "organization:edit_role",
             Self::OrganizationDelete => "organization:delete",
             //
+            Self::CustomRoleList => "custom_role:list",
+            Self::CustomRoleCreate => "custom_role:create",
+            Self::CustomRoleGet => "custom_role:get",
+            Self::CustomRoleEdit { .. } => "custom_role:edit",
+            Self::CustomRoleDelete { .. } => "custom_role:delete",
+            //
             Self::ServiceTokenList => "service_token:list",
             Self::ServiceTokenCreate => "service_token:create",
             Self::ServiceTokenGet => "service_token:get",
@@ -540,14 +703,27 @@ impl Action<'_> {
             Self::SubscriptionGet { .. } => "subscription:get",
             Self::SubscriptionEdit { .. } => "subscription:edit",
             Self::SubscriptionDelete { .. } => "subscription:delete",
+            Self::SubscriptionRotateSecret { .. } => "subscription:rotate_secret",
+            //
+            Self::ClientCertificateList { .. } => "client_certificate:list",
+            Self::ClientCertificateCreate { .. } => "client_certificate:create",
+            Self::ClientCertificateDelete { .. } => "client_certificate:delete",
+            //
+            Self::SigningKeyRotate { .. } => "signing_key:rotate",
             //
             Self::EventList { .. } => "event:list",
             Self::EventGet { .. } => "event:get",
             Self::EventIngest { .. } => "event:ingest",
             Self::EventReplay { .. } => "event:replay",
+            Self::EventCancel { .. } => "event:cancel",
+            //
+            Self::ReplayJobList { .. } => "replay_job:list",
+            Self::ReplayJobGet { .. } => "replay_job:get",
+            Self::ReplayJobCancel { .. } => "replay_job:cancel",
             //
             Self::RequestAttemptList { .. } => "request_attempt:list",
             Self::RequestAttemptGet { .. } => "request_attempt:get",
+            Self::RequestAttemptRetry { .. } => "request_attempt:retry",
             //
             Self::ResponseGet { .. } => "response:get",
             //
@@ -579,6 +755,12 @@ impl Action<'_> {
             Self::OrganizationEditRole => vec![],
             Self::OrganizationDelete => vec![],
             //
+            Self::CustomRoleList => vec![Role::Viewer],
+            Self::CustomRoleCreate => vec![],
+            Self::CustomRoleGet => vec![Role::Viewer],
+            Self::CustomRoleEdit { .. } => vec![],
+            Self::CustomRoleDelete { .. } => vec![],
+            //
             Self::ServiceTokenList => vec![],
             Self::ServiceTokenCreate => vec![],
             Self::ServiceTokenGet => vec![],
@@ -610,14 +792,27 @@ impl Action<'_> {
             Self::SubscriptionGet { .. } => vec![Role::Viewer],
             Self::SubscriptionEdit { .. } => vec![],
             Self::SubscriptionDelete { .. } => vec![],
+            Self::SubscriptionRotateSecret { .. } => vec![],
+            //
+            Self::ClientCertificateList { .. } => vec![Role::Viewer],
+            Self::ClientCertificateCreate { .. } => vec![],
+            Self::ClientCertificateDelete { .. } => vec![],
+            //
+            Self::SigningKeyRotate { .. } => vec![],
             //
             Self::EventList { .. } => vec![Role::Viewer],
             Self::EventGet { .. } => vec![Role::Viewer],
             Self::EventIngest { .. } => vec![],
             Self::EventReplay { .. } => vec![],
+            Self::EventCancel { .. } => vec![],
+            //
+            Self::ReplayJobList { .. } => vec![Role::Viewer],
+            Self::ReplayJobGet { .. } => vec![Role::Viewer],
+            Self::ReplayJobCancel { .. } => vec![],
             //
             Self::RequestAttemptList { .. } => vec![Role::Viewer],
             Self::RequestAttemptGet { .. } => vec![Role::Viewer],
+            Self::RequestAttemptRetry { .. } => vec![],
             //
             Self::ResponseGet { .. } => vec![Role::Viewer],
             //
@@ -665,6 +860,12 @@ impl Action<'_> {
             Self::OrganizationEditRole => None,
             Self::OrganizationDelete => None,
             //
+            Self::CustomRoleList => None,
+            Self::CustomRoleCreate => None,
+            Self::CustomRoleGet => None,
+            Self::CustomRoleEdit { .. } => None,
+            Self::CustomRoleDelete { .. } => None,
+            //
             Self::ServiceTokenList => None,
             Self::ServiceTokenCreate => None,
             Self::ServiceTokenGet => None,
@@ -696,14 +897,27 @@ impl Action<'_> {
             Self::SubscriptionGet { application_id, .. } => Some(**application_id),
             Self::SubscriptionEdit { application_id, .. } => Some(**application_id),
             Self::SubscriptionDelete { application_id, .. } => Some(**application_id),
+            Self::SubscriptionRotateSecret { application_id, .. } => Some(**application_id),
+            //
+            Self::ClientCertificateList { application_id, .. } => Some(**application_id),
+            Self::ClientCertificateCreate { application_id, .. } => Some(**application_id),
+            Self::ClientCertificateDelete { application_id, .. } => Some(**application_id),
+            //
+            Self::SigningKeyRotate { application_id, .. } => Some(**application_id),
             //
             Self::EventList { application_id, .. } => Some(**application_id),
             Self::EventGet { application_id, .. } => Some(**application_id),
             Self::EventIngest { application_id, .. } => Some(**application_id),
             Self::EventReplay { application_id, .. } => Some(**application_id),
+            Self::EventCancel { application_id, .. } => Some(**application_id),
+            //
+            Self::ReplayJobList { application_id, .. } => Some(**application_id),
+            Self::ReplayJobGet { application_id, .. } => Some(**application_id),
+            Self::ReplayJobCancel { application_id, .. } => Some(**application_id),
             //
             Self::RequestAttemptList { application_id, .. } => Some(**application_id),
             Self::RequestAttemptGet { application_id, .. } => Some(**application_id),
+            Self::RequestAttemptRetry { application_id, .. } => Some(**application_id),
             //
             Self::ResponseGet { application_id, .. } => Some(**application_id),
             //
@@ -733,6 +947,18 @@ impl Action<'_> {
             Self::OrganizationEditRole => vec![],
             Self::OrganizationDelete => vec![],
             //
+            Self::CustomRoleList => vec![],
+            Self::CustomRoleCreate => vec![],
+            Self::CustomRoleGet => vec![],
+            Self::CustomRoleEdit { custom_role_id } => vec![fact!(
+                "custom_role_id({custom_role_id})",
+                custom_role_id = *custom_role_id
+            )],
+            Self::CustomRoleDelete { custom_role_id } => vec![fact!(
+                "custom_role_id({custom_role_id})",
+                custom_role_id = *custom_role_id
+            )],
+            //
             Self::ServiceTokenList => vec![],
             Self::ServiceTokenCreate => vec![],
             Self::ServiceTokenGet => vec![],
@@ -790,11 +1016,28 @@ impl Action<'_> {
                 "subscription_id({subscription_id})",
                 subscription_id = *subscription_id
             )],
+            Self::SubscriptionRotateSecret {
+                subscription_id, ..
+            } => vec![fact!(
+                "subscription_id({subscription_id})",
+                subscription_id = *subscription_id
+            )],
+            //
+            Self::ClientCertificateList { .. } => vec![],
+            Self::ClientCertificateCreate { .. } => vec![],
+            Self::ClientCertificateDelete { .. } => vec![],
+            //
+            Self::SigningKeyRotate { .. } => vec![],
             //
             Self::EventList { .. } => vec![],
             Self::EventGet { .. } => vec![],
             Self::EventIngest { .. } => vec![],
             Self::EventReplay { .. } => vec![],
+            Self::EventCancel { .. } => vec![],
+            //
+            Self::ReplayJobList { .. } => vec![],
+            Self::ReplayJobGet { .. } => vec![],
+            Self::ReplayJobCancel { .. } => vec![],
             //
             Self::RequestAttemptList {
                 event_type_names, ..
@@ -803,6 +1046,7 @@ impl Action<'_> {
                 event_type_names,
             )],
             Self::RequestAttemptGet { .. } => vec![],
+            Self::RequestAttemptRetry { .. } => vec![],
             //
             Self::ResponseGet { .. } => vec![],
             //
@@ -816,6 +1060,9 @@ impl Action<'_> {
                 "application_id({application_id})",
                 application_id = application_id
             ));
+            facts.push(fact!("action_scope(\"application\")"));
+        } else {
+            facts.push(fact!("action_scope(\"organization\")"));
         }
 
         for role in self.allowed_roles() {
@@ -902,6 +1149,12 @@ fn authorize(
 
                 role($r) <- type("user_access"), organization_id($id), organization_role($id, $r);
                 valid_role($r) <- role($r), allowed_role($r);
+
+                granted_by_custom_role($cr) <- role("custom"), organization_id($id), organization_custom_role($id, $cr, $actions), action($a), $actions.contains($a);
+                valid_role("custom") <- granted_by_custom_role($cr), action_scope("organization");
+                valid_role("custom") <- granted_by_custom_role($cr), organization_id($id), organization_custom_role_all_applications($id, $cr);
+                valid_role("custom") <- granted_by_custom_role($cr), organization_id($id), application_id($app), organization_custom_role_application($id, $cr, $app);
+
                 valid_role("service") <- type("service_access");
                 valid_role("master") <- type("master_access");
                 check if valid_role($r);
@@ -1645,6 +1898,7 @@ mod tests {
             "first_name",
             "last_name",
             roles,
+            vec![],
         )
         .unwrap();
 
@@ -1690,6 +1944,7 @@ mod tests {
             "",
             "",
             roles,
+            vec![],
         )
         .unwrap();
 
@@ -1761,6 +2016,134 @@ mod tests {
         );
     }
 
+    #[test_log::test]
+    #[test_log(default_log_filter = "trace")]
+    fn user_access_token_authorization_custom_roles() {
+        let keypair = KeyPair::new();
+        let token_id = Uuid::new_v4();
+        let user_id = Uuid::new_v4();
+        let session_id = Uuid::new_v4();
+        let organization_id1 = Uuid::new_v4();
+        let organization_id2 = Uuid::new_v4();
+        let organization_id3 = Uuid::new_v4();
+        let application_id1 = Uuid::new_v4();
+        let application_id2 = Uuid::new_v4();
+        let roles = vec![
+            (organization_id1, "custom".to_owned()),
+            (organization_id2, "custom".to_owned()),
+            (organization_id3, "custom".to_owned()),
+        ];
+        let custom_roles = vec![
+            CustomRoleGrant {
+                organization_id: organization_id1,
+                custom_role_id: Uuid::new_v4(),
+                actions: vec!["test:simple".to_owned(), "test:with_application".to_owned()],
+                application_ids: Some(vec![application_id1]),
+            },
+            CustomRoleGrant {
+                organization_id: organization_id2,
+                custom_role_id: Uuid::new_v4(),
+                actions: vec!["test:with_application".to_owned()],
+                application_ids: None,
+            },
+        ];
+        let RootToken { biscuit, .. } = create_user_access_token(
+            &keypair.private(),
+            token_id,
+            session_id,
+            user_id,
+            "",
+            "",
+            "",
+            roles,
+            custom_roles,
+        )
+        .unwrap();
+
+        let token = dbg!(authorize(
+            &biscuit,
+            Some(organization_id1),
+            Action::TestSimple,
+            MAX_DURATION_TIME,
+            true
+        ))
+        .unwrap();
+        match token {
+            AuthorizedToken::User(AuthorizedUserToken { organizations, .. }) => {
+                assert!(organizations.contains(&(organization_id1, Role::Custom)))
+            }
+            _ => panic!("Expected a user token"),
+        }
+        assert!(
+            dbg!(authorize(
+                &biscuit,
+                Some(organization_id1),
+                Action::TestWithApplication {
+                    application_id: &application_id1
+                },
+                MAX_DURATION_TIME,
+                true
+            ))
+            .is_ok()
+        );
+        assert!(
+            dbg!(authorize(
+                &biscuit,
+                Some(organization_id1),
+                Action::TestWithApplication {
+                    application_id: &application_id2
+                },
+                MAX_DURATION_TIME,
+                true
+            ))
+            .is_err()
+        );
+        assert!(
+            dbg!(authorize(
+                &biscuit,
+                Some(organization_id2),
+                Action::TestSimple,
+                MAX_DURATION_TIME,
+                true
+            ))
+            .is_err()
+        );
+        assert!(
+            dbg!(authorize(
+                &biscuit,
+                Some(organization_id2),
+                Action::TestWithApplication {
+                    application_id: &application_id2
+                },
+                MAX_DURATION_TIME,
+                true
+            ))
+            .is_ok()
+        );
+        assert!(
+            dbg!(authorize(
+                &biscuit,
+                Some(organization_id3),
+                Action::TestSimple,
+                MAX_DURATION_TIME,
+                true
+            ))
+            .is_err()
+        );
+        assert!(
+            dbg!(authorize(
+                &biscuit,
+                Some(organization_id3),
+                Action::TestWithApplication {
+                    application_id: &application_id1
+                },
+                MAX_DURATION_TIME,
+                true
+            ))
+            .is_err()
+        );
+    }
+
     #[test_log::test]
     #[test_log(default_log_filter = "trace")]
     fn refresh_token_authorization() {
@@ -0,0 +1,59 @@
+use actix_web::rt::time::sleep;
+use sqlx::postgres::types::PgInterval;
+use sqlx::{PgPool, query};
+use std::time::{Duration, Instant};
+use thousands::Separable;
+use tokio::sync::Semaphore;
+use tracing::{debug, error, trace};
+
+use crate::humanize::humanize_duration;
+
+const STARTUP_GRACE_PERIOD: Duration = Duration::from_secs(25);
+
+pub async fn periodically_clean_up_idempotency_keys(
+    housekeeping_semaphore: &Semaphore,
+    db: &PgPool,
+    period: Duration,
+    window: Duration,
+) {
+    sleep(STARTUP_GRACE_PERIOD).await;
+
+    match PgInterval::try_from(window) {
+        Ok(window) => {
+            while let Ok(permit) = housekeeping_semaphore.acquire().await {
+                if let Err(e) = clean_up_idempotency_keys(db, &window).await {
+                    error!("Could not clean up expired idempotency keys: {e}");
+                }
+                drop(permit);
+
+                sleep(period).await;
+            }
+        }
+        Err(e) => {
+            error!("Could not convert idempotency window ({window:?}) to a PG interval: {e}")
+        }
+    }
+}
+
+async fn clean_up_idempotency_keys(db: &PgPool, window: &PgInterval) -> Result<(), sqlx::Error> {
+    trace!("Cleaning up expired idempotency keys...");
+    let start = Instant::now();
+
+    // Expired keys cannot be used to deduplicate events anymore, so there is no point in keeping them
+    let res = query!(
+        "
+            DELETE FROM event.idempotency_key
+            WHERE created_at + $1 < statement_timestamp()
+        ",
+        window,
+    )
+    .execute(db)
+    .await?;
+
+    debug!(
+        "Cleaned up {} expired idempotency keys in {}",
+        res.rows_affected().separate_with_commas(),
+        humanize_duration(start.elapsed()),
+    );
+    Ok(())
+}
@@ -66,6 +66,17 @@ pub enum Mail {
         events_per_days_limit: i32,
         extra_variables: Vec<(String, String)>,
     },
+    /// Sent when an output worker disabled a subscription because its deliveries kept failing
+    ///
+    /// It is only sent to the members of the organization who can enable the subscription again: editors, and members whose custom role grants `subscription:edit` on its application. Viewers are not notified.
+    SubscriptionDisabled {
+        recipient_first_name: Option<String>,
+        application_name: String,
+        subscription_description: String,
+        target_url: String,
+        disabled_reason: String,
+        url: Url,
+    },
 }
 
 // Design tokens — sourced from www.hook0.com brand:
@@ -176,6 +187,16 @@ fn with_matomo(url: &Url, campaign: &str) -> String {
     u.to_string()
 }
 
+/// Escape user-provided values before substituting them in a template.
+fn escape_html(value: &str) -> String {
+    value
+        .replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+        .replace('\'', "&#39;")
+}
+
 impl Mail {
     pub fn template(&self) -> &'static str {
         match self {
@@ -188,6 +209,9 @@ impl Mail {
             Mail::QuotaEventsPerDayReached { .. } => {
                 include_str!("mail_templates/quotas/events_per_day_reached.mjml")
             }
+            Mail::SubscriptionDisabled { .. } => {
+                include_str!("mail_templates/subscription_disabled.mjml")
+            }
         }
     }
 
@@ -203,6 +227,9 @@ impl Mail {
             Mail::QuotaEventsPerDayReached { .. } => {
                 "Daily event limit reached. Events paused.".to_owned()
             }
+            Mail::SubscriptionDisabled { .. } => {
+                "A subscription was disabled after repeated delivery failures".to_owned()
+            }
         }
     }
 
@@ -223,6 +250,9 @@ impl Mail {
             Mail::QuotaEventsPerDayReached { .. } => {
                 "Hook0 will resume at the next daily reset, or as soon as you upgrade."
             }
+            Mail::SubscriptionDisabled { .. } => {
+                "Its endpoint kept failing, so Hook0 stopped sending webhooks to it."
+            }
         }
     }
 
@@ -235,6 +265,7 @@ impl Mail {
             Mail::Welcome { .. } => "welcome",
             Mail::QuotaEventsPerDayWarning { .. } => "quota_warning",
             Mail::QuotaEventsPerDayReached { .. } => "quota_reached",
+            Mail::SubscriptionDisabled { .. } => "subscription_disabled",
         }
     }
 
@@ -272,6 +303,10 @@ impl Mail {
             | Mail::QuotaEventsPerDayReached {
                 recipient_first_name,
                 ..
+            }
+            | Mail::SubscriptionDisabled {
+                recipient_first_name,
+                ..
             } => recipient_first_name.as_deref(),
         }
     }
@@ -327,6 +362,21 @@ impl Mail {
                 vars.extend(extra_variables.clone());
                 vars
             }
+            Mail::SubscriptionDisabled {
+                application_name,
+                subscription_description,
+                target_url,
+                disabled_reason,
+                ..
+            } => vec![
+                ("application_name".to_owned(), escape_html(application_name)),
+                (
+                    "subscription_description".to_owned(),
+                    escape_html(subscription_description),
+                ),
+                ("target_url".to_owned(), escape_html(target_url)),
+                ("disabled_reason".to_owned(), escape_html(disabled_reason)),
+            ],
         }
     }
 
@@ -338,6 +388,7 @@ impl Mail {
             Mail::Welcome { .. } => vec![],
             Mail::QuotaEventsPerDayWarning { .. } => vec![],
             Mail::QuotaEventsPerDayReached { .. } => vec![],
+            Mail::SubscriptionDisabled { url, .. } => vec![("url".to_owned(), url.clone())],
         }
     }
 
@@ -593,6 +644,17 @@ mod tests {
             },
             quota_warning,
             quota_reached,
+            Mail::SubscriptionDisabled {
+                recipient_first_name: Some("Sarah".to_owned()),
+                application_name: "Billing".to_owned(),
+                subscription_description: "Invoices to ERP".to_owned(),
+                target_url: "https://erp.example.com/webhooks".to_owned(),
+                disabled_reason: "delivery failed 50 times in a row".to_owned(),
+                url: Url::from_str(
+                    "https://app.hook0.com/organizations/x/applications/y/subscriptions/z",
+                )
+                .unwrap(),
+            },
         ]
     }
 
@@ -843,6 +905,23 @@ mod tests {
         }
     }
 
+    /// Subscription descriptions and target URLs are user-provided, so they
+    /// must not be able to inject markup in the rendered mail.
+    #[test]
+    fn subscription_disabled_escapes_user_provided_values() {
+        let mail = Mail::SubscriptionDisabled {
+            recipient_first_name: Some("Sarah".to_owned()),
+            application_name: "Billing".to_owned(),
+            subscription_description: "<b>ERP</b> & co".to_owned(),
+            target_url: "https://erp.example.com/webhooks".to_owned(),
+            disabled_reason: "delivery failed 50 times in a row".to_owned(),
+            url: Url::from_str("https://app.hook0.com/organizations/x").unwrap(),
+        };
+        let html = render(&mail);
+        assert!(!html.contains("<b>ERP</b>"));
+        assert!(html.contains("&lt;b&gt;ERP&lt;/b&gt; &amp; co"));
+    }
+
     /// Test #14 — with_matomo preserves pre-existing query parameters
     /// (critical for verify/reset URLs that already carry a token).
     #[test]
@@ -29,13 +29,17 @@ use url::Url;
 use uuid::Uuid;
 
 mod cloudflare_turnstile;
+mod credentials_encryption;
+mod disabled_subscriptions_notifications;
 mod expired_tokens_cleanup;
+mod extractor_idempotency_key;
 mod extractor_user_ip;
 mod google_ads;
 mod handlers;
 mod hook0_client;
 mod humanize;
 mod iam;
+mod idempotency_keys_cleanup;
 mod mailer;
 mod materialized_views;
 mod middleware_biscuit;
@@ -50,6 +54,7 @@ mod pagination;
 mod problems;
 mod quotas;
 mod rate_limiting;
+mod replay_jobs;
 mod soft_deleted_applications_cleanup;
 mod unverified_users_cleanup;
 mod validators;
@@ -444,6 +449,10 @@ struct Config {
     #[clap(long, env, value_parser = humantime::parse_duration, default_value = "7m")]
     object_storage_cleanup_operation_timeout: Duration,
 
+    /// [Housekeeping] Duration to wait between checks for subscriptions that were automatically disabled by output workers (organization editors are notified by email)
+    #[clap(long, env, value_parser = humantime::parse_duration, default_value = "1m")]
+    disabled_subscriptions_notifications_period: Duration,
+
     /// [Housekeeping] Duration to wait between expired tokens cleanups
     #[clap(long, env, value_parser = humantime::parse_duration, default_value = "1h")]
     expired_tokens_cleanup_period: Duration,
@@ -456,6 +465,10 @@ struct Config {
     #[clap(long, env, default_value = "false")]
     expired_tokens_cleanup_report_and_delete: bool,
 
+    /// [Housekeeping] Duration to wait between expired idempotency keys cleanups
+    #[clap(long, env, value_parser = humantime::parse_duration, default_value = "1h")]
+    idempotency_keys_cleanup_period: Duration,
+
     /// [Housekeeping] If true, unverified users will be remove from database after a while
     #[clap(long, env, default_value = "false")]
     enable_unverified_users_cleanup: bool,
@@ -484,6 +497,43 @@ struct Config {
     #[clap(long, env, value_parser = humantime::parse_duration, default_value = "30d")]
     soft_deleted_applications_cleanup_grace_period: Duration,
 
+    /// [Replay Jobs] Duration to wait between checks for replay jobs to process
+    #[clap(long, env, value_parser = humantime::parse_duration, default_value = "10s")]
+    replay_jobs_period: Duration,
+
+    /// [Replay Jobs] Number of events replayed in each batch
+    #[clap(long, env, default_value = "100")]
+    replay_jobs_batch_size: u16,
+
+    /// [Replay Jobs] Duration to wait between two batches of replayed events (throttles replays so that output workers are not flooded)
+    #[clap(long, env, value_parser = humantime::parse_duration, default_value = "1s")]
+    replay_jobs_batch_interval: Duration,
+
+    /// [Target Credentials] Key used to encrypt the credentials that subscriptions use to authenticate to their targets (private keys of client certificates and OAuth2 client secrets) the private keys that sign webhooks, and the per-application keys that encrypt subscription secrets and sensitive target headers; client certificates, OAuth2 authentication and signing keys cannot be used if it is not set, and secrets and headers are then stored in plaintext; it must be a 32-byte key encoded in base64 (for example generated with `openssl rand -base64 32`); output workers must be given the same key
+    #[clap(long, env, hide_env_values = true)]
+    target_credentials_encryption_key: Option<credentials_encryption::EncryptionKey>,
+
+    /// [Target Credentials] New key to encrypt target credentials with; if set, the API re-encrypts everything that is encrypted with the current key and exits without serving requests; the API and output workers must then be restarted with the new key
+    #[clap(
+        long,
+        env,
+        hide_env_values = true,
+        requires = "target_credentials_encryption_key"
+    )]
+    new_target_credentials_encryption_key: Option<credentials_encryption::EncryptionKey>,
+
+    /// [Web Server] Duration during which ingesting an event with an already used idempotency key returns the original event instead of ingesting a new one
+    #[clap(long, env, value_parser = humantime::parse_duration, default_value = "24h")]
+    idempotency_window: Duration,
+
+    /// [Web Server] Maximum number of events that can be sent in a single batch ingestion request
+    #[clap(long, env, value_parser = clap::value_parser!(u16).range(1..), default_value_t = 1000)]
+    max_events_per_batch: u16,
+
+    /// [Web Server] Maximum size (in MiB) of the body of a batch ingestion request
+    #[clap(long, env, default_value_t = 32)]
+    max_event_batch_size_in_mib: u16,
+
     /// [Web Server] If true, the secured HTTP headers will be enabled
     #[clap(long, env, default_value = "true")]
     enable_security_headers: bool,
@@ -724,6 +774,9 @@ pub struct State {
     health_check_timeout: Duration,
     max_authorization_time: Duration,
     debug_authorizer: bool,
+    idempotency_window: Duration,
+    max_events_per_batch: u16,
+    target_credentials_encryption_key: Option<credentials_encryption::EncryptionKey>,
     enable_quota_enforcement: bool,
     matomo_url: Option<Url>,
     matomo_site_id: Option<u16>,
@@ -909,6 +962,16 @@ async fn main() -> anyhow::Result<()> {
                 .await?;
         }
 
+        // Rotate the key that encrypts target credentials instead of starting, if requested
+        if let (Some(current_key), Some(new_key)) = (
+            &config.target_credentials_encryption_key,
+            &config.new_target_credentials_encryption_key,
+        ) {
+            credentials_encryption::rotate_encryption_key(&housekeeping_pool, current_key, new_key)
+                .await?;
+            return Ok(());
+        }
+
         // Create Pulsar client
         let pulsar_config = if let (
             Some(pulsar_binary_url),
@@ -1175,6 +1238,19 @@ async fn main() -> anyhow::Result<()> {
             .await;
         });
 
+        // Spawn task to clean up expired idempotency keys
+        let clean_idempotency_keys_db = housekeeping_pool.clone();
+        let clean_idempotency_keys_semaphore = housekeeping_semaphore.clone();
+        actix_web::rt::spawn(async move {
+            idempotency_keys_cleanup::periodically_clean_up_idempotency_keys(
+                &clean_idempotency_keys_semaphore,
+                &clean_idempotency_keys_db,
+                config.idempotency_keys_cleanup_period,
+                config.idempotency_window,
+            )
+            .await;
+        });
+
         // Spawn task to clean unverified users if enabled
         if config.enable_unverified_users_cleanup {
             let clean_unverified_users_db = housekeeping_pool.clone();
@@ -1191,6 +1267,20 @@ async fn main() -> anyhow::Result<()> {
             });
         }
 
+        // Spawn task to encrypt the credentials that were stored in plaintext before an encryption key was configured
+        if let Some(encryption_key) = config.target_credentials_encryption_key.clone() {
+            let encryption_db = housekeeping_pool.clone();
+            let encryption_semaphore = housekeeping_semaphore.clone();
+            actix_web::rt::spawn(async move {
+                credentials_encryption::encrypt_plaintext_credentials(
+                    &encryption_semaphore,
+                    &encryption_db,
+                    &encryption_key,
+                )
+                .await;
+            });
+        }
+
         // Spawn task to clean up object storage
         // No housekeeping semaphore here because this task is not database-intensive and should be able to run for a long time without keeping other tasks from running
         if let Some(os) = &object_storage_config
@@ -1235,6 +1325,20 @@ async fn main() -> anyhow::Result<()> {
         .await
         .expect("Could not initialize mailer; check SMTP configuration");
 
+        // Spawn task to notify organization members about automatically disabled subscriptions
+        let notifications_db = housekeeping_pool.clone();
+        let notifications_mailer = mailer.clone();
+        let notifications_app_url = config.app_url.clone();
+        actix_web::rt::spawn(async move {
+            disabled_subscriptions_notifications::periodically_notify_disabled_subscriptions(
+                &notifications_db,
+                &notifications_mailer,
+                &notifications_app_url,
+                config.disabled_subscriptions_notifications_period,
+            )
+            .await;
+        });
+
         // Initialize state
         let initial_state = State {
             db: pool,
@@ -1272,6 +1376,9 @@ async fn main() -> anyhow::Result<()> {
             health_check_timeout: config.health_check_timeout,
             max_authorization_time: config.max_authorization_time,
             debug_authorizer: config.debug_authorizer,
+            idempotency_window: config.idempotency_window,
+            max_events_per_batch: config.max_events_per_batch,
+            target_credentials_encryption_key: config.target_credentials_encryption_key,
             enable_quota_enforcement: config.enable_quota_enforcement,
             matomo_url: config.matomo_url,
             matomo_site_id: config.matomo_site_id,
@@ -1286,6 +1393,18 @@ async fn main() -> anyhow::Result<()> {
             google_ads: google_ads_client,
         };
 
+        // Spawn task to process replay jobs
+        let replay_jobs_state = initial_state.clone();
+        actix_web::rt::spawn(async move {
+            replay_jobs::periodically_process_replay_jobs(
+                &replay_jobs_state,
+                config.replay_jobs_period,
+                config.replay_jobs_batch_size,
+                config.replay_jobs_batch_interval,
+            )
+            .await;
+        });
+
         // Run web server
         let webapp_path = config.webapp_path.clone();
         let app_url = config.app_url;
@@ -1451,6 +1570,12 @@ async fn main() -> anyhow::Result<()> {
                                     .route(web::post().to(handlers::registrations::register)),
                             ),
                         )
+                        .service(
+                            web::scope("/jwks").service(
+                                web::resource("/{application_id}")
+                                    .route(web::get().to(handlers::signing_keys::jwks)),
+                            ),
+                        )
                         // with authentication
                         .service(
                             web::scope("/organizations")
@@ -1517,6 +1642,11 @@ async fn main() -> anyhow::Result<()> {
                                     web::resource("/{event_type_name}")
                                         .route(web::get().to(handlers::event_types::get))
                                         .route(web::delete().to(handlers::event_types::delete)),
+                                )
+                                .service(
+                                    web::resource("/{event_type_name}/schemas")
+                                        .route(web::get().to(handlers::event_types::list_schemas))
+                                        .route(web::post().to(handlers::event_types::add_schema)),
                                 ),
                         )
                         .service(
@@ -1541,6 +1671,22 @@ async fn main() -> anyhow::Result<()> {
                             #[cfg(not(feature = "application-secret-compatibility"))]
                             web::resource("/"),
                         )
+                        .service(
+                            web::scope("/custom_roles")
+                                .wrap(Compat::new(rate_limiters.token())) // Middleware order is counter intuitive: this is executed second
+                                .wrap(biscuit_auth.clone()) // Middleware order is counter intuitive: this is executed first
+                                .service(
+                                    web::resource("")
+                                        .route(web::get().to(handlers::custom_roles::list))
+                                        .route(web::post().to(handlers::custom_roles::create)),
+                                )
+                                .service(
+                                    web::resource("/{custom_role_id}")
+                                        .route(web::get().to(handlers::custom_roles::get))
+                                        .route(web::put().to(handlers::custom_roles::edit))
+                                        .route(web::delete().to(handlers::custom_roles::delete)),
+                                ),
+                        )
                         .service(
                             web::scope("/service_token")
                                 .wrap(Compat::new(rate_limiters.token())) // Middleware order is counter intuitive: this is executed second
@@ -1564,6 +1710,10 @@ async fn main() -> anyhow::Result<()> {
                                 .service(
                                     web::resource("").route(web::get().to(handlers::events::list)),
                                 )
+                                .service(
+                                    web::resource("/replay")
+                                        .route(web::post().to(handlers::replay_jobs::create)),
+                                )
                                 .service(
                                     web::resource("/{event_id}")
                                         .route(web::get().to(handlers::events::get)),
@@ -1571,6 +1721,10 @@ async fn main() -> anyhow::Result<()> {
                                 .service(
                                     web::resource("/{event_id}/replay")
                                         .route(web::post().to(handlers::events::replay)),
+                                )
+                                .service(
+                                    web::resource("/{event_id}/cancel")
+                                        .route(web::post().to(handlers::events::cancel)),
                                 ),
                         )
                         .service(
@@ -1580,6 +1734,42 @@ async fn main() -> anyhow::Result<()> {
                                 .service(
                                     web::resource("")
                                         .route(web::post().to(handlers::events::ingest)),
+                                )
+                                .service(
+                                    web::resource("/batch")
+                                        .app_data(
+                                            web::JsonConfig::default()
+                                                .limit(
+                                                    usize::from(config.max_event_batch_size_in_mib)
+                                                        * 1024
+                                                        * 1024,
+                                                )
+                                                .error_handler(|e, _req| {
+                                                    let problem =
+                                                        problems::Hook0Problem::JsonPayload(
+                                                            problems::JsonPayloadProblem::from(e),
+                                                        );
+                                                    actix_web::error::Error::from(problem)
+                                                }),
+                                        )
+                                        .route(web::post().to(handlers::events::ingest_batch)),
+                                ),
+                        )
+                        .service(
+                            web::scope("/replay_jobs")
+                                .wrap(Compat::new(rate_limiters.token())) // Middleware order is counter intuitive: this is executed second
+                                .wrap(biscuit_auth.clone()) // Middleware order is counter intuitive: this is executed first
+                                .service(
+                                    web::resource("")
+                                        .route(web::get().to(handlers::replay_jobs::list)),
+                                )
+                                .service(
+                                    web::resource("/{replay_job_id}")
+                                        .route(web::get().to(handlers::replay_jobs::get)),
+                                )
+                                .service(
+                                    web::resource("/{replay_job_id}/cancel")
+                                        .route(web::post().to(handlers::replay_jobs::cancel)),
                                 ),
                         )
                         .service(
@@ -1606,11 +1796,45 @@ async fn main() -> anyhow::Result<()> {
                                         .route(web::get().to(handlers::subscriptions::list))
                                         .route(web::post().to(handlers::subscriptions::create)),
                                 )
+                                .service(web::resource("/payload-transformation-preview").route(
+                                    web::post().to(
+                                        handlers::subscriptions::preview_payload_transformation,
+                                    ),
+                                ))
                                 .service(
                                     web::resource("/{subscription_id}")
                                         .route(web::get().to(handlers::subscriptions::get))
                                         .route(web::put().to(handlers::subscriptions::edit))
                                         .route(web::delete().to(handlers::subscriptions::delete)),
+                                )
+                                .service(
+                                    web::resource("/{subscription_id}/rotate_secret").route(
+                                        web::post().to(handlers::subscriptions::rotate_secret),
+                                    ),
+                                ),
+                        )
+                        .service(
+                            web::scope("/client_certificates")
+                                .wrap(Compat::new(rate_limiters.token())) // Middleware order is counter intuitive: this is executed second
+                                .wrap(biscuit_auth.clone()) // Middleware order is counter intuitive: this is executed first
+                                .service(
+                                    web::resource("")
+                                        .route(web::get().to(handlers::client_certificates::list))
+                                        .route(
+                                            web::post().to(handlers::client_certificates::create),
+                                        ),
+                                )
+                                .service(web::resource("/{client_certificate_id}").route(
+                                    web::delete().to(handlers::client_certificates::delete),
+                                )),
+                        )
+                        .service(
+                            web::scope("/signing_keys")
+                                .wrap(Compat::new(rate_limiters.token())) // Middleware order is counter intuitive: this is executed second
+                                .wrap(biscuit_auth.clone()) // Middleware order is counter intuitive: this is executed first
+                                .service(
+                                    web::resource("/rotate")
+                                        .route(web::post().to(handlers::signing_keys::rotate)),
                                 ),
                         )
                         .service(
@@ -1624,6 +1848,10 @@ async fn main() -> anyhow::Result<()> {
                                 .service(
                                     web::resource("/{request_attempt_id}")
                                         .route(web::get().to(handlers::request_attempts::get)),
+                                )
+                                .service(
+                                    web::resource("/{request_attempt_id}/retry")
+                                        .route(web::post().to(handlers::request_attempts::retry)),
                                 ),
                         )
                         .service(
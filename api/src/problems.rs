@@ -10,6 +10,7 @@ use std::fmt::Display;
 use strum::{EnumIter, VariantNames};
 use tracing::{error, warn};
 
+use crate::credentials_encryption::CredentialsEncryptionError;
 use crate::handlers::events::PayloadContentType;
 use crate::iam::Role;
 use crate::quotas::QuotaValue;
@@ -35,16 +36,43 @@ pub enum Hook0Problem {
     ApplicationNameMissing,
 
     InvalidRole,
+    CustomRoleAlreadyExist,
+    CustomRoleInvalid(String),
+    CustomRoleDoesNotExist,
+    CustomRoleInUse,
 
     EventTypeAlreadyExist,
     EventTypeDoesNotExist,
+    EventTypeInvalidSchema(String),
 
     UnauthorizedWorkers(Vec<String>),
 
+    ClientCertificatesDisabled,
+    ClientCertificateInvalid(String),
+    ClientCertificateDoesNotExist,
+    ClientCertificateInUse,
+
+    TargetAuthenticationDisabled,
+    TargetAuthenticationClientSecretMissing,
+
+    SubscriptionBatchingDisabled,
+
+    SigningKeysDisabled,
+
     EventAlreadyIngested,
+    EventBatchInvalidSize(u16),
+    EventDeliverAtBeyondRetention(QuotaValue),
+    EventNotCancellable,
+    EventDeliveryCancelled,
+    InvalidIdempotencyKey,
     EventInvalidPayloadContentType,
     EventInvalidBase64Payload(String),
     EventInvalidJsonPayload(String),
+    EventPayloadSchemaMismatch(String),
+
+    PayloadTransformationFailed(String),
+
+    RequestAttemptNotRetryable,
 
     LabelsAmbiguity,
 
@@ -98,6 +126,12 @@ impl From<sqlx::Error> for Hook0Problem {
                     Some("user__organization_pkey") => {
                         Hook0Problem::InvitedUserAlreadyInOrganization
                     }
+                    Some("custom_role_organization__id_name_key") => {
+                        Hook0Problem::CustomRoleAlreadyExist
+                    }
+                    Some("target_http_oauth2_check") => {
+                        Hook0Problem::TargetAuthenticationClientSecretMissing
+                    }
                     constraint => {
                         error!(
                             "Database error (failed constraint = {}): {}",
@@ -120,6 +154,18 @@ impl From<sqlx::Error> for Hook0Problem {
     }
 }
 
+impl From<CredentialsEncryptionError> for Hook0Problem {
+    fn from(err: CredentialsEncryptionError) -> Hook0Problem {
+        match err {
+            CredentialsEncryptionError::Database(e) => e.into(),
+            CredentialsEncryptionError::Decryption(_) => {
+                error!("{err}");
+                Hook0Problem::InternalServerError
+            }
+        }
+    }
+}
+
 impl From<lettre::error::Error> for Hook0Problem {
     fn from(err: lettre::error::Error) -> Hook0Problem {
         warn!("{err}");
@@ -284,6 +330,37 @@ impl From<Hook0Problem> for Problem {
                     status: StatusCode::BAD_REQUEST,
                 }
             },
+            Hook0Problem::CustomRoleAlreadyExist => Problem {
+                id: Hook0Problem::CustomRoleAlreadyExist,
+                title: "This custom role already exist",
+                detail: "A custom role with this name is already present in the organization.".into(),
+                validation: None,
+                status: StatusCode::CONFLICT,
+            },
+            Hook0Problem::CustomRoleInvalid(e) => {
+                let detail = format!("Custom role is not valid: {e}");
+                Problem {
+                    id: Hook0Problem::CustomRoleInvalid(e),
+                    title: "Invalid custom role",
+                    detail: detail.into(),
+                    validation: None,
+                    status: StatusCode::BAD_REQUEST,
+                }
+            },
+            Hook0Problem::CustomRoleDoesNotExist => Problem {
+                id: Hook0Problem::CustomRoleDoesNotExist,
+                title: "Invalid custom role",
+                detail: "Custom role does not exist or belongs to another organization. A custom role must be provided if and only if the role is 'custom'.".into(),
+                validation: None,
+                status: StatusCode::BAD_REQUEST,
+            },
+            Hook0Problem::CustomRoleInUse => Problem {
+                id: Hook0Problem::CustomRoleInUse,
+                title: "This custom role is assigned to members",
+                detail: "Custom roles cannot be deleted while members of the organization have them. Change the role of these members first.".into(),
+                validation: None,
+                status: StatusCode::CONFLICT,
+            },
 
             Hook0Problem::EventTypeAlreadyExist => Problem {
                 id: Hook0Problem::EventTypeAlreadyExist,
@@ -292,6 +369,16 @@ impl From<Hook0Problem> for Problem {
                 validation: None,
                 status: StatusCode::CONFLICT,
             },
+            Hook0Problem::EventTypeInvalidSchema(e) => {
+                let detail = format!("Event type schema is not a valid JSON Schema: {e}");
+                Problem {
+                    id: Hook0Problem::EventTypeInvalidSchema(e),
+                    title: "Invalid event type schema",
+                    detail: detail.into(),
+                    validation: None,
+                    status: StatusCode::BAD_REQUEST,
+                }
+            },
             Hook0Problem::EventTypeDoesNotExist => Problem {
                 id: Hook0Problem::EventTypeDoesNotExist,
                 title: "Invalid event type",
@@ -311,6 +398,75 @@ impl From<Hook0Problem> for Problem {
                 }
             },
 
+            Hook0Problem::ClientCertificatesDisabled => Problem {
+                id: Hook0Problem::ClientCertificatesDisabled,
+                title: "Client certificates are disabled",
+                detail: "This instance was not configured with a key to encrypt client certificates.".into(),
+                validation: None,
+                status: StatusCode::BAD_REQUEST,
+            },
+            Hook0Problem::ClientCertificateInvalid(e) => {
+                let detail = format!("Client certificate or private key is not valid: {e}");
+                Problem {
+                    id: Hook0Problem::ClientCertificateInvalid(e),
+                    title: "Invalid client certificate",
+                    detail: detail.into(),
+                    validation: None,
+                    status: StatusCode::BAD_REQUEST,
+                }
+            },
+            Hook0Problem::ClientCertificateDoesNotExist => Problem {
+                id: Hook0Problem::ClientCertificateDoesNotExist,
+                title: "Invalid client certificate",
+                detail: "Client certificate does not exist or belongs to another application.".into(),
+                validation: None,
+                status: StatusCode::BAD_REQUEST,
+            },
+            Hook0Problem::ClientCertificateInUse => Problem {
+                id: Hook0Problem::ClientCertificateInUse,
+                title: "This client certificate is used by subscriptions",
+                detail: "Client certificates cannot be deleted while subscriptions use them. Update these subscriptions first.".into(),
+                validation: None,
+                status: StatusCode::CONFLICT,
+            },
+
+            Hook0Problem::TargetAuthenticationDisabled => Problem {
+                id: Hook0Problem::TargetAuthenticationDisabled,
+                title: "OAuth2 authentication of targets is disabled",
+                detail: "This instance was not configured with a key to encrypt the client secrets of targets.".into(),
+                validation: None,
+                status: StatusCode::BAD_REQUEST,
+            },
+            Hook0Problem::SubscriptionBatchingDisabled => Problem {
+                id: Hook0Problem::SubscriptionBatchingDisabled,
+                title: "Batching of webhooks is disabled",
+                detail: "This instance sends request attempts to output workers through Pulsar, which delivers events one by one. Subscriptions cannot set 'batch_max_size' or 'batch_max_wait_ms'.".into(),
+                validation: None,
+                status: StatusCode::BAD_REQUEST,
+            },
+            Hook0Problem::SigningKeysDisabled => Problem {
+                id: Hook0Problem::SigningKeysDisabled,
+                title: "Signing keys are disabled",
+                detail: "This instance was not configured with a key to encrypt the private keys used to sign webhooks.".into(),
+                validation: None,
+                status: StatusCode::BAD_REQUEST,
+            },
+            Hook0Problem::TargetAuthenticationClientSecretMissing => Problem {
+                id: Hook0Problem::TargetAuthenticationClientSecretMissing,
+                title: "Missing client secret",
+                detail: "A client secret is required to add OAuth2 authentication to a target or to change its token URL or client ID.".into(),
+                validation: None,
+                status: StatusCode::BAD_REQUEST,
+            },
+
+            Hook0Problem::RequestAttemptNotRetryable => Problem {
+                id: Hook0Problem::RequestAttemptNotRetryable,
+                title: "This request attempt cannot be retried",
+                detail: "Only failed request attempts that will not be retried automatically can be retried manually, and only if their subscription is enabled and the delivery of their event was not cancelled.".into(),
+                validation: None,
+                status: StatusCode::CONFLICT,
+            },
+
             Hook0Problem::EventAlreadyIngested => Problem {
                 id: Hook0Problem::EventAlreadyIngested,
                 title: "Event already Ingested",
@@ -318,6 +474,47 @@ impl From<Hook0Problem> for Problem {
                 validation: None,
                 status: StatusCode::CONFLICT,
             },
+            Hook0Problem::EventBatchInvalidSize(max) => {
+                let detail = format!("A batch must contain between 1 and {max} events.");
+                Problem {
+                    id: Hook0Problem::EventBatchInvalidSize(max),
+                    title: "Invalid event batch size",
+                    detail: detail.into(),
+                    validation: None,
+                    status: StatusCode::BAD_REQUEST,
+                }
+            },
+            Hook0Problem::EventDeliverAtBeyondRetention(days) => {
+                let detail = format!("Events can only be held until the end of their retention period, which is {days} days for this application.");
+                Problem {
+                    id: Hook0Problem::EventDeliverAtBeyondRetention(days),
+                    title: "Event delivery date is too far in the future",
+                    detail: detail.into(),
+                    validation: None,
+                    status: StatusCode::BAD_REQUEST,
+                }
+            },
+            Hook0Problem::EventNotCancellable => Problem {
+                id: Hook0Problem::EventNotCancellable,
+                title: "The delivery of this event cannot be cancelled",
+                detail: "Only events that are held or that have request attempts that are not done yet can be cancelled, and only once.".into(),
+                validation: None,
+                status: StatusCode::CONFLICT,
+            },
+            Hook0Problem::EventDeliveryCancelled => Problem {
+                id: Hook0Problem::EventDeliveryCancelled,
+                title: "The delivery of this event was cancelled",
+                detail: "Events whose delivery was cancelled cannot be replayed, and their request attempts cannot be retried.".into(),
+                validation: None,
+                status: StatusCode::CONFLICT,
+            },
+            Hook0Problem::InvalidIdempotencyKey => Problem {
+                id: Hook0Problem::InvalidIdempotencyKey,
+                title: "Invalid idempotency key",
+                detail: "Idempotency keys must contain between 1 and 255 visible ASCII characters. If both the `Idempotency-Key` header and the `idempotency_key` property are set, they must be equal.".into(),
+                validation: None,
+                status: StatusCode::BAD_REQUEST,
+            },
             Hook0Problem::EventInvalidPayloadContentType => {
                 let detail = format!("The specified event payload content type is not handled. Valid content types are: {}", PayloadContentType::VARIANTS.join(", "));
                 Problem {
@@ -348,6 +545,26 @@ impl From<Hook0Problem> for Problem {
                     status: StatusCode::BAD_REQUEST,
                 }
             },
+            Hook0Problem::EventPayloadSchemaMismatch(e) => {
+                let detail = format!("Event payload does not match the schema of its event type: {e}.");
+                Problem {
+                    id: Hook0Problem::EventPayloadSchemaMismatch(e),
+                    title: "Event payload does not match schema",
+                    detail: detail.into(),
+                    validation: None,
+                    status: StatusCode::BAD_REQUEST,
+                }
+            },
+            Hook0Problem::PayloadTransformationFailed(e) => {
+                let detail = format!("Payload transformation could not be applied to this event: {e}");
+                Problem {
+                    id: Hook0Problem::PayloadTransformationFailed(e),
+                    title: "Payload transformation failed",
+                    detail: detail.into(),
+                    validation: None,
+                    status: StatusCode::BAD_REQUEST,
+                }
+            },
             Hook0Problem::LabelsAmbiguity => Problem {
                 id: Hook0Problem::LabelsAmbiguity,
                 title: "Ambiguous labels specification",
@@ -0,0 +1,292 @@
+use actix_web::rt::time::sleep;
+use chrono::{DateTime, Utc};
+use sqlx::{Acquire, query, query_as, query_scalar};
+use std::time::Duration;
+use tracing::{error, info, trace, warn};
+use uuid::Uuid;
+
+use crate::State;
+use crate::handlers::events::replay_event_in_transaction;
+use crate::opentelemetry::report_replayed_events;
+use crate::problems::Problem;
+
+const STARTUP_GRACE_PERIOD: Duration = Duration::from_secs(20);
+
+pub async fn periodically_process_replay_jobs(
+    state: &State,
+    period: Duration,
+    batch_size: u16,
+    batch_interval: Duration,
+) {
+    sleep(STARTUP_GRACE_PERIOD).await;
+
+    loop {
+        match process_replay_job_batch(state, i64::from(batch_size)).await {
+            // Throttle replays so that output workers are not flooded
+            Ok(true) => sleep(batch_interval).await,
+            Ok(false) => sleep(period).await,
+            Err(e) => {
+                error!("Could not process replay jobs: {e}");
+                sleep(period).await;
+            }
+        }
+    }
+}
+
+/// Replay the next batch of events of the unfinished replay job that was processed the longest time ago
+///
+/// Returns `false` if there was no replay job to process.
+async fn process_replay_job_batch(state: &State, batch_size: i64) -> Result<bool, sqlx::Error> {
+    trace!("Looking for replay jobs to process...");
+
+    let mut tx = state.db.begin().await?;
+
+    #[allow(non_snake_case)]
+    struct PickedReplayJob {
+        replay_job__id: Uuid,
+        application__id: Uuid,
+        min_received_at: DateTime<Utc>,
+        max_received_at: DateTime<Utc>,
+        event_type_names: Vec<String>,
+        subscription__id: Option<Uuid>,
+        only_failed: bool,
+        is_pending: bool,
+        cursor_received_at: Option<DateTime<Utc>>,
+        cursor_event__id: Option<Uuid>,
+    }
+    // Locking the job prevents other API instances from starting it or selecting its next batch concurrently
+    let job = query_as!(
+        PickedReplayJob,
+        r#"
+            SELECT replay_job__id, application__id, min_received_at, max_received_at, event_type_names, subscription__id, only_failed, status = 'pending' AS "is_pending!", cursor_received_at, cursor_event__id
+            FROM event.replay_job
+            WHERE status IN ('pending', 'running')
+            ORDER BY picked_at NULLS FIRST, created_at
+            LIMIT 1
+            FOR UPDATE SKIP LOCKED
+        "#,
+    )
+    .fetch_optional(&mut *tx)
+    .await?;
+
+    let Some(job) = job else {
+        tx.commit().await?;
+        return Ok(false);
+    };
+
+    if job.is_pending {
+        let total_events = query_scalar!(
+            r#"
+                SELECT COUNT(*) AS "count!"
+                FROM event.event AS e
+                WHERE e.application__id = $1
+                    AND e.received_at BETWEEN $2 AND $3
+                    AND e.delivery_cancelled_at IS NULL
+                    AND (cardinality($4::text[]) = 0 OR e.event_type__name = ANY($4))
+                    AND ($5::uuid IS NULL OR EXISTS (
+                        SELECT 1
+                        FROM webhook.subscription AS s
+                        INNER JOIN event.event_type AS et ON et.application__id = e.application__id AND et.event_type__name = e.event_type__name
+                        WHERE s.subscription__id = $5
+                            AND e.labels @> s.labels
+                            AND (
+                                EXISTS (SELECT 1 FROM webhook.subscription__event_type AS set WHERE set.subscription__id = s.subscription__id AND set.event_type__name = e.event_type__name)
+                                OR EXISTS (SELECT 1 FROM unnest(s.event_type_patterns) AS p(pattern) WHERE webhook.event_type_matches_pattern(p.pattern, et.service__name, et.resource_type__name, et.verb__name))
+                            )
+                    ))
+                    AND (NOT $6 OR EXISTS (
+                        SELECT 1
+                        FROM webhook.request_attempt AS ra
+                        WHERE ra.event__id = e.event__id
+                            AND ra.failed_at IS NOT NULL
+                            AND ($5::uuid IS NULL OR ra.subscription__id = $5)
+                    ))
+            "#,
+            job.application__id,
+            job.min_received_at,
+            job.max_received_at,
+            &job.event_type_names,
+            job.subscription__id,
+            job.only_failed,
+        )
+        .fetch_one(&mut *tx)
+        .await?;
+
+        query!(
+            "
+                UPDATE event.replay_job
+                SET status = 'running', started_at = statement_timestamp(), total_events = $2
+                WHERE replay_job__id = $1
+            ",
+            job.replay_job__id,
+            total_events,
+        )
+        .execute(&mut *tx)
+        .await?;
+
+        info!(
+            "Replay job {} started ({total_events} events to replay)",
+            job.replay_job__id
+        );
+    }
+
+    #[allow(non_snake_case)]
+    struct EventToReplay {
+        event__id: Uuid,
+        received_at: DateTime<Utc>,
+    }
+    let events = query_as!(
+        EventToReplay,
+        "
+            SELECT e.event__id, e.received_at
+            FROM event.event AS e
+            WHERE e.application__id = $1
+                AND e.received_at BETWEEN $2 AND $3
+                AND e.delivery_cancelled_at IS NULL
+                AND (cardinality($4::text[]) = 0 OR e.event_type__name = ANY($4))
+                AND ($5::uuid IS NULL OR EXISTS (
+                    SELECT 1
+                    FROM webhook.subscription AS s
+                    INNER JOIN event.event_type AS et ON et.application__id = e.application__id AND et.event_type__name = e.event_type__name
+                    WHERE s.subscription__id = $5
+                        AND e.labels @> s.labels
+                        AND (
+                            EXISTS (SELECT 1 FROM webhook.subscription__event_type AS set WHERE set.subscription__id = s.subscription__id AND set.event_type__name = e.event_type__name)
+                            OR EXISTS (SELECT 1 FROM unnest(s.event_type_patterns) AS p(pattern) WHERE webhook.event_type_matches_pattern(p.pattern, et.service__name, et.resource_type__name, et.verb__name))
+                        )
+                ))
+                AND (NOT $6 OR EXISTS (
+                    SELECT 1
+                    FROM webhook.request_attempt AS ra
+                    WHERE ra.event__id = e.event__id
+                        AND ra.failed_at IS NOT NULL
+                        AND ($5::uuid IS NULL OR ra.subscription__id = $5)
+                ))
+                AND ($7::timestamptz IS NULL OR (e.received_at, e.event__id) > ($7, $8::uuid))
+            ORDER BY e.received_at, e.event__id
+            LIMIT $9
+        ",
+        job.application__id,
+        job.min_received_at,
+        job.max_received_at,
+        &job.event_type_names,
+        job.subscription__id,
+        job.only_failed,
+        job.cursor_received_at,
+        job.cursor_event__id,
+        batch_size,
+    )
+    .fetch_all(&mut *tx)
+    .await?;
+
+    // The job is unlocked while its events are replayed, so that cancelling it only waits for the replay of the current event
+    let is_completed = i64::try_from(events.len()).unwrap_or(i64::MAX) < batch_size;
+    query!(
+        "
+            UPDATE event.replay_job
+            SET picked_at = statement_timestamp(),
+                status = CASE WHEN $2 THEN 'completed' ELSE status END,
+                finished_at = CASE WHEN $2 THEN statement_timestamp() ELSE NULL END
+            WHERE replay_job__id = $1
+        ",
+        job.replay_job__id,
+        events.is_empty() && is_completed,
+    )
+    .execute(&mut *tx)
+    .await?;
+    tx.commit().await?;
+
+    // Each event is replayed in the same transaction as the move of the cursor past it, so that it is replayed exactly once even if this API instance stops or another one picks the job meanwhile
+    let mut cursor = (job.cursor_received_at, job.cursor_event__id);
+    for (i, event) in events.iter().enumerate() {
+        let mut tx = state.db.begin().await?;
+
+        let still_at_cursor = query_scalar!(
+            r#"
+                SELECT true AS "still_at_cursor!"
+                FROM event.replay_job
+                WHERE replay_job__id = $1
+                    AND status = 'running'
+                    AND cursor_received_at IS NOT DISTINCT FROM $2
+                    AND cursor_event__id IS NOT DISTINCT FROM $3
+                FOR UPDATE
+            "#,
+            job.replay_job__id,
+            cursor.0,
+            cursor.1,
+        )
+        .fetch_optional(&mut *tx)
+        .await?
+        .is_some();
+        if !still_at_cursor {
+            // The job was cancelled, or another API instance replayed this event
+            tx.rollback().await?;
+            return Ok(true);
+        }
+
+        // A savepoint keeps the job transaction usable if the replay fails
+        let mut replay_tx = tx.begin().await?;
+        let outcome = replay_event_in_transaction(
+            &mut replay_tx,
+            state,
+            job.application__id,
+            event.event__id,
+            job.subscription__id,
+        )
+        .await;
+        let last_error = match outcome {
+            Ok(()) => {
+                replay_tx.commit().await?;
+                None
+            }
+            Err(e) => {
+                replay_tx.rollback().await?;
+                let problem = Problem::from(e);
+                warn!(
+                    "Replay job {}: could not replay event {}: {}",
+                    job.replay_job__id, event.event__id, problem.detail
+                );
+                Some(format!(
+                    "Could not replay event {}: {}",
+                    event.event__id, problem.title
+                ))
+            }
+        };
+
+        let is_last = is_completed && i + 1 == events.len();
+        query!(
+            "
+                UPDATE event.replay_job
+                SET replayed_events = replayed_events + $2,
+                    failed_events = failed_events + $3,
+                    last_error = COALESCE($4, last_error),
+                    cursor_received_at = $5,
+                    cursor_event__id = $6,
+                    status = CASE WHEN $7 THEN 'completed' ELSE status END,
+                    finished_at = CASE WHEN $7 THEN statement_timestamp() ELSE NULL END
+                WHERE replay_job__id = $1
+            ",
+            job.replay_job__id,
+            i64::from(last_error.is_none()),
+            i64::from(last_error.is_some()),
+            last_error,
+            event.received_at,
+            event.event__id,
+            is_last,
+        )
+        .execute(&mut *tx)
+        .await?;
+
+        tx.commit().await?;
+        if last_error.is_none() {
+            report_replayed_events(1);
+        }
+        cursor = (Some(event.received_at), Some(event.event__id));
+    }
+
+    if is_completed {
+        info!("Replay job {} completed", job.replay_job__id);
+    }
+
+    Ok(true)
+}
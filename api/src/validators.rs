@@ -14,11 +14,21 @@ const EVENT_TYPES_MIN_SIZE: usize = 1;
 const EVENT_TYPES_MAX_SIZE: usize = 100;
 const EVENT_TYPES_NAME_MIN_LENGTH: usize = 1;
 const EVENT_TYPES_NAME_MAX_LENGTH: usize = 200;
+const EVENT_TYPE_PATTERNS_MAX_SIZE: usize = 20;
+const EVENT_TYPE_PATTERN_PART_MAX_LENGTH: usize = 50;
 const SUBSCRIPTION_TARGET_HTTP_ALLOWED_METHODS: &[&str] =
     &["GET", "PATCH", "POST", "PUT", "DELETE", "OPTIONS", "HEAD"];
 const SUBSCRIPTION_TARGET_HTTP_URL_MAX_LENGTH: usize = 1000;
 const SUBSCRIPTION_TARGET_HTTP_HEADERS_MAX_SIZE: usize = 10;
 const SUBSCRIPTION_TARGET_HTTP_HEADERS_PROPERTY_MAX_LENGTH: usize = 500;
+const SUBSCRIPTION_TARGET_HTTP_CA_CERTIFICATES_MAX_LENGTH: usize = 65536;
+const SUBSCRIPTION_TARGET_AUTHENTICATION_SCOPES_MAX_SIZE: usize = 20;
+const SUBSCRIPTION_TARGET_AUTHENTICATION_SCOPE_MAX_LENGTH: usize = 200;
+const SUBSCRIPTION_RETRY_POLICY_DELAYS_MIN_SIZE: usize = 1;
+const SUBSCRIPTION_RETRY_POLICY_DELAYS_MAX_SIZE: usize = 100;
+const SUBSCRIPTION_RETRY_POLICY_DELAY_MIN: u32 = 1;
+const SUBSCRIPTION_RETRY_POLICY_DELAY_MAX: u32 = 7 * 24 * 60 * 60;
+const SUBSCRIPTION_FILTER_PATH_MAX_LENGTH: usize = 200;
 
 const CODE_METADATA_SIZE: &str = "metadata-size";
 const CODE_METADATA_PROPERTY_LENGTH: &str = "metadata-property-length";
@@ -26,11 +36,20 @@ const CODE_LABELS_SIZE: &str = "labels-size";
 const CODE_LABELS_PROPERTY_LENGTH: &str = "labels-property-length";
 const CODE_EVENT_TYPES_SIZE: &str = "event-types-size";
 const CODE_EVENT_TYPES_NAME_LENGTH: &str = "event-types-name-length";
+const CODE_EVENT_TYPE_PATTERNS_SIZE: &str = "event-type-patterns-size";
+const CODE_EVENT_TYPE_PATTERNS_FORMAT: &str = "event-type-patterns-format";
 const CODE_SUBSCRIPTION_TARGET_HTTP_METHOD: &str = "subscription-target-http-method";
 const CODE_SUBSCRIPTION_TARGET_HTTP_URL_LENGTH: &str = "subscription-target-http-url-length";
 const CODE_SUBSCRIPTION_TARGET_HTTP_HEADERS_SIZE: &str = "subscription-target-http-headers-size";
 const CODE_SUBSCRIPTION_TARGET_HTTP_HEADERS_PROPERTY_LENGTH: &str =
     "subscription-target-http-headers-property-length";
+const CODE_SUBSCRIPTION_TARGET_HTTP_CA_CERTIFICATES: &str =
+    "subscription-target-http-ca-certificates";
+const CODE_SUBSCRIPTION_TARGET_AUTHENTICATION_SCOPES: &str =
+    "subscription-target-authentication-scopes";
+const CODE_SUBSCRIPTION_RETRY_POLICY_DELAYS_SIZE: &str = "subscription-retry-policy-delays-size";
+const CODE_SUBSCRIPTION_RETRY_POLICY_DELAYS_VALUE: &str = "subscription-retry-policy-delays-value";
+const CODE_SUBSCRIPTION_FILTER_PATH: &str = "subscription-filter-path";
 
 pub fn metadata(val: &HashMap<String, String>) -> Result<(), ValidationError> {
     if val.len() > METADATA_MAX_SIZE {
@@ -147,6 +166,53 @@ pub fn event_types(val: &[String]) -> Result<(), ValidationError> {
     }
 }
 
+pub fn event_type_patterns(val: &[String]) -> Result<(), ValidationError> {
+    let size = val.len();
+    if size > EVENT_TYPE_PATTERNS_MAX_SIZE {
+        return Err(ValidationError {
+            code: CODE_EVENT_TYPE_PATTERNS_SIZE.into(),
+            message: Some(
+                format!(
+                    "There must be at most {EVENT_TYPE_PATTERNS_MAX_SIZE} event type patterns (found {size})"
+                )
+                .into(),
+            ),
+            params: HashMap::new(),
+        });
+    }
+
+    fn is_valid(pattern: &str) -> bool {
+        let parts = pattern.split('.').collect::<Vec<_>>();
+        pattern == "*"
+            || (parts.len() == 3
+                && parts.contains(&"*")
+                && parts.iter().all(|part| {
+                    *part == "*"
+                        || ((1..=EVENT_TYPE_PATTERN_PART_MAX_LENGTH).contains(&part.len())
+                            && !part.contains('*')
+                            && !part.chars().any(char::is_control))
+                }))
+    }
+
+    let invalid_patterns = val
+        .iter()
+        .enumerate()
+        .filter(|(_, pattern)| !is_valid(pattern))
+        .map(|(index, _)| index.to_string())
+        .collect::<Vec<_>>();
+
+    if !invalid_patterns.is_empty() {
+        let invalid = invalid_patterns.join(", ");
+        Err(ValidationError {
+                code: CODE_EVENT_TYPE_PATTERNS_FORMAT.into(),
+                message: Some(format!("Event type patterns must be `*` or follow the `service.resource_type.verb` structure with at least one part being `*`, such as `billing.*.*` (invalid patterns were spotted at the following indexes: {invalid})").into()),
+                params: HashMap::new(),
+            })
+    } else {
+        Ok(())
+    }
+}
+
 pub fn subscription_target_http_method(val: &String) -> Result<(), ValidationError> {
     if !SUBSCRIPTION_TARGET_HTTP_ALLOWED_METHODS.contains(&val.as_str()) {
         Err(ValidationError {
@@ -236,6 +302,149 @@ pub fn subscription_target_http_method_headers(val: &HeaderMap) -> Result<(), Va
     }
 }
 
+pub fn subscription_target_http_ca_certificates(val: &str) -> Result<(), ValidationError> {
+    let error = |message: String| ValidationError {
+        code: CODE_SUBSCRIPTION_TARGET_HTTP_CA_CERTIFICATES.into(),
+        message: Some(message.into()),
+        params: HashMap::from_iter([(
+            "max".into(),
+            Value::Number(SUBSCRIPTION_TARGET_HTTP_CA_CERTIFICATES_MAX_LENGTH.into()),
+        )]),
+    };
+
+    if val.len() > SUBSCRIPTION_TARGET_HTTP_CA_CERTIFICATES_MAX_LENGTH {
+        Err(error(format!(
+            "CA certificates must be smaller than {SUBSCRIPTION_TARGET_HTTP_CA_CERTIFICATES_MAX_LENGTH} characters"
+        )))
+    } else {
+        match reqwest::Certificate::from_pem_bundle(val.as_bytes()) {
+            Ok(certificates) if !certificates.is_empty() => Ok(()),
+            Ok(_) => Err(error(
+                "CA certificates must contain at least one PEM-encoded certificate".to_owned(),
+            )),
+            Err(e) => Err(error(format!("CA certificates are not valid: {e}"))),
+        }
+    }
+}
+
+/// Scopes must be valid OAuth2 scope tokens (RFC 6749, section 3.3): printable ASCII characters except spaces, double quotes and backslashes
+pub fn subscription_target_authentication_scopes(val: &[String]) -> Result<(), ValidationError> {
+    fn is_valid(scope: &str) -> bool {
+        (1..=SUBSCRIPTION_TARGET_AUTHENTICATION_SCOPE_MAX_LENGTH).contains(&scope.len())
+            && scope
+                .bytes()
+                .all(|b| b.is_ascii_graphic() && b != b'"' && b != b'\\')
+    }
+
+    if val.len() > SUBSCRIPTION_TARGET_AUTHENTICATION_SCOPES_MAX_SIZE
+        || !val.iter().all(|scope| is_valid(scope))
+    {
+        Err(ValidationError {
+            code: CODE_SUBSCRIPTION_TARGET_AUTHENTICATION_SCOPES.into(),
+            message: Some(
+                format!("There must be at most {SUBSCRIPTION_TARGET_AUTHENTICATION_SCOPES_MAX_SIZE} scopes, each made of 1 to {SUBSCRIPTION_TARGET_AUTHENTICATION_SCOPE_MAX_LENGTH} printable ASCII characters other than spaces, double quotes and backslashes")
+                .into(),
+            ),
+            params: HashMap::from_iter([
+                (
+                    "max".into(),
+                    Value::Number(SUBSCRIPTION_TARGET_AUTHENTICATION_SCOPES_MAX_SIZE.into()),
+                ),
+            ]),
+        })
+    } else {
+        Ok(())
+    }
+}
+
+pub fn subscription_retry_policy_delays(val: &[u32]) -> Result<(), ValidationError> {
+    let size = val.len();
+    if !(SUBSCRIPTION_RETRY_POLICY_DELAYS_MIN_SIZE..=SUBSCRIPTION_RETRY_POLICY_DELAYS_MAX_SIZE)
+        .contains(&size)
+    {
+        return Err(ValidationError {
+            code: CODE_SUBSCRIPTION_RETRY_POLICY_DELAYS_SIZE.into(),
+            message: Some(
+                format!(
+                    "There must be between {SUBSCRIPTION_RETRY_POLICY_DELAYS_MIN_SIZE} and {SUBSCRIPTION_RETRY_POLICY_DELAYS_MAX_SIZE} retry delays (found {size})"
+                )
+                .into(),
+            ),
+            params: HashMap::new(),
+        });
+    }
+
+    let invalid_values = val
+        .iter()
+        .enumerate()
+        .filter(|(_, d)| {
+            !(SUBSCRIPTION_RETRY_POLICY_DELAY_MIN..=SUBSCRIPTION_RETRY_POLICY_DELAY_MAX).contains(d)
+        })
+        .map(|(i, _)| i.to_string())
+        .collect::<Vec<_>>();
+
+    if !invalid_values.is_empty() {
+        let invalid = invalid_values.join(", ");
+        Err(ValidationError {
+            code: CODE_SUBSCRIPTION_RETRY_POLICY_DELAYS_VALUE.into(),
+            message: Some(format!("Retry delays must be between {SUBSCRIPTION_RETRY_POLICY_DELAY_MIN} and {SUBSCRIPTION_RETRY_POLICY_DELAY_MAX} seconds (invalid delays were spotted at the following indexes: {invalid})").into()),
+            params: HashMap::from_iter([
+                ("min".into(), Value::Number(SUBSCRIPTION_RETRY_POLICY_DELAY_MIN.into())),
+                ("max".into(), Value::Number(SUBSCRIPTION_RETRY_POLICY_DELAY_MAX.into())),
+            ]),
+        })
+    } else {
+        Ok(())
+    }
+}
+
+/// Only a subset of the JSONPath syntax is allowed: object keys (`.key`) and array indexes (`[0]`), starting from the root (`$`)
+pub fn subscription_filter_path(val: &str) -> Result<(), ValidationError> {
+    fn is_valid(path: &str) -> bool {
+        let Some(mut rest) = path.strip_prefix('$') else {
+            return false;
+        };
+
+        while !rest.is_empty() {
+            if let Some(r) = rest.strip_prefix('.') {
+                let end = r
+                    .find(|c: char| !(c.is_ascii_alphanumeric() || c == '_'))
+                    .unwrap_or(r.len());
+                let key = &r[..end];
+                if key.is_empty() || key.starts_with(|c: char| c.is_ascii_digit()) {
+                    return false;
+                }
+                rest = &r[end..];
+            } else if let Some(r) = rest.strip_prefix('[') {
+                let Some((index, r)) = r.split_once(']') else {
+                    return false;
+                };
+                if index.is_empty() || !index.chars().all(|c| c.is_ascii_digit()) {
+                    return false;
+                }
+                rest = r;
+            } else {
+                return false;
+            }
+        }
+
+        true
+    }
+
+    if val.len() > SUBSCRIPTION_FILTER_PATH_MAX_LENGTH || !is_valid(val) {
+        Err(ValidationError {
+            code: CODE_SUBSCRIPTION_FILTER_PATH.into(),
+            message: Some(
+                format!("Filter path must be a JSONPath made of object keys and array indexes, such as `$.customer.addresses[0].country`, and be smaller than {SUBSCRIPTION_FILTER_PATH_MAX_LENGTH} characters")
+                .into(),
+            ),
+            params: HashMap::from_iter([("value".into(), Value::String(val.to_owned()))]),
+        })
+    } else {
+        Ok(())
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -468,4 +677,140 @@ mod tests {
             CODE_EVENT_TYPES_NAME_LENGTH
         );
     }
+
+    #[test]
+    fn event_type_patterns_valid() {
+        let val = [
+            "*",
+            "billing.*.*",
+            "*.invoice.*",
+            "*.*.created",
+            "billing.invoice.*",
+        ]
+        .map(str::to_owned);
+        assert!(event_type_patterns(&val).is_ok())
+    }
+
+    #[test]
+    fn event_type_patterns_invalid_format() {
+        for pattern in [
+            "",
+            "billing.*",
+            "billing.*.*.*",
+            "billing.invoice.paid",
+            "bill*.invoice.paid",
+            "billing..*",
+            "**",
+        ] {
+            let output = event_type_patterns(&[pattern.to_owned()]);
+            assert!(output.is_err(), "{pattern}");
+            assert_eq!(
+                output.err().map(|e| e.code).unwrap_or_else(|| "".into()),
+                CODE_EVENT_TYPE_PATTERNS_FORMAT
+            );
+        }
+    }
+
+    #[test]
+    fn subscription_retry_policy_delays_valid() {
+        let val = vec![1, 30, 3600];
+        assert!(subscription_retry_policy_delays(&val).is_ok())
+    }
+
+    #[test]
+    fn subscription_retry_policy_delays_empty() {
+        let output = subscription_retry_policy_delays(&[]);
+        assert!(output.is_err());
+        assert_eq!(
+            output.err().map(|e| e.code).unwrap_or_else(|| "".into()),
+            CODE_SUBSCRIPTION_RETRY_POLICY_DELAYS_SIZE
+        );
+    }
+
+    #[test]
+    fn subscription_retry_policy_delays_invalid_value() {
+        let val = vec![10, 0, SUBSCRIPTION_RETRY_POLICY_DELAY_MAX + 1];
+        let output = subscription_retry_policy_delays(&val);
+        assert!(output.is_err());
+        assert_eq!(
+            output.err().map(|e| e.code).unwrap_or_else(|| "".into()),
+            CODE_SUBSCRIPTION_RETRY_POLICY_DELAYS_VALUE
+        );
+    }
+
+    #[test]
+    fn subscription_filter_path_valid() {
+        for val in [
+            "$",
+            "$.amount",
+            "$.customer.addresses[0].country",
+            "$[12]._id2",
+        ] {
+            assert!(subscription_filter_path(val).is_ok(), "{val}");
+        }
+    }
+
+    #[test]
+    fn subscription_filter_path_invalid() {
+        for val in [
+            "",
+            "amount",
+            "$.",
+            "$..amount",
+            "$.2fa",
+            "$[]",
+            "$[-1]",
+            "$[0",
+            "$.amount > 1000",
+            "$.a-b",
+            "$.*",
+        ] {
+            let output = subscription_filter_path(val);
+            assert!(output.is_err(), "{val}");
+            assert_eq!(
+                output.err().map(|e| e.code).unwrap_or_else(|| "".into()),
+                CODE_SUBSCRIPTION_FILTER_PATH
+            );
+        }
+    }
+
+    #[test]
+    fn subscription_target_http_ca_certificates_invalid() {
+        for val in [
+            "",
+            "not a certificate",
+            "-----BEGIN CERTIFICATE-----\nnot base64\n-----END CERTIFICATE-----\n",
+        ] {
+            let output = subscription_target_http_ca_certificates(val);
+            assert!(output.is_err(), "{val}");
+            assert_eq!(
+                output.err().map(|e| e.code).unwrap_or_else(|| "".into()),
+                CODE_SUBSCRIPTION_TARGET_HTTP_CA_CERTIFICATES
+            );
+        }
+    }
+
+    #[test]
+    fn subscription_target_authentication_scopes_valid() {
+        let val = vec!["openid".to_owned(), "webhooks:write".to_owned()];
+        assert!(subscription_target_authentication_scopes(&val).is_ok());
+    }
+
+    #[test]
+    fn subscription_target_authentication_scopes_invalid() {
+        for val in [
+            vec!["".to_owned()],
+            vec!["two scopes".to_owned()],
+            vec!["quoted\"scope".to_owned()],
+            vec!["é".to_owned()],
+            vec!["scope".to_owned(); 21],
+        ] {
+            let output = subscription_target_authentication_scopes(&val);
+            assert!(output.is_err(), "{val:?}");
+            assert_eq!(
+                output.err().map(|e| e.code).unwrap_or_else(|| "".into()),
+                CODE_SUBSCRIPTION_TARGET_AUTHENTICATION_SCOPES
+            );
+        }
+    }
 }
@@ -535,6 +535,56 @@ impl ApiClient {
         self.handle_response(response).await
     }
 
+    /// Create a replay job that replays all the events matching a filter
+    pub async fn create_replay_job(
+        &self,
+        replay_job: &ReplayJobPost,
+    ) -> Result<ReplayJob, ApiError> {
+        let response = self
+            .client
+            .post(self.url("/events/replay"))
+            .bearer_auth(&self.secret)
+            .json(replay_job)
+            .send()
+            .await?;
+
+        self.handle_response(response).await
+    }
+
+    /// Get a replay job by ID
+    pub async fn get_replay_job(
+        &self,
+        replay_job_id: &Uuid,
+        application_id: &Uuid,
+    ) -> Result<ReplayJob, ApiError> {
+        let response = self
+            .client
+            .get(self.url(&format!("/replay_jobs/{}", replay_job_id)))
+            .query(&[("application_id", application_id.to_string())])
+            .bearer_auth(&self.secret)
+            .send()
+            .await?;
+
+        self.handle_response(response).await
+    }
+
+    /// Cancel a replay job
+    pub async fn cancel_replay_job(
+        &self,
+        replay_job_id: &Uuid,
+        application_id: &Uuid,
+    ) -> Result<ReplayJob, ApiError> {
+        let response = self
+            .client
+            .post(self.url(&format!("/replay_jobs/{}/cancel", replay_job_id)))
+            .query(&[("application_id", application_id.to_string())])
+            .bearer_auth(&self.secret)
+            .send()
+            .await?;
+
+        self.handle_response(response).await
+    }
+
     // =========================================================================
     // Application Secret endpoints
     // =========================================================================
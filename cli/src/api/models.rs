@@ -275,7 +275,9 @@ pub struct Subscription {
     pub event_types: Vec<String>,
     #[serde(default)]
     pub description: Option<String>,
-    pub secret: Uuid,
+    /// Not returned by instances that encrypt subscription secrets, except when the subscription is created
+    #[serde(default)]
+    pub secret: Option<Uuid>,
     #[serde(default)]
     pub metadata: HashMap<String, String>,
     #[serde(default)]
@@ -429,6 +431,50 @@ impl RequestAttemptStatus {
     }
 }
 
+// =============================================================================
+// Replay Job (Bulk Replay)
+// =============================================================================
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ReplayJob {
+    pub replay_job_id: Uuid,
+    pub application_id: Uuid,
+    pub min_received_at: DateTime<Utc>,
+    pub max_received_at: DateTime<Utc>,
+    #[serde(default)]
+    pub event_type_names: Vec<String>,
+    #[serde(default)]
+    pub subscription_id: Option<Uuid>,
+    #[serde(default)]
+    pub only_failed: bool,
+    pub status: String,
+    #[serde(default)]
+    pub total_events: Option<i64>,
+    #[serde(default)]
+    pub replayed_events: i64,
+    #[serde(default)]
+    pub failed_events: i64,
+    #[serde(default)]
+    pub last_error: Option<String>,
+    pub created_at: DateTime<Utc>,
+    #[serde(default)]
+    pub started_at: Option<DateTime<Utc>>,
+    #[serde(default)]
+    pub finished_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ReplayJobPost {
+    pub application_id: Uuid,
+    pub min_received_at: DateTime<Utc>,
+    pub max_received_at: DateTime<Utc>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub event_type_names: Vec<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub subscription_id: Option<Uuid>,
+    pub only_failed: bool,
+}
+
 // =============================================================================
 // Response (Webhook Response)
 // =============================================================================
@@ -1,23 +1,31 @@
 use anyhow::{Result, anyhow};
-use chrono::{Duration, Utc};
+use chrono::{DateTime, Duration, Utc};
 use clap::Args;
 use uuid::Uuid;
 
 use crate::Cli;
-use crate::api::models::{EventFilters, PaginationParams};
+use crate::api::models::{EventFilters, PaginationParams, ReplayJobPost};
 use crate::commands::require_auth;
-use crate::output::{OutputFormat, output_many, output_success, output_warning};
+use crate::output::{OutputFormat, output_many, output_one, output_success, output_warning};
 
 #[derive(Args, Debug)]
 pub struct ReplayArgs {
     /// Event ID to replay
     pub event_id: Option<Uuid>,
 
-    /// Replay all events matching criteria (requires --confirm)
+    /// Replay all events matching criteria in the background (requires --confirm)
     #[arg(long)]
     pub all: bool,
 
-    /// Filter by status (failed, successful, etc.)
+    /// Show the progress of a replay job created with --all
+    #[arg(long)]
+    pub job: Option<Uuid>,
+
+    /// Cancel the replay job given with --job
+    #[arg(long, requires = "job")]
+    pub cancel: bool,
+
+    /// Filter by status (bulk replays only support failed)
     #[arg(long)]
     pub status: Option<String>,
 
@@ -33,6 +41,10 @@ pub struct ReplayArgs {
     #[arg(long)]
     pub event_type: Option<String>,
 
+    /// Only replay events to this subscription
+    #[arg(long)]
+    pub subscription: Option<Uuid>,
+
     /// Dry run - show what would be replayed without actually replaying
     #[arg(long)]
     pub dry_run: bool,
@@ -41,7 +53,7 @@ pub struct ReplayArgs {
     #[arg(long)]
     pub confirm: bool,
 
-    /// Maximum number of events to replay
+    /// Maximum number of events to show in dry run
     #[arg(long, default_value = "100")]
     pub limit: i32,
 }
@@ -114,6 +126,22 @@ pub async fn execute(cli: &Cli, args: &ReplayArgs) -> Result<()> {
         return Ok(());
     }
 
+    // Replay job progress or cancellation
+    if let Some(replay_job_id) = args.job {
+        let replay_job = if args.cancel {
+            client
+                .cancel_replay_job(&replay_job_id, &profile.application_id)
+                .await?
+        } else {
+            client
+                .get_replay_job(&replay_job_id, &profile.application_id)
+                .await?
+        };
+
+        output_one(&replay_job, cli.output);
+        return Ok(());
+    }
+
     // Bulk replay
     if !args.all {
         return Err(anyhow!(
@@ -147,6 +175,42 @@ pub async fn execute(cli: &Cli, args: &ReplayArgs) -> Result<()> {
         filters.until = Some(Utc::now() - duration);
     }
 
+    // Events are replayed in the background by the API, which throttles replays
+    if !args.dry_run {
+        let only_failed = match args.status.as_deref() {
+            None => false,
+            Some("failed") => true,
+            Some(status) => {
+                return Err(anyhow!(
+                    "Bulk replay only supports --status failed (got '{}')",
+                    status
+                ));
+            }
+        };
+
+        let replay_job = client
+            .create_replay_job(&ReplayJobPost {
+                application_id: profile.application_id,
+                min_received_at: filters.since.unwrap_or(DateTime::<Utc>::UNIX_EPOCH),
+                max_received_at: filters.until.unwrap_or_else(Utc::now),
+                event_type_names: args.event_type.iter().cloned().collect(),
+                subscription_id: args.subscription,
+                only_failed,
+            })
+            .await?;
+
+        if cli.output == OutputFormat::Json {
+            output_one(&replay_job, cli.output);
+        } else {
+            output_success(&format!(
+                "Replay job {} created!\n  Events are replayed in the background; follow the progress with: hook0 replay --job {}",
+                replay_job.replay_job_id, replay_job.replay_job_id
+            ));
+        }
+
+        return Ok(());
+    }
+
     let pagination = PaginationParams::new(Some(1), Some(args.limit));
 
     // Get events matching criteria
@@ -159,74 +223,21 @@ pub async fn execute(cli: &Cli, args: &ReplayArgs) -> Result<()> {
         return Ok(());
     }
 
-    if args.dry_run {
-        if cli.output == OutputFormat::Json {
-            let ids: Vec<&uuid::Uuid> = events.iter().map(|e| &e.event_id).collect();
-            println!(
-                "{}",
-                serde_json::json!({"dry_run": true, "count": events.len(), "event_ids": ids})
-            );
-        } else {
-            println!("Would replay {} event(s):", events.len());
-            for event in &events {
-                println!(
-                    "  - {} ({})",
-                    event.event_id,
-                    event.event_type_name.as_deref().unwrap_or("unknown")
-                );
-            }
-        }
-        return Ok(());
-    }
-
-    // Confirm and replay
-    if cli.output != OutputFormat::Json {
-        println!("Replaying {} event(s)...", events.len());
-    }
-
-    let mut total_attempts = 0;
-    let mut failed = 0;
-
-    for event in &events {
-        match client
-            .replay_event(&event.event_id, &profile.application_id)
-            .await
-        {
-            Ok(attempts) => {
-                total_attempts += attempts.len();
-                if cli.output != OutputFormat::Json {
-                    println!(
-                        "  Replayed {} ({} attempts)",
-                        event.event_id,
-                        attempts.len()
-                    );
-                }
-            }
-            Err(e) => {
-                failed += 1;
-                if cli.output != OutputFormat::Json {
-                    eprintln!("  Failed to replay {}: {}", event.event_id, e);
-                }
-            }
-        }
-    }
-
     if cli.output == OutputFormat::Json {
+        let ids: Vec<&uuid::Uuid> = events.iter().map(|e| &e.event_id).collect();
         println!(
             "{}",
-            serde_json::json!({
-                "replayed": events.len() - failed,
-                "failed": failed,
-                "total_attempts": total_attempts,
-            })
+            serde_json::json!({"dry_run": true, "count": events.len(), "event_ids": ids})
         );
     } else {
-        output_success(&format!(
-            "Replay complete!\n  Events: {} replayed, {} failed\n  Total request attempts: {}",
-            events.len() - failed,
-            failed,
-            total_attempts
-        ));
+        println!("Would replay {} event(s):", events.len());
+        for event in &events {
+            println!(
+                "  - {} ({})",
+                event.event_id,
+                event.event_type_name.as_deref().unwrap_or("unknown")
+            );
+        }
     }
 
     Ok(())
@@ -288,7 +288,13 @@ async fn get(cli: &Cli, args: &GetArgs) -> Result<()> {
                     labels_str
                 },
             ),
-            ("Secret", subscription.secret.to_string()),
+            (
+                "Secret",
+                subscription
+                    .secret
+                    .map(|secret| secret.to_string())
+                    .unwrap_or_else(|| "-".to_string()),
+            ),
             ("Created At", subscription.created_at.to_rfc3339()),
         ]);
     }
@@ -263,6 +263,37 @@ impl Outputable for RequestAttempt {
     }
 }
 
+impl Outputable for ReplayJob {
+    fn table_headers() -> Vec<&'static str> {
+        vec!["ID", "Status", "Replayed", "Failed", "Total", "Created At"]
+    }
+
+    fn table_row(&self) -> Vec<String> {
+        vec![
+            self.replay_job_id.to_string(),
+            self.status.clone(),
+            self.replayed_events.to_string(),
+            self.failed_events.to_string(),
+            self.total_events
+                .map(|t| t.to_string())
+                .unwrap_or_else(|| "-".to_string()),
+            self.created_at.format("%Y-%m-%d %H:%M:%S").to_string(),
+        ]
+    }
+
+    fn compact_line(&self) -> String {
+        format!(
+            "{}\t{}\t{}/{}",
+            self.replay_job_id,
+            self.status,
+            self.replayed_events + self.failed_events,
+            self.total_events
+                .map(|t| t.to_string())
+                .unwrap_or_else(|| "?".to_string())
+        )
+    }
+}
+
 impl Outputable for ApplicationSecret {
     fn table_headers() -> Vec<&'static str> {
         vec!["Token", "Name", "Created At"]
@@ -32,12 +32,20 @@ use url::ParseError;
 #[cfg(feature = "producer")]
 use uuid::Uuid;
 
+#[cfg(feature = "consumer")]
+use base64::Engine;
+#[cfg(feature = "consumer")]
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
 #[cfg(feature = "consumer")]
 use chrono::{Duration, OutOfRangeError};
 #[cfg(feature = "consumer")]
+use ed25519_dalek::VerifyingKey;
+#[cfg(feature = "consumer")]
 use std::time::Duration as StdDuration;
 #[cfg(feature = "consumer")]
 mod signature;
+#[cfg(feature = "consumer")]
+mod standard_webhooks;
 
 #[cfg(feature = "producer")]
 /// The Hook0 client
@@ -242,7 +250,7 @@ impl Hook0Client {
 /// - `signature` - The value of the `X-Hook0-Signature` header.
 /// - `payload` - The raw body of the webhook request.
 /// - `headers` - Headers of the webhook request.
-/// - `subscription_secret` - The signing secret used to validate the signature.
+/// - `subscription_secret` - The signing secret used to validate the signature (while the secret of the subscription is being rotated, webhooks are signed with both the previous and the new secret, and either of them is accepted).
 /// - `tolerance` - The maximum allowed time difference for the timestamp (5 minutes is a good trade-off between flexibility and protecting against replay attacks).
 /// - `current_time` - The current time (used to check the timestamp).
 pub fn verify_webhook_signature_with_current_time<
@@ -258,7 +266,21 @@ pub fn verify_webhook_signature_with_current_time<
 ) -> Result<(), Hook0ClientError> {
     let parsed_sig =
         signature::Signature::parse(signature).map_err(|_| Hook0ClientError::InvalidSignature)?;
+    let headers_vec = signed_header_values(&parsed_sig, headers)?;
+
+    if !parsed_sig.verify(payload, &headers_vec, subscription_secret) {
+        Err(Hook0ClientError::InvalidSignature)
+    } else {
+        check_timestamp(parsed_sig.timestamp, tolerance, current_time)
+    }
+}
 
+#[cfg(feature = "consumer")]
+/// Get the values of the headers that are part of the signature, in the order of its `h` field
+fn signed_header_values<HeaderKey: AsRef<[u8]>, HeaderValue: AsRef<[u8]>>(
+    parsed_sig: &signature::Signature,
+    headers: &[(HeaderKey, HeaderValue)],
+) -> Result<Vec<String>, Hook0ClientError> {
     let headers_with_parsed_name = headers
         .iter()
         .map(|(k, v)| {
@@ -271,7 +293,7 @@ pub fn verify_webhook_signature_with_current_time<
             name.map(|n| (n, v))
         })
         .collect::<Result<std::collections::HashMap<_, _>, _>>()?;
-    let headers_vec = parsed_sig
+    parsed_sig
         .h
         .iter()
         .map(|expected| {
@@ -288,33 +310,37 @@ pub fn verify_webhook_signature_with_current_time<
                     })
                 })
         })
-        .collect::<Result<Vec<_>, _>>()?;
+        .collect()
+}
 
-    if !parsed_sig.verify(payload, &headers_vec, subscription_secret) {
-        Err(Hook0ClientError::InvalidSignature)
-    } else {
-        let signed_at = DateTime::from_timestamp(parsed_sig.timestamp, 0);
-
-        match signed_at {
-            Some(signed_at) => {
-                let tolerance = Duration::from_std(tolerance);
-                match tolerance {
-                    Ok(tolerance) => {
-                        if (current_time - signed_at) > tolerance {
-                            Err(Hook0ClientError::ExpiredWebhook {
-                                signed_at,
-                                tolerance,
-                                current_time,
-                            })
-                        } else {
-                            Ok(())
-                        }
+#[cfg(feature = "consumer")]
+/// Check that a webhook was not signed too long ago
+fn check_timestamp(
+    timestamp: i64,
+    tolerance: StdDuration,
+    current_time: DateTime<Utc>,
+) -> Result<(), Hook0ClientError> {
+    let signed_at = DateTime::from_timestamp(timestamp, 0);
+
+    match signed_at {
+        Some(signed_at) => {
+            let tolerance = Duration::from_std(tolerance);
+            match tolerance {
+                Ok(tolerance) => {
+                    if (current_time - signed_at) > tolerance {
+                        Err(Hook0ClientError::ExpiredWebhook {
+                            signed_at,
+                            tolerance,
+                            current_time,
+                        })
+                    } else {
+                        Ok(())
                     }
-                    Err(e) => Err(Hook0ClientError::InvalidTolerance(e)),
                 }
+                Err(e) => Err(Hook0ClientError::InvalidTolerance(e)),
             }
-            None => Err(Hook0ClientError::InvalidSignature),
         }
+        None => Err(Hook0ClientError::InvalidSignature),
     }
 }
 
@@ -324,7 +350,7 @@ pub fn verify_webhook_signature_with_current_time<
 /// - `signature` - The value of the `X-Hook0-Signature` header.
 /// - `payload` - The raw body of the webhook request.
 /// - `headers` - Headers of the webhook request.
-/// - `subscription_secret` - The signing secret used to validate the signature.
+/// - `subscription_secret` - The signing secret used to validate the signature (while the secret of the subscription is being rotated, webhooks are signed with both the previous and the new secret, and either of them is accepted).
 /// - `tolerance` - The maximum allowed time difference for the timestamp (5 minutes is a good trade-off between flexibility and protecting against replay attacks).
 pub fn verify_webhook_signature<HeaderKey: AsRef<[u8]>, HeaderValue: AsRef<[u8]>>(
     signature: &str,
@@ -343,6 +369,163 @@ pub fn verify_webhook_signature<HeaderKey: AsRef<[u8]>, HeaderValue: AsRef<[u8]>
     )
 }
 
+#[cfg(feature = "consumer")]
+/// Verifies the [Standard Webhooks](https://www.standardwebhooks.com/) signature of a webhook
+///
+/// - `payload` - The raw body of the webhook request.
+/// - `headers` - Headers of the webhook request (`webhook-id`, `webhook-timestamp` and `webhook-signature` are used).
+/// - `secret` - The signing secret used to validate the signature, either the subscription secret or the equivalent Standard Webhooks secret (`whsec_` followed by the base64 encoding of the subscription secret).
+/// - `tolerance` - The maximum allowed time difference for the timestamp (5 minutes is a good trade-off between flexibility and protecting against replay attacks).
+/// - `current_time` - The current time (used to check the timestamp).
+pub fn verify_standard_webhooks_signature_with_current_time<
+    HeaderKey: AsRef<[u8]>,
+    HeaderValue: AsRef<[u8]>,
+>(
+    payload: &[u8],
+    headers: &[(HeaderKey, HeaderValue)],
+    secret: &str,
+    tolerance: StdDuration,
+    current_time: DateTime<Utc>,
+) -> Result<(), Hook0ClientError> {
+    let header = |name: &'static str| {
+        let expected = http::HeaderName::from_static(name);
+        headers
+            .iter()
+            .find(|(k, _v)| {
+                http::HeaderName::from_bytes(k.as_ref()).is_ok_and(|name| name == expected)
+            })
+            .ok_or_else(|| Hook0ClientError::MissingHeader(expected.to_owned()))
+            .and_then(|(_k, v)| {
+                std::str::from_utf8(v.as_ref()).map_err(|_| Hook0ClientError::InvalidSignature)
+            })
+    };
+    let id = header("webhook-id")?;
+    let timestamp = header("webhook-timestamp")?;
+    let signature = header("webhook-signature")?;
+
+    let parsed_sig = standard_webhooks::StandardWebhooksSignature::parse(id, timestamp, signature)
+        .ok_or(Hook0ClientError::InvalidSignature)?;
+
+    if !parsed_sig.verify(payload, secret) {
+        Err(Hook0ClientError::InvalidSignature)
+    } else {
+        check_timestamp(parsed_sig.timestamp, tolerance, current_time)
+    }
+}
+
+#[cfg(feature = "consumer")]
+/// Verifies the [Standard Webhooks](https://www.standardwebhooks.com/) signature of a webhook
+///
+/// - `payload` - The raw body of the webhook request.
+/// - `headers` - Headers of the webhook request (`webhook-id`, `webhook-timestamp` and `webhook-signature` are used).
+/// - `secret` - The signing secret used to validate the signature, either the subscription secret or the equivalent Standard Webhooks secret (`whsec_` followed by the base64 encoding of the subscription secret).
+/// - `tolerance` - The maximum allowed time difference for the timestamp (5 minutes is a good trade-off between flexibility and protecting against replay attacks).
+pub fn verify_standard_webhooks_signature<HeaderKey: AsRef<[u8]>, HeaderValue: AsRef<[u8]>>(
+    payload: &[u8],
+    headers: &[(HeaderKey, HeaderValue)],
+    secret: &str,
+    tolerance: StdDuration,
+) -> Result<(), Hook0ClientError> {
+    verify_standard_webhooks_signature_with_current_time(
+        payload,
+        headers,
+        secret,
+        tolerance,
+        Utc::now(),
+    )
+}
+
+#[cfg(feature = "consumer")]
+/// Public keys of an application, as returned by the `/jwks/{application_id}` endpoint of the Hook0 API
+///
+/// Keys are rotated from time to time; fetch the key set again when a signature refers to a key that is not in it.
+#[derive(Debug, Clone, serde::Deserialize)]
+pub struct Jwks {
+    /// Keys that can verify `v2` signatures
+    pub keys: Vec<Jwk>,
+}
+
+#[cfg(feature = "consumer")]
+/// Public key (Ed25519) of an application, as a JSON Web Key
+#[derive(Debug, Clone, serde::Deserialize)]
+pub struct Jwk {
+    /// ID of the key, as found in the `k` field of signatures
+    pub kid: String,
+
+    /// Public key (base64url-encoded)
+    pub x: String,
+}
+
+#[cfg(feature = "consumer")]
+/// Verifies the `v2` (Ed25519) signature of a webhook
+///
+/// - `signature` - The value of the `X-Hook0-Signature` header.
+/// - `payload` - The raw body of the webhook request.
+/// - `headers` - Headers of the webhook request.
+/// - `jwks` - The public keys of the application (see [`Jwks`]).
+/// - `tolerance` - The maximum allowed time difference for the timestamp (5 minutes is a good trade-off between flexibility and protecting against replay attacks).
+/// - `current_time` - The current time (used to check the timestamp).
+pub fn verify_webhook_signature_v2_with_current_time<
+    HeaderKey: AsRef<[u8]>,
+    HeaderValue: AsRef<[u8]>,
+>(
+    signature: &str,
+    payload: &[u8],
+    headers: &[(HeaderKey, HeaderValue)],
+    jwks: &Jwks,
+    tolerance: StdDuration,
+    current_time: DateTime<Utc>,
+) -> Result<(), Hook0ClientError> {
+    let parsed_sig =
+        signature::Signature::parse(signature).map_err(|_| Hook0ClientError::InvalidSignature)?;
+    let Some(key_id) = parsed_sig.k.as_deref().filter(|_| parsed_sig.v2.is_some()) else {
+        return Err(Hook0ClientError::MissingV2Signature);
+    };
+    let jwk = jwks
+        .keys
+        .iter()
+        .find(|jwk| jwk.kid == key_id)
+        .ok_or_else(|| Hook0ClientError::UnknownSigningKey(key_id.to_owned()))?;
+    let public_key = URL_SAFE_NO_PAD
+        .decode(&jwk.x)
+        .ok()
+        .and_then(|x| <[u8; 32]>::try_from(x).ok())
+        .and_then(|x| VerifyingKey::from_bytes(&x).ok())
+        .ok_or_else(|| Hook0ClientError::InvalidPublicKey(jwk.kid.to_owned()))?;
+    let headers_vec = signed_header_values(&parsed_sig, headers)?;
+
+    if !parsed_sig.verify_v2(payload, &headers_vec, &public_key) {
+        Err(Hook0ClientError::InvalidSignature)
+    } else {
+        check_timestamp(parsed_sig.timestamp, tolerance, current_time)
+    }
+}
+
+#[cfg(feature = "consumer")]
+/// Verifies the `v2` (Ed25519) signature of a webhook
+///
+/// - `signature` - The value of the `X-Hook0-Signature` header.
+/// - `payload` - The raw body of the webhook request.
+/// - `headers` - Headers of the webhook request.
+/// - `jwks` - The public keys of the application (see [`Jwks`]).
+/// - `tolerance` - The maximum allowed time difference for the timestamp (5 minutes is a good trade-off between flexibility and protecting against replay attacks).
+pub fn verify_webhook_signature_v2<HeaderKey: AsRef<[u8]>, HeaderValue: AsRef<[u8]>>(
+    signature: &str,
+    payload: &[u8],
+    headers: &[(HeaderKey, HeaderValue)],
+    jwks: &Jwks,
+    tolerance: StdDuration,
+) -> Result<(), Hook0ClientError> {
+    verify_webhook_signature_v2_with_current_time(
+        signature,
+        payload,
+        headers,
+        jwks,
+        tolerance,
+        Utc::now(),
+    )
+}
+
 #[cfg(feature = "producer")]
 /// A structured event type
 #[derive(Debug, Serialize, PartialEq, Eq)]
@@ -561,6 +744,32 @@ pub enum Hook0ClientError {
         error: hex::FromHexError,
     },
 
+    #[cfg(feature = "consumer")]
+    /// Could not parse v2 signature
+    #[error("Could not parse v2 signature `{signature}`: {error}")]
+    V2SignatureParsing {
+        /// Invalid signature value
+        signature: String,
+
+        /// Signature parsing error
+        error: hex::FromHexError,
+    },
+
+    #[cfg(feature = "consumer")]
+    /// The webhook was not signed with the signing key of the application (`v2` signature)
+    #[error("The webhook has no v2 signature")]
+    MissingV2Signature,
+
+    #[cfg(feature = "consumer")]
+    /// The webhook was signed with a key that is not part of the provided key set
+    #[error("The signing key `{0}` of the webhook is not part of the provided key set")]
+    UnknownSigningKey(String),
+
+    #[cfg(feature = "consumer")]
+    /// A key of the provided key set is not a valid Ed25519 public key
+    #[error("The `{0}` key of the provided key set is not a valid Ed25519 public key")]
+    InvalidPublicKey(String),
+
     #[cfg(feature = "consumer")]
     /// A header present in the webhook's signature was not provided with a value
     #[error("The `{0}` header present in the webhook's signature was not provided with a value")]
@@ -734,6 +943,41 @@ mod tests {
         );
     }
 
+    #[cfg(feature = "consumer")]
+    #[test]
+    fn verifying_valid_signature_v1_during_secret_rotation() {
+        let signature = "t=1636936200,v0=8a4e57ff0ecd20b68fb645934d68dd1128fce73bc88a7530aaed2e7516b58b3f,v0=1b3d69df55f1e52f05224ba94a5162abeb17ef52cd7f4948c390f810d6a87e98,h=x-event-id x-event-type,v1=b5d41bb81eadc6b6e120f9153f337bc5c219267c0b1c04b0b0533d55dca080fe,v1=bc521546ba5de381b12f135782d2008b028c3065c191760b12b76850a8fc8f51";
+        let payload = "hello !".as_bytes();
+        let header_values = [
+            ("x-event-id", "1a01cb48-5142-4d9b-8f90-d20cca61f0ee"),
+            ("x-event-type", "service.resource.verb"),
+        ];
+        let tolerance = StdDuration::from_secs((i64::MAX / 1000) as u64);
+
+        for subscription_secret in ["new secret", "secret"] {
+            assert!(
+                verify_webhook_signature::<&str, &str>(
+                    signature,
+                    payload,
+                    &header_values,
+                    subscription_secret,
+                    tolerance
+                )
+                .is_ok()
+            );
+        }
+        assert!(
+            verify_webhook_signature::<&str, &str>(
+                signature,
+                payload,
+                &header_values,
+                "another secret",
+                tolerance
+            )
+            .is_err()
+        );
+    }
+
     #[cfg(feature = "consumer")]
     #[test]
     fn verifying_valid_signature_v1_with_current_time() {
@@ -754,4 +998,123 @@ mod tests {
             .is_ok()
         );
     }
+
+    #[cfg(feature = "consumer")]
+    #[test]
+    fn verifying_valid_signature_v2() {
+        let signature = "t=1636936200,h=x-event-id x-event-type,v1=bc521546ba5de381b12f135782d2008b028c3065c191760b12b76850a8fc8f51,k=0cdd6c77-4d4b-4bc1-8e58-8e5c6a6e8b0a,v2=87ef31f30b36d4207855dcfffe757006d4b93221964cd3f214cebf867bf37cac80f23790c7b6498c5e37911f2d41f6c4811998d0da3ae643454bee32411e2b06";
+        let payload = "hello !".as_bytes();
+        let header_values = [
+            ("x-event-id", "1a01cb48-5142-4d9b-8f90-d20cca61f0ee"),
+            ("x-event-type", "service.resource.verb"),
+        ];
+        let jwks = Jwks {
+            keys: vec![
+                Jwk {
+                    kid: "0cdd6c77-4d4b-4bc1-8e58-8e5c6a6e8b0a".to_owned(),
+                    x: "6kpsY-KcUgq-9VB7Ey7F-ZVHdq6-vnuSQh7qaRRG0iw".to_owned(),
+                },
+                Jwk {
+                    kid: "5a1c4cd4-8e07-4f0c-a4d0-6a1b8a3a7f49".to_owned(),
+                    x: "E5j2LG0aRXxRumpLXz29L2n8qTIWIY3ImX5Ba9F9k8o".to_owned(),
+                },
+            ],
+        };
+        let tolerance = StdDuration::from_secs((i64::MAX / 1000) as u64);
+
+        assert!(
+            verify_webhook_signature_v2::<&str, &str>(
+                signature,
+                payload,
+                &header_values,
+                &jwks,
+                tolerance
+            )
+            .is_ok()
+        );
+    }
+
+    #[cfg(feature = "consumer")]
+    #[test]
+    fn verifying_signature_v2_with_unknown_key() {
+        let signature = "t=1636936200,h=x-event-id x-event-type,k=0cdd6c77-4d4b-4bc1-8e58-8e5c6a6e8b0a,v2=87ef31f30b36d4207855dcfffe757006d4b93221964cd3f214cebf867bf37cac80f23790c7b6498c5e37911f2d41f6c4811998d0da3ae643454bee32411e2b06";
+        let payload = "hello !".as_bytes();
+        let header_values = [
+            ("x-event-id", "1a01cb48-5142-4d9b-8f90-d20cca61f0ee"),
+            ("x-event-type", "service.resource.verb"),
+        ];
+        let jwks = Jwks {
+            keys: vec![Jwk {
+                kid: "5a1c4cd4-8e07-4f0c-a4d0-6a1b8a3a7f49".to_owned(),
+                x: "E5j2LG0aRXxRumpLXz29L2n8qTIWIY3ImX5Ba9F9k8o".to_owned(),
+            }],
+        };
+        let tolerance = StdDuration::from_secs((i64::MAX / 1000) as u64);
+
+        assert!(matches!(
+            verify_webhook_signature_v2::<&str, &str>(
+                signature,
+                payload,
+                &header_values,
+                &jwks,
+                tolerance
+            ),
+            Err(Hook0ClientError::UnknownSigningKey(_))
+        ));
+    }
+
+    #[cfg(feature = "consumer")]
+    #[test]
+    fn verifying_valid_standard_webhooks_signature() {
+        let payload = "hello !".as_bytes();
+        let headers = [
+            ("Content-Type", "text/plain"),
+            ("Webhook-Id", "1a01cb48-5142-4d9b-8f90-d20cca61f0ee"),
+            ("Webhook-Timestamp", "1636936200"),
+            (
+                "Webhook-Signature",
+                "v1,jv6nrhFagT2ZrtruVCw4OdKkVhut1Qa4CaD0Pw4/oGk=",
+            ),
+        ];
+        let tolerance = StdDuration::from_secs((i64::MAX / 1000) as u64);
+
+        for secret in ["secret", "whsec_c2VjcmV0"] {
+            assert!(
+                verify_standard_webhooks_signature::<&str, &str>(
+                    payload, &headers, secret, tolerance
+                )
+                .is_ok()
+            );
+        }
+        assert!(
+            verify_standard_webhooks_signature::<&str, &str>(
+                payload,
+                &headers,
+                "another secret",
+                tolerance
+            )
+            .is_err()
+        );
+    }
+
+    #[cfg(feature = "consumer")]
+    #[test]
+    fn verifying_standard_webhooks_signature_with_missing_header() {
+        let payload = "hello !".as_bytes();
+        let headers = [
+            ("webhook-id", "1a01cb48-5142-4d9b-8f90-d20cca61f0ee"),
+            (
+                "webhook-signature",
+                "v1,jv6nrhFagT2ZrtruVCw4OdKkVhut1Qa4CaD0Pw4/oGk=",
+            ),
+        ];
+        let tolerance = StdDuration::from_secs((i64::MAX / 1000) as u64);
+
+        assert!(matches!(
+            verify_standard_webhooks_signature::<&str, &str>(
+                payload, &headers, "secret", tolerance
+            ),
+            Err(Hook0ClientError::MissingHeader(_))
+        ));
+    }
 }
@@ -1,3 +1,4 @@
+use ed25519_dalek::{Signature as Ed25519Signature, VerifyingKey};
 use hmac::{Hmac, KeyInit, Mac};
 use http::HeaderName;
 use sha2::Sha256;
@@ -7,11 +8,17 @@ use tracing::trace;
 
 use crate::Hook0ClientError;
 
+/// Parsed `X-Hook0-Signature` header
+///
+/// There can be several `v0` and `v1` signatures while the secret of the subscription is being rotated (one for each valid secret).
+/// The `v2` signature is made with the signing key of the application whose ID is in the `k` field.
 pub struct Signature {
     pub timestamp: i64,
-    pub v0: Option<Vec<u8>>,
+    pub v0: Vec<Vec<u8>>,
     pub h: Vec<HeaderName>,
-    pub v1: Option<Vec<u8>>,
+    pub v1: Vec<Vec<u8>>,
+    pub k: Option<String>,
+    pub v2: Option<Vec<u8>>,
 }
 
 impl Signature {
@@ -29,10 +36,17 @@ impl Signature {
                     .map(|(k, v)| vec![(k.trim(), v.trim())])
                     .unwrap_or_default()
             })
-            .collect::<HashMap<_, _>>();
+            .collect::<Vec<_>>();
+        let fields = parts.iter().copied().collect::<HashMap<_, _>>();
+        let signatures = |name: &'static str| {
+            parts
+                .iter()
+                .filter(move |(k, _v)| *k == name)
+                .map(|(_k, v)| *v)
+        };
 
-        if parts.len() >= 2 {
-            let t = parts.get("t").copied().ok_or_else(|| {
+        if fields.len() >= 2 {
+            let t = fields.get("t").copied().ok_or_else(|| {
                 Hook0ClientError::SignatureHeaderParsing("Missing 't' field".to_owned())
             })?;
             let timestamp =
@@ -41,17 +55,16 @@ impl Signature {
                     error,
                 })?;
 
-            let v0 = match parts.get("v0").copied() {
-                Some(v0_str) => Some(hex::decode(v0_str).map_err(|error| {
-                    Hook0ClientError::V0SignatureParsing {
+            let v0 = signatures("v0")
+                .map(|v0_str| {
+                    hex::decode(v0_str).map_err(|error| Hook0ClientError::V0SignatureParsing {
                         signature: v0_str.to_owned(),
                         error,
-                    }
-                })?),
-                None => None,
-            };
+                    })
+                })
+                .collect::<Result<Vec<_>, _>>()?;
 
-            let h = match parts.get("h").copied() {
+            let h = match fields.get("h").copied() {
                 Some(h_str) => h_str
                     .split(' ')
                     .map(|h| {
@@ -66,19 +79,35 @@ impl Signature {
                 None => Vec::new(),
             };
 
-            let v1 = match parts.get("v1").copied() {
-                Some(v1_str) => Some(hex::decode(v1_str).map_err(|error| {
-                    Hook0ClientError::V1SignatureParsing {
+            let v1 = signatures("v1")
+                .map(|v1_str| {
+                    hex::decode(v1_str).map_err(|error| Hook0ClientError::V1SignatureParsing {
                         signature: v1_str.to_owned(),
                         error,
-                    }
-                })?),
-                None => None,
-            };
+                    })
+                })
+                .collect::<Result<Vec<_>, _>>()?;
 
-            if v0.is_none() && v1.is_none() {
+            let k = fields.get("k").map(|k| (*k).to_owned());
+
+            let v2 = fields
+                .get("v2")
+                .copied()
+                .map(|v2_str| {
+                    hex::decode(v2_str).map_err(|error| Hook0ClientError::V2SignatureParsing {
+                        signature: v2_str.to_owned(),
+                        error,
+                    })
+                })
+                .transpose()?;
+
+            if v0.is_empty() && v1.is_empty() && v2.is_none() {
+                Err(Hook0ClientError::SignatureHeaderParsing(
+                    "There must be at least one of 'v0', 'v1' or 'v2' field".to_owned(),
+                ))
+            } else if v2.is_some() && k.is_none() {
                 Err(Hook0ClientError::SignatureHeaderParsing(
-                    "There must be at least one of 'v0' or 'v1' field".to_owned(),
+                    "Missing 'k' field for 'v2' field".to_owned(),
                 ))
             } else {
                 Ok(Self {
@@ -86,6 +115,8 @@ impl Signature {
                     v0,
                     h,
                     v1,
+                    k,
+                    v2,
                 })
             }
         } else {
@@ -95,6 +126,7 @@ impl Signature {
         }
     }
 
+    /// Check that one of the signatures was made with the secret
     pub fn verify(&self, payload: &[u8], ordered_header_values: &[String], secret: &str) -> bool {
         let timestamp_str = self.timestamp.to_string();
         let timestamp_str_bytes = timestamp_str.as_bytes();
@@ -104,7 +136,7 @@ impl Signature {
         mac.update(timestamp_str_bytes);
         mac.update(Self::PAYLOAD_SEPARATOR_BYTES);
 
-        if let Some(v1) = self.v1.as_ref() {
+        if !self.v1.is_empty() {
             trace!("Verifying v1 signature...");
 
             mac.update(
@@ -120,18 +152,56 @@ impl Signature {
             );
             mac.update(Self::PAYLOAD_SEPARATOR_BYTES);
             mac.update(payload);
-            mac.verify_slice(v1).is_ok()
-        } else if let Some(v0) = self.v0.as_ref() {
+            self.v1
+                .iter()
+                .any(|v1| mac.clone().verify_slice(v1).is_ok())
+        } else if !self.v0.is_empty() {
             trace!("Verifying v0 signature...");
 
             mac.update(payload);
-            mac.verify_slice(v0).is_ok()
+            self.v0
+                .iter()
+                .any(|v0| mac.clone().verify_slice(v0).is_ok())
         } else {
             // This cannot happen because this error would be raised while parsing the signature
             trace!("Failed to decode signature: no v0 nor v1 field");
             false
         }
     }
+
+    /// Check that the `v2` signature was made with the private key matching the public key
+    pub fn verify_v2(
+        &self,
+        payload: &[u8],
+        ordered_header_values: &[String],
+        public_key: &VerifyingKey,
+    ) -> bool {
+        let Some(v2) = self.v2.as_deref() else {
+            trace!("Failed to verify signature: no v2 field");
+            return false;
+        };
+        let Ok(v2) = Ed25519Signature::from_slice(v2) else {
+            trace!("Failed to decode v2 signature");
+            return false;
+        };
+
+        trace!("Verifying v2 signature...");
+        let signed_content = [
+            self.timestamp.to_string().as_bytes(),
+            Self::PAYLOAD_SEPARATOR_BYTES,
+            self.h
+                .join(Self::SIGNATURE_PART_HEADER_NAMES_SEPARATOR)
+                .as_bytes(),
+            Self::PAYLOAD_SEPARATOR_BYTES,
+            ordered_header_values
+                .join(Self::PAYLOAD_SEPARATOR)
+                .as_bytes(),
+            Self::PAYLOAD_SEPARATOR_BYTES,
+            payload,
+        ]
+        .concat();
+        public_key.verify_strict(&signed_content, &v2).is_ok()
+    }
 }
 
 #[cfg(test)]
@@ -142,9 +212,9 @@ mod tests {
     fn parse_signature_v0() {
         let signature = Signature::parse("t=123,v0=abcd").unwrap();
         assert_eq!(signature.timestamp, 123);
-        assert_eq!(signature.v0, Some(hex::decode("abcd").unwrap()));
+        assert_eq!(signature.v0, vec![hex::decode("abcd").unwrap()]);
         assert_eq!(signature.h, Vec::<HeaderName>::new());
-        assert_eq!(signature.v1, None);
+        assert_eq!(signature.v1, Vec::<Vec<u8>>::new());
     }
 
     #[test]
@@ -163,7 +233,7 @@ mod tests {
     fn parse_signature_v1() {
         let signature = Signature::parse("t=123,h=x-test x-test2,v1=1234").unwrap();
         assert_eq!(signature.timestamp, 123);
-        assert_eq!(signature.v0, None);
+        assert_eq!(signature.v0, Vec::<Vec<u8>>::new());
         assert_eq!(
             signature.h,
             vec![
@@ -171,14 +241,14 @@ mod tests {
                 HeaderName::from_static("x-test2")
             ]
         );
-        assert_eq!(signature.v1, Some(hex::decode("1234").unwrap()));
+        assert_eq!(signature.v1, vec![hex::decode("1234").unwrap()]);
     }
 
     #[test]
     fn parse_signature_v0_v1() {
         let signature = Signature::parse("t=123,v0=abcd,h=x-test x-test2,v1=1234").unwrap();
         assert_eq!(signature.timestamp, 123);
-        assert_eq!(signature.v0, Some(hex::decode("abcd").unwrap()));
+        assert_eq!(signature.v0, vec![hex::decode("abcd").unwrap()]);
         assert_eq!(
             signature.h,
             vec![
@@ -186,19 +256,43 @@ mod tests {
                 HeaderName::from_static("x-test2")
             ]
         );
-        assert_eq!(signature.v1, Some(hex::decode("1234").unwrap()));
+        assert_eq!(signature.v1, vec![hex::decode("1234").unwrap()]);
+    }
+
+    #[test]
+    fn parse_signature_several_secrets() {
+        let signature =
+            Signature::parse("t=123,v0=abcd,v0=ef01,h=x-test x-test2,v1=1234,v1=5678").unwrap();
+        assert_eq!(signature.timestamp, 123);
+        assert_eq!(
+            signature.v0,
+            vec![hex::decode("abcd").unwrap(), hex::decode("ef01").unwrap()]
+        );
+        assert_eq!(
+            signature.h,
+            vec![
+                HeaderName::from_static("x-test"),
+                HeaderName::from_static("x-test2")
+            ]
+        );
+        assert_eq!(
+            signature.v1,
+            vec![hex::decode("1234").unwrap(), hex::decode("5678").unwrap()]
+        );
     }
 
     #[test]
     fn verify_signature_v0_valid() {
         let signature = Signature {
             timestamp: 1636936200,
-            v0: Some(
+            v0: vec![
                 hex::decode("1b3d69df55f1e52f05224ba94a5162abeb17ef52cd7f4948c390f810d6a87e98")
                     .unwrap(),
-            ),
+            ],
             h: Vec::new(),
-            v1: None,
+            v1: Vec::new(),
+            k: None,
+            v2: None,
         };
         let payload = "hello !".as_bytes();
         let secret = "secret";
@@ -209,12 +303,14 @@ mod tests {
     fn verify_signature_v0_invalid() {
         let signature = Signature {
             timestamp: 1636936200,
-            v0: Some(
+            v0: vec![
                 hex::decode("1b3d69df55f1e52f05224ba94a5162abeb17ef52cd7f4948c390f810d6a87e98")
                     .unwrap(),
-            ),
+            ],
             h: Vec::new(),
-            v1: None,
+            v1: Vec::new(),
+            k: None,
+            v2: None,
         };
         let payload = "hello !".as_bytes();
         let secret = "another secret";
@@ -236,19 +332,56 @@ mod tests {
     fn verify_signature_v1_valid() {
         let signature = Signature {
             timestamp: 1636936200,
-            v0: None,
+            v0: Vec::new(),
             h: vec![
                 HeaderName::from_static("x-test"),
                 HeaderName::from_static("x-test2"),
             ],
-            v1: Some(
+            v1: vec![
                 hex::decode("493c35f05443fdb74cb99fd4f00e0e7653c2ab6b24fbc97f4a7bd4d56b31758a")
                     .unwrap(),
-            ),
+            ],
+            k: None,
+            v2: None,
         };
         let payload = "hello !".as_bytes();
         let header_values = vec!["val1".to_owned(), "val2".to_owned()];
         let secret = "secret";
         assert!(signature.verify(payload, &header_values, secret));
     }
+
+    #[test]
+    fn parse_signature_v2() {
+        let signature =
+            Signature::parse("t=123,h=x-test x-test2,v1=1234,k=key-id,v2=abcd").unwrap();
+        assert_eq!(signature.timestamp, 123);
+        assert_eq!(signature.v1, vec![hex::decode("1234").unwrap()]);
+        assert_eq!(signature.k.as_deref(), Some("key-id"));
+        assert_eq!(signature.v2, Some(hex::decode("abcd").unwrap()));
+    }
+
+    #[test]
+    fn parse_signature_v2_without_key_id() {
+        let signature = Signature::parse("t=123,h=x-test x-test2,v2=abcd");
+        assert!(signature.is_err());
+    }
+
+    #[test]
+    fn verify_signature_v2() {
+        let signature = Signature::parse("t=1636936200,h=x-event-id x-event-type,k=0cdd6c77-4d4b-4bc1-8e58-8e5c6a6e8b0a,v2=87ef31f30b36d4207855dcfffe757006d4b93221964cd3f214cebf867bf37cac80f23790c7b6498c5e37911f2d41f6c4811998d0da3ae643454bee32411e2b06").unwrap();
+        let payload = "hello !".as_bytes();
+        let header_values = vec![
+            "1a01cb48-5142-4d9b-8f90-d20cca61f0ee".to_owned(),
+            "service.resource.verb".to_owned(),
+        ];
+        let public_key = VerifyingKey::from_bytes(
+            &hex::decode("ea4a6c63e29c520abef5507b132ec5f9954776aebebe7b92421eea691446d22c")
+                .unwrap()
+                .try_into()
+                .unwrap(),
+        )
+        .unwrap();
+        assert!(signature.verify_v2(payload, &header_values, &public_key));
+        assert!(!signature.verify_v2("hello ?".as_bytes(), &header_values, &public_key));
+    }
 }
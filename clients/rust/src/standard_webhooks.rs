@@ -0,0 +1,100 @@
+use base64::Engine;
+use base64::engine::general_purpose::STANDARD;
+use hmac::{Hmac, KeyInit, Mac};
+use sha2::Sha256;
+use tracing::trace;
+
+/// Headers of a webhook sent according to the [Standard Webhooks](https://www.standardwebhooks.com/) specification
+pub struct StandardWebhooksSignature<'a> {
+    pub id: &'a str,
+    pub timestamp: i64,
+    /// Signatures of the `v1` scheme (HMAC-SHA256); there can be several of them while the secret of the subscription is being rotated
+    pub v1: Vec<Vec<u8>>,
+}
+
+impl<'a> StandardWebhooksSignature<'a> {
+    const SECRET_PREFIX: &'static str = "whsec_";
+
+    /// Parse the values of the `webhook-id`, `webhook-timestamp` and `webhook-signature` headers
+    ///
+    /// Signatures of other schemes than `v1` are ignored.
+    pub fn parse(id: &'a str, timestamp: &str, signature: &str) -> Option<Self> {
+        let timestamp = timestamp.parse().ok()?;
+        let v1 = signature
+            .split(' ')
+            .filter_map(|part| part.split_once(','))
+            .filter(|(version, _)| *version == "v1")
+            .map(|(_, sig)| STANDARD.decode(sig).ok())
+            .collect::<Option<Vec<_>>>()?;
+
+        if v1.is_empty() {
+            trace!("Failed to decode Standard Webhooks signature: no v1 signature");
+            None
+        } else {
+            Some(Self { id, timestamp, v1 })
+        }
+    }
+
+    /// Check that one of the signatures was made with the secret
+    ///
+    /// The secret can be given either as a Standard Webhooks secret (`whsec_` followed by the base64-encoded key) or as the Hook0 subscription secret.
+    pub fn verify(&self, payload: &[u8], secret: &str) -> bool {
+        let key = match secret.strip_prefix(Self::SECRET_PREFIX) {
+            Some(encoded_key) => match STANDARD.decode(encoded_key) {
+                Ok(key) => key,
+                Err(_) => {
+                    trace!("Failed to decode Standard Webhooks secret");
+                    return false;
+                }
+            },
+            None => secret.as_bytes().to_vec(),
+        };
+
+        type HmacSha256 = Hmac<Sha256>;
+        let mut mac = HmacSha256::new_from_slice(&key).unwrap(); // MAC can take key of any size; this should never fail
+        mac.update(self.id.as_bytes());
+        mac.update(b".");
+        mac.update(self.timestamp.to_string().as_bytes());
+        mac.update(b".");
+        mac.update(payload);
+        self.v1
+            .iter()
+            .any(|v1| mac.clone().verify_slice(v1).is_ok())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_signature() {
+        let signature =
+            StandardWebhooksSignature::parse("msg_1", "1636936200", "v1,q80= v1a,AAAA v1,YWJj")
+                .unwrap();
+        assert_eq!(signature.id, "msg_1");
+        assert_eq!(signature.timestamp, 1636936200);
+        assert_eq!(signature.v1, vec![vec![0xab, 0xcd], b"abc".to_vec()]);
+    }
+
+    #[test]
+    fn parse_signature_without_v1() {
+        assert!(StandardWebhooksSignature::parse("msg_1", "1636936200", "v1a,AAAA").is_none());
+        assert!(StandardWebhooksSignature::parse("msg_1", "error", "v1,q80=").is_none());
+    }
+
+    #[test]
+    fn verify_signature() {
+        let signature = StandardWebhooksSignature::parse(
+            "1a01cb48-5142-4d9b-8f90-d20cca61f0ee",
+            "1636936200",
+            "v1,WIJG2BZDrngCgdG1Ol9Ah1ujkq9qX3KBGWp+UTSWHec= v1,jv6nrhFagT2ZrtruVCw4OdKkVhut1Qa4CaD0Pw4/oGk=",
+        )
+        .unwrap();
+        let payload = "hello !".as_bytes();
+        assert!(signature.verify(payload, "secret"));
+        assert!(signature.verify(payload, "whsec_c2VjcmV0"));
+        assert!(signature.verify(payload, "new secret"));
+        assert!(!signature.verify(payload, "another secret"));
+    }
+}
@@ -49,6 +49,7 @@ function generateMarkdown(envVars) {
     'Rate Limiting',
     'Quotas',
     'Housekeeping',
+    'Replay Jobs',
     'Monitoring',
     'Hook0 Client',
     'Object Storage',
@@ -151,13 +152,18 @@ The output-worker is a separate binary with its own configuration. Run \`hook0-o
 | \`CONCURRENT_LP_RESERVED\` | Number of concurrent slots reserved exclusively for low-priority jobs (later retries) | \`0\` |  |
 | \`MAX_RETRIES\` | Maximum number of delivery retries before giving up (the effective number of retries is limited by \`MAX_RETRIES\`, \`MAX_RETRY_WINDOW\` and the retry policy) | \`25\` |  |
 | \`MAX_RETRY_WINDOW\` | Maximum time window for delivery retries before giving up (the effective number of retries is limited by \`MAX_RETRIES\`, \`MAX_RETRY_WINDOW\` and the retry policy) | \`8d\` |  |
+| \`MAX_RETRY_AFTER\` | Maximum delay before the next delivery attempt that a webhook target can request using a \`Retry-After\` header in a 429 or 503 response | \`1h\` |  |
+| \`NON_RETRYABLE_HTTP_CODES\` | A comma-separated list of HTTP status codes that are considered permanent failures: delivery is not retried when the target answers with one of them | \`400,401,403,404,410\` |  |
+| \`RETRY_INVALID_TARGETS\` | If set to true, deliveries to invalid targets (invalid URL, method or headers, forbidden IP) will be retried instead of being considered permanent failures | \`false\` |  |
+| \`CIRCUIT_BREAKER_MAX_CONSECUTIVE_FAILURES\` | If set, subscriptions are automatically disabled after this number of consecutive failed delivery attempts | - |  |
+| \`CIRCUIT_BREAKER_FAILURE_WINDOW\` | If set, subscriptions are automatically disabled when all their delivery attempts failed during this time window | - |  |
 | \`MONITORING_HEARTBEAT_URL\` | Heartbeat URL that should be called regularly | - |  |
 | \`MONITORING_HEARTBEAT_MIN_PERIOD_IN_S\` | Minimal duration (in second) to wait between sending two heartbeats | \`60\` |  |
 | \`DISABLE_TARGET_IP_CHECK\` | If set to false (default), webhooks that target IPs that are not globally reachable (like "127.0.0.1" for example) will fail | \`false\` |  |
 | \`CONNECT_TIMEOUT\` | Timeout for establishing a connection to the target (if exceeded, request attempt will fail) | \`5s\` |  |
 | \`TIMEOUT\` | Timeout for obtaining a HTTP response from the target, including connect phase (if exceeded, request attempt will fail) | \`15s\` |  |
 | \`SIGNATURE_HEADER_NAME\` | Name of the header containing webhook's signature | \`X-Hook0-Signature\` |  |
-| \`ENABLED_SIGNATURE_VERSIONS\` | A comma-separated list of enabled signature versions | \`v1\` |  |
+| \`ENABLED_SIGNATURE_VERSIONS\` | A comma-separated list of enabled signature versions (\`v2\` signs webhooks with the Ed25519 signing key of their application, if it has one; \`standard-webhooks\` adds Standard Webhooks headers to webhooks of subscriptions that do not choose themselves) | \`v1\` |  |
 | \`LOAD_WAITING_REQUEST_ATTEMPTS_INTO_PULSAR\` | Loads request attempts that haven't been delivered yet from the DB into Pulsar before starting work; \`all\` loads everything; \`due-now\` skips request attempts scheduled more than ~10 s in the future; this is useful when migrating to a Pulsar worker (only for Pulsar workers) | \`off\` |  |
 | \`REQUEST_ATTEMPT_DB_COMMIT_GRACE_PERIOD\` | Grace period to wait for database commit before dropping unfound request attempts (only for Pulsar workers) | \`10s\` |  |
 | \`PULSAR_CONSUMER_STATS_INTERVAL\` | Period of Pulsar consumer stats collection (set to "0s" to disable) (only for Pulsar workers) [this feature is unstable/unreliable] | \`0\` |  |
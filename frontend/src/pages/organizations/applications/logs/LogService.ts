@@ -13,6 +13,8 @@ export const enum RequestAttemptStatusType {
   InProgress = 'inprogress',
   Successful = 'successful',
   Failed = 'failed',
+  PermanentlyFailed = 'permanently_failed',
+  Cancelled = 'cancelled',
 }
 
 export type RequestAttemptStatus = {
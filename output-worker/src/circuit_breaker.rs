@@ -0,0 +1,158 @@
+use chrono::{DateTime, Utc};
+use humantime::format_duration;
+use sqlx::{PgConnection, query, query_as};
+use std::time::Duration;
+use tracing::warn;
+use uuid::Uuid;
+
+use crate::Config;
+
+fn is_enabled(config: &Config) -> bool {
+    config.circuit_breaker_max_consecutive_failures.is_some()
+        || config.circuit_breaker_failure_window.is_some()
+}
+
+/// Reset the failure counters of a subscription after a successful delivery
+pub async fn record_success(
+    conn: &mut PgConnection,
+    config: &Config,
+    subscription_id: &Uuid,
+) -> Result<(), sqlx::Error> {
+    if is_enabled(config) {
+        // The condition avoids writing to the subscription row in the usual case
+        query!(
+            "
+                UPDATE webhook.subscription
+                SET consecutive_failures = 0, failing_since = NULL
+                WHERE subscription__id = $1
+                    AND consecutive_failures > 0
+            ",
+            subscription_id,
+        )
+        .execute(conn)
+        .await?;
+    }
+
+    Ok(())
+}
+
+/// Count a failed delivery and disable the subscription if it has been failing for too long
+///
+/// This must be called before computing the next retry so that no retry is scheduled for a subscription that was just disabled.
+pub async fn record_failure(
+    conn: &mut PgConnection,
+    config: &Config,
+    subscription_id: &Uuid,
+) -> Result<(), sqlx::Error> {
+    if !is_enabled(config) {
+        return Ok(());
+    }
+
+    struct Failures {
+        consecutive_failures: i32,
+        failing_since: Option<DateTime<Utc>>,
+    }
+    let failures = query_as!(
+        Failures,
+        "
+            UPDATE webhook.subscription
+            SET consecutive_failures = consecutive_failures + 1,
+                failing_since = COALESCE(failing_since, statement_timestamp())
+            WHERE subscription__id = $1
+                AND is_enabled
+                AND deleted_at IS NULL
+            RETURNING consecutive_failures, failing_since
+        ",
+        subscription_id,
+    )
+    .fetch_optional(&mut *conn)
+    .await?;
+
+    if let Some(failures) = failures
+        && let Some(reason) = trip_reason(
+            config.circuit_breaker_max_consecutive_failures,
+            config.circuit_breaker_failure_window,
+            failures.consecutive_failures,
+            failures
+                .failing_since
+                .and_then(|since| (Utc::now() - since).to_std().ok()),
+        )
+    {
+        warn!(%subscription_id, "Disabling subscription: {reason}");
+        query!(
+            "
+                UPDATE webhook.subscription
+                SET is_enabled = false, disabled_at = statement_timestamp(), disabled_reason = $2, disabled_notified_at = NULL
+                WHERE subscription__id = $1
+            ",
+            subscription_id,
+            reason,
+        )
+        .execute(conn)
+        .await?;
+    }
+
+    Ok(())
+}
+
+fn trip_reason(
+    max_consecutive_failures: Option<u32>,
+    failure_window: Option<Duration>,
+    consecutive_failures: i32,
+    failing_for: Option<Duration>,
+) -> Option<String> {
+    let consecutive_failures = u32::try_from(consecutive_failures).unwrap_or(0);
+
+    if let Some(max) = max_consecutive_failures
+        && consecutive_failures >= max
+    {
+        Some(format!(
+            "delivery failed {consecutive_failures} times in a row"
+        ))
+    } else if let Some(window) = failure_window
+        && let Some(failing_for) = failing_for
+        && failing_for >= window
+    {
+        Some(format!(
+            "delivery kept failing for more than {}",
+            format_duration(window)
+        ))
+    } else {
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn trip_on_consecutive_failures() {
+        assert_eq!(trip_reason(Some(10), None, 9, None), None);
+        assert_eq!(
+            trip_reason(Some(10), None, 10, None).as_deref(),
+            Some("delivery failed 10 times in a row")
+        );
+    }
+
+    #[test]
+    fn trip_on_failure_window() {
+        let window = Duration::from_hours(24);
+        assert_eq!(
+            trip_reason(None, Some(window), 1000, Some(Duration::from_hours(23))),
+            None
+        );
+        assert_eq!(
+            trip_reason(None, Some(window), 3, Some(Duration::from_hours(25))).as_deref(),
+            Some("delivery kept failing for more than 1day")
+        );
+    }
+
+    #[test]
+    fn never_trip_when_disabled() {
+        assert_eq!(
+            trip_reason(None, None, 1000, Some(Duration::from_hours(1000))),
+            None
+        );
+    }
+}
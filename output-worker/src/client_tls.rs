@@ -0,0 +1,141 @@
+use reqwest::{Certificate, Identity};
+use sqlx::{Acquire, Postgres, query};
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex, MutexGuard};
+use tracing::debug;
+use uuid::Uuid;
+
+use crate::encryption_key::EncryptionKey;
+
+/// TLS settings of the HTTP target of a subscription, as stored in `webhook.target_http` by the API
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct TargetTls {
+    pub client_certificate_id: Option<Uuid>,
+    pub ca_certificates: Option<String>,
+}
+
+impl TargetTls {
+    fn is_default(&self) -> bool {
+        self.client_certificate_id.is_none() && self.ca_certificates.is_none()
+    }
+}
+
+/// TLS material used by the HTTP client that calls a target
+#[derive(Clone)]
+pub struct ClientTls {
+    /// Client certificate and private key presented to the target (mutual TLS)
+    pub identity: Option<Identity>,
+    /// Certificate authorities trusted instead of the public ones to verify the certificate of the target
+    pub ca_certificates: Option<Vec<Certificate>>,
+}
+
+/// Keeps the TLS material of subscriptions within this worker process
+///
+/// Private keys are only decrypted and parsed again when the TLS settings of the target of a subscription change.
+#[derive(Default)]
+pub struct ClientTlsCache {
+    subscriptions: Mutex<HashMap<Uuid, (TargetTls, Arc<ClientTls>)>>,
+}
+
+impl ClientTlsCache {
+    /// Get the TLS material to use for a delivery to the target of a subscription
+    ///
+    /// The inner result is `Ok(None)` if the target uses the default TLS settings, and an error message if its TLS settings cannot be used (this should be reported as an invalid target).
+    /// The outer result only holds errors that are not related to the target, such as database connection errors.
+    pub async fn get<'a, A: Acquire<'a, Database = Postgres>>(
+        &self,
+        db: A,
+        encryption_key: Option<&EncryptionKey>,
+        subscription_id: Uuid,
+        target: TargetTls,
+    ) -> Result<Result<Option<Arc<ClientTls>>, String>, sqlx::Error> {
+        if target.is_default() {
+            self.lock().remove(&subscription_id);
+            return Ok(Ok(None));
+        }
+
+        if let Some((cached_target, client_tls)) = self.lock().get(&subscription_id)
+            && *cached_target == target
+        {
+            return Ok(Ok(Some(client_tls.clone())));
+        }
+
+        debug!(%subscription_id, "Loading TLS material of subscription target");
+        match load(db, encryption_key, &target).await? {
+            Ok(client_tls) => {
+                let client_tls = Arc::new(client_tls);
+                self.lock()
+                    .insert(subscription_id, (target, client_tls.clone()));
+                Ok(Ok(Some(client_tls)))
+            }
+            Err(msg) => Ok(Err(msg)),
+        }
+    }
+
+    fn lock(&self) -> MutexGuard<'_, HashMap<Uuid, (TargetTls, Arc<ClientTls>)>> {
+        self.subscriptions
+            .lock()
+            .expect("client TLS cache mutex was poisoned")
+    }
+}
+
+async fn load<'a, A: Acquire<'a, Database = Postgres>>(
+    db: A,
+    encryption_key: Option<&EncryptionKey>,
+    target: &TargetTls,
+) -> Result<Result<ClientTls, String>, sqlx::Error> {
+    let identity = match target.client_certificate_id {
+        Some(client_certificate_id) => {
+            let Some(encryption_key) = encryption_key else {
+                return Ok(Err(
+                    "Target uses a client certificate but this worker has no key to decrypt it"
+                        .to_owned(),
+                ));
+            };
+
+            let mut conn = db.acquire().await?;
+            let Some(client_certificate) = query!(
+                "
+                    SELECT certificate, encrypted_private_key
+                    FROM webhook.client_certificate
+                    WHERE client_certificate__id = $1
+                ",
+                client_certificate_id,
+            )
+            .fetch_optional(&mut *conn)
+            .await?
+            else {
+                return Ok(Err("Client certificate does not exist".to_owned()));
+            };
+            let Some(private_key) = encryption_key
+                .decrypt(&client_certificate.encrypted_private_key)
+                .and_then(|private_key| String::from_utf8(private_key).ok())
+            else {
+                return Ok(Err(
+                    "Could not decrypt client certificate; the key of this worker may be wrong"
+                        .to_owned(),
+                ));
+            };
+
+            let pem = format!("{}\n{}", client_certificate.certificate, private_key);
+            match Identity::from_pem(pem.as_bytes()) {
+                Ok(identity) => Some(identity),
+                Err(e) => return Ok(Err(format!("Client certificate is not valid: {e}"))),
+            }
+        }
+        None => None,
+    };
+
+    let ca_certificates = match target.ca_certificates.as_deref() {
+        Some(pem) => match Certificate::from_pem_bundle(pem.as_bytes()) {
+            Ok(certificates) => Some(certificates),
+            Err(e) => return Ok(Err(format!("CA certificates are not valid: {e}"))),
+        },
+        None => None,
+    };
+
+    Ok(Ok(ClientTls {
+        identity,
+        ca_certificates,
+    }))
+}
@@ -0,0 +1,191 @@
+use base64::Engine;
+use base64::engine::general_purpose::STANDARD as Base64;
+use serde_json::Value;
+use sqlx::{Acquire, Postgres, query_scalar};
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex, MutexGuard};
+use tracing::debug;
+use uuid::Uuid;
+
+use crate::encryption_key::EncryptionKey;
+
+/// Secrets and headers of a subscription as they are stored in the database
+///
+/// If the instance has a key to encrypt them, secrets and sensitive headers are encrypted by the API with the data key of the application (which is itself encrypted with the instance key), and `headers` only holds placeholders for sensitive headers.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StoredCredentials {
+    pub headers: Value,
+    /// Base64-encoded encrypted values of sensitive headers, by header name
+    pub encrypted_headers: Value,
+    pub secret: Option<Uuid>,
+    pub encrypted_secret: Option<Vec<u8>>,
+    /// Previous secret of the subscription, if it was rotated and its grace period is not over
+    pub previous_secret: Option<Uuid>,
+    pub encrypted_previous_secret: Option<Vec<u8>>,
+}
+
+impl StoredCredentials {
+    fn is_encrypted(&self) -> bool {
+        self.encrypted_secret.is_some()
+            || self.encrypted_previous_secret.is_some()
+            || self
+                .encrypted_headers
+                .as_object()
+                .is_some_and(|encrypted_headers| !encrypted_headers.is_empty())
+    }
+}
+
+/// Secrets and headers used to deliver the webhooks of a subscription
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SubscriptionCredentials {
+    pub secret: Uuid,
+    /// Previous secret of the subscription, if it was rotated and its grace period is not over
+    pub previous_secret: Option<Uuid>,
+    pub headers: Value,
+}
+
+/// Keeps the decrypted credentials of subscriptions within this worker process
+///
+/// Credentials are only decrypted again when the secrets or headers of a subscription change.
+#[derive(Default)]
+pub struct SubscriptionCredentialsCache {
+    subscriptions: Mutex<HashMap<Uuid, (StoredCredentials, Arc<SubscriptionCredentials>)>>,
+}
+
+impl SubscriptionCredentialsCache {
+    /// Get the credentials to use for a delivery to the target of a subscription
+    ///
+    /// The inner result is an error message if the credentials cannot be decrypted.
+    /// The outer result only holds errors that are not related to the credentials, such as database connection errors.
+    pub async fn get<'a, A: Acquire<'a, Database = Postgres>>(
+        &self,
+        db: A,
+        encryption_key: Option<&EncryptionKey>,
+        application_id: Uuid,
+        subscription_id: Uuid,
+        stored: StoredCredentials,
+    ) -> Result<Result<Arc<SubscriptionCredentials>, String>, sqlx::Error> {
+        if !stored.is_encrypted() {
+            self.lock().remove(&subscription_id);
+            return Ok(stored
+                .secret
+                .map(|secret| {
+                    Arc::new(SubscriptionCredentials {
+                        secret,
+                        previous_secret: stored.previous_secret,
+                        headers: stored.headers,
+                    })
+                })
+                .ok_or_else(|| "Subscription has no secret".to_owned()));
+        }
+
+        if let Some((cached_stored, credentials)) = self.lock().get(&subscription_id)
+            && *cached_stored == stored
+        {
+            return Ok(Ok(credentials.clone()));
+        }
+
+        debug!(%subscription_id, "Decrypting credentials of subscription");
+        match decrypt_credentials(db, encryption_key, application_id, &stored).await? {
+            Ok(credentials) => {
+                let credentials = Arc::new(credentials);
+                self.lock()
+                    .insert(subscription_id, (stored, credentials.clone()));
+                Ok(Ok(credentials))
+            }
+            Err(msg) => Ok(Err(msg)),
+        }
+    }
+
+    fn lock(
+        &self,
+    ) -> MutexGuard<'_, HashMap<Uuid, (StoredCredentials, Arc<SubscriptionCredentials>)>> {
+        self.subscriptions
+            .lock()
+            .expect("subscription credentials cache mutex was poisoned")
+    }
+}
+
+async fn decrypt_credentials<'a, A: Acquire<'a, Database = Postgres>>(
+    db: A,
+    encryption_key: Option<&EncryptionKey>,
+    application_id: Uuid,
+    stored: &StoredCredentials,
+) -> Result<Result<SubscriptionCredentials, String>, sqlx::Error> {
+    let Some(encryption_key) = encryption_key else {
+        return Ok(Err(
+            "Subscription credentials are encrypted but this worker has no key to decrypt them"
+                .to_owned(),
+        ));
+    };
+
+    let mut conn = db.acquire().await?;
+    let Some(encrypted_data_key) = query_scalar!(
+        "
+            SELECT encrypted_data_key
+            FROM webhook.application_data_key
+            WHERE application__id = $1
+        ",
+        application_id,
+    )
+    .fetch_optional(&mut *conn)
+    .await?
+    else {
+        return Ok(Err(
+            "Application has no data key to decrypt the credentials of its subscriptions"
+                .to_owned(),
+        ));
+    };
+    let Some(data_key) = encryption_key.decrypt_key(&encrypted_data_key) else {
+        return Ok(Err(
+            "Could not decrypt the data key of the application; the key of this worker may be wrong"
+                .to_owned(),
+        ));
+    };
+
+    let decrypt_secret =
+        |encrypted_secret: Option<&Vec<u8>>, fallback: Option<Uuid>| match encrypted_secret {
+            Some(encrypted_secret) => data_key
+                .decrypt(encrypted_secret)
+                .and_then(|secret| Uuid::from_slice(&secret).ok())
+                .map(Some)
+                .ok_or_else(|| "Could not decrypt subscription secret".to_owned()),
+            None => Ok(fallback),
+        };
+    let secret = match decrypt_secret(stored.encrypted_secret.as_ref(), stored.secret) {
+        Ok(Some(secret)) => secret,
+        Ok(None) => return Ok(Err("Subscription has no secret".to_owned())),
+        Err(msg) => return Ok(Err(msg)),
+    };
+    let previous_secret = match decrypt_secret(
+        stored.encrypted_previous_secret.as_ref(),
+        stored.previous_secret,
+    ) {
+        Ok(previous_secret) => previous_secret,
+        Err(msg) => return Ok(Err(msg)),
+    };
+
+    let mut headers = stored.headers.to_owned();
+    if let (Some(headers), Some(encrypted_headers)) = (
+        headers.as_object_mut(),
+        stored.encrypted_headers.as_object(),
+    ) {
+        for (name, encrypted_value) in encrypted_headers {
+            let Some(value) = encrypted_value
+                .as_str()
+                .and_then(|encrypted_value| Base64.decode(encrypted_value).ok())
+                .and_then(|encrypted_value| data_key.decrypt(&encrypted_value))
+                .and_then(|value| String::from_utf8(value).ok())
+            else {
+                return Ok(Err(format!("Could not decrypt target header {name}")));
+            };
+            headers.insert(name.to_owned(), Value::String(value));
+        }
+    }
+
+    Ok(Ok(SubscriptionCredentials {
+        secret,
+        previous_secret,
+        headers,
+    }))
+}
@@ -0,0 +1,103 @@
+use aes_gcm::aead::Aead;
+use aes_gcm::{Aes256Gcm, KeyInit};
+use base64::Engine;
+use base64::engine::general_purpose::STANDARD as Base64;
+use std::str::FromStr;
+
+/// Size of the nonce that is stored before each ciphertext
+const NONCE_SIZE: usize = 12;
+
+/// AES-256-GCM key that decrypts target credentials (key encryption key of the instance or data key of an application)
+///
+/// Decryption happens in the worker so that keys are never sent to the database.
+#[derive(Clone)]
+pub struct EncryptionKey([u8; 32]);
+
+impl EncryptionKey {
+    /// Decrypt data that was encrypted by the API (nonce followed by the ciphertext)
+    ///
+    /// Returns `None` if the data was not encrypted with this key or was tampered with.
+    pub fn decrypt(&self, data: &[u8]) -> Option<Vec<u8>> {
+        let (nonce, ciphertext) = data.split_at_checked(NONCE_SIZE)?;
+        let nonce: [u8; NONCE_SIZE] = nonce.try_into().ok()?;
+        Aes256Gcm::new(&self.0.into())
+            .decrypt(&nonce.into(), ciphertext)
+            .ok()
+    }
+
+    /// Decrypt a data key of an application
+    pub fn decrypt_key(&self, encrypted_key: &[u8]) -> Option<Self> {
+        self.decrypt(encrypted_key)
+            .and_then(|key| <[u8; 32]>::try_from(key.as_slice()).ok())
+            .map(Self)
+    }
+}
+
+impl FromStr for EncryptionKey {
+    type Err = String;
+
+    /// Parse a key encoded in base64 (for example generated with `openssl rand -base64 32`)
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let key = Base64
+            .decode(s.trim())
+            .map_err(|e| format!("key is not valid base64: {e}"))?;
+        <[u8; 32]>::try_from(key.as_slice())
+            .map(Self)
+            .map_err(|_| format!("key must be 32 bytes long, not {}", key.len()))
+    }
+}
+
+impl std::fmt::Debug for EncryptionKey {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_tuple("EncryptionKey").field(&"[REDACTED]").finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use aes_gcm::Nonce;
+    use aes_gcm::aead::Generate;
+
+    /// Same format as the one produced by the API
+    fn encrypt(key: &[u8; 32], plaintext: &[u8]) -> Vec<u8> {
+        let nonce = Nonce::generate();
+        let mut data = nonce.to_vec();
+        data.extend(
+            Aes256Gcm::new(key.into())
+                .encrypt(&nonce, plaintext)
+                .unwrap(),
+        );
+        data
+    }
+
+    #[test]
+    fn decrypt() {
+        let key = EncryptionKey([1; 32]);
+        let data = encrypt(&[1; 32], b"secret");
+
+        assert_eq!(key.decrypt(&data).as_deref(), Some(b"secret".as_slice()));
+        assert_eq!(EncryptionKey([2; 32]).decrypt(&data), None);
+        assert_eq!(key.decrypt(&data[..NONCE_SIZE]), None);
+        assert_eq!(key.decrypt(b"short"), None);
+
+        let mut tampered = data.clone();
+        *tampered.last_mut().unwrap() ^= 1;
+        assert_eq!(key.decrypt(&tampered), None);
+
+        let data_key = key.decrypt_key(&encrypt(&[1; 32], &[3; 32])).unwrap();
+        assert_eq!(data_key.0, [3; 32]);
+        assert!(key.decrypt_key(&data).is_none());
+    }
+
+    #[test]
+    fn from_str() {
+        let key = EncryptionKey::from_str(&Base64.encode([4; 32])).unwrap();
+        assert_eq!(key.0, [4; 32]);
+        assert_eq!(format!("{key:?}"), r#"EncryptionKey("[REDACTED]")"#);
+
+        assert!(EncryptionKey::from_str(&Base64.encode([4; 16])).is_err());
+        assert!(EncryptionKey::from_str("not base64!").is_err());
+    }
+}
@@ -1,8 +1,17 @@
+mod circuit_breaker;
+mod client_tls;
+mod credentials;
+mod encryption_key;
 mod monitoring;
+mod oauth2;
 mod opentelemetry;
 mod pg;
 mod pulsar;
+mod rate_limit;
+mod retry_policy;
+mod signing_key;
 mod throughput_log;
+mod transformation;
 mod work;
 
 use ::pulsar::{Authentication, ConnectionRetryOptions, Pulsar, TokioExecutor};
@@ -32,7 +41,9 @@ use tokio_util::task::TaskTracker;
 use tracing::{debug, error, info, warn};
 use uuid::Uuid;
 
+use crate::encryption_key::EncryptionKey;
 use crate::pulsar::LoadMode;
+use crate::retry_policy::RetryPolicy;
 use crate::work::*;
 use hook0_protobuf::RequestAttempt;
 
@@ -40,6 +51,8 @@ use hook0_protobuf::RequestAttempt;
 enum SignatureVersion {
     V0,
     V1,
+    V2,
+    StandardWebhooks,
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
@@ -188,6 +201,31 @@ struct Config {
     #[clap(long, env, value_parser = humantime::parse_duration, default_value = "8d")]
     max_retry_window: Duration,
 
+    /// Maximum delay before the next delivery attempt that a webhook target can request using a `Retry-After` header in a 429 or 503 response
+    #[clap(long, env, value_parser = humantime::parse_duration, default_value = "1h")]
+    max_retry_after: Duration,
+
+    /// A comma-separated list of HTTP status codes that are considered permanent failures: delivery is not retried when the target answers with one of them
+    #[clap(
+        long,
+        env,
+        default_value = "400,401,403,404,410",
+        value_delimiter = ','
+    )]
+    non_retryable_http_codes: Vec<u16>,
+
+    /// If set to true, deliveries to invalid targets (invalid URL, method or headers, forbidden IP) will be retried instead of being considered permanent failures
+    #[clap(long, env, default_value_t = false)]
+    retry_invalid_targets: bool,
+
+    /// If set, subscriptions are automatically disabled after this number of consecutive failed delivery attempts
+    #[clap(long, env)]
+    circuit_breaker_max_consecutive_failures: Option<u32>,
+
+    /// If set, subscriptions are automatically disabled when all their delivery attempts failed during this time window
+    #[clap(long, env, value_parser = humantime::parse_duration)]
+    circuit_breaker_failure_window: Option<Duration>,
+
     /// Heartbeat URL that should be called regularly
     #[clap(long, env)]
     monitoring_heartbeat_url: Option<Url>,
@@ -208,11 +246,15 @@ struct Config {
     #[clap(long, env, value_parser = humantime::parse_duration, default_value = "15s")]
     timeout: Duration,
 
+    /// Key used to decrypt the credentials of subscription targets (private keys of client certificates, OAuth2 client secrets, subscription secrets and sensitive headers) and the private keys that sign webhooks, encoded in base64; it must be the same as the API's; deliveries that need such credentials fail if it is not set
+    #[clap(long, env, hide_env_values = true)]
+    target_credentials_encryption_key: Option<EncryptionKey>,
+
     /// Name of the header containing webhook's signature
     #[clap(long, env, default_value = "X-Hook0-Signature")]
     signature_header_name: HeaderName,
 
-    /// A comma-separated list of enabled signature versions
+    /// A comma-separated list of enabled signature versions (`v2` signs webhooks with the Ed25519 signing key of their application, if it has one; `standard-webhooks` adds Standard Webhooks headers to webhooks of subscriptions that do not choose themselves)
     #[clap(long, env, default_value = "v1", value_delimiter = ',')]
     enabled_signature_versions: Vec<SignatureVersion>,
 
@@ -349,10 +391,28 @@ pub struct RequestAttemptWithOptionalPayload {
     pub http_method: String,
     pub http_url: String,
     pub http_headers: serde_json::Value,
+    pub client_certificate_id: Option<Uuid>,
+    pub ca_certificates: Option<String>,
+    pub oauth2_token_url: Option<String>,
+    pub oauth2_client_id: Option<String>,
+    pub oauth2_encrypted_client_secret: Option<Vec<u8>>,
+    pub oauth2_scopes: Option<Vec<String>>,
     pub event_type_name: String,
     pub payload: Option<Vec<u8>>,
     pub payload_content_type: String,
-    pub secret: Uuid,
+    pub encrypted_headers: serde_json::Value,
+    pub secret: Option<Uuid>,
+    pub encrypted_secret: Option<Vec<u8>>,
+    /// Previous secret of the subscription, if it was rotated and its grace period is not over
+    pub previous_secret: Option<Uuid>,
+    pub encrypted_previous_secret: Option<Vec<u8>>,
+    pub max_requests_per_second: Option<i32>,
+    pub max_in_flight: Option<i32>,
+    pub batch_max_size: Option<i32>,
+    pub payload_transformation: Option<serde_json::Value>,
+    pub standard_webhooks: Option<String>,
+    pub event_labels: serde_json::Value,
+    pub event_metadata: Option<serde_json::Value>,
 }
 
 #[tokio::main]
@@ -613,6 +673,24 @@ async fn main() -> anyhow::Result<()> {
         });
     }
 
+    // Per-subscription delivery limits are shared by all units of this worker
+    let rate_limiter = Arc::new(rate_limit::RateLimiter::default());
+
+    // So is the TLS material of subscription targets
+    let client_tls_cache = Arc::new(client_tls::ClientTlsCache::default());
+
+    // And the OAuth2 access tokens of subscription targets
+    let oauth2_client_cache = Arc::new(oauth2::OAuth2ClientCache::default());
+
+    // And the signing keys of applications
+    let signing_key_cache = Arc::new(signing_key::SigningKeyCache::default());
+
+    // And the decrypted secrets and headers of subscriptions
+    let credentials_cache = Arc::new(credentials::SubscriptionCredentialsCache::default());
+
+    // And the compiled payload transformations of subscriptions
+    let transformation_cache = Arc::new(transformation::TransformationCache::default());
+
     // This task waits for a soft termination signal
     let task_tracker_signal = task_tracker.clone();
     tasks.spawn(async move {
@@ -737,6 +815,12 @@ async fn main() -> anyhow::Result<()> {
             }
 
             let stats_pulsar = stats.clone();
+            let rate_limiter_pulsar = rate_limiter.clone();
+            let client_tls_cache_pulsar = client_tls_cache.clone();
+            let oauth2_client_cache_pulsar = oauth2_client_cache.clone();
+            let signing_key_cache_pulsar = signing_key_cache.clone();
+            let credentials_cache_pulsar = credentials_cache.clone();
+            let transformation_cache_pulsar = transformation_cache.clone();
             tasks.spawn(async move {
                 loop {
                     let result = pulsar::look_for_work(
@@ -750,6 +834,12 @@ async fn main() -> anyhow::Result<()> {
                         heartbeat_tx.clone(),
                         &task_tracker_main,
                         &stats_pulsar,
+                        &rate_limiter_pulsar,
+                        &client_tls_cache_pulsar,
+                        &oauth2_client_cache_pulsar,
+                        &signing_key_cache_pulsar,
+                        &credentials_cache_pulsar,
+                        &transformation_cache_pulsar,
                     )
                     .await;
                     if let Err(ref e) = result {
@@ -783,6 +873,12 @@ async fn main() -> anyhow::Result<()> {
             let cfg = config.to_owned();
             let tt = task_tracker_main.clone();
             let stats_pg = stats.clone();
+            let rate_limiter_pg = rate_limiter.clone();
+            let client_tls_cache_pg = client_tls_cache.clone();
+            let oauth2_client_cache_pg = oauth2_client_cache.clone();
+            let signing_key_cache_pg = signing_key_cache.clone();
+            let credentials_cache_pg = credentials_cache.clone();
+            let transformation_cache_pg = transformation_cache.clone();
             task_tracker_main.spawn(async move {
                 // Start units progressively
                 sleep(Duration::from_millis(u64::from(unit_id) * 100)).await;
@@ -799,6 +895,12 @@ async fn main() -> anyhow::Result<()> {
                         tx.clone(),
                         &tt,
                         &stats_pg,
+                        &rate_limiter_pg,
+                        &client_tls_cache_pg,
+                        &oauth2_client_cache_pg,
+                        &signing_key_cache_pg,
+                        &credentials_cache_pg,
+                        &transformation_cache_pg,
                     )
                     .await;
                     if let Err(ref e) = t {
@@ -890,8 +992,10 @@ async fn compute_next_retry(
     conn: &mut PgConnection,
     attempt: &RequestAttempt,
     response: &Response,
-    max_retries: u8,
+    config: &Config,
 ) -> Result<Option<Duration>, sqlx::Error> {
+    let max_retries = config.max_retries;
+
     match response.response_error {
         Some(ResponseError::InvalidHeader) => {
             let msg = response
@@ -902,8 +1006,15 @@ async fn compute_next_retry(
             error!(request_attempt_id = %attempt.request_attempt_id, "Could not construct signature ({msg}); giving up");
             Ok(None)
         }
+        _ if response.is_permanent_failure(
+            &config.non_retryable_http_codes,
+            config.retry_invalid_targets,
+        ) =>
+        {
+            info!(request_attempt_id = %attempt.request_attempt_id, http_code = ?response.http_code, response_error = ?response.response_error, "Request attempt failed permanently; giving up");
+            Ok(None)
+        }
         _ => {
-            // Temporary warning message; this will be replaced by actual actions at some point
             if let Some(ResponseError::InvalidTarget) = response.response_error {
                 let msg = response
                     .body
@@ -913,34 +1024,93 @@ async fn compute_next_retry(
                 warn!(request_attempt_id = %attempt.request_attempt_id, "Invalid target ({msg}); continuing as normal");
             }
 
+            // The event is locked until the retry is created, so that a concurrent cancellation of its delivery either prevents the retry or also cancels it
             let sub = query!(
                 "
-                    SELECT true AS whatever
+                    SELECT s.retry_policy
                     FROM webhook.subscription AS s
                     INNER JOIN event.application AS a ON a.application__id = s.application__id
+                    INNER JOIN event.event AS e ON e.application__id = a.application__id
                     WHERE s.subscription__id = $1
                         AND s.deleted_at IS NULL
                         AND s.is_enabled
                         AND a.deleted_at IS NULL
+                        AND e.event__id = $2
+                        AND e.delivery_cancelled_at IS NULL
+                    FOR SHARE OF e
                 ",
-                attempt.subscription_id
+                attempt.subscription_id,
+                attempt.event_id,
             )
             .fetch_optional(conn)
             .await?;
 
-            if sub.is_some() {
-                Ok(compute_next_retry_duration(
-                    max_retries,
-                    attempt.retry_count,
-                ))
+            let elapsed = (Utc::now() - attempt.event_received_at)
+                .to_std()
+                .unwrap_or(Duration::ZERO);
+            let (next_retry, retry_policy) = if let Some(sub) = sub {
+                let retry_policy = sub.retry_policy.and_then(|rp| {
+                    serde_json::from_value::<RetryPolicy>(rp)
+                        .inspect_err(|e| {
+                            warn!(subscription_id = %attempt.subscription_id, "Could not parse retry policy of subscription ({e}); using default retry policy");
+                        })
+                        .ok()
+                });
+
+                let next_retry = match &retry_policy {
+                    // Retries are still bounded by MAX_RETRIES when a subscription has its own retry policy
+                    Some(rp) if attempt.retry_count < max_retries.into() => {
+                        rp.next_retry_duration(attempt.retry_count, elapsed)
+                    }
+                    Some(_) => None,
+                    None => compute_next_retry_duration(max_retries, attempt.retry_count),
+                };
+                (next_retry, retry_policy)
             } else {
-                // If the subscription was disabled or soft-deleted (or its application was deleted), we do not schedule a next attempt
-                Ok(None)
+                // If the subscription was disabled or soft-deleted (or its application was deleted), or if the delivery of the event was cancelled, we do not schedule a next attempt
+                (None, None)
+            };
+
+            let retry_after = response.retry_after(Utc::now());
+            if let Some(retry_after) = retry_after {
+                debug!(request_attempt_id = %attempt.request_attempt_id, "Target asked to retry in {}", format_duration(retry_after));
             }
+            Ok(next_retry.and_then(|retry_in| {
+                postpone_retry(
+                    retry_in,
+                    retry_after,
+                    config.max_retry_after,
+                    retry_policy.as_ref(),
+                    elapsed,
+                )
+            }))
         }
     }
 }
 
+/// Apply the delay the target asked for through a `Retry-After` header to the delay given by the retry schedule
+///
+/// The target can postpone the next attempt (by up to `max_retry_after`) but not bring it forward.
+/// If the postponed attempt would be too late for the retry policy of the subscription, we give up.
+fn postpone_retry(
+    retry_in: Duration,
+    retry_after: Option<Duration>,
+    max_retry_after: Duration,
+    retry_policy: Option<&RetryPolicy>,
+    elapsed: Duration,
+) -> Option<Duration> {
+    let Some(retry_after) = retry_after else {
+        return Some(retry_in);
+    };
+
+    let retry_in = retry_in.max(retry_after.min(max_retry_after));
+    if retry_policy.is_some_and(|rp| rp.exceeds_max_age(elapsed, retry_in)) {
+        None
+    } else {
+        Some(retry_in)
+    }
+}
+
 fn compute_next_retry_duration(max_retries: u8, retry_count: i16) -> Option<Duration> {
     if retry_count < max_retries.into() {
         match retry_count {
@@ -1003,6 +1173,82 @@ mod tests {
         assert_eq!(compute_next_retry_duration(0, 0), None);
     }
 
+    #[test]
+    fn test_postpone_retry_does_not_bring_retries_forward() {
+        let max_retry_after = Duration::from_hours(1);
+        let retry_in = Duration::from_secs(30);
+        assert_eq!(
+            postpone_retry(retry_in, None, max_retry_after, None, Duration::ZERO),
+            Some(retry_in)
+        );
+        assert_eq!(
+            postpone_retry(
+                retry_in,
+                Some(Duration::ZERO),
+                max_retry_after,
+                None,
+                Duration::ZERO
+            ),
+            Some(retry_in)
+        );
+        assert_eq!(
+            postpone_retry(
+                retry_in,
+                Some(Duration::from_secs(120)),
+                max_retry_after,
+                None,
+                Duration::ZERO
+            ),
+            Some(Duration::from_secs(120))
+        );
+        assert_eq!(
+            postpone_retry(
+                retry_in,
+                Some(Duration::from_hours(5)),
+                max_retry_after,
+                None,
+                Duration::ZERO
+            ),
+            Some(max_retry_after)
+        );
+    }
+
+    #[test]
+    fn test_postpone_retry_respects_max_age() {
+        let retry_policy = RetryPolicy {
+            strategy: retry_policy::RetryStrategy::Linear,
+            initial_delay_in_s: Some(10),
+            multiplier: None,
+            increment_in_s: None,
+            max_delay_in_s: None,
+            delays_in_s: None,
+            max_attempts: 5,
+            max_age_in_s: Some(60),
+        };
+        let retry_in = Duration::from_secs(10);
+        let elapsed = Duration::from_secs(30);
+        assert_eq!(
+            postpone_retry(
+                retry_in,
+                Some(Duration::from_secs(20)),
+                Duration::from_hours(1),
+                Some(&retry_policy),
+                elapsed
+            ),
+            Some(Duration::from_secs(20))
+        );
+        assert_eq!(
+            postpone_retry(
+                retry_in,
+                Some(Duration::from_secs(40)),
+                Duration::from_hours(1),
+                Some(&retry_policy),
+                elapsed
+            ),
+            None
+        );
+    }
+
     #[test]
     fn test_evaluate_retry_policy_unlimited_window() {
         let window = Duration::from_hours(365 * 24);
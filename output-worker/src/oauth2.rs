@@ -0,0 +1,213 @@
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::time::{Duration, Instant};
+use tracing::debug;
+use uuid::Uuid;
+
+use crate::Config;
+use crate::encryption_key::EncryptionKey;
+use crate::work::{mk_http_client, resolve_target_url};
+
+/// Access tokens are renewed this long before they expire so that they do not expire while a request is in flight
+const EXPIRATION_MARGIN: Duration = Duration::from_secs(30);
+
+/// Maximum number of characters of the body of a failed token request that are kept in the response of the request attempt
+const MAX_ERROR_BODY_LENGTH: usize = 500;
+
+/// OAuth2 authentication of the HTTP target of a subscription, as stored in `webhook.target_http` by the API
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TargetOAuth2 {
+    pub token_url: String,
+    pub client_id: String,
+    pub encrypted_client_secret: Vec<u8>,
+    pub scopes: Vec<String>,
+}
+
+impl TargetOAuth2 {
+    /// Gather the `oauth2_*` columns of `webhook.target_http` (`None` if the target does not use OAuth2 authentication)
+    pub fn from_columns(
+        token_url: Option<String>,
+        client_id: Option<String>,
+        encrypted_client_secret: Option<Vec<u8>>,
+        scopes: Option<Vec<String>>,
+    ) -> Option<Self> {
+        Some(Self {
+            token_url: token_url?,
+            client_id: client_id?,
+            encrypted_client_secret: encrypted_client_secret?,
+            scopes: scopes.unwrap_or_default(),
+        })
+    }
+}
+
+/// Client credentials of a target and the access token that was last obtained with them
+pub struct OAuth2Client {
+    token_url: String,
+    client_id: String,
+    client_secret: String,
+    scopes: Vec<String>,
+    /// Held while a new access token is requested, so that concurrent deliveries to the same target do not all request one
+    access_token: tokio::sync::Mutex<Option<AccessToken>>,
+}
+
+struct AccessToken {
+    value: String,
+    /// `None` if the token endpoint did not tell when the access token expires; it is then kept until the target rejects it
+    expires_at: Option<Instant>,
+}
+
+/// Successful response of a token endpoint (RFC 6749, section 5.1)
+#[derive(Debug, Deserialize)]
+struct TokenResponse {
+    access_token: String,
+    token_type: String,
+    expires_in: Option<u64>,
+}
+
+impl OAuth2Client {
+    /// Get an access token for the target, requesting a new one from the token endpoint if there is no valid one
+    ///
+    /// `rejected` is an access token the target just answered 401 to; a new access token is requested unless another delivery already did it in the meantime.
+    pub async fn access_token(
+        &self,
+        config: &Config,
+        rejected: Option<&str>,
+    ) -> Result<String, String> {
+        let mut access_token = self.access_token.lock().await;
+
+        if let Some(token) = access_token.as_ref()
+            && token
+                .expires_at
+                .is_none_or(|expires_at| expires_at > Instant::now())
+            && rejected != Some(token.value.as_str())
+        {
+            return Ok(token.value.clone());
+        }
+
+        debug!(token_url = self.token_url, "Requesting OAuth2 access token");
+        let token = self.request_access_token(config).await?;
+        let value = token.value.clone();
+        *access_token = Some(token);
+        Ok(value)
+    }
+
+    async fn request_access_token(&self, config: &Config) -> Result<AccessToken, String> {
+        // The token endpoint is subject to the same restrictions as the target itself
+        let (url, addrs) = resolve_target_url(config, &self.token_url)
+            .map_err(|e| format!("Token URL is not valid: {e}"))?;
+        let pin = url.domain().map(|host| (host, addrs.as_slice()));
+        let client = mk_http_client(config.connect_timeout, config.timeout, pin, None)
+            .map_err(|e| format!("Could not create HTTP client: {e}"))?;
+
+        let mut form = vec![("grant_type", "client_credentials".to_owned())];
+        if !self.scopes.is_empty() {
+            form.push(("scope", self.scopes.join(" ")));
+        }
+
+        let requested_at = Instant::now();
+        let response = client
+            .post(url)
+            .basic_auth(&self.client_id, Some(&self.client_secret))
+            .form(&form)
+            .send()
+            .await
+            .map_err(|e| format!("Could not reach token endpoint: {e}"))?;
+
+        let status = response.status();
+        if !status.is_success() {
+            let body = response.text().await.unwrap_or_default();
+            let body = body.chars().take(MAX_ERROR_BODY_LENGTH).collect::<String>();
+            return Err(format!(
+                "Token endpoint responded with HTTP status {status}: {body}"
+            ));
+        }
+
+        let token = response
+            .json::<TokenResponse>()
+            .await
+            .map_err(|e| format!("Token endpoint returned an invalid response: {e}"))?;
+        if !token.token_type.eq_ignore_ascii_case("bearer") {
+            return Err(format!(
+                "Token endpoint returned an unsupported token type: {}",
+                token.token_type
+            ));
+        }
+
+        Ok(AccessToken {
+            value: token.access_token,
+            expires_at: token.expires_in.map(|expires_in| {
+                requested_at + Duration::from_secs(expires_in).saturating_sub(EXPIRATION_MARGIN)
+            }),
+        })
+    }
+}
+
+/// Keeps the OAuth2 credentials and access tokens of subscriptions within this worker process
+///
+/// Client secrets are only decrypted again when the OAuth2 authentication of the target of a subscription changes.
+#[derive(Default)]
+pub struct OAuth2ClientCache {
+    subscriptions: Mutex<HashMap<Uuid, (TargetOAuth2, Arc<OAuth2Client>)>>,
+}
+
+impl OAuth2ClientCache {
+    /// Get the OAuth2 client to use for a delivery to the target of a subscription
+    ///
+    /// The result is `Ok(None)` if the target does not use OAuth2 authentication, and an error message if its client secret cannot be decrypted (this should be reported as an invalid target).
+    pub fn get(
+        &self,
+        encryption_key: Option<&EncryptionKey>,
+        subscription_id: Uuid,
+        target: Option<TargetOAuth2>,
+    ) -> Result<Option<Arc<OAuth2Client>>, String> {
+        let Some(target) = target else {
+            self.lock().remove(&subscription_id);
+            return Ok(None);
+        };
+
+        if let Some((cached_target, client)) = self.lock().get(&subscription_id)
+            && *cached_target == target
+        {
+            return Ok(Some(client.clone()));
+        }
+
+        debug!(%subscription_id, "Loading OAuth2 credentials of subscription target");
+        let client_secret = decrypt_client_secret(encryption_key, &target.encrypted_client_secret)?;
+        let client = Arc::new(OAuth2Client {
+            token_url: target.token_url.clone(),
+            client_id: target.client_id.clone(),
+            client_secret,
+            scopes: target.scopes.clone(),
+            access_token: tokio::sync::Mutex::new(None),
+        });
+        self.lock()
+            .insert(subscription_id, (target, client.clone()));
+        Ok(Some(client))
+    }
+
+    fn lock(&self) -> MutexGuard<'_, HashMap<Uuid, (TargetOAuth2, Arc<OAuth2Client>)>> {
+        self.subscriptions
+            .lock()
+            .expect("OAuth2 client cache mutex was poisoned")
+    }
+}
+
+fn decrypt_client_secret(
+    encryption_key: Option<&EncryptionKey>,
+    encrypted_client_secret: &[u8],
+) -> Result<String, String> {
+    let Some(encryption_key) = encryption_key else {
+        return Err(
+            "Target uses OAuth2 authentication but this worker has no key to decrypt its client secret"
+                .to_owned(),
+        );
+    };
+
+    encryption_key
+        .decrypt(encrypted_client_secret)
+        .and_then(|client_secret| String::from_utf8(client_secret).ok())
+        .ok_or_else(|| {
+            "Could not decrypt OAuth2 client secret; the key of this worker may be wrong".to_owned()
+        })
+}
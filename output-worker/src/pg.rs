@@ -4,19 +4,30 @@ use aws_sdk_s3::operation::get_object::GetObjectError;
 use aws_sdk_s3::primitives::ByteStream;
 use chrono::Utc;
 use sqlx::postgres::types::PgInterval;
-use sqlx::{PgPool, query, query_as};
+use sqlx::{PgConnection, PgPool, query, query_as, query_scalar};
+use std::str::FromStr;
+use std::sync::Arc;
 use std::time::{Duration, Instant};
 use tokio::sync::mpsc::Sender;
 use tokio::time::sleep;
 use tokio_util::task::TaskTracker;
 use tracing::{debug, info, trace, warn};
 
+use crate::circuit_breaker;
+use crate::client_tls::{ClientTlsCache, TargetTls};
+use crate::credentials::{StoredCredentials, SubscriptionCredentialsCache};
+use crate::oauth2::{OAuth2ClientCache, TargetOAuth2};
 use crate::opentelemetry::{end_request_attempt_span, start_request_attempt_span};
+use crate::rate_limit::{Limits, RateLimiter};
+use crate::signing_key::SigningKeyCache;
 use crate::throughput_log::ThroughputStats;
-use crate::work::{ResponseError, work};
+use crate::transformation::TransformationCache;
+use crate::work::{
+    DeliveryContext, ResponseError, StandardWebhooksMode, Transformation, work, work_batch,
+};
 use crate::{
-    Config, ObjectStorageConfig, RequestAttemptWithOptionalPayload, SlotRole, Worker,
-    compute_next_retry,
+    Config, ObjectStorageConfig, RequestAttemptWithOptionalPayload, SignatureVersion, SlotRole,
+    Worker, compute_next_retry,
 };
 use hook0_protobuf::{ObjectStorageResponse, RequestAttempt};
 use hook0_sentry_integration::log_object_storage_error_with_context;
@@ -39,6 +50,12 @@ pub async fn look_for_work(
     heartbeat_tx: Option<Sender<u16>>,
     task_tracker: &TaskTracker,
     stats: &ThroughputStats,
+    rate_limiter: &Arc<RateLimiter>,
+    client_tls_cache: &Arc<ClientTlsCache>,
+    oauth2_client_cache: &Arc<OAuth2ClientCache>,
+    signing_key_cache: &Arc<SigningKeyCache>,
+    credentials_cache: &Arc<SubscriptionCredentialsCache>,
+    transformation_cache: &Arc<TransformationCache>,
 ) -> anyhow::Result<()> {
     let (retry_count_lt, retry_count_gte): (Option<i16>, Option<i16>) = match slot_role {
         SlotRole::HpReserved => (Some(config.hp_retry_cutoff), None),
@@ -66,10 +83,27 @@ pub async fn look_for_work(
                     t_http.method AS http_method,
                     t_http.url AS http_url,
                     t_http.headers AS http_headers,
+                    t_http.encrypted_headers,
+                    t_http.client_certificate__id AS client_certificate_id,
+                    t_http.ca_certificates,
+                    t_http.oauth2_token_url,
+                    t_http.oauth2_client_id,
+                    t_http.oauth2_encrypted_client_secret,
+                    t_http.oauth2_scopes,
                     e.event_type__name AS event_type_name,
                     e.payload AS payload,
                     e.payload_content_type AS payload_content_type,
-                    s.secret
+                    s.secret,
+                    s.encrypted_secret,
+                    CASE WHEN s.previous_secret_expires_at > statement_timestamp() THEN s.previous_secret END AS previous_secret,
+                    CASE WHEN s.previous_secret_expires_at > statement_timestamp() THEN s.encrypted_previous_secret END AS encrypted_previous_secret,
+                    s.max_requests_per_second,
+                    s.max_in_flight,
+                    s.batch_max_size,
+                    s.payload_transformation,
+                    s.standard_webhooks,
+                    e.labels AS event_labels,
+                    e.metadata AS event_metadata
                 FROM webhook.request_attempt AS ra
                 INNER JOIN webhook.subscription AS s ON s.subscription__id = ra.subscription__id
                 LEFT JOIN webhook.subscription__worker AS sw ON sw.subscription__id = s.subscription__id
@@ -83,6 +117,7 @@ pub async fn look_for_work(
                     AND ra.failed_at IS NULL
                     AND s.is_enabled
                     AND s.deleted_at IS NULL
+                    AND e.delivery_cancelled_at IS NULL
                     AND (ra.delay_until IS NULL OR ra.delay_until <= statement_timestamp())
                     AND (
                         ($2 AND COALESCE(sw.worker__id, ow.worker__id) IS NULL)
@@ -90,6 +125,29 @@ pub async fn look_for_work(
                     )
                     AND ($3::smallint IS NULL OR ra.retry_count < $3)
                     AND ($4::smallint IS NULL OR ra.retry_count >= $4)
+                    AND NOT (s.subscription__id = ANY($5))
+                    -- Subscriptions with ordered delivery must wait for previous events (in the same ordering sequence) to be done
+                    AND (
+                        NOT s.ordered_delivery
+                        OR NOT EXISTS (SELECT 1 FROM webhook.pending_ordered_predecessors(s, e))
+                    )
+                    -- Subscriptions with batching wait until a full batch is ready or the request attempt waited long enough
+                    AND (
+                        s.batch_max_wait_ms IS NULL
+                        OR COALESCE(ra.delay_until, ra.created_at) <= statement_timestamp() - s.batch_max_wait_ms * interval '1 millisecond'
+                        OR (
+                            SELECT count(*)
+                            FROM (
+                                SELECT 1
+                                FROM webhook.request_attempt AS ra_batch
+                                WHERE ra_batch.subscription__id = ra.subscription__id
+                                    AND ra_batch.succeeded_at IS NULL
+                                    AND ra_batch.failed_at IS NULL
+                                    AND (ra_batch.delay_until IS NULL OR ra_batch.delay_until <= statement_timestamp())
+                                LIMIT s.batch_max_size
+                            ) AS pending
+                        ) >= s.batch_max_size
+                    )
                 ORDER BY ra.created_at ASC
                 LIMIT 1
                 FOR UPDATE OF ra
@@ -99,12 +157,56 @@ pub async fn look_for_work(
             worker.scope.is_public(),
             retry_count_lt,
             retry_count_gte,
+            &rate_limiter.saturated_subscriptions(Instant::now()),
         )
         .fetch_optional(&mut *tx)
         .await?;
         stats.record_db_fetch(fetch_start.elapsed());
 
-        if let Some(attempt) = next_attempt {
+        if let Some(mut attempt) = next_attempt {
+            // Subscriptions that reached their limits are excluded by the query, but another unit may have used the remaining capacity in the meantime
+            // In this case the request attempt is left for later; it is neither failed nor counted as a retry
+            let limits = Limits {
+                max_requests_per_second: attempt.max_requests_per_second,
+                max_in_flight: attempt.max_in_flight,
+            };
+            let _rate_limit_permit = match rate_limiter.try_acquire(
+                attempt.subscription_id,
+                limits,
+                Instant::now(),
+            ) {
+                Ok(permit) => permit,
+                Err(retry_in) => {
+                    trace!(unit_id, request_attempt_id = %attempt.request_attempt_id, retry_in_ms = retry_in.as_millis(), "Subscription reached its delivery limits; postponing request attempt");
+                    tx.rollback().await?;
+                    stats.record_not_ready();
+                    continue;
+                }
+            };
+
+            // The in-flight limit is shared by all workers: each delivery holds one of the subscription's slots (as a transaction-level advisory lock) until its transaction ends
+            if let Some(max_in_flight) = attempt.max_in_flight.filter(|max| *max > 0) {
+                let slot = query_scalar!(
+                    r#"
+                        SELECT slot AS "slot!"
+                        FROM generate_series(0, $2 - 1) AS slot
+                        WHERE pg_try_advisory_xact_lock(hashtextextended($1::uuid::text, slot))
+                        LIMIT 1
+                    "#,
+                    attempt.subscription_id,
+                    max_in_flight,
+                )
+                .fetch_optional(&mut *tx)
+                .await?;
+                if slot.is_none() {
+                    trace!(unit_id, request_attempt_id = %attempt.request_attempt_id, "Subscription has too many requests in flight across workers; postponing request attempt");
+                    tx.rollback().await?;
+                    rate_limiter.defer(attempt.subscription_id, limits, Instant::now());
+                    stats.record_not_ready();
+                    continue;
+                }
+            }
+
             let attempt_is_hp = SlotRole::is_hp(attempt.retry_count, config.hp_retry_cutoff);
             let _slot_guard = stats.slot_enter(attempt_is_hp);
 
@@ -133,64 +235,20 @@ pub async fn look_for_work(
             .await?;
             debug!(unit_id, request_attempt_id = %attempt.request_attempt_id, "Picked request attempt");
 
-            // Fetch the payload. `give_up` distinguishes a payload object that is
-            // permanently missing (S3 NoSuchKey) from a transient/unavailable read (or
-            // object storage not being configured), which we retry later instead.
-            let (payload, give_up): (Option<Vec<u8>>, bool) = if let Some(p) = attempt.payload {
-                (Some(p), false)
-            } else if let Some(os) = &object_storage {
-                let key = format!(
-                    "{}/event/{}/{}",
-                    attempt.application_id,
-                    attempt.event_received_at.naive_utc().date(),
-                    attempt.event_id
-                );
-                match os
-                    .client
-                    .get_object()
-                    .bucket(&os.bucket)
-                    .key(&key)
-                    .send()
-                    .await
-                {
-                    Ok(obj) => match obj.body.collect().await {
-                        Ok(ab) => (Some(ab.to_vec()), false),
-                        Err(e) => {
-                            log_object_storage_error_with_context!(
-                                "S3 GET OBJECT body collect failed",
-                                error_chain = format!("{e}"),
-                                object_key = &key,
-                            );
-                            (None, false)
-                        }
-                    },
-                    Err(e)
-                        if matches!(e.as_service_error(), Some(GetObjectError::NoSuchKey(_))) =>
-                    {
-                        log_object_storage_error_with_context!(
-                            "S3 GET OBJECT failed: payload object is missing",
-                            error_chain = DisplayErrorContext(&e).to_string(),
-                            object_key = &key,
-                        );
-                        (None, true)
-                    }
-                    Err(e) => {
-                        log_object_storage_error_with_context!(
-                            "S3 GET OBJECT failed",
-                            error_chain = DisplayErrorContext(&e).to_string(),
-                            object_key = &key,
-                        );
-                        (None, false)
-                    }
-                }
-            } else {
-                // Object storage is not configured but the payload is not in the DB
-                // either. Treat as recoverable (an operator can fix the config and
-                // restart) rather than dropping the event.
-                (None, false)
-            };
+            // Fetch the payload
+            let (payload, give_up) = fetch_payload(object_storage, &mut attempt).await;
 
             if let Some(p) = payload {
+                let transformation = transformation_cache
+                    .get(
+                        attempt.subscription_id,
+                        attempt.payload_transformation.take(),
+                    )
+                    .map(|compiled| Transformation {
+                        compiled,
+                        labels: attempt.event_labels.take(),
+                        metadata: attempt.event_metadata.take(),
+                    });
                 let attempt_with_payload = RequestAttempt {
                     application_id: attempt.application_id,
                     request_attempt_id: attempt.request_attempt_id,
@@ -208,12 +266,102 @@ pub async fn look_for_work(
                     secret: attempt.secret,
                 };
 
+                // Subscriptions with batching send other pending request attempts in the same request
+                let mut batch = vec![attempt_with_payload];
+                if let Some(batch_max_size) = attempt.batch_max_size {
+                    let others = pick_batch(
+                        &mut tx,
+                        object_storage,
+                        worker,
+                        worker_version,
+                        &batch[0],
+                        batch_max_size,
+                        retry_count_lt,
+                        retry_count_gte,
+                    )
+                    .await?;
+                    batch.extend(others);
+                }
+                let attempt_with_payload = &batch[0];
+
+                let context = DeliveryContext {
+                    // Load the TLS material of the target (cached per subscription)
+                    client_tls: client_tls_cache
+                        .get(
+                            &mut *tx,
+                            config.target_credentials_encryption_key.as_ref(),
+                            attempt.subscription_id,
+                            TargetTls {
+                                client_certificate_id: attempt.client_certificate_id,
+                                ca_certificates: attempt.ca_certificates.take(),
+                            },
+                        )
+                        .await?,
+                    // Load the OAuth2 credentials of the target (cached per subscription, along with its access token)
+                    oauth2: oauth2_client_cache.get(
+                        config.target_credentials_encryption_key.as_ref(),
+                        attempt.subscription_id,
+                        TargetOAuth2::from_columns(
+                            attempt.oauth2_token_url.take(),
+                            attempt.oauth2_client_id.take(),
+                            attempt.oauth2_encrypted_client_secret.take(),
+                            attempt.oauth2_scopes.take(),
+                        ),
+                    ),
+                    // Load the signing key of the application (cached per application)
+                    signing_key: if config
+                        .enabled_signature_versions
+                        .contains(&SignatureVersion::V2)
+                    {
+                        signing_key_cache
+                            .get(
+                                &mut *tx,
+                                config.target_credentials_encryption_key.as_ref(),
+                                attempt.application_id,
+                            )
+                            .await?
+                    } else {
+                        Ok(None)
+                    },
+                    // Load the secrets and headers of the subscription (cached per subscription)
+                    credentials: credentials_cache
+                        .get(
+                            &mut *tx,
+                            config.target_credentials_encryption_key.as_ref(),
+                            attempt.application_id,
+                            attempt.subscription_id,
+                            StoredCredentials {
+                                headers: attempt_with_payload.http_headers.clone(),
+                                encrypted_headers: attempt.encrypted_headers.take(),
+                                secret: attempt.secret,
+                                encrypted_secret: attempt.encrypted_secret.take(),
+                                previous_secret: attempt.previous_secret,
+                                encrypted_previous_secret: attempt.encrypted_previous_secret.take(),
+                            },
+                        )
+                        .await?,
+                    standard_webhooks: attempt
+                        .standard_webhooks
+                        .as_deref()
+                        .and_then(|mode| StandardWebhooksMode::from_str(mode).ok()),
+                };
+
                 // Start OpenTelemetry span
-                let span = start_request_attempt_span(&attempt_with_payload);
+                let span = start_request_attempt_span(attempt_with_payload);
 
                 // Work
-                let response = work(config, &attempt_with_payload).await;
-                trace!(unit_id, request_attempt_id = %attempt.request_attempt_id, elapsed_ms = response.elapsed_time_ms(), "Got response for request attempt");
+                let response = if attempt.batch_max_size.is_some() {
+                    work_batch(config, &batch, context).await
+                } else {
+                    work(
+                        config,
+                        attempt_with_payload,
+                        transformation.as_ref(),
+                        context,
+                    )
+                    .await
+                };
+                trace!(unit_id, request_attempt_id = %attempt.request_attempt_id, batch_size = batch.len(), elapsed_ms = response.elapsed_time_ms(), "Got response for request attempt");
 
                 // Store response
                 trace!(unit_id, request_attempt_id = %attempt.request_attempt_id, "Storing response");
@@ -287,77 +435,98 @@ pub async fn look_for_work(
                         })?;
                 }
 
-                // Associate response and request attempt
-                trace!(unit_id, request_attempt_id = %attempt.request_attempt_id, %response_id, "Associating response with request attempt");
-                query!(
-                    "UPDATE webhook.request_attempt SET response__id = $1 WHERE request_attempt__id = $2",
-                    response_id, attempt.request_attempt_id
-                )
-                .execute(&mut *tx)
-                .await?;
-
-                if response.is_success() {
-                    // Mark attempt as completed
-                    trace!(unit_id, request_attempt_id = %attempt.request_attempt_id, "Completing request attempt");
+                // Associate response and request attempts
+                // Request attempts of a batch share the same response and succeed or fail together
+                for delivered in &batch {
+                    trace!(unit_id, request_attempt_id = %delivered.request_attempt_id, %response_id, "Associating response with request attempt");
                     query!(
-                        "UPDATE webhook.request_attempt SET succeeded_at = statement_timestamp() WHERE request_attempt__id = $1",
-                        attempt.request_attempt_id
+                        "UPDATE webhook.request_attempt SET response__id = $1 WHERE request_attempt__id = $2",
+                        response_id, delivered.request_attempt_id
                     )
                     .execute(&mut *tx)
                     .await?;
+                }
 
-                    debug!(unit_id, request_attempt_id = %attempt.request_attempt_id, "Request attempt completed successfully");
-                } else {
-                    // Mark attempt as failed
-                    trace!(unit_id, request_attempt_id = %attempt.request_attempt_id, "Failing request attempt");
-                    query!(
-                        "UPDATE webhook.request_attempt SET failed_at = statement_timestamp() WHERE request_attempt__id = $1",
-                        attempt.request_attempt_id
-                    )
-                    .execute(&mut *tx)
-                    .await?;
+                if response.is_success() {
+                    // Mark attempts as completed
+                    for delivered in &batch {
+                        trace!(unit_id, request_attempt_id = %delivered.request_attempt_id, "Completing request attempt");
+                        query!(
+                            "UPDATE webhook.request_attempt SET succeeded_at = statement_timestamp() WHERE request_attempt__id = $1",
+                            delivered.request_attempt_id
+                        )
+                        .execute(&mut *tx)
+                        .await?;
+                    }
 
-                    // Creating a retry request or giving up
-                    if let Some(retry_in) = compute_next_retry(
-                        &mut tx,
-                        &attempt_with_payload,
-                        &response,
-                        config.max_retries,
-                    )
-                    .await?
-                    {
-                        let next_retry_count = attempt.retry_count + 1;
-                        let retry_id = query!(
-                            "
-                                INSERT INTO webhook.request_attempt (application__id, event__id, subscription__id, delay_until, retry_count)
-                                VALUES ($1, $2, $3, statement_timestamp() + $4, $5)
-                                RETURNING request_attempt__id
-                            ",
-                            attempt.application_id,
-                            attempt.event_id,
-                            attempt.subscription_id,
-                            PgInterval::try_from(retry_in).unwrap(),
-                            next_retry_count,
+                    circuit_breaker::record_success(&mut tx, config, &attempt.subscription_id)
+                        .await?;
+
+                    debug!(unit_id, request_attempt_id = %attempt.request_attempt_id, batch_size = batch.len(), "Request attempt completed successfully");
+                } else {
+                    // Mark attempts as failed
+                    for delivered in &batch {
+                        trace!(unit_id, request_attempt_id = %delivered.request_attempt_id, "Failing request attempt");
+                        query!(
+                            "UPDATE webhook.request_attempt SET failed_at = statement_timestamp(), is_permanent_failure = $2 WHERE request_attempt__id = $1",
+                            delivered.request_attempt_id,
+                            response.is_permanent_failure(&config.non_retryable_http_codes, config.retry_invalid_targets),
                         )
-                        .fetch_one(&mut *tx)
-                        .await?
-                        .request_attempt__id;
+                        .execute(&mut *tx)
+                        .await?;
+                    }
 
-                        debug!(unit_id, request_attempt_id = %attempt.request_attempt_id, retry_count = next_retry_count, %retry_id, retry_in_secs = retry_in.as_secs(), "Request attempt failed; retry created");
-                    } else {
-                        info!(unit_id, request_attempt_id = %attempt.request_attempt_id, retry_count = attempt.retry_count, "Request attempt failed; giving up");
+                    circuit_breaker::record_failure(&mut tx, config, &attempt.subscription_id)
+                        .await?;
+
+                    // Creating retry requests or giving up
+                    for delivered in &batch {
+                        let next_retry =
+                            compute_next_retry(&mut tx, delivered, &response, config).await?;
+                        let next_retry = next_retry.and_then(|retry_in| {
+                            PgInterval::try_from(retry_in)
+                                .inspect_err(|e| {
+                                    warn!(unit_id, request_attempt_id = %delivered.request_attempt_id, retry_in_secs = retry_in.as_secs(), "Could not schedule a retry ({e})");
+                                })
+                                .ok()
+                                .map(|interval| (retry_in, interval))
+                        });
+                        if let Some((retry_in, interval)) = next_retry {
+                            let next_retry_count = delivered.retry_count + 1;
+                            let retry_id = query!(
+                                "
+                                    INSERT INTO webhook.request_attempt (application__id, event__id, subscription__id, delay_until, retry_count)
+                                    VALUES ($1, $2, $3, statement_timestamp() + $4, $5)
+                                    RETURNING request_attempt__id
+                                ",
+                                delivered.application_id,
+                                delivered.event_id,
+                                delivered.subscription_id,
+                                interval,
+                                next_retry_count,
+                            )
+                            .fetch_one(&mut *tx)
+                            .await?
+                            .request_attempt__id;
+
+                            debug!(unit_id, request_attempt_id = %delivered.request_attempt_id, retry_count = next_retry_count, %retry_id, retry_in_secs = retry_in.as_secs(), "Request attempt failed; retry created");
+                        } else {
+                            info!(unit_id, request_attempt_id = %delivered.request_attempt_id, retry_count = delivered.retry_count, "Request attempt failed; giving up");
+                        }
                     }
                 }
 
                 // Commit transaction
                 tx.commit().await?;
 
-                stats.record_attempt(
-                    response.is_success(),
-                    attempt.retry_count,
-                    response.elapsed_time,
-                    config.hp_retry_cutoff,
-                );
+                for delivered in &batch {
+                    stats.record_attempt(
+                        response.is_success(),
+                        delivered.retry_count,
+                        response.elapsed_time,
+                        config.hp_retry_cutoff,
+                    );
+                }
 
                 // End OpenTelemetry span
                 end_request_attempt_span(span, &response);
@@ -443,6 +612,128 @@ pub async fn look_for_work(
     }
 }
 
+/// Pick other pending request attempts of the same subscription, so that they are sent in the same request as the given one
+///
+/// Request attempts whose payload cannot be fetched are left for later; they will be picked on their own.
+#[allow(clippy::too_many_arguments)]
+async fn pick_batch(
+    conn: &mut PgConnection,
+    object_storage: &Option<ObjectStorageConfig>,
+    worker: &Worker,
+    worker_version: &str,
+    attempt: &RequestAttempt,
+    batch_max_size: i32,
+    retry_count_lt: Option<i16>,
+    retry_count_gte: Option<i16>,
+) -> anyhow::Result<Vec<RequestAttempt>> {
+    let candidates = query_as!(
+        RequestAttemptWithOptionalPayload,
+        "
+            SELECT
+                e.application__id AS application_id,
+                ra.request_attempt__id AS request_attempt_id,
+                ra.event__id AS event_id,
+                e.received_at AS event_received_at,
+                ra.subscription__id AS subscription_id,
+                ra.created_at,
+                ra.retry_count,
+                ra.delay_until,
+                t_http.method AS http_method,
+                t_http.url AS http_url,
+                t_http.headers AS http_headers,
+                t_http.encrypted_headers,
+                t_http.client_certificate__id AS client_certificate_id,
+                t_http.ca_certificates,
+                t_http.oauth2_token_url,
+                t_http.oauth2_client_id,
+                t_http.oauth2_encrypted_client_secret,
+                t_http.oauth2_scopes,
+                e.event_type__name AS event_type_name,
+                e.payload AS payload,
+                e.payload_content_type AS payload_content_type,
+                s.secret,
+                s.encrypted_secret,
+                CASE WHEN s.previous_secret_expires_at > statement_timestamp() THEN s.previous_secret END AS previous_secret,
+                CASE WHEN s.previous_secret_expires_at > statement_timestamp() THEN s.encrypted_previous_secret END AS encrypted_previous_secret,
+                s.max_requests_per_second,
+                s.max_in_flight,
+                s.batch_max_size,
+                s.payload_transformation,
+                s.standard_webhooks,
+                e.labels AS event_labels,
+                e.metadata AS event_metadata
+            FROM webhook.request_attempt AS ra
+            INNER JOIN webhook.subscription AS s ON s.subscription__id = ra.subscription__id
+            INNER JOIN webhook.target_http AS t_http ON t_http.target__id = s.target__id
+            INNER JOIN event.event AS e ON e.event__id = ra.event__id
+            WHERE
+                ra.subscription__id = $1
+                AND ra.request_attempt__id <> $2
+                AND ra.succeeded_at IS NULL
+                AND ra.failed_at IS NULL
+                AND e.delivery_cancelled_at IS NULL
+                AND (ra.delay_until IS NULL OR ra.delay_until <= statement_timestamp())
+                AND ($3::smallint IS NULL OR ra.retry_count < $3)
+                AND ($4::smallint IS NULL OR ra.retry_count >= $4)
+            ORDER BY ra.created_at ASC
+            LIMIT $5
+            FOR UPDATE OF ra
+            SKIP LOCKED
+        ",
+        attempt.subscription_id,
+        attempt.request_attempt_id,
+        retry_count_lt,
+        retry_count_gte,
+        i64::from(batch_max_size - 1),
+    )
+    .fetch_all(&mut *conn)
+    .await?;
+
+    let mut batch = Vec::with_capacity(candidates.len());
+    for mut candidate in candidates {
+        if let (Some(payload), _) = fetch_payload(object_storage, &mut candidate).await {
+            batch.push(RequestAttempt {
+                application_id: candidate.application_id,
+                request_attempt_id: candidate.request_attempt_id,
+                event_id: candidate.event_id,
+                event_received_at: candidate.event_received_at,
+                subscription_id: candidate.subscription_id,
+                created_at: candidate.created_at,
+                retry_count: candidate.retry_count,
+                http_method: candidate.http_method,
+                http_url: candidate.http_url,
+                http_headers: candidate.http_headers,
+                event_type_name: candidate.event_type_name,
+                payload,
+                payload_content_type: candidate.payload_content_type,
+                secret: candidate.secret,
+            });
+        } else {
+            debug!(request_attempt_id = %candidate.request_attempt_id, "Could not get payload for event; leaving request attempt out of the batch");
+        }
+    }
+
+    if !batch.is_empty() {
+        query!(
+            "
+                UPDATE webhook.request_attempt
+                SET picked_at = statement_timestamp(), worker_name = $1, worker_version = $2
+                WHERE request_attempt__id = ANY($3)
+            ",
+            &worker.name,
+            &worker_version,
+            &batch
+                .iter()
+                .map(|a| a.request_attempt_id)
+                .collect::<Vec<_>>(),
+        )
+        .execute(&mut *conn)
+        .await?;
+    }
+
+    Ok(batch)
+}
+
 async fn wait_because_no_work(unit_id: u16) {
     // In order to reduce load on the database when there is no work to do, but simultaneously keep a low latency when some work becomes available,
     // we wait a variable duration between checks:
@@ -457,3 +748,63 @@ async fn wait_because_no_work(unit_id: u16) {
     };
     sleep(sleep_duration).await;
 }
+
+/// Take the payload of a request attempt, fetching it from object storage if it is not stored in the database
+///
+/// The returned boolean is `true` when the payload object is permanently missing (S3 NoSuchKey), in which case the request attempt should be given up; a transient/unavailable read (or object storage not being configured) should be retried later instead.
+async fn fetch_payload(
+    object_storage: &Option<ObjectStorageConfig>,
+    attempt: &mut RequestAttemptWithOptionalPayload,
+) -> (Option<Vec<u8>>, bool) {
+    if let Some(p) = attempt.payload.take() {
+        (Some(p), false)
+    } else if let Some(os) = &object_storage {
+        let key = format!(
+            "{}/event/{}/{}",
+            attempt.application_id,
+            attempt.event_received_at.naive_utc().date(),
+            attempt.event_id
+        );
+        match os
+            .client
+            .get_object()
+            .bucket(&os.bucket)
+            .key(&key)
+            .send()
+            .await
+        {
+            Ok(obj) => match obj.body.collect().await {
+                Ok(ab) => (Some(ab.to_vec()), false),
+                Err(e) => {
+                    log_object_storage_error_with_context!(
+                        "S3 GET OBJECT body collect failed",
+                        error_chain = format!("{e}"),
+                        object_key = &key,
+                    );
+                    (None, false)
+                }
+            },
+            Err(e) if matches!(e.as_service_error(), Some(GetObjectError::NoSuchKey(_))) => {
+                log_object_storage_error_with_context!(
+                    "S3 GET OBJECT failed: payload object is missing",
+                    error_chain = DisplayErrorContext(&e).to_string(),
+                    object_key = &key,
+                );
+                (None, true)
+            }
+            Err(e) => {
+                log_object_storage_error_with_context!(
+                    "S3 GET OBJECT failed",
+                    error_chain = DisplayErrorContext(&e).to_string(),
+                    object_key = &key,
+                );
+                (None, false)
+            }
+        }
+    } else {
+        // Object storage is not configured but the payload is not in the DB
+        // either. Treat as recoverable (an operator can fix the config and
+        // restart) rather than dropping the event.
+        (None, false)
+    }
+}
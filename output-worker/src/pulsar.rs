@@ -11,6 +11,7 @@ use pulsar::{
     TokioExecutor,
 };
 use sqlx::{PgPool, query, query_as};
+use std::str::FromStr;
 use std::sync::Arc;
 use std::time::Duration;
 use tokio::sync::mpsc::error::SendError;
@@ -22,20 +23,30 @@ use tokio_util::task::TaskTracker;
 use tracing::{debug, error, info, trace, warn};
 use uuid::Uuid;
 
+use crate::circuit_breaker;
+use crate::client_tls::{ClientTlsCache, TargetTls};
+use crate::credentials::{StoredCredentials, SubscriptionCredentialsCache};
+use crate::oauth2::{OAuth2ClientCache, TargetOAuth2};
 use crate::opentelemetry::{
     end_request_attempt_span, gather_pulsar_consumer_metrics, start_request_attempt_span,
 };
+use crate::rate_limit::{Limits, RateLimiter};
+use crate::signing_key::SigningKeyCache;
 use crate::throughput_log::ThroughputStats;
-use crate::work::work;
+use crate::transformation::TransformationCache;
+use crate::work::{DeliveryContext, StandardWebhooksMode, Transformation, work, work_batch};
 use crate::{
     Config, ObjectStorageConfig, PulsarConfig, RequestAttempt, RequestAttemptWithOptionalPayload,
-    SlotRole, compute_next_retry,
+    SignatureVersion, SlotRole, compute_next_retry,
 };
 use hook0_protobuf::ObjectStorageResponse;
 use hook0_sentry_integration::log_object_storage_error_with_context;
 
 const DELAY_TOLERANCE: Duration = Duration::from_secs(1);
 
+/// Extra delay before checking again a request attempt that waits for previous events of an ordered subscription
+const ORDERED_DELIVERY_RECHECK_DELAY: Duration = Duration::from_secs(1);
+
 #[derive(Debug, Clone, Copy)]
 pub enum LoadMode {
     All,
@@ -141,10 +152,27 @@ pub async fn load_waiting_request_attempts_from_db(
                 t_http.method as http_method,
                 t_http.url as http_url,
                 t_http.headers as http_headers,
+                t_http.encrypted_headers,
+                t_http.client_certificate__id AS client_certificate_id,
+                t_http.ca_certificates,
+                t_http.oauth2_token_url,
+                t_http.oauth2_client_id,
+                t_http.oauth2_encrypted_client_secret,
+                t_http.oauth2_scopes,
                 e.event_type__name AS event_type_name,
                 e.payload,
                 e.payload_content_type,
-                s.secret
+                s.secret,
+                s.encrypted_secret,
+                CASE WHEN s.previous_secret_expires_at > statement_timestamp() THEN s.previous_secret END AS previous_secret,
+                CASE WHEN s.previous_secret_expires_at > statement_timestamp() THEN s.encrypted_previous_secret END AS encrypted_previous_secret,
+                s.max_requests_per_second,
+                s.max_in_flight,
+                s.batch_max_size,
+                s.payload_transformation,
+                s.standard_webhooks,
+                e.labels AS event_labels,
+                e.metadata AS event_metadata
             FROM webhook.request_attempt AS ra
             INNER JOIN webhook.subscription AS s ON s.subscription__id = ra.subscription__id
             INNER JOIN webhook.target_http AS t_http ON t_http.target__id = s.target__id
@@ -154,6 +182,7 @@ pub async fn load_waiting_request_attempts_from_db(
             LEFT JOIN iam.organization__worker AS ow ON ow.organization__id = a.organization__id AND ow.default = true
             WHERE ra.succeeded_at IS NULL AND ra.failed_at IS NULL
                 AND a.deleted_at IS NULL
+                AND e.delivery_cancelled_at IS NULL
                 AND COALESCE(sw.worker__id, ow.worker__id) = $1
                 AND (
                     NOT $2
@@ -293,6 +322,12 @@ pub async fn look_for_work(
     heartbeat_tx: Option<Sender<u16>>,
     task_tracker: &TaskTracker,
     stats: &Arc<ThroughputStats>,
+    rate_limiter: &Arc<RateLimiter>,
+    client_tls_cache: &Arc<ClientTlsCache>,
+    oauth2_client_cache: &Arc<OAuth2ClientCache>,
+    signing_key_cache: &Arc<SigningKeyCache>,
+    credentials_cache: &Arc<SubscriptionCredentialsCache>,
+    transformation_cache: &Arc<TransformationCache>,
 ) -> anyhow::Result<()> {
     info!("Begin looking for work");
 
@@ -502,11 +537,17 @@ pub async fn look_for_work(
                         let lp_rp = lp_retry_producer.clone();
                         let st = stats.clone();
                         let infl = in_flight.clone();
+                        let rl = rate_limiter.clone();
+                        let ctc = client_tls_cache.clone();
+                        let occ = oauth2_client_cache.clone();
+                        let skc = signing_key_cache.clone();
+                        let scc = credentials_cache.clone();
+                        let trc = transformation_cache.clone();
 
                         // We handle the request attempt in a new Tokio task
                         task_tracker.spawn(async move {
                             if let Err(e) = handle_message(
-                                &c, &po, &os, &wi, &wn, &wv, &hp_rp, &lp_rp, msg, permit, ack_tx, &st, is_lp, infl,
+                                &c, &po, &os, &wi, &wn, &wv, &hp_rp, &lp_rp, msg, permit, ack_tx, &st, is_lp, infl, &rl, &ctc, &occ, &skc, &scc, &trc,
                             )
                             .await
                             {
@@ -575,6 +616,10 @@ pub async fn look_for_work(
 enum RequestAttemptStatus {
     Ready {
         delay_until: Option<DateTime<Utc>>,
+        limits: Limits,
+        batched: bool,
+        transformation: Option<Transformation>,
+        context: DeliveryContext,
     },
     Delayed {
         delay_until: DateTime<Utc>,
@@ -602,6 +647,12 @@ async fn handle_message(
     stats: &ThroughputStats,
     is_lp: bool,
     in_flight: Arc<papaya::HashSet<Uuid>>,
+    rate_limiter: &Arc<RateLimiter>,
+    client_tls_cache: &Arc<ClientTlsCache>,
+    oauth2_client_cache: &Arc<OAuth2ClientCache>,
+    signing_key_cache: &Arc<SigningKeyCache>,
+    credentials_cache: &Arc<SubscriptionCredentialsCache>,
+    transformation_cache: &Arc<TransformationCache>,
 ) -> anyhow::Result<()> {
     let picked_at = Utc::now();
     let attempt_is_hp = !is_lp;
@@ -637,15 +688,54 @@ async fn handle_message(
                     not_done: bool,
                     delay_until: Option<DateTime<Utc>>,
                     for_this_worker: bool,
+                    max_requests_per_second: Option<i32>,
+                    max_in_flight: Option<i32>,
+                    batch_max_size: Option<i32>,
+                    payload_transformation: Option<serde_json::Value>,
+                    standard_webhooks: Option<String>,
+                    event_labels: serde_json::Value,
+                    event_metadata: Option<serde_json::Value>,
+                    client_certificate_id: Option<Uuid>,
+                    ca_certificates: Option<String>,
+                    oauth2_token_url: Option<String>,
+                    oauth2_client_id: Option<String>,
+                    oauth2_encrypted_client_secret: Option<Vec<u8>>,
+                    oauth2_scopes: Option<Vec<String>>,
+                    headers: serde_json::Value,
+                    encrypted_headers: serde_json::Value,
+                    secret: Option<Uuid>,
+                    encrypted_secret: Option<Vec<u8>>,
+                    previous_secret: Option<Uuid>,
+                    encrypted_previous_secret: Option<Vec<u8>>,
+                    blocked_until: Option<DateTime<Utc>>,
                 }
                 let fetch_start = std::time::Instant::now();
                 let request_attempt_status = match query_as!(
                     RawRequestAttemptStatus,
                     r#"
                         SELECT
-                            (s.is_enabled AND a.deleted_at IS NULL) AS "not_cancelled!",
+                            (s.is_enabled AND a.deleted_at IS NULL AND e.delivery_cancelled_at IS NULL) AS "not_cancelled!",
                             (ra.succeeded_at IS NULL AND ra.failed_at IS NULL) AS "not_done!",
                             ra.delay_until,
+                            s.max_requests_per_second,
+                            s.max_in_flight,
+                            s.batch_max_size,
+                            s.payload_transformation,
+                            s.standard_webhooks,
+                            e.labels AS event_labels,
+                            e.metadata AS event_metadata,
+                            t_http.client_certificate__id AS client_certificate_id,
+                            t_http.ca_certificates,
+                            t_http.oauth2_token_url,
+                            t_http.oauth2_client_id,
+                            t_http.oauth2_encrypted_client_secret,
+                            t_http.oauth2_scopes,
+                            t_http.headers,
+                            t_http.encrypted_headers,
+                            s.secret,
+                            s.encrypted_secret,
+                            CASE WHEN s.previous_secret_expires_at > statement_timestamp() THEN s.previous_secret END AS previous_secret,
+                            CASE WHEN s.previous_secret_expires_at > statement_timestamp() THEN s.encrypted_previous_secret END AS encrypted_previous_secret,
                             (
                                 EXISTS (
                                     SELECT 1
@@ -667,10 +757,17 @@ async fn handle_message(
                                             AND ow.worker__id IS NOT DISTINCT FROM $2
                                     )
                                 )
-                            ) AS "for_this_worker!"
+                            ) AS "for_this_worker!",
+                            -- Subscriptions with ordered delivery must wait for previous events (in the same ordering sequence) to be done
+                            (
+                                SELECT min(greatest(ra_prev.delay_until, statement_timestamp()))
+                                FROM webhook.pending_ordered_predecessors(s, e) AS ra_prev
+                            ) AS blocked_until
                         FROM webhook.request_attempt AS ra
                         INNER JOIN webhook.subscription AS s ON s.subscription__id = ra.subscription__id
                         INNER JOIN event.application AS a ON a.application__id = s.application__id
+                        INNER JOIN webhook.target_http AS t_http ON t_http.target__id = s.target__id
+                        INNER JOIN event.event AS e ON e.event__id = ra.event__id
                         WHERE ra.request_attempt__id = $1
                     "#,
                     attempt.request_attempt_id,
@@ -684,6 +781,7 @@ async fn handle_message(
                         not_done: true,
                         for_this_worker: true,
                         delay_until: Some(d),
+                        ..
                     }) if d > (Utc::now() + DELAY_TOLERANCE) => RequestAttemptStatus::Delayed {
                         delay_until: d,
                         lead: d - Utc::now(),
@@ -692,8 +790,115 @@ async fn handle_message(
                         not_cancelled: true,
                         not_done: true,
                         for_this_worker: true,
+                        blocked_until: Some(b),
+                        ..
+                    }) => {
+                        let d = b.max(Utc::now()) + ORDERED_DELIVERY_RECHECK_DELAY;
+                        RequestAttemptStatus::Delayed {
+                            delay_until: d,
+                            lead: d - Utc::now(),
+                        }
+                    }
+                    Some(RawRequestAttemptStatus {
+                        not_cancelled: true,
+                        not_done: true,
+                        for_this_worker: true,
+                        delay_until,
+                        max_requests_per_second,
+                        max_in_flight,
+                        batch_max_size,
+                        payload_transformation,
+                        standard_webhooks,
+                        event_labels,
+                        event_metadata,
+                        client_certificate_id,
+                        ca_certificates,
+                        oauth2_token_url,
+                        oauth2_client_id,
+                        oauth2_encrypted_client_secret,
+                        oauth2_scopes,
+                        headers,
+                        encrypted_headers,
+                        secret,
+                        encrypted_secret,
+                        previous_secret,
+                        encrypted_previous_secret,
+                        ..
+                    }) => RequestAttemptStatus::Ready {
                         delay_until,
-                    }) => RequestAttemptStatus::Ready { delay_until },
+                        limits: Limits {
+                            max_requests_per_second,
+                            max_in_flight,
+                        },
+                        batched: batch_max_size.is_some(),
+                        transformation: transformation_cache
+                            .get(attempt.subscription_id, payload_transformation)
+                            .map(|compiled| Transformation {
+                                compiled,
+                                labels: event_labels,
+                                metadata: event_metadata,
+                            }),
+                        context: DeliveryContext {
+                            // Load the TLS material of the target (cached per subscription)
+                            client_tls: client_tls_cache
+                                .get(
+                                    pool,
+                                    config.target_credentials_encryption_key.as_ref(),
+                                    attempt.subscription_id,
+                                    TargetTls {
+                                        client_certificate_id,
+                                        ca_certificates,
+                                    },
+                                )
+                                .await?,
+                            // Load the OAuth2 credentials of the target (cached per subscription, along with its access token)
+                            oauth2: oauth2_client_cache.get(
+                                config.target_credentials_encryption_key.as_ref(),
+                                attempt.subscription_id,
+                                TargetOAuth2::from_columns(
+                                    oauth2_token_url,
+                                    oauth2_client_id,
+                                    oauth2_encrypted_client_secret,
+                                    oauth2_scopes,
+                                ),
+                            ),
+                            // Load the signing key of the application (cached per application)
+                            signing_key: if config
+                                .enabled_signature_versions
+                                .contains(&SignatureVersion::V2)
+                            {
+                                signing_key_cache
+                                    .get(
+                                        pool,
+                                        config.target_credentials_encryption_key.as_ref(),
+                                        attempt.application_id,
+                                    )
+                                    .await?
+                            } else {
+                                Ok(None)
+                            },
+                            // Load the secrets and headers of the subscription (cached per subscription); the ones in the message may be outdated or left out because they are encrypted
+                            credentials: credentials_cache
+                                .get(
+                                    pool,
+                                    config.target_credentials_encryption_key.as_ref(),
+                                    attempt.application_id,
+                                    attempt.subscription_id,
+                                    StoredCredentials {
+                                        headers,
+                                        encrypted_headers,
+                                        secret,
+                                        encrypted_secret,
+                                        previous_secret,
+                                        encrypted_previous_secret,
+                                    },
+                                )
+                                .await?,
+                            standard_webhooks: standard_webhooks
+                                .as_deref()
+                                .and_then(|mode| StandardWebhooksMode::from_str(mode).ok()),
+                        },
+                    },
                     Some(RawRequestAttemptStatus {
                         not_cancelled: true,
                         not_done: false,
@@ -713,8 +918,53 @@ async fn handle_message(
                 };
                 stats.record_db_fetch(fetch_start.elapsed());
 
+                // Request attempts of subscriptions that reached their limits are sent back to Pulsar for later; they are neither failed nor counted as retries
+                let mut rate_limit_permit = None;
+                let request_attempt_status = match request_attempt_status {
+                    RequestAttemptStatus::Ready {
+                        delay_until,
+                        limits,
+                        batched,
+                        transformation,
+                        context,
+                    } => {
+                        match rate_limiter.try_acquire(
+                            attempt.subscription_id,
+                            limits,
+                            std::time::Instant::now(),
+                        ) {
+                            Ok(permit) => {
+                                rate_limit_permit = permit;
+                                RequestAttemptStatus::Ready {
+                                    delay_until,
+                                    limits,
+                                    batched,
+                                    transformation,
+                                    context,
+                                }
+                            }
+                            Err(retry_in) => {
+                                let lead = TimeDelta::from_std(retry_in)?;
+                                RequestAttemptStatus::Delayed {
+                                    delay_until: Utc::now() + lead,
+                                    lead,
+                                }
+                            }
+                        }
+                    }
+                    status => status,
+                };
+
                 match request_attempt_status {
-                    RequestAttemptStatus::Ready { delay_until } => {
+                    RequestAttemptStatus::Ready {
+                        delay_until,
+                        batched,
+                        transformation,
+                        context,
+                        ..
+                    } => {
+                        let _rate_limit_permit = rate_limit_permit;
+
                         // Record queue lag: time between becoming eligible and pickup
                         let eligible_at = delay_until
                             .unwrap_or(attempt.created_at)
@@ -727,7 +977,13 @@ async fn handle_message(
                         let span = start_request_attempt_span(&attempt);
 
                         // Work
-                        let response = work(config, &attempt).await;
+                        // Request attempts are not grouped when consumed from Pulsar, which is why the API does not let subscriptions enable batching when Pulsar is used
+                        // Subscriptions that enabled it beforehand still receive the batch format, one event at a time
+                        let response = if batched {
+                            work_batch(config, std::slice::from_ref(&attempt), context).await
+                        } else {
+                            work(config, &attempt, transformation.as_ref(), context).await
+                        };
                         trace!(request_attempt_id = %attempt.request_attempt_id, elapsed_ms = response.elapsed_time_ms(), "Got response for request attempt");
 
                         // Open DB transaction
@@ -835,6 +1091,13 @@ async fn handle_message(
                                 warn!(request_attempt_id = %attempt.request_attempt_id, "Race detected: request attempt was already finalized by another process; skipping");
                                 true
                             } else {
+                                circuit_breaker::record_success(
+                                    &mut tx,
+                                    config,
+                                    &attempt.subscription_id,
+                                )
+                                .await?;
+
                                 debug!(request_attempt_id = %attempt.request_attempt_id, "Request attempt completed successfully");
                                 false
                             }
@@ -848,7 +1111,8 @@ async fn handle_message(
                                         worker_version = $2,
                                         picked_at = $3,
                                         response__id = $4,
-                                        failed_at = statement_timestamp()
+                                        failed_at = statement_timestamp(),
+                                        is_permanent_failure = $6
                                     WHERE request_attempt__id = $5
                                         AND succeeded_at IS NULL
                                         AND failed_at IS NULL
@@ -858,6 +1122,10 @@ async fn handle_message(
                                 picked_at,
                                 response_id,
                                 attempt.request_attempt_id,
+                                response.is_permanent_failure(
+                                    &config.non_retryable_http_codes,
+                                    config.retry_invalid_targets,
+                                ),
                             )
                             .execute(&mut *tx)
                             .await?;
@@ -866,17 +1134,29 @@ async fn handle_message(
                                 warn!(request_attempt_id = %attempt.request_attempt_id, "Race detected: request attempt was already finalized by another process; skipping retry");
                                 true
                             } else {
-                                // Creating a retry request or giving up
-                                if let Some(retry_in) = compute_next_retry(
+                                circuit_breaker::record_failure(
                                     &mut tx,
-                                    &attempt,
-                                    &response,
-                                    config.max_retries,
+                                    config,
+                                    &attempt.subscription_id,
                                 )
-                                .await?
-                                {
+                                .await?;
+
+                                // Creating a retry request or giving up
+                                let next_retry =
+                                    compute_next_retry(&mut tx, &attempt, &response, config)
+                                        .await?;
+                                let next_retry = next_retry.and_then(|retry_in| {
+                                    TimeDelta::from_std(retry_in)
+                                        .ok()
+                                        .and_then(|delta| Utc::now().checked_add_signed(delta))
+                                        .or_else(|| {
+                                            warn!(request_attempt_id = %attempt.request_attempt_id, retry_in_secs = retry_in.as_secs(), "Could not schedule a retry (delay is too long)");
+                                            None
+                                        })
+                                        .map(|delay_until| (retry_in, delay_until))
+                                });
+                                if let Some((retry_in, delay_until)) = next_retry {
                                     let next_retry_count = attempt.retry_count + 1;
-                                    let delay_until = Utc::now() + retry_in;
 
                                     #[allow(non_snake_case)]
                                     struct Retry {
@@ -921,6 +1201,8 @@ async fn handle_message(
                                             request_attempt_id,
                                             created_at: retry.created_at,
                                             retry_count: next_retry_count,
+                                            // The secret is read from the database when the retry is handled, so it does not need to travel through Pulsar again
+                                            secret: None,
                                             ..attempt
                                         })
                                         .send_non_blocking()
@@ -969,7 +1251,7 @@ async fn handle_message(
 
                         Ok(())
                     }
-                    // This should never happen because delayed request attempts are sent to Pulsar with a `deliver_at` constraint
+                    // Apart from subscriptions that reached their limits or wait for previous events to be delivered in order, this should never happen because delayed request attempts are sent to Pulsar with a `deliver_at` constraint
                     // This process is there to make sure delayed request attempts will not be processed immediately if the Pulsar producer made a mistake
                     RequestAttemptStatus::Delayed { delay_until, lead } => {
                         stats.record_not_ready();
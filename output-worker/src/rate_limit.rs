@@ -0,0 +1,322 @@
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::time::{Duration, Instant};
+use uuid::Uuid;
+
+/// How long to postpone a delivery when its subscription already has too many requests in flight
+const IN_FLIGHT_DEFERRAL: Duration = Duration::from_secs(1);
+
+/// How often the state of subscriptions that are back to idle is dropped
+const EVICTION_INTERVAL: Duration = Duration::from_secs(60);
+
+/// Delivery limits of a subscription, as stored in `webhook.subscription` by the API
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Limits {
+    pub max_requests_per_second: Option<i32>,
+    pub max_in_flight: Option<i32>,
+}
+
+impl Limits {
+    fn requests_per_second(&self) -> Option<f64> {
+        self.max_requests_per_second
+            .filter(|rps| *rps > 0)
+            .map(f64::from)
+    }
+
+    fn in_flight(&self) -> Option<u32> {
+        self.max_in_flight.and_then(|n| u32::try_from(n).ok())
+    }
+
+    fn is_unlimited(&self) -> bool {
+        self.requests_per_second().is_none() && self.in_flight().is_none()
+    }
+}
+
+#[derive(Debug)]
+struct SubscriptionState {
+    limits: Limits,
+    in_flight: u32,
+    /// Token bucket that can hold up to `max_requests_per_second` tokens
+    tokens: f64,
+    refilled_at: Instant,
+    /// Postponed deliveries are given successive slots so that they do not all come back at the same time
+    next_slot: Instant,
+    /// Set when all in-flight slots of the subscription were found to be used by other workers
+    deferred_until: Instant,
+}
+
+impl SubscriptionState {
+    fn new(limits: Limits, now: Instant) -> Self {
+        Self {
+            limits,
+            in_flight: 0,
+            tokens: limits.requests_per_second().unwrap_or(0.0),
+            refilled_at: now,
+            next_slot: now,
+            deferred_until: now,
+        }
+    }
+
+    fn available_tokens(&self, now: Instant) -> f64 {
+        match self.limits.requests_per_second() {
+            Some(rps) => {
+                let elapsed = now.saturating_duration_since(self.refilled_at);
+                (self.tokens + elapsed.as_secs_f64() * rps).min(rps)
+            }
+            None => 0.0,
+        }
+    }
+
+    fn is_saturated(&self, now: Instant) -> bool {
+        self.deferred_until > now
+            || self
+                .limits
+                .in_flight()
+                .is_some_and(|max| self.in_flight >= max)
+            || (self.limits.requests_per_second().is_some() && self.available_tokens(now) < 1.0)
+    }
+
+    /// Whether the state is the same as a fresh one, in which case it can be dropped
+    fn is_idle(&self, now: Instant) -> bool {
+        self.in_flight == 0
+            && self.next_slot <= now
+            && self.deferred_until <= now
+            && self
+                .limits
+                .requests_per_second()
+                .is_none_or(|rps| self.available_tokens(now) >= rps)
+    }
+}
+
+#[derive(Debug)]
+struct State {
+    subscriptions: HashMap<Uuid, SubscriptionState>,
+    evicted_at: Instant,
+}
+
+impl Default for State {
+    fn default() -> Self {
+        Self {
+            subscriptions: HashMap::new(),
+            evicted_at: Instant::now(),
+        }
+    }
+}
+
+/// Enforces per-subscription delivery limits within this worker process
+///
+/// Deliveries that would go over a limit must be postponed by the caller; they are neither failed nor counted as retries.
+#[derive(Debug, Default)]
+pub struct RateLimiter {
+    state: Mutex<State>,
+}
+
+impl RateLimiter {
+    /// Lock the state of subscriptions, dropping the ones that are back to idle from time to time so that it does not grow forever
+    fn lock(&self, now: Instant) -> MutexGuard<'_, State> {
+        let mut state = self.state.lock().expect("rate limiter mutex was poisoned");
+        if now.saturating_duration_since(state.evicted_at) >= EVICTION_INTERVAL {
+            state
+                .subscriptions
+                .retain(|_, subscription| !subscription.is_idle(now));
+            state.evicted_at = now;
+        }
+        state
+    }
+
+    /// Try to start a delivery for a subscription
+    ///
+    /// On success, the returned permit must be kept until the delivery is done (it is `None` if the subscription has no limits).
+    /// Otherwise, the duration after which the delivery should be attempted again is returned.
+    pub fn try_acquire(
+        self: &Arc<Self>,
+        subscription_id: Uuid,
+        limits: Limits,
+        now: Instant,
+    ) -> Result<Option<Permit>, Duration> {
+        let mut state = self.lock(now);
+        let subscriptions = &mut state.subscriptions;
+
+        if limits.is_unlimited() {
+            // Keep the state around while deliveries that started under a limit are still in flight
+            if subscriptions
+                .get(&subscription_id)
+                .is_some_and(|state| state.in_flight == 0)
+            {
+                subscriptions.remove(&subscription_id);
+            }
+            return Ok(None);
+        }
+
+        let state = subscriptions
+            .entry(subscription_id)
+            .or_insert_with(|| SubscriptionState::new(limits, now));
+        state.tokens = state.available_tokens(now);
+        state.refilled_at = now;
+        state.limits = limits;
+
+        if let Some(max) = limits.in_flight()
+            && state.in_flight >= max
+        {
+            return Err(IN_FLIGHT_DEFERRAL);
+        }
+
+        if let Some(rps) = limits.requests_per_second() {
+            if state.tokens < 1.0 {
+                let wait = Duration::from_secs_f64((1.0 - state.tokens) / rps);
+                let slot = state.next_slot.max(now + wait);
+                state.next_slot = slot + Duration::from_secs_f64(1.0 / rps);
+                return Err(slot - now);
+            }
+            state.tokens -= 1.0;
+        }
+
+        state.in_flight += 1;
+        Ok(Some(Permit {
+            limiter: self.clone(),
+            subscription_id,
+        }))
+    }
+
+    /// Consider a subscription as saturated for a while because all its in-flight slots are used by other workers
+    pub fn defer(&self, subscription_id: Uuid, limits: Limits, now: Instant) {
+        self.lock(now)
+            .subscriptions
+            .entry(subscription_id)
+            .or_insert_with(|| SubscriptionState::new(limits, now))
+            .deferred_until = now + IN_FLIGHT_DEFERRAL;
+    }
+
+    /// List subscriptions that cannot accept another delivery right now
+    pub fn saturated_subscriptions(&self, now: Instant) -> Vec<Uuid> {
+        self.lock(now)
+            .subscriptions
+            .iter()
+            .filter(|(_, state)| state.is_saturated(now))
+            .map(|(id, _)| *id)
+            .collect()
+    }
+}
+
+/// Releases the in-flight slot of a subscription on drop
+#[derive(Debug)]
+pub struct Permit {
+    limiter: Arc<RateLimiter>,
+    subscription_id: Uuid,
+}
+
+impl Drop for Permit {
+    fn drop(&mut self) {
+        if let Ok(mut limiter_state) = self.limiter.state.lock()
+            && let Some(state) = limiter_state.subscriptions.get_mut(&self.subscription_id)
+        {
+            state.in_flight = state.in_flight.saturating_sub(1);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn unlimited() {
+        let limiter = Arc::new(RateLimiter::default());
+        let now = Instant::now();
+        for _ in 0..100 {
+            assert!(matches!(
+                limiter.try_acquire(Uuid::nil(), Limits::default(), now),
+                Ok(None)
+            ));
+        }
+        assert!(limiter.saturated_subscriptions(now).is_empty());
+    }
+
+    #[test]
+    fn max_in_flight() {
+        let limiter = Arc::new(RateLimiter::default());
+        let limits = Limits {
+            max_requests_per_second: None,
+            max_in_flight: Some(2),
+        };
+        let now = Instant::now();
+
+        let first = limiter.try_acquire(Uuid::nil(), limits, now).unwrap();
+        let _second = limiter.try_acquire(Uuid::nil(), limits, now).unwrap();
+        assert_eq!(
+            limiter.try_acquire(Uuid::nil(), limits, now).unwrap_err(),
+            IN_FLIGHT_DEFERRAL
+        );
+        assert_eq!(limiter.saturated_subscriptions(now), vec![Uuid::nil()]);
+
+        drop(first);
+        assert!(limiter.saturated_subscriptions(now).is_empty());
+        assert!(limiter.try_acquire(Uuid::nil(), limits, now).is_ok());
+    }
+
+    #[test]
+    fn max_requests_per_second() {
+        let limiter = Arc::new(RateLimiter::default());
+        let limits = Limits {
+            max_requests_per_second: Some(2),
+            max_in_flight: None,
+        };
+        let now = Instant::now();
+
+        assert!(limiter.try_acquire(Uuid::nil(), limits, now).is_ok());
+        assert!(limiter.try_acquire(Uuid::nil(), limits, now).is_ok());
+        assert_eq!(limiter.saturated_subscriptions(now), vec![Uuid::nil()]);
+
+        // Postponed deliveries are spread according to the rate
+        assert_eq!(
+            limiter.try_acquire(Uuid::nil(), limits, now).unwrap_err(),
+            Duration::from_millis(500)
+        );
+        assert_eq!(
+            limiter.try_acquire(Uuid::nil(), limits, now).unwrap_err(),
+            Duration::from_secs(1)
+        );
+
+        let later = now + Duration::from_millis(500);
+        assert!(limiter.saturated_subscriptions(later).is_empty());
+        assert!(limiter.try_acquire(Uuid::nil(), limits, later).is_ok());
+    }
+
+    #[test]
+    fn defer() {
+        let limiter = Arc::new(RateLimiter::default());
+        let limits = Limits {
+            max_requests_per_second: None,
+            max_in_flight: Some(1),
+        };
+        let now = Instant::now();
+
+        limiter.defer(Uuid::nil(), limits, now);
+        assert_eq!(limiter.saturated_subscriptions(now), vec![Uuid::nil()]);
+        assert!(
+            limiter
+                .saturated_subscriptions(now + IN_FLIGHT_DEFERRAL)
+                .is_empty()
+        );
+    }
+
+    #[test]
+    fn eviction() {
+        let limiter = Arc::new(RateLimiter::default());
+        let limits = Limits {
+            max_requests_per_second: Some(1),
+            max_in_flight: Some(1),
+        };
+        let now = Instant::now();
+
+        let busy = Uuid::from_u128(1);
+        let _permit = limiter.try_acquire(busy, limits, now).unwrap();
+        drop(limiter.try_acquire(Uuid::nil(), limits, now).unwrap());
+        assert_eq!(limiter.state.lock().unwrap().subscriptions.len(), 2);
+
+        // Subscriptions with a delivery in flight are kept, the others are dropped once their bucket is full again
+        limiter.saturated_subscriptions(now + EVICTION_INTERVAL);
+        let state = limiter.state.lock().unwrap();
+        assert_eq!(state.subscriptions.keys().collect::<Vec<_>>(), vec![&busy]);
+    }
+}
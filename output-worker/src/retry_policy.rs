@@ -0,0 +1,194 @@
+use serde::Deserialize;
+use std::time::Duration;
+
+/// Per-subscription retry policy, as stored in `webhook.subscription.retry_policy` by the API
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+pub struct RetryPolicy {
+    pub strategy: RetryStrategy,
+    pub initial_delay_in_s: Option<u32>,
+    pub multiplier: Option<u32>,
+    pub increment_in_s: Option<u32>,
+    pub max_delay_in_s: Option<u32>,
+    pub delays_in_s: Option<Vec<u32>>,
+    pub max_attempts: u16,
+    pub max_age_in_s: Option<u32>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum RetryStrategy {
+    Exponential,
+    Linear,
+    Custom,
+}
+
+const DEFAULT_MULTIPLIER: u32 = 2;
+
+/// Upper bound of a single delay when the policy does not set `max_delay_in_s` (the highest value the API accepts, one week)
+const DEFAULT_MAX_DELAY_IN_S: u32 = 604_800;
+
+impl RetryPolicy {
+    /// Compute the delay before the next attempt, or `None` if the policy says we should give up
+    ///
+    /// `retry_count` is the retry count of the attempt that just failed and `elapsed` is the time since the event was received.
+    pub fn next_retry_duration(&self, retry_count: i16, elapsed: Duration) -> Option<Duration> {
+        let retry_count = u32::try_from(retry_count).ok()?;
+
+        // The first attempt is not a retry, so the next attempt would be attempt number retry_count + 2
+        if retry_count + 2 > u32::from(self.max_attempts) {
+            return None;
+        }
+
+        let delay = self.delay(retry_count)?;
+
+        if self.exceeds_max_age(elapsed, delay) {
+            return None;
+        }
+
+        Some(delay)
+    }
+
+    /// Whether an attempt made after `delay` would be too late, `elapsed` being the time since the event was received
+    pub fn exceeds_max_age(&self, elapsed: Duration, delay: Duration) -> bool {
+        self.max_age_in_s.is_some_and(|max_age| {
+            elapsed
+                .checked_add(delay)
+                .is_none_or(|age| age > Duration::from_secs(max_age.into()))
+        })
+    }
+
+    fn delay(&self, retry_count: u32) -> Option<Duration> {
+        let delay_in_s = match self.strategy {
+            RetryStrategy::Exponential => {
+                let initial = u64::from(self.initial_delay_in_s?);
+                let multiplier = u64::from(self.multiplier.unwrap_or(DEFAULT_MULTIPLIER));
+                multiplier
+                    .checked_pow(retry_count)
+                    .and_then(|factor| initial.checked_mul(factor))
+                    .unwrap_or(u64::MAX)
+            }
+            RetryStrategy::Linear => {
+                let initial = u64::from(self.initial_delay_in_s?);
+                let increment = self.increment_in_s.map(u64::from).unwrap_or(initial);
+                increment
+                    .saturating_mul(retry_count.into())
+                    .saturating_add(initial)
+            }
+            RetryStrategy::Custom => {
+                return self
+                    .delays_in_s
+                    .as_ref()?
+                    .get(usize::try_from(retry_count).ok()?)
+                    .map(|d| Duration::from_secs((*d).into()));
+            }
+        };
+
+        let max_delay_in_s = self.max_delay_in_s.unwrap_or(DEFAULT_MAX_DELAY_IN_S);
+        Some(Duration::from_secs(delay_in_s.min(max_delay_in_s.into())))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn policy(strategy: RetryStrategy) -> RetryPolicy {
+        RetryPolicy {
+            strategy,
+            initial_delay_in_s: Some(10),
+            multiplier: None,
+            increment_in_s: None,
+            max_delay_in_s: None,
+            delays_in_s: None,
+            max_attempts: 5,
+            max_age_in_s: None,
+        }
+    }
+
+    fn delays(policy: &RetryPolicy) -> Vec<u64> {
+        (0..)
+            .map_while(|i| policy.next_retry_duration(i, Duration::ZERO))
+            .map(|d| d.as_secs())
+            .collect()
+    }
+
+    #[test]
+    fn exponential() {
+        let mut p = policy(RetryStrategy::Exponential);
+        assert_eq!(delays(&p), vec![10, 20, 40, 80]);
+
+        p.multiplier = Some(3);
+        p.max_delay_in_s = Some(100);
+        assert_eq!(delays(&p), vec![10, 30, 90, 100]);
+    }
+
+    #[test]
+    fn default_max_delay() {
+        let mut p = policy(RetryStrategy::Exponential);
+        p.multiplier = Some(10);
+        p.max_attempts = 100;
+        assert_eq!(
+            p.next_retry_duration(3, Duration::ZERO),
+            Some(Duration::from_secs(10_000))
+        );
+        assert_eq!(
+            p.next_retry_duration(50, Duration::ZERO),
+            Some(Duration::from_secs(DEFAULT_MAX_DELAY_IN_S.into()))
+        );
+    }
+
+    #[test]
+    fn linear() {
+        let mut p = policy(RetryStrategy::Linear);
+        assert_eq!(delays(&p), vec![10, 20, 30, 40]);
+
+        p.increment_in_s = Some(5);
+        assert_eq!(delays(&p), vec![10, 15, 20, 25]);
+    }
+
+    #[test]
+    fn custom() {
+        let mut p = policy(RetryStrategy::Custom);
+        p.delays_in_s = Some(vec![1, 60, 3600]);
+        assert_eq!(delays(&p), vec![1, 60, 3600]);
+
+        p.max_attempts = 2;
+        assert_eq!(delays(&p), vec![1]);
+    }
+
+    #[test]
+    fn single_attempt() {
+        let mut p = policy(RetryStrategy::Exponential);
+        p.max_attempts = 1;
+        assert_eq!(p.next_retry_duration(0, Duration::ZERO), None);
+    }
+
+    #[test]
+    fn max_age() {
+        let mut p = policy(RetryStrategy::Linear);
+        p.max_age_in_s = Some(60);
+        assert_eq!(
+            p.next_retry_duration(0, Duration::from_secs(45)),
+            Some(Duration::from_secs(10))
+        );
+        assert_eq!(p.next_retry_duration(1, Duration::from_secs(45)), None);
+        assert_eq!(p.next_retry_duration(0, Duration::MAX), None);
+    }
+
+    #[test]
+    fn deserialize() {
+        let p: RetryPolicy = serde_json::from_value(serde_json::json!({
+            "strategy": "custom",
+            "initial_delay_in_s": null,
+            "multiplier": null,
+            "increment_in_s": null,
+            "max_delay_in_s": null,
+            "delays_in_s": [5, 10],
+            "max_attempts": 3,
+            "max_age_in_s": 3600,
+        }))
+        .unwrap();
+        assert_eq!(p.strategy, RetryStrategy::Custom);
+        assert_eq!(p.delays_in_s, Some(vec![5, 10]));
+    }
+}
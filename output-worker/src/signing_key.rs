@@ -0,0 +1,114 @@
+use ed25519_dalek::SigningKey;
+use sqlx::{Acquire, Postgres, query};
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::time::{Duration, Instant};
+use tracing::debug;
+use uuid::Uuid;
+
+use crate::encryption_key::EncryptionKey;
+
+/// Signing keys of applications are looked up again after this duration, so that rotations are taken into account
+const REFRESH_PERIOD: Duration = Duration::from_secs(60);
+
+/// Ed25519 key that signs the webhooks of an application (`v2` signatures)
+pub struct ApplicationSigningKey {
+    /// Published as `kid` in the key set of the application and sent as `k` in the signature header
+    pub id: Uuid,
+    pub key: SigningKey,
+}
+
+/// Keeps the signing keys of applications within this worker process
+///
+/// Private keys are only decrypted again when the signing key of an application is rotated.
+#[derive(Default)]
+pub struct SigningKeyCache {
+    applications: Mutex<HashMap<Uuid, (Instant, Option<Arc<ApplicationSigningKey>>)>>,
+}
+
+impl SigningKeyCache {
+    /// Get the key to sign webhooks of an application with
+    ///
+    /// The inner result is `Ok(None)` if the application has no signing key, and an error message if its signing key cannot be used.
+    /// The outer result only holds errors that are not related to the signing key, such as database connection errors.
+    pub async fn get<'a, A: Acquire<'a, Database = Postgres>>(
+        &self,
+        db: A,
+        encryption_key: Option<&EncryptionKey>,
+        application_id: Uuid,
+    ) -> Result<Result<Option<Arc<ApplicationSigningKey>>, String>, sqlx::Error> {
+        let cached = self.lock().get(&application_id).cloned();
+        if let Some((fetched_at, signing_key)) = &cached
+            && fetched_at.elapsed() < REFRESH_PERIOD
+        {
+            return Ok(Ok(signing_key.clone()));
+        }
+
+        let mut conn = db.acquire().await?;
+        let fetched_at = Instant::now();
+        let Some(row) = query!(
+            "
+                SELECT signing_key__id AS signing_key_id, encrypted_private_key
+                FROM webhook.signing_key
+                WHERE application__id = $1 AND expires_at IS NULL
+            ",
+            application_id,
+        )
+        .fetch_optional(&mut *conn)
+        .await?
+        else {
+            self.lock().insert(application_id, (fetched_at, None));
+            return Ok(Ok(None));
+        };
+
+        // The key did not change since it was last decrypted
+        if let Some((_, Some(signing_key))) = cached
+            && signing_key.id == row.signing_key_id
+        {
+            self.lock()
+                .insert(application_id, (fetched_at, Some(signing_key.clone())));
+            return Ok(Ok(Some(signing_key)));
+        }
+
+        debug!(%application_id, "Loading signing key of application");
+        match decrypt_private_key(encryption_key, &row.encrypted_private_key) {
+            Ok(key) => {
+                let signing_key = Arc::new(ApplicationSigningKey {
+                    id: row.signing_key_id,
+                    key,
+                });
+                self.lock()
+                    .insert(application_id, (fetched_at, Some(signing_key.clone())));
+                Ok(Ok(Some(signing_key)))
+            }
+            Err(msg) => Ok(Err(msg)),
+        }
+    }
+
+    fn lock(&self) -> MutexGuard<'_, HashMap<Uuid, (Instant, Option<Arc<ApplicationSigningKey>>)>> {
+        self.applications
+            .lock()
+            .expect("signing key cache mutex was poisoned")
+    }
+}
+
+fn decrypt_private_key(
+    encryption_key: Option<&EncryptionKey>,
+    encrypted_private_key: &[u8],
+) -> Result<SigningKey, String> {
+    let Some(encryption_key) = encryption_key else {
+        return Err(
+            "Application has a signing key but this worker has no key to decrypt it".to_owned(),
+        );
+    };
+
+    let Some(private_key) = encryption_key.decrypt(encrypted_private_key) else {
+        return Err(
+            "Could not decrypt signing key; the key of this worker may be wrong".to_owned(),
+        );
+    };
+    match <[u8; 32]>::try_from(private_key.as_slice()) {
+        Ok(secret_key) => Ok(SigningKey::from_bytes(&secret_key)),
+        Err(_) => Err("Signing key is not valid".to_owned()),
+    }
+}
@@ -0,0 +1,80 @@
+use hook0_payload_transformation::{CompiledTransformation, PayloadTransformation};
+use serde_json::Value;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex, MutexGuard};
+use tracing::debug;
+use uuid::Uuid;
+
+/// Keeps the compiled payload transformations of subscriptions within this worker process
+///
+/// Templates are only compiled again when the payload transformation of a subscription changes.
+#[derive(Default)]
+pub struct TransformationCache {
+    subscriptions: Mutex<HashMap<Uuid, (Value, Arc<CompiledTransformation>)>>,
+}
+
+impl TransformationCache {
+    /// Get the compiled payload transformation of a subscription from the value of its `payload_transformation` column
+    ///
+    /// The result is `None` if the subscription has no payload transformation, and an error message if its payload transformation is invalid.
+    pub fn get(
+        &self,
+        subscription_id: Uuid,
+        spec: Option<Value>,
+    ) -> Option<Result<Arc<CompiledTransformation>, String>> {
+        let Some(spec) = spec else {
+            self.lock().remove(&subscription_id);
+            return None;
+        };
+
+        if let Some((cached_spec, compiled)) = self.lock().get(&subscription_id)
+            && *cached_spec == spec
+        {
+            return Some(Ok(compiled.clone()));
+        }
+
+        debug!(%subscription_id, "Compiling payload transformation of subscription");
+        let compiled = serde_json::from_value::<PayloadTransformation>(spec.clone())
+            .map_err(|e| format!("Subscription has an invalid payload transformation: {e}"))
+            .and_then(|transformation| transformation.compile().map_err(|e| e.to_string()))
+            .map(Arc::new);
+        if let Ok(compiled) = &compiled {
+            self.lock()
+                .insert(subscription_id, (spec, compiled.clone()));
+        }
+        Some(compiled)
+    }
+
+    fn lock(&self) -> MutexGuard<'_, HashMap<Uuid, (Value, Arc<CompiledTransformation>)>> {
+        self.subscriptions
+            .lock()
+            .expect("payload transformation cache mutex was poisoned")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    #[test]
+    fn compile_once_per_spec() {
+        let cache = TransformationCache::default();
+        let subscription_id = Uuid::nil();
+        let get = |spec| cache.get(subscription_id, Some(spec)).unwrap();
+
+        let spec = json!({ "template": "{{ event_type }}", "content_type": "text/plain" });
+        let compiled = get(spec.clone()).unwrap();
+        assert!(Arc::ptr_eq(&compiled, &get(spec).unwrap()));
+
+        let other_spec = json!({ "template": "{{ event_id }}", "content_type": "text/plain" });
+        assert!(!Arc::ptr_eq(&compiled, &get(other_spec).unwrap()));
+
+        assert!(get(json!({ "template": "{{" })).is_err());
+        assert!(get(json!("not a transformation")).is_err());
+
+        assert!(cache.get(subscription_id, None).is_none());
+        assert!(cache.lock().is_empty());
+    }
+}
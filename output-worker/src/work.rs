@@ -1,19 +1,30 @@
+use base64::Engine;
+use base64::engine::general_purpose::STANDARD;
 use chrono::{DateTime, Utc};
 use clap::{crate_name, crate_version};
+use ed25519_dalek::{Signer, SigningKey};
 use hex::ToHex;
 use hmac::{Hmac, KeyInit, Mac};
-use reqwest::header::{HeaderMap, HeaderName, HeaderValue, InvalidHeaderValue};
-use reqwest::{Client, Method, Url};
+use hook0_payload_transformation::{CompiledTransformation, TransformationContext, payload_value};
+use reqwest::header::{AUTHORIZATION, HeaderMap, HeaderName, HeaderValue, InvalidHeaderValue};
+use reqwest::{Client, Method, Request, Url};
+use serde::Serialize;
 use serde_json::Value;
 use sha2::Sha256;
 use std::collections::HashMap;
 use std::fmt;
 use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
 use std::str::FromStr;
+use std::sync::Arc;
 use std::time::{Duration, Instant};
-use strum::VariantNames;
+use strum::{EnumString, VariantNames};
 use tracing::{debug, error, instrument, trace, warn};
+use uuid::Uuid;
 
+use crate::client_tls::ClientTls;
+use crate::credentials::SubscriptionCredentials;
+use crate::oauth2::OAuth2Client;
+use crate::signing_key::ApplicationSigningKey;
 use crate::{Config, RequestAttempt, SignatureVersion};
 
 const USER_AGENT: &str = concat!(crate_name!(), "/", crate_version!());
@@ -32,6 +43,16 @@ pub enum ResponseError {
     Timeout,
     #[strum(serialize = "E_HTTP")]
     Http,
+    #[strum(serialize = "E_TRANSFORMATION")]
+    Transformation,
+    #[strum(serialize = "E_AUTHENTICATION")]
+    Authentication,
+    /// The hostname of the target could not be resolved, which may be temporary
+    #[strum(serialize = "E_RESOLUTION")]
+    Resolution,
+    /// The worker could not load the TLS material, OAuth2 credentials, secrets or signing key needed for the delivery
+    #[strum(serialize = "E_DELIVERY_SETTINGS")]
+    DeliverySettings,
 }
 
 #[derive(Debug, Clone)]
@@ -77,49 +98,247 @@ impl Response {
     pub fn elapsed_time_ms(&self) -> i32 {
         self.elapsed_time.as_millis().try_into().unwrap_or(0)
     }
+
+    /// Whether retrying would be pointless because the target is invalid or explicitly refused the request
+    pub fn is_permanent_failure(
+        &self,
+        non_retryable_http_codes: &[u16],
+        retry_invalid_targets: bool,
+    ) -> bool {
+        match self.response_error {
+            Some(ResponseError::InvalidHeader) => true,
+            Some(ResponseError::Transformation) => true,
+            Some(ResponseError::InvalidTarget) => !retry_invalid_targets,
+            Some(ResponseError::Http) => self
+                .http_code
+                .is_some_and(|code| non_retryable_http_codes.contains(&code)),
+            _ => false,
+        }
+    }
+
+    /// Delay requested by the target through a `Retry-After` header (only honored on 429 and 503 responses)
+    ///
+    /// The header can either contain a number of seconds or an HTTP-date.
+    pub fn retry_after(&self, now: DateTime<Utc>) -> Option<Duration> {
+        if !matches!(self.http_code, Some(429 | 503)) {
+            return None;
+        }
+
+        let value = self
+            .headers
+            .as_ref()?
+            .get(reqwest::header::RETRY_AFTER)?
+            .to_str()
+            .ok()?
+            .trim();
+
+        if let Ok(seconds) = value.parse::<u64>() {
+            Some(Duration::from_secs(seconds))
+        } else {
+            let date = DateTime::parse_from_rfc2822(value).ok()?;
+            Some(
+                (date.with_timezone(&Utc) - now)
+                    .to_std()
+                    .unwrap_or(Duration::ZERO),
+            )
+        }
+    }
+}
+
+/// Payload transformation of a subscription, along with the event data it needs that is not part of [`RequestAttempt`]
+#[derive(Debug, Clone)]
+pub struct Transformation {
+    /// Compiled payload transformation of the subscription, or an error message if it is invalid (see [`TransformationCache`](crate::transformation::TransformationCache))
+    pub compiled: Result<Arc<CompiledTransformation>, String>,
+    pub labels: Value,
+    pub metadata: Option<Value>,
+}
+
+impl Transformation {
+    /// Render the body of the webhook; returns the body and its content type
+    fn apply(&self, attempt: &RequestAttempt) -> Result<(Vec<u8>, String), String> {
+        let compiled = self.compiled.as_ref().map_err(|msg| msg.to_owned())?;
+        let context = TransformationContext::new(
+            attempt.event_id,
+            &attempt.event_type_name,
+            attempt.event_received_at,
+            &attempt.payload_content_type,
+            &attempt.payload,
+            self.labels.clone(),
+            self.metadata.clone(),
+        );
+        let body = compiled.render(&context).map_err(|e| e.to_string())?;
+        Ok((body, compiled.content_type.to_owned()))
+    }
+}
+
+/// Settings of the target and secrets of the subscription used to deliver a request attempt, loaded once per request attempt
+///
+/// Loading errors are kept so that they are reported as the response of the request attempt.
+#[derive(Clone)]
+pub struct DeliveryContext {
+    pub client_tls: Result<Option<Arc<ClientTls>>, String>,
+    pub oauth2: Result<Option<Arc<OAuth2Client>>, String>,
+    pub credentials: Result<Arc<SubscriptionCredentials>, String>,
+    pub signing_key: Result<Option<Arc<ApplicationSigningKey>>, String>,
+    pub standard_webhooks: Option<StandardWebhooksMode>,
+}
+
+// Secrets are left out
+impl fmt::Debug for DeliveryContext {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("DeliveryContext")
+            .field("standard_webhooks", &self.standard_webhooks)
+            .finish_non_exhaustive()
+    }
 }
 
 #[instrument(skip_all, fields(request_attempt_id = %attempt.request_attempt_id))]
-pub async fn work(config: &Config, attempt: &RequestAttempt) -> Response {
+pub async fn work(
+    config: &Config,
+    attempt: &RequestAttempt,
+    transformation: Option<&Transformation>,
+    context: DeliveryContext,
+) -> Response {
     debug!("Processing request attempt");
+
+    let (body, content_type) = match transformation.map(|t| t.apply(attempt)) {
+        Some(Ok(transformed)) => transformed,
+        Some(Err(msg)) => {
+            warn!("Could not transform payload: {msg}");
+            return Response {
+                response_error: Some(ResponseError::Transformation),
+                http_code: None,
+                headers: None,
+                body: Some(msg.into_bytes()),
+                elapsed_time: Duration::ZERO,
+            };
+        }
+        None => (
+            attempt.payload.clone(),
+            attempt.payload_content_type.to_owned(),
+        ),
+    };
+
+    let event_headers = HeaderValue::from_str(&attempt.event_type_name)
+        .map_err(|_| {
+            format!(
+                "Event type has an invalid header value: {}",
+                attempt.event_type_name
+            )
+        })
+        .and_then(|et| {
+            let event_id = HeaderValue::from_str(attempt.event_id.to_string().as_str())
+                .expect("Could not create a header value from the event ID UUID");
+            let content_type = HeaderValue::from_str(&content_type)
+                .map_err(|_| format!("Content type has an invalid header value: {content_type}"))?;
+            Ok(vec![
+                ("Content-Type", content_type),
+                ("X-Event-Id", event_id),
+                ("X-Event-Type", et),
+            ])
+        });
+
+    call_target(config, attempt, event_headers, body, context).await
+}
+
+/// Send several events of the same subscription to its target in a single request
+///
+/// The body is a JSON array of [`BatchItem`] and is signed as a whole. `attempts` must not be empty; the target is taken from the first request attempt.
+#[instrument(skip_all, fields(request_attempt_id = %attempts[0].request_attempt_id, batch_size = attempts.len()))]
+pub async fn work_batch(
+    config: &Config,
+    attempts: &[RequestAttempt],
+    context: DeliveryContext,
+) -> Response {
+    debug!("Processing batch of request attempts");
+
+    let items = attempts.iter().map(BatchItem::from).collect::<Vec<_>>();
+    let body = serde_json::to_vec(&items).expect("Could not serialize batch of events");
+    let event_headers = Ok(vec![(
+        "Content-Type",
+        HeaderValue::from_static("application/json"),
+    )]);
+
+    call_target(config, &attempts[0], event_headers, body, context).await
+}
+
+/// Event sent to the target as part of a batch
+#[derive(Debug, Serialize)]
+struct BatchItem<'a> {
+    /// Same value as the `X-Event-Id` header of single deliveries
+    event_id: String,
+    /// Same value as the `X-Event-Type` header of single deliveries
+    event_type: &'a str,
+    payload_content_type: &'a str,
+    /// JSON payloads are embedded as is, text payloads as strings and binary payloads as base64 strings
+    payload: Value,
+}
+
+impl<'a> From<&'a RequestAttempt> for BatchItem<'a> {
+    fn from(attempt: &'a RequestAttempt) -> Self {
+        Self {
+            event_id: attempt.event_id.to_string(),
+            event_type: &attempt.event_type_name,
+            payload_content_type: &attempt.payload_content_type,
+            payload: payload_value(&attempt.payload_content_type, &attempt.payload),
+        }
+    }
+}
+
+async fn call_target(
+    config: &Config,
+    attempt: &RequestAttempt,
+    event_headers: Result<Vec<(&'static str, HeaderValue)>, String>,
+    body: Vec<u8>,
+    context: DeliveryContext,
+) -> Response {
     let start = Instant::now();
+    let DeliveryContext {
+        client_tls,
+        oauth2,
+        credentials,
+        signing_key,
+        standard_webhooks,
+    } = context;
+
+    let credentials = match credentials {
+        Ok(credentials) => credentials,
+        Err(msg) => {
+            error!("Could not load subscription credentials: {msg}");
+            return Response {
+                response_error: Some(ResponseError::DeliverySettings),
+                http_code: None,
+                headers: None,
+                body: Some(msg.into_bytes()),
+                elapsed_time: start.elapsed(),
+            };
+        }
+    };
 
     let m = Method::from_str(attempt.http_method.as_str());
-    let u = Url::parse(attempt.http_url.as_str())
-        .map_err(|e| e.to_string())
-        .and_then(|url| {
-            let addrs = url.socket_addrs(|| None).map_err(|e| e.to_string())?;
-            if addrs.is_empty() {
-                Err("URL did not resolve to any IP address".to_string())
-            } else {
-                // Reject if *any* resolved address is forbidden: a hostname that resolves to a mix of public and internal addresses must not pass.
-                let has_forbidden_ip = addrs.iter().any(|addr| is_forbidden_ip(addr.ip()));
+    let u = resolve_target_url(config, attempt.http_url.as_str());
+    let hs = parse_headers(credentials.headers.clone());
 
-                if has_forbidden_ip {
-                    if config.disable_target_ip_check {
-                        debug!("Target URL resolves to a forbidden IP but this is allowed in the worker's configuration");
-                        Ok((url, addrs))
-                    } else {
-                        Err("URL resolves to a forbidden IP".to_string())
-                    }
-                } else {
-                    Ok((url, addrs))
-                }
-            }
-        });
-    let hs = parse_headers(attempt.http_headers.clone());
-    let et = HeaderValue::from_str(&attempt.event_type_name);
-    let event_id = HeaderValue::from_str(attempt.event_id.to_string().as_str())
-        .expect("Could not create a header value from the event ID UUID");
-    let content_type = HeaderValue::from_str(attempt.payload_content_type.as_str())
-        .expect("Could not create a header value from the event content type");
-
-    match (m, u, hs, et) {
-        (Ok(method), Ok((url, addrs)), Ok(mut headers), Ok(et)) => {
+    match (m, u, hs, event_headers, client_tls, oauth2, signing_key) {
+        (
+            Ok(method),
+            Ok((url, addrs)),
+            Ok(mut headers),
+            Ok(event_headers),
+            Ok(client_tls),
+            Ok(oauth2),
+            Ok(signing_key),
+        ) => {
             // Pin the connection to the exact addresses we just vetted so reqwest cannot re-resolve the hostname to a different (forbidden) IP between the check and the request (DNS rebinding).
             // Only domain hosts need this; IP-literal URLs skip DNS.
             let pin = url.domain().map(|host| (host, addrs.as_slice()));
-            let client = match mk_http_client(config.connect_timeout, config.timeout, pin) {
+            let client = match mk_http_client(
+                config.connect_timeout,
+                config.timeout,
+                pin,
+                client_tls.as_deref(),
+            ) {
                 Ok(client) => client,
                 Err(e) => {
                     error!("Could not create HTTP client: {e}");
@@ -133,138 +352,73 @@ pub async fn work(config: &Config, attempt: &RequestAttempt) -> Response {
                 }
             };
 
-            headers.insert("Content-Type", content_type);
-            headers.insert("X-Event-Id", event_id);
-            headers.insert("X-Event-Type", et);
+            for (name, value) in event_headers {
+                headers.insert(name, value);
+            }
+            let signing = RequestSigning {
+                credentials: &credentials,
+                signing_key: signing_key.as_deref(),
+                standard_webhooks,
+            };
 
-            let s = Signature::new(
-                &attempt.secret.to_string(),
-                &attempt.payload,
-                Utc::now(),
-                &headers,
-            )
-            .map_err(|e| {
-                let msg =
-                    format!("Could not construct header '{e}' because it has an invalid value");
-                warn!["{msg}"];
-                Box::new(Response {
-                    response_error: Some(ResponseError::InvalidHeader),
-                    http_code: None,
-                    headers: None,
-                    body: Some(msg.into_bytes()),
-                    elapsed_time: start.elapsed(),
-                })
-            })
-            .and_then(|sig| {
-                sig.to_header_value(
-                    config
-                        .enabled_signature_versions
-                        .contains(&SignatureVersion::V0),
-                    config
-                        .enabled_signature_versions
-                        .contains(&SignatureVersion::V1),
+            let Some(oauth2) = oauth2 else {
+                let mut request = Request::new(method, url);
+                *request.headers_mut() = headers;
+                return send_signed_request(
+                    config, attempt, signing, &client, request, body, start,
                 )
-                .map_err(|_| {
-                    Box::new(Response {
-                        response_error: Some(ResponseError::InvalidHeader),
-                        http_code: None,
-                        headers: None,
-                        body: None,
-                        elapsed_time: start.elapsed(),
-                    })
-                })
-            });
-
-            match s {
-                Ok(sig) => {
-                    headers.insert(&config.signature_header_name, sig);
-
-                    debug!("Calling webhook...");
-                    let redacted_headers = RedactedHeaders {
-                        headers: &headers,
-                        safe_headers: &[
-                            HeaderName::from_static("content-type"),
-                            HeaderName::from_static("x-event-id"),
-                            HeaderName::from_static("x-event-type"),
-                            config.signature_header_name.clone(),
-                        ],
-                    };
-                    trace!(
-                        http_method = %method.to_string().to_uppercase(),
-                        %url,
-                        headers = ?redacted_headers,
-                        headers_count = headers.len(),
-                        "Calling webhook"
-                    );
-                    let response = client
-                        .request(method, url)
-                        .headers(headers)
-                        .body(attempt.payload.clone())
-                        .send()
-                        .await;
-
-                    match response {
-                        Ok(res) => {
-                            let status = res.status();
-                            let headers = res.headers().clone();
-                            let body = res.bytes().await.ok().map(|b| b.to_vec());
-
-                            if status.is_success() {
-                                debug!("Webhook call was successful");
-                                Response {
-                                    response_error: None,
-                                    http_code: Some(status.as_u16()),
-                                    headers: Some(headers),
-                                    body,
-                                    elapsed_time: start.elapsed(),
-                                }
-                            } else {
-                                warn!(http_status = %status, "Webhook call failed with HTTP error");
-                                Response {
-                                    response_error: Some(ResponseError::Http),
-                                    http_code: Some(status.as_u16()),
-                                    headers: Some(headers),
-                                    body,
-                                    elapsed_time: start.elapsed(),
-                                }
-                            }
-                        }
-                        Err(e) if e.is_connect() => {
-                            warn!("Webhook call failed with connection error: {e}");
-                            Response {
-                                response_error: Some(ResponseError::Connection),
-                                http_code: None,
-                                headers: None,
-                                body: Some(e.to_string().into_bytes()),
-                                elapsed_time: start.elapsed(),
-                            }
-                        }
-                        Err(e) if e.is_timeout() => {
-                            warn!("Webhook call failed with timeout error: {e}");
-                            Response {
-                                response_error: Some(ResponseError::Timeout),
-                                http_code: None,
-                                headers: None,
-                                body: Some(e.to_string().into_bytes()),
-                                elapsed_time: start.elapsed(),
-                            }
-                        }
-                        Err(e) => {
-                            warn!("Webhook call failed with unknown error: {e}");
-                            Response {
-                                response_error: Some(ResponseError::Unknown),
-                                http_code: None,
-                                headers: None,
-                                body: Some(e.to_string().into_bytes()),
-                                elapsed_time: start.elapsed(),
-                            }
-                        }
+                .await;
+            };
+
+            // The access token replaces any `Authorization` header of the target; if the target rejects it, a new one is requested once (it may have been revoked before it expired)
+            let mut rejected_access_token = None;
+            loop {
+                let (access_token, authorization) = match oauth2
+                    .access_token(config, rejected_access_token.as_deref())
+                    .await
+                    .and_then(|access_token| {
+                        HeaderValue::from_str(&format!("Bearer {access_token}"))
+                            .map(|value| (access_token, value))
+                            .map_err(|_| {
+                                "Token endpoint returned an access token that is not a valid header value".to_owned()
+                            })
+                    }) {
+                    Ok(access_token_and_authorization) => access_token_and_authorization,
+                    Err(msg) => {
+                        warn!("Could not obtain an OAuth2 access token: {msg}");
+                        return Response {
+                            response_error: Some(ResponseError::Authentication),
+                            http_code: None,
+                            headers: None,
+                            body: Some(msg.into_bytes()),
+                            elapsed_time: start.elapsed(),
+                        };
                     }
+                };
+
+                let mut request = Request::new(method.clone(), url.clone());
+                *request.headers_mut() = headers.clone();
+                request.headers_mut().insert(AUTHORIZATION, authorization);
+                let response = send_signed_request(
+                    config,
+                    attempt,
+                    signing,
+                    &client,
+                    request,
+                    body.clone(),
+                    start,
+                )
+                .await;
+
+                if response.http_code == Some(401) && rejected_access_token.is_none() {
+                    debug!("Target rejected the OAuth2 access token; requesting a new one");
+                    rejected_access_token = Some(access_token);
+                } else {
+                    return response;
                 }
-                Err(e) => *e,
             }
         }
-        (Err(e), _, _, _) => {
+        (Err(e), _, _, _, _, _, _) => {
             error!(
                 target_http_method = attempt.http_method,
                 "Target has an invalid HTTP method: {e}"
@@ -277,7 +431,7 @@ pub async fn work(config: &Config, attempt: &RequestAttempt) -> Response {
                 elapsed_time: start.elapsed(),
             }
         }
-        (_, Err(e), _, _) => {
+        (_, Err(TargetUrlError::Invalid(e)), _, _, _, _, _) => {
             warn!(
                 target_http_url = attempt.http_url,
                 "Target has an invalid URL: {e}"
@@ -286,11 +440,24 @@ pub async fn work(config: &Config, attempt: &RequestAttempt) -> Response {
                 response_error: Some(ResponseError::InvalidTarget),
                 http_code: None,
                 headers: None,
-                body: Some(e.to_string().into_bytes()),
+                body: Some(e.into_bytes()),
                 elapsed_time: start.elapsed(),
             }
         }
-        (_, _, Err(e), _) => {
+        (_, Err(TargetUrlError::Resolution(e)), _, _, _, _, _) => {
+            warn!(
+                target_http_url = attempt.http_url,
+                "Could not resolve target URL: {e}"
+            );
+            Response {
+                response_error: Some(ResponseError::Resolution),
+                http_code: None,
+                headers: None,
+                body: Some(e.into_bytes()),
+                elapsed_time: start.elapsed(),
+            }
+        }
+        (_, _, Err(e), _, _, _, _) => {
             warn!("Target has invalid headers: {e}");
             Response {
                 response_error: Some(ResponseError::InvalidTarget),
@@ -300,11 +467,7 @@ pub async fn work(config: &Config, attempt: &RequestAttempt) -> Response {
                 elapsed_time: start.elapsed(),
             }
         }
-        (_, _, _, Err(_)) => {
-            let msg = format!(
-                "Event type has an invalid header value: {}",
-                attempt.event_type_name
-            );
+        (_, _, _, Err(msg), _, _, _) => {
             warn!("{msg}");
             Response {
                 response_error: Some(ResponseError::InvalidHeader),
@@ -314,6 +477,288 @@ pub async fn work(config: &Config, attempt: &RequestAttempt) -> Response {
                 elapsed_time: start.elapsed(),
             }
         }
+        (_, _, _, _, Err(msg), _, _) => {
+            error!("Could not load TLS settings of target: {msg}");
+            Response {
+                response_error: Some(ResponseError::DeliverySettings),
+                http_code: None,
+                headers: None,
+                body: Some(msg.into_bytes()),
+                elapsed_time: start.elapsed(),
+            }
+        }
+        (_, _, _, _, _, Err(msg), _) => {
+            error!("Could not load OAuth2 settings of target: {msg}");
+            Response {
+                response_error: Some(ResponseError::DeliverySettings),
+                http_code: None,
+                headers: None,
+                body: Some(msg.into_bytes()),
+                elapsed_time: start.elapsed(),
+            }
+        }
+        (_, _, _, _, _, _, Err(msg)) => {
+            error!("Could not load signing key: {msg}");
+            Response {
+                response_error: Some(ResponseError::DeliverySettings),
+                http_code: None,
+                headers: None,
+                body: Some(msg.into_bytes()),
+                elapsed_time: start.elapsed(),
+            }
+        }
+    }
+}
+
+/// Secrets used to sign the requests of a delivery
+#[derive(Clone, Copy)]
+struct RequestSigning<'a> {
+    credentials: &'a SubscriptionCredentials,
+    signing_key: Option<&'a ApplicationSigningKey>,
+    standard_webhooks: Option<StandardWebhooksMode>,
+}
+
+/// Sign the request and send it to the target
+///
+/// While the secret of the subscription is being rotated, the request is signed with both its current and its previous secret.
+/// If the application has a signing key, the request is also signed with it (`v2` signature).
+/// Standard Webhooks headers are added if the subscription or the instance enables them; they can replace the signature header.
+async fn send_signed_request(
+    config: &Config,
+    attempt: &RequestAttempt,
+    signing: RequestSigning<'_>,
+    client: &Client,
+    mut request: Request,
+    body: Vec<u8>,
+    start: Instant,
+) -> Response {
+    let RequestSigning {
+        credentials,
+        signing_key,
+        standard_webhooks,
+    } = signing;
+    let secrets = std::iter::once(credentials.secret)
+        .chain(credentials.previous_secret)
+        .map(|secret| secret.to_string())
+        .collect::<Vec<_>>();
+    let secrets = secrets.iter().map(String::as_str).collect::<Vec<_>>();
+    let signed_at = Utc::now();
+    let v0_enabled = config
+        .enabled_signature_versions
+        .contains(&SignatureVersion::V0);
+    let v1_enabled = config
+        .enabled_signature_versions
+        .contains(&SignatureVersion::V1);
+    let standard_webhooks = standard_webhooks.or_else(|| {
+        config
+            .enabled_signature_versions
+            .contains(&SignatureVersion::StandardWebhooks)
+            .then_some(StandardWebhooksMode::InAddition)
+    });
+
+    // The signature header is left out if it would not hold any signature
+    let s = if standard_webhooks == Some(StandardWebhooksMode::Instead)
+        || !(v0_enabled || v1_enabled || signing_key.is_some())
+    {
+        Ok(Vec::new())
+    } else {
+        Signature::new(
+            &secrets,
+            signing_key.map(|sk| (sk.id, &sk.key)),
+            &body,
+            signed_at,
+            request.headers(),
+        )
+        .map_err(|e| {
+            let msg = format!("Could not construct header '{e}' because it has an invalid value");
+            warn!["{msg}"];
+            Box::new(Response {
+                response_error: Some(ResponseError::InvalidHeader),
+                http_code: None,
+                headers: None,
+                body: Some(msg.into_bytes()),
+                elapsed_time: start.elapsed(),
+            })
+        })
+        .and_then(|sig| {
+            sig.to_header_value(v0_enabled, v1_enabled)
+                .map(|value| vec![(config.signature_header_name.clone(), value)])
+                .map_err(|_| {
+                    Box::new(Response {
+                        response_error: Some(ResponseError::InvalidHeader),
+                        http_code: None,
+                        headers: None,
+                        body: None,
+                        elapsed_time: start.elapsed(),
+                    })
+                })
+        })
+    };
+
+    match s {
+        Ok(signature_headers) => {
+            for (name, value) in signature_headers {
+                request.headers_mut().insert(name, value);
+            }
+
+            if standard_webhooks.is_some() {
+                let signature = StandardWebhooksSignature::new(
+                    &secrets,
+                    signing_key.map(|sk| &sk.key),
+                    &attempt.event_id.to_string(),
+                    &body,
+                    signed_at,
+                );
+                for (name, value) in signature.headers() {
+                    request.headers_mut().insert(name, value);
+                }
+            }
+
+            debug!("Calling webhook...");
+            let redacted_headers = RedactedHeaders {
+                headers: request.headers(),
+                safe_headers: &[
+                    HeaderName::from_static("content-type"),
+                    HeaderName::from_static("x-event-id"),
+                    HeaderName::from_static("x-event-type"),
+                    config.signature_header_name.clone(),
+                    HeaderName::from_static(StandardWebhooksSignature::ID_HEADER),
+                    HeaderName::from_static(StandardWebhooksSignature::TIMESTAMP_HEADER),
+                    HeaderName::from_static(StandardWebhooksSignature::SIGNATURE_HEADER),
+                ],
+            };
+            trace!(
+                http_method = %request.method().to_string().to_uppercase(),
+                url = %request.url(),
+                headers = ?redacted_headers,
+                headers_count = request.headers().len(),
+                "Calling webhook"
+            );
+            *request.body_mut() = Some(body.into());
+            let response = client.execute(request).await;
+
+            match response {
+                Ok(res) => {
+                    let status = res.status();
+                    let headers = res.headers().clone();
+                    let body = res.bytes().await.ok().map(|b| b.to_vec());
+
+                    if status.is_success() {
+                        debug!("Webhook call was successful");
+                        Response {
+                            response_error: None,
+                            http_code: Some(status.as_u16()),
+                            headers: Some(headers),
+                            body,
+                            elapsed_time: start.elapsed(),
+                        }
+                    } else {
+                        warn!(http_status = %status, "Webhook call failed with HTTP error");
+                        Response {
+                            response_error: Some(ResponseError::Http),
+                            http_code: Some(status.as_u16()),
+                            headers: Some(headers),
+                            body,
+                            elapsed_time: start.elapsed(),
+                        }
+                    }
+                }
+                Err(e) if e.is_connect() => {
+                    warn!("Webhook call failed with connection error: {e}");
+                    Response {
+                        response_error: Some(ResponseError::Connection),
+                        http_code: None,
+                        headers: None,
+                        body: Some(e.to_string().into_bytes()),
+                        elapsed_time: start.elapsed(),
+                    }
+                }
+                Err(e) if e.is_timeout() => {
+                    warn!("Webhook call failed with timeout error: {e}");
+                    Response {
+                        response_error: Some(ResponseError::Timeout),
+                        http_code: None,
+                        headers: None,
+                        body: Some(e.to_string().into_bytes()),
+                        elapsed_time: start.elapsed(),
+                    }
+                }
+                Err(e) => {
+                    warn!("Webhook call failed with unknown error: {e}");
+                    Response {
+                        response_error: Some(ResponseError::Unknown),
+                        http_code: None,
+                        headers: None,
+                        body: Some(e.to_string().into_bytes()),
+                        elapsed_time: start.elapsed(),
+                    }
+                }
+            }
+        }
+        Err(e) => *e,
+    }
+}
+
+/// Reason why a target URL cannot be called
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TargetUrlError {
+    /// The URL cannot be parsed or resolves to a forbidden IP; retrying will not help
+    Invalid(String),
+    /// The hostname could not be resolved (or did not resolve to any IP address), which may be temporary
+    Resolution(String),
+}
+
+impl fmt::Display for TargetUrlError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Invalid(msg) | Self::Resolution(msg) => f.write_str(msg),
+        }
+    }
+}
+
+/// Parse a URL and resolve its hostname
+fn resolve_url(url: &str) -> Result<(Url, Vec<SocketAddr>), TargetUrlError> {
+    let url = Url::parse(url).map_err(|e| TargetUrlError::Invalid(e.to_string()))?;
+    let addrs = url.socket_addrs(|| None).map_err(|e| {
+        // URLs without a host or a known port are rejected before any DNS lookup
+        if e.kind() == std::io::ErrorKind::InvalidData {
+            TargetUrlError::Invalid(e.to_string())
+        } else {
+            TargetUrlError::Resolution(e.to_string())
+        }
+    })?;
+    if addrs.is_empty() {
+        Err(TargetUrlError::Resolution(
+            "URL did not resolve to any IP address".to_string(),
+        ))
+    } else {
+        Ok((url, addrs))
+    }
+}
+
+/// Parse a target URL and check the addresses it resolves to
+pub fn resolve_target_url(
+    config: &Config,
+    url: &str,
+) -> Result<(Url, Vec<SocketAddr>), TargetUrlError> {
+    let (url, addrs) = resolve_url(url)?;
+
+    // Reject if *any* resolved address is forbidden: a hostname that resolves to a mix of public and internal addresses must not pass.
+    let has_forbidden_ip = addrs.iter().any(|addr| is_forbidden_ip(addr.ip()));
+
+    if has_forbidden_ip {
+        if config.disable_target_ip_check {
+            debug!(
+                "Target URL resolves to a forbidden IP but this is allowed in the worker's configuration"
+            );
+            Ok((url, addrs))
+        } else {
+            Err(TargetUrlError::Invalid(
+                "URL resolves to a forbidden IP".to_string(),
+            ))
+        }
+    } else {
+        Ok((url, addrs))
     }
 }
 
@@ -387,12 +832,14 @@ fn is_forbidden_ip(ip: IpAddr) -> bool {
     }
 }
 
-fn mk_http_client(
+pub fn mk_http_client(
     connect_timeout: Duration,
     timeout: Duration,
     // When set, pins DNS resolution of `host` to the already-vetted addresses so reqwest
     // cannot re-resolve the hostname to a different IP than the one we checked.
     pin: Option<(&str, &[SocketAddr])>,
+    // Client certificate and certificate authorities of the target, if it does not use the default TLS settings
+    tls: Option<&ClientTls>,
 ) -> reqwest::Result<Client> {
     let mut builder = Client::builder()
         .connection_verbose(true)
@@ -413,6 +860,15 @@ fn mk_http_client(
         builder = builder.resolve_to_addrs(host, addrs);
     }
 
+    if let Some(tls) = tls {
+        if let Some(identity) = &tls.identity {
+            builder = builder.identity(identity.clone());
+        }
+        if let Some(ca_certificates) = &tls.ca_certificates {
+            builder = builder.tls_certs_only(ca_certificates.iter().cloned());
+        }
+    }
+
     builder.build()
 }
 
@@ -445,8 +901,12 @@ fn parse_headers(value: Value) -> anyhow::Result<HeaderMap> {
 struct Signature {
     pub timestamp: i64,
     pub headers: String,
-    pub v0: String,
-    pub v1: String,
+    /// One signature per secret, in the same order as the secrets
+    pub v0: Vec<String>,
+    /// One signature per secret, in the same order as the secrets
+    pub v1: Vec<String>,
+    /// ID of the signing key and Ed25519 signature of the same content as `v1`
+    pub v2: Option<(String, String)>,
 }
 
 impl Signature {
@@ -456,8 +916,10 @@ impl Signature {
     const SIGNATURE_PART_SEPARATOR: &'static str = ",";
     const SIGNATURE_PART_HEADER_NAMES_SEPARATOR: &'static str = " ";
 
+    /// Sign the payload with each of the secrets (several secrets are used while the secret of a subscription is being rotated) and with the signing key of the application, if any
     pub fn new(
-        secret: &str,
+        secrets: &[&str],
+        signing_key: Option<(Uuid, &SigningKey)>,
         payload: &[u8],
         signed_at: DateTime<Utc>,
         headers: &HeaderMap,
@@ -479,43 +941,68 @@ impl Signature {
             hs
         };
 
-        type HmacSha256 = Hmac<Sha256>;
-        let mut mac_v0 = HmacSha256::new_from_slice(secret.as_bytes()).unwrap(); // MAC can take key of any size; this should never fail
-        mac_v0.update(timestamp_str_bytes);
-        mac_v0.update(Self::PAYLOAD_SEPARATOR_BYTES);
-
-        let mut mac_v1 = mac_v0.clone();
-
-        mac_v0.update(payload);
-        let v0 = mac_v0.finalize().into_bytes().encode_hex::<String>();
-
         let header_names = sorted_headers_with_lowercased_names
             .iter()
             .map(|(k, _v)| k.as_str())
             .collect::<Vec<_>>()
             .join(Self::SIGNATURE_PART_HEADER_NAMES_SEPARATOR);
+        let header_values = sorted_headers_with_lowercased_names
+            .iter()
+            .map(|(_k, v)| *v)
+            .collect::<Vec<_>>()
+            .join(Self::PAYLOAD_SEPARATOR);
 
-        mac_v1.update(header_names.as_bytes());
-        mac_v1.update(Self::PAYLOAD_SEPARATOR_BYTES);
+        type HmacSha256 = Hmac<Sha256>;
+        let (v0, v1) = secrets
+            .iter()
+            .map(|secret| {
+                let mut mac_v0 = HmacSha256::new_from_slice(secret.as_bytes()).unwrap(); // MAC can take key of any size; this should never fail
+                mac_v0.update(timestamp_str_bytes);
+                mac_v0.update(Self::PAYLOAD_SEPARATOR_BYTES);
 
-        mac_v1.update(
-            sorted_headers_with_lowercased_names
-                .iter()
-                .map(|(_k, v)| *v)
-                .collect::<Vec<_>>()
-                .join(Self::PAYLOAD_SEPARATOR)
-                .as_bytes(),
-        );
-        mac_v1.update(Self::PAYLOAD_SEPARATOR_BYTES);
+                let mut mac_v1 = mac_v0.clone();
+
+                mac_v0.update(payload);
+                let v0 = mac_v0.finalize().into_bytes().encode_hex::<String>();
+
+                mac_v1.update(header_names.as_bytes());
+                mac_v1.update(Self::PAYLOAD_SEPARATOR_BYTES);
+                mac_v1.update(header_values.as_bytes());
+                mac_v1.update(Self::PAYLOAD_SEPARATOR_BYTES);
 
-        mac_v1.update(payload);
-        let v1 = mac_v1.finalize().into_bytes().encode_hex::<String>();
+                mac_v1.update(payload);
+                let v1 = mac_v1.finalize().into_bytes().encode_hex::<String>();
+
+                (v0, v1)
+            })
+            .unzip();
+
+        let v2 = signing_key.map(|(signing_key_id, signing_key)| {
+            let signed_content = [
+                timestamp_str_bytes,
+                Self::PAYLOAD_SEPARATOR_BYTES,
+                header_names.as_bytes(),
+                Self::PAYLOAD_SEPARATOR_BYTES,
+                header_values.as_bytes(),
+                Self::PAYLOAD_SEPARATOR_BYTES,
+                payload,
+            ]
+            .concat();
+            (
+                signing_key_id.to_string(),
+                signing_key
+                    .sign(&signed_content)
+                    .to_bytes()
+                    .encode_hex::<String>(),
+            )
+        });
 
         Ok(Self {
             timestamp,
             headers: header_names,
             v0,
             v1,
+            v2,
         })
     }
 
@@ -524,12 +1011,20 @@ impl Signature {
         let mut parts = vec![("t", timestamp_str.as_str())];
 
         if v0_enabled {
-            parts.push(("v0", self.v0.as_str()));
+            parts.extend(self.v0.iter().map(|v0| ("v0", v0.as_str())));
         }
 
-        if v1_enabled {
+        if v1_enabled || self.v2.is_some() {
             parts.push(("h", self.headers.as_str()));
-            parts.push(("v1", self.v1.as_str()));
+        }
+
+        if v1_enabled {
+            parts.extend(self.v1.iter().map(|v1| ("v1", v1.as_str())));
+        }
+
+        if let Some((signing_key_id, v2)) = &self.v2 {
+            parts.push(("k", signing_key_id.as_str()));
+            parts.push(("v2", v2.as_str()));
         }
 
         itertools::Itertools::intersperse(
@@ -550,11 +1045,94 @@ impl Signature {
     }
 }
 
+/// Whether webhooks carry [Standard Webhooks](https://www.standardwebhooks.com/) headers in addition to or instead of the signature header (as set on subscriptions)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, EnumString)]
+#[strum(serialize_all = "snake_case")]
+pub enum StandardWebhooksMode {
+    InAddition,
+    Instead,
+}
+
+/// Headers defined by the [Standard Webhooks](https://www.standardwebhooks.com/) specification
+///
+/// Signatures cover `<webhook-id>.<webhook-timestamp>.<body>`. There is one `v1` (HMAC-SHA256) signature per secret, whose UTF-8 bytes are the key (the `whsec_` secret of Standard Webhooks libraries is the base64 encoding of the secret), and a `v1a` (Ed25519) signature if the application has a signing key.
+struct StandardWebhooksSignature {
+    pub id: String,
+    pub timestamp: i64,
+    pub signatures: Vec<String>,
+}
+
+impl StandardWebhooksSignature {
+    const ID_HEADER: &'static str = "webhook-id";
+    const TIMESTAMP_HEADER: &'static str = "webhook-timestamp";
+    const SIGNATURE_HEADER: &'static str = "webhook-signature";
+
+    pub fn new(
+        secrets: &[&str],
+        signing_key: Option<&SigningKey>,
+        id: &str,
+        payload: &[u8],
+        signed_at: DateTime<Utc>,
+    ) -> Self {
+        let timestamp = signed_at.timestamp();
+        let signed_content = [
+            id.as_bytes(),
+            b".",
+            timestamp.to_string().as_bytes(),
+            b".",
+            payload,
+        ]
+        .concat();
+
+        type HmacSha256 = Hmac<Sha256>;
+        let mut signatures = secrets
+            .iter()
+            .map(|secret| {
+                let mut mac = HmacSha256::new_from_slice(secret.as_bytes()).unwrap(); // MAC can take key of any size; this should never fail
+                mac.update(&signed_content);
+                format!("v1,{}", STANDARD.encode(mac.finalize().into_bytes()))
+            })
+            .collect::<Vec<_>>();
+        if let Some(signing_key) = signing_key {
+            signatures.push(format!(
+                "v1a,{}",
+                STANDARD.encode(signing_key.sign(&signed_content).to_bytes())
+            ));
+        }
+
+        Self {
+            id: id.to_owned(),
+            timestamp,
+            signatures,
+        }
+    }
+
+    pub fn headers(&self) -> [(HeaderName, HeaderValue); 3] {
+        [
+            (
+                HeaderName::from_static(Self::ID_HEADER),
+                HeaderValue::from_str(&self.id)
+                    .expect("Could not create a header value from the webhook ID"),
+            ),
+            (
+                HeaderName::from_static(Self::TIMESTAMP_HEADER),
+                HeaderValue::from(self.timestamp),
+            ),
+            (
+                HeaderName::from_static(Self::SIGNATURE_HEADER),
+                HeaderValue::from_str(&self.signatures.join(" "))
+                    .expect("Could not create a header value from base64 signatures"),
+            ),
+        ]
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
 
     use chrono::prelude::*;
+    use uuid::Uuid;
 
     #[test]
     fn create_signature_v0() {
@@ -562,7 +1140,14 @@ mod tests {
         let payload = "hello !";
         let secret = "secret";
 
-        let sig = Signature::new(secret, payload.as_bytes(), signed_at, &HeaderMap::new()).unwrap();
+        let sig = Signature::new(
+            &[secret],
+            None,
+            payload.as_bytes(),
+            signed_at,
+            &HeaderMap::new(),
+        )
+        .unwrap();
         assert_eq!(
             sig.value(true, false),
             "t=1636936200,v0=1b3d69df55f1e52f05224ba94a5162abeb17ef52cd7f4948c390f810d6a87e98"
@@ -586,7 +1171,7 @@ mod tests {
                 .expect("Invalid header values"),
         );
 
-        let sig = Signature::new(secret, payload.as_bytes(), signed_at, &headers).unwrap();
+        let sig = Signature::new(&[secret], None, payload.as_bytes(), signed_at, &headers).unwrap();
         assert_eq!(
             sig.value(false, true),
             "t=1636936200,h=x-event-id x-event-type,v1=bc521546ba5de381b12f135782d2008b028c3065c191760b12b76850a8fc8f51"
@@ -609,13 +1194,138 @@ mod tests {
             HeaderValue::from_str("service.resource.verb").expect("Invalid header values"),
         );
 
-        let sig = Signature::new(secret, payload.as_bytes(), signed_at, &headers).unwrap();
+        let sig = Signature::new(&[secret], None, payload.as_bytes(), signed_at, &headers).unwrap();
         assert_eq!(
             sig.value(true, true),
             "t=1636936200,v0=1b3d69df55f1e52f05224ba94a5162abeb17ef52cd7f4948c390f810d6a87e98,h=x-event-id x-event-type,v1=bc521546ba5de381b12f135782d2008b028c3065c191760b12b76850a8fc8f51"
         );
     }
 
+    #[test]
+    fn create_signature_with_previous_secret() {
+        let signed_at = Utc.with_ymd_and_hms(2021, 11, 15, 0, 30, 0).unwrap();
+        let payload = "hello !";
+        let mut headers = HeaderMap::new();
+        headers.insert(
+            "X-Event-Id",
+            HeaderValue::from_str("1a01cb48-5142-4d9b-8f90-d20cca61f0ee")
+                .expect("Invalid header values"),
+        );
+        headers.insert(
+            "X-Event-Type",
+            HeaderValue::from_str("service.resource.verb").expect("Invalid header values"),
+        );
+
+        let sig = Signature::new(
+            &["new secret", "secret"],
+            None,
+            payload.as_bytes(),
+            signed_at,
+            &headers,
+        )
+        .unwrap();
+        assert_eq!(
+            sig.value(true, true),
+            "t=1636936200,v0=8a4e57ff0ecd20b68fb645934d68dd1128fce73bc88a7530aaed2e7516b58b3f,v0=1b3d69df55f1e52f05224ba94a5162abeb17ef52cd7f4948c390f810d6a87e98,h=x-event-id x-event-type,v1=b5d41bb81eadc6b6e120f9153f337bc5c219267c0b1c04b0b0533d55dca080fe,v1=bc521546ba5de381b12f135782d2008b028c3065c191760b12b76850a8fc8f51"
+        );
+    }
+
+    #[test]
+    fn create_signature_v2() {
+        let signed_at = Utc.with_ymd_and_hms(2021, 11, 15, 0, 30, 0).unwrap();
+        let payload = "hello !";
+        let secret = "secret";
+        let signing_key_id = Uuid::from_str("0cdd6c77-4d4b-4bc1-8e58-8e5c6a6e8b0a").unwrap();
+        let signing_key = SigningKey::from_bytes(&[7; 32]);
+        let mut headers = HeaderMap::new();
+        headers.insert(
+            "X-Event-Id",
+            HeaderValue::from_str("1a01cb48-5142-4d9b-8f90-d20cca61f0ee")
+                .expect("Invalid header values"),
+        );
+        headers.insert(
+            "X-Event-Type",
+            HeaderValue::from_str("service.resource.verb").expect("Invalid header values"),
+        );
+
+        let sig = Signature::new(
+            &[secret],
+            Some((signing_key_id, &signing_key)),
+            payload.as_bytes(),
+            signed_at,
+            &headers,
+        )
+        .unwrap();
+        assert_eq!(
+            sig.value(false, true),
+            "t=1636936200,h=x-event-id x-event-type,v1=bc521546ba5de381b12f135782d2008b028c3065c191760b12b76850a8fc8f51,k=0cdd6c77-4d4b-4bc1-8e58-8e5c6a6e8b0a,v2=87ef31f30b36d4207855dcfffe757006d4b93221964cd3f214cebf867bf37cac80f23790c7b6498c5e37911f2d41f6c4811998d0da3ae643454bee32411e2b06"
+        );
+        assert_eq!(
+            sig.value(false, false),
+            "t=1636936200,h=x-event-id x-event-type,k=0cdd6c77-4d4b-4bc1-8e58-8e5c6a6e8b0a,v2=87ef31f30b36d4207855dcfffe757006d4b93221964cd3f214cebf867bf37cac80f23790c7b6498c5e37911f2d41f6c4811998d0da3ae643454bee32411e2b06"
+        );
+    }
+
+    #[test]
+    fn create_standard_webhooks_signature() {
+        let signed_at = Utc.with_ymd_and_hms(2021, 11, 15, 0, 30, 0).unwrap();
+        let payload = "hello !";
+
+        let sig = StandardWebhooksSignature::new(
+            &["new secret", "secret"],
+            None,
+            "1a01cb48-5142-4d9b-8f90-d20cca61f0ee",
+            payload.as_bytes(),
+            signed_at,
+        );
+        let [id, timestamp, signature] = sig.headers();
+        assert_eq!(
+            id,
+            (
+                HeaderName::from_static("webhook-id"),
+                HeaderValue::from_static("1a01cb48-5142-4d9b-8f90-d20cca61f0ee")
+            )
+        );
+        assert_eq!(
+            timestamp,
+            (
+                HeaderName::from_static("webhook-timestamp"),
+                HeaderValue::from_static("1636936200")
+            )
+        );
+        assert_eq!(
+            signature,
+            (
+                HeaderName::from_static("webhook-signature"),
+                HeaderValue::from_static(
+                    "v1,WIJG2BZDrngCgdG1Ol9Ah1ujkq9qX3KBGWp+UTSWHec= v1,jv6nrhFagT2ZrtruVCw4OdKkVhut1Qa4CaD0Pw4/oGk="
+                )
+            )
+        );
+    }
+
+    #[test]
+    fn create_standard_webhooks_signature_with_signing_key() {
+        let signed_at = Utc.with_ymd_and_hms(2021, 11, 15, 0, 30, 0).unwrap();
+        let payload = "hello !";
+        let signing_key = SigningKey::from_bytes(&[7; 32]);
+
+        let sig = StandardWebhooksSignature::new(
+            &["secret"],
+            Some(&signing_key),
+            "1a01cb48-5142-4d9b-8f90-d20cca61f0ee",
+            payload.as_bytes(),
+            signed_at,
+        );
+        assert_eq!(
+            sig.signatures,
+            vec![
+                "v1,jv6nrhFagT2ZrtruVCw4OdKkVhut1Qa4CaD0Pw4/oGk=".to_owned(),
+                "v1a,2lOkaDmxl8Zna8uWBk+jzSLWszdjifRKoze/tP2gBW1kVbsdE6qmeYl1PHTAKrYB2r/syr9Me7LYHGq5n0A/CA==".to_owned(),
+            ]
+        );
+    }
+
     #[test]
     fn create_signature_wrong_header() {
         let signed_at = Utc.with_ymd_and_hms(2021, 11, 15, 0, 30, 0).unwrap();
@@ -628,10 +1338,60 @@ mod tests {
             HeaderValue::from_str("pj.cartão.autorização").expect("Invalid header values"),
         );
 
-        let sig = Signature::new(secret, payload.as_bytes(), signed_at, &headers);
+        let sig = Signature::new(&[secret], None, payload.as_bytes(), signed_at, &headers);
         assert!(matches!(sig, Err(h) if h == HeaderName::from_static("x-event-type")));
     }
 
+    #[test]
+    fn batch_items() {
+        let attempt = |payload_content_type: &str, payload: &[u8]| RequestAttempt {
+            application_id: Uuid::nil(),
+            request_attempt_id: Uuid::nil(),
+            event_id: Uuid::from_u128(1),
+            event_received_at: Utc.with_ymd_and_hms(2021, 11, 15, 0, 30, 0).unwrap(),
+            subscription_id: Uuid::nil(),
+            created_at: Utc.with_ymd_and_hms(2021, 11, 15, 0, 30, 0).unwrap(),
+            retry_count: 0,
+            http_method: "POST".to_owned(),
+            http_url: "https://www.hook0.com".to_owned(),
+            http_headers: serde_json::json!({}),
+            event_type_name: "service.resource.verb".to_owned(),
+            payload: payload.to_vec(),
+            payload_content_type: payload_content_type.to_owned(),
+            secret: None,
+        };
+        let attempts = [
+            attempt("application/json", br#"{"hello": "world"}"#),
+            attempt("text/plain", b"hello !"),
+            attempt("application/octet-stream+base64", &[0, 1, 2]),
+        ];
+
+        let items = attempts.iter().map(BatchItem::from).collect::<Vec<_>>();
+        assert_eq!(
+            serde_json::to_value(&items).unwrap(),
+            serde_json::json!([
+                {
+                    "event_id": "00000000-0000-0000-0000-000000000001",
+                    "event_type": "service.resource.verb",
+                    "payload_content_type": "application/json",
+                    "payload": { "hello": "world" },
+                },
+                {
+                    "event_id": "00000000-0000-0000-0000-000000000001",
+                    "event_type": "service.resource.verb",
+                    "payload_content_type": "text/plain",
+                    "payload": "hello !",
+                },
+                {
+                    "event_id": "00000000-0000-0000-0000-000000000001",
+                    "event_type": "service.resource.verb",
+                    "payload_content_type": "application/octet-stream+base64",
+                    "payload": "AAEC",
+                },
+            ])
+        );
+    }
+
     fn ip(s: &str) -> IpAddr {
         s.parse().expect("invalid test IP")
     }
@@ -680,4 +1440,116 @@ mod tests {
         // First block just above `2001::/23` (b == 0x200) is globally reachable
         assert!(!is_forbidden_ip(ip("2001:200::1")));
     }
+
+    fn response_with_retry_after(http_code: u16, retry_after: &str) -> Response {
+        let mut headers = HeaderMap::new();
+        headers.insert(
+            reqwest::header::RETRY_AFTER,
+            HeaderValue::from_str(retry_after).expect("Invalid header values"),
+        );
+        Response {
+            response_error: Some(ResponseError::Http),
+            http_code: Some(http_code),
+            headers: Some(headers),
+            body: None,
+            elapsed_time: Duration::ZERO,
+        }
+    }
+
+    #[test]
+    fn permanent_failures() {
+        let non_retryable_http_codes = [400, 401, 403, 404, 410];
+        let response = |response_error, http_code| Response {
+            response_error,
+            http_code,
+            headers: None,
+            body: None,
+            elapsed_time: Duration::ZERO,
+        };
+
+        assert!(
+            response(Some(ResponseError::Http), Some(410))
+                .is_permanent_failure(&non_retryable_http_codes, false)
+        );
+        assert!(
+            !response(Some(ResponseError::Http), Some(500))
+                .is_permanent_failure(&non_retryable_http_codes, false)
+        );
+        assert!(!response(Some(ResponseError::Http), Some(410)).is_permanent_failure(&[], false));
+        assert!(
+            response(Some(ResponseError::InvalidTarget), None)
+                .is_permanent_failure(&non_retryable_http_codes, false)
+        );
+        assert!(
+            !response(Some(ResponseError::InvalidTarget), None)
+                .is_permanent_failure(&non_retryable_http_codes, true)
+        );
+        assert!(
+            !response(Some(ResponseError::Timeout), None)
+                .is_permanent_failure(&non_retryable_http_codes, false)
+        );
+        assert!(
+            !response(Some(ResponseError::Resolution), None)
+                .is_permanent_failure(&non_retryable_http_codes, false)
+        );
+        assert!(
+            !response(Some(ResponseError::DeliverySettings), None)
+                .is_permanent_failure(&non_retryable_http_codes, false)
+        );
+        assert!(!response(None, Some(200)).is_permanent_failure(&non_retryable_http_codes, false));
+    }
+
+    #[test]
+    fn resolve_url_errors() {
+        assert!(matches!(
+            resolve_url("not a url"),
+            Err(TargetUrlError::Invalid(_))
+        ));
+        assert!(matches!(
+            resolve_url("mailto:someone@example.com"),
+            Err(TargetUrlError::Invalid(_))
+        ));
+        // The `.invalid` top-level domain never resolves
+        assert!(matches!(
+            resolve_url("https://hook0.invalid/webhook"),
+            Err(TargetUrlError::Resolution(_))
+        ));
+        assert!(resolve_url("https://127.0.0.1/webhook").is_ok());
+    }
+
+    #[test]
+    fn retry_after_delay_seconds() {
+        let now = Utc.with_ymd_and_hms(2021, 11, 15, 0, 30, 0).unwrap();
+        assert_eq!(
+            response_with_retry_after(429, "120").retry_after(now),
+            Some(Duration::from_secs(120))
+        );
+        assert_eq!(
+            response_with_retry_after(503, " 5 ").retry_after(now),
+            Some(Duration::from_secs(5))
+        );
+    }
+
+    #[test]
+    fn retry_after_http_date() {
+        let now = Utc.with_ymd_and_hms(2021, 11, 15, 0, 30, 0).unwrap();
+        assert_eq!(
+            response_with_retry_after(503, "Mon, 15 Nov 2021 00:35:00 GMT").retry_after(now),
+            Some(Duration::from_secs(5 * 60))
+        );
+        assert_eq!(
+            response_with_retry_after(503, "Mon, 15 Nov 2021 00:00:00 GMT").retry_after(now),
+            Some(Duration::ZERO)
+        );
+    }
+
+    #[test]
+    fn retry_after_ignored() {
+        let now = Utc.with_ymd_and_hms(2021, 11, 15, 0, 30, 0).unwrap();
+        assert_eq!(response_with_retry_after(500, "120").retry_after(now), None);
+        assert_eq!(
+            response_with_retry_after(429, "tomorrow").retry_after(now),
+            None
+        );
+    }
 }
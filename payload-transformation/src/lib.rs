@@ -0,0 +1,349 @@
+//! Payload transformations let a subscription reshape the body of its webhooks (for example to match the format expected by a chat service) using a sandboxed [MiniJinja](https://docs.rs/minijinja) template.
+//!
+//! The same code is used by the API (to validate and preview transformations) and by the output worker (to apply them before signing webhooks).
+
+use base64::Engine;
+use base64::engine::general_purpose::STANDARD as Base64;
+use chrono::{DateTime, SecondsFormat, Utc};
+use minijinja::{AutoEscape, Environment};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::io;
+use uuid::Uuid;
+
+/// Maximum length of a template, in bytes
+pub const MAX_TEMPLATE_LENGTH: usize = 10_000;
+
+/// Maximum amount of work a template can do while being rendered (see [`Environment::set_fuel`])
+const FUEL: u64 = 100_000;
+
+/// Maximum size of a rendered payload, in bytes
+const MAX_OUTPUT_SIZE: usize = 1_000_000;
+
+/// Name of the only template of the environment of a [`CompiledTransformation`]
+const TEMPLATE_NAME: &str = "payload";
+
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+pub enum TransformationError {
+    /// The template is too long
+    #[error("Template must not be longer than {MAX_TEMPLATE_LENGTH} bytes")]
+    TemplateTooLong,
+
+    /// The template could not be compiled
+    #[error("Invalid template: {0}")]
+    InvalidTemplate(String),
+
+    /// The template could not be rendered with the given event
+    #[error("Could not render template: {0}")]
+    Render(String),
+
+    /// The rendered payload is too big
+    #[error("Rendered payload must not be bigger than {MAX_OUTPUT_SIZE} bytes")]
+    OutputTooBig,
+
+    /// The rendered payload is not valid JSON, although the transformation produces JSON
+    #[error("Rendered payload is not valid JSON: {0}")]
+    InvalidJsonOutput(String),
+}
+
+/// Transformation applied to the payload of events before they are sent to a subscription's target
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+pub struct PayloadTransformation {
+    /// MiniJinja template rendering the body of webhooks
+    pub template: String,
+    /// Value of the `Content-Type` header of webhooks
+    #[serde(default = "default_content_type")]
+    pub content_type: String,
+}
+
+fn default_content_type() -> String {
+    "application/json".to_owned()
+}
+
+impl PayloadTransformation {
+    /// Whether the rendered payload must be valid JSON
+    ///
+    /// In this case, values printed by the template are escaped as JSON (strings are quoted).
+    pub fn produces_json(&self) -> bool {
+        self.content_type == "application/json" || self.content_type.ends_with("+json")
+    }
+
+    fn environment(&self) -> Environment<'static> {
+        let mut env = Environment::new();
+        env.set_fuel(Some(FUEL));
+        let auto_escape = if self.produces_json() {
+            AutoEscape::Json
+        } else {
+            AutoEscape::None
+        };
+        env.set_auto_escape_callback(move |_| auto_escape);
+        env
+    }
+
+    /// Compile the template, so that it can render many webhooks
+    pub fn compile(&self) -> Result<CompiledTransformation, TransformationError> {
+        if self.template.len() > MAX_TEMPLATE_LENGTH {
+            return Err(TransformationError::TemplateTooLong);
+        }
+
+        let mut env = self.environment();
+        env.add_template_owned(TEMPLATE_NAME, self.template.clone())
+            .map_err(|e| TransformationError::InvalidTemplate(e.to_string()))?;
+        Ok(CompiledTransformation {
+            env,
+            content_type: self.content_type.clone(),
+            produces_json: self.produces_json(),
+        })
+    }
+
+    /// Make sure the template can be compiled
+    pub fn check(&self) -> Result<(), TransformationError> {
+        self.compile().map(|_| ())
+    }
+
+    /// Render the payload of a webhook for the given event
+    pub fn render(&self, event: &TransformationContext) -> Result<Vec<u8>, TransformationError> {
+        self.compile()?.render(event)
+    }
+}
+
+/// Payload transformation whose template was compiled by [`PayloadTransformation::compile`]
+#[derive(Debug)]
+pub struct CompiledTransformation {
+    env: Environment<'static>,
+    /// Value of the `Content-Type` header of webhooks
+    pub content_type: String,
+    produces_json: bool,
+}
+
+impl CompiledTransformation {
+    /// Render the payload of a webhook for the given event
+    pub fn render(&self, event: &TransformationContext) -> Result<Vec<u8>, TransformationError> {
+        let template = self
+            .env
+            .get_template(TEMPLATE_NAME)
+            .map_err(|e| TransformationError::InvalidTemplate(e.to_string()))?;
+
+        let mut output = BoundedOutput::default();
+        if let Err(e) = template.render_captured_to(event, &mut output) {
+            return Err(if output.overflowed {
+                TransformationError::OutputTooBig
+            } else {
+                TransformationError::Render(e.to_string())
+            });
+        }
+
+        if self.produces_json {
+            serde_json::from_slice::<serde::de::IgnoredAny>(&output.buffer)
+                .map_err(|e| TransformationError::InvalidJsonOutput(e.to_string()))?;
+        }
+        Ok(output.buffer)
+    }
+}
+
+/// Buffer that refuses to grow beyond [`MAX_OUTPUT_SIZE`], so that rendering stops as soon as the payload is too big
+#[derive(Debug, Default)]
+struct BoundedOutput {
+    buffer: Vec<u8>,
+    overflowed: bool,
+}
+
+impl io::Write for BoundedOutput {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        if self.buffer.len() + buf.len() > MAX_OUTPUT_SIZE {
+            self.overflowed = true;
+            Err(io::Error::other(TransformationError::OutputTooBig))
+        } else {
+            self.buffer.extend_from_slice(buf);
+            Ok(buf.len())
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+/// Data about an event that is available to templates
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct TransformationContext {
+    pub event_id: Uuid,
+    pub event_type: String,
+    /// RFC 3339 date at which Hook0 received the event
+    pub received_at: String,
+    pub payload_content_type: String,
+    /// See [`payload_value`]
+    pub payload: Value,
+    pub labels: Value,
+    pub metadata: Value,
+}
+
+impl TransformationContext {
+    pub fn new(
+        event_id: Uuid,
+        event_type: &str,
+        received_at: DateTime<Utc>,
+        payload_content_type: &str,
+        payload: &[u8],
+        labels: Value,
+        metadata: Option<Value>,
+    ) -> Self {
+        Self {
+            event_id,
+            event_type: event_type.to_owned(),
+            received_at: received_at.to_rfc3339_opts(SecondsFormat::Millis, true),
+            payload_content_type: payload_content_type.to_owned(),
+            payload: payload_value(payload_content_type, payload),
+            labels,
+            metadata: metadata.unwrap_or_else(|| Value::Object(Default::default())),
+        }
+    }
+}
+
+/// Represent an event payload as a JSON value
+///
+/// JSON payloads are kept as is, text payloads become strings and binary payloads become base64 strings.
+pub fn payload_value(content_type: &str, payload: &[u8]) -> Value {
+    match content_type {
+        "application/json" => serde_json::from_slice(payload)
+            .unwrap_or_else(|_| Value::String(String::from_utf8_lossy(payload).into_owned())),
+        "text/plain" => Value::String(String::from_utf8_lossy(payload).into_owned()),
+        _ => Value::String(Base64.encode(payload)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    fn context() -> TransformationContext {
+        TransformationContext::new(
+            Uuid::nil(),
+            "billing.invoice.paid",
+            DateTime::from_timestamp(0, 0).unwrap(),
+            "application/json",
+            br#"{"amount": 42, "customer": {"name": "Jane \"JD\" Doe"}}"#,
+            json!({ "tenant": "acme" }),
+            None,
+        )
+    }
+
+    #[test]
+    fn render_json() {
+        let transformation = PayloadTransformation {
+            template: r#"{"text": {{ event_type ~ " by " ~ payload.customer.name }}, "amount": {{ payload.amount }}, "tenant": {{ labels.tenant }}, "at": {{ received_at }}}"#.to_owned(),
+            content_type: default_content_type(),
+        };
+        let output = transformation.render(&context()).unwrap();
+        assert_eq!(
+            serde_json::from_slice::<Value>(&output).unwrap(),
+            json!({
+                "text": "billing.invoice.paid by Jane \"JD\" Doe",
+                "amount": 42,
+                "tenant": "acme",
+                "at": "1970-01-01T00:00:00.000Z",
+            })
+        );
+    }
+
+    #[test]
+    fn render_text() {
+        let transformation = PayloadTransformation {
+            template: "{{ event_id }}: {{ payload.customer.name }}".to_owned(),
+            content_type: "text/plain".to_owned(),
+        };
+        assert_eq!(
+            transformation.render(&context()).unwrap(),
+            b"00000000-0000-0000-0000-000000000000: Jane \"JD\" Doe"
+        );
+    }
+
+    #[test]
+    fn render_compiled() {
+        let compiled = PayloadTransformation {
+            template: "{{ event_type }}".to_owned(),
+            content_type: "text/plain".to_owned(),
+        }
+        .compile()
+        .unwrap();
+        assert_eq!(compiled.content_type, "text/plain");
+
+        let mut other_context = context();
+        other_context.event_type = "billing.invoice.refunded".to_owned();
+        assert_eq!(
+            compiled.render(&context()).unwrap(),
+            b"billing.invoice.paid"
+        );
+        assert_eq!(
+            compiled.render(&other_context).unwrap(),
+            b"billing.invoice.refunded"
+        );
+    }
+
+    #[test]
+    fn invalid_templates() {
+        let transformation = PayloadTransformation {
+            template: "{{ payload.amount ".to_owned(),
+            content_type: default_content_type(),
+        };
+        assert!(matches!(
+            transformation.check(),
+            Err(TransformationError::InvalidTemplate(_))
+        ));
+
+        let transformation = PayloadTransformation {
+            template: "x".repeat(MAX_TEMPLATE_LENGTH + 1),
+            content_type: "text/plain".to_owned(),
+        };
+        assert_eq!(
+            transformation.check(),
+            Err(TransformationError::TemplateTooLong)
+        );
+    }
+
+    #[test]
+    fn invalid_outputs() {
+        let transformation = PayloadTransformation {
+            template: r#"{"amount": {{ payload.amount }}"#.to_owned(),
+            content_type: default_content_type(),
+        };
+        assert!(matches!(
+            transformation.render(&context()),
+            Err(TransformationError::InvalidJsonOutput(_))
+        ));
+
+        let transformation = PayloadTransformation {
+            template: "{% for i in range(1000000) %}{% endfor %}".to_owned(),
+            content_type: "text/plain".to_owned(),
+        };
+        assert!(matches!(
+            transformation.render(&context()),
+            Err(TransformationError::Render(_))
+        ));
+
+        let transformation = PayloadTransformation {
+            template: "{{ 'x' * 2000000 }}".to_owned(),
+            content_type: "text/plain".to_owned(),
+        };
+        assert_eq!(
+            transformation.render(&context()),
+            Err(TransformationError::OutputTooBig)
+        );
+    }
+
+    #[test]
+    fn payload_values() {
+        assert_eq!(
+            payload_value("application/json", br#"{"a": 1}"#),
+            json!({ "a": 1 })
+        );
+        assert_eq!(payload_value("application/json", b"{"), json!("{"));
+        assert_eq!(payload_value("text/plain", b"hello"), json!("hello"));
+        assert_eq!(
+            payload_value("application/octet-stream+base64", &[0, 1, 2]),
+            json!("AAEC")
+        );
+    }
+}
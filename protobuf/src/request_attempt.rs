@@ -21,7 +21,8 @@ pub struct RequestAttempt {
     pub event_type_name: String,
     pub payload: Vec<u8>,
     pub payload_content_type: String,
-    pub secret: Uuid,
+    /// Secret of the subscription; it is not sent when subscription secrets are encrypted at rest, so the output worker has to read it from the database
+    pub secret: Option<Uuid>,
 }
 
 impl TryFrom<crate::raw_proto::request_attempt::RequestAttempt> for RequestAttempt {
@@ -76,11 +77,16 @@ impl TryFrom<crate::raw_proto::request_attempt::RequestAttempt> for RequestAttem
                     error: e.to_string(),
                 }
             })?;
-        let secret =
-            Uuid::parse_str(&value.secret).map_err(|error| Hook0ProtobufError::InvalidUuid {
-                error,
-                str: value.secret,
-            })?;
+        let secret = if value.secret.is_empty() {
+            None
+        } else {
+            Some(Uuid::parse_str(&value.secret).map_err(|error| {
+                Hook0ProtobufError::InvalidUuid {
+                    error,
+                    str: value.secret,
+                }
+            })?)
+        };
 
         Ok(Self {
             application_id,
@@ -129,7 +135,10 @@ impl TryFrom<RequestAttempt> for crate::raw_proto::request_attempt::RequestAttem
             event_type_name: value.event_type_name,
             payload: value.payload,
             payload_content_type: value.payload_content_type,
-            secret: value.secret.to_string(),
+            secret: value
+                .secret
+                .map(|secret| secret.to_string())
+                .unwrap_or_default(),
         })
     }
 }
@@ -195,11 +204,21 @@ mod tests {
             event_type_name: "test.test.test".to_owned(),
             payload: b"this is a test payload".to_vec(),
             payload_content_type: "text/plain".to_owned(),
-            secret: uuid!("00000000-0000-0000-0000-000000000004"),
+            secret: Some(uuid!("00000000-0000-0000-0000-000000000004")),
         };
         let proto_request_attempt: crate::raw_proto::request_attempt::RequestAttempt =
             request_attempt.clone().try_into().unwrap();
         let output: RequestAttempt = proto_request_attempt.try_into().unwrap();
-        assert_eq!(output, request_attempt)
+        assert_eq!(output, request_attempt);
+
+        let request_attempt_without_secret = RequestAttempt {
+            secret: None,
+            ..request_attempt
+        };
+        let proto_request_attempt: crate::raw_proto::request_attempt::RequestAttempt =
+            request_attempt_without_secret.clone().try_into().unwrap();
+        assert!(proto_request_attempt.secret.is_empty());
+        let output: RequestAttempt = proto_request_attempt.try_into().unwrap();
+        assert_eq!(output, request_attempt_without_secret)
     }
 }